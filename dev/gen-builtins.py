@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""Generate `src/builtin/generated.rs` from `dev/builtins.json`.
+
+`builtins.json` is the source of truth of builtins, with fields Nix does not report itself,
+like signatures, global names and the version introducing each builtin.
+
+Usage:
+    dev/gen-builtins.py            Regenerate the Rust table.
+    dev/gen-builtins.py --check    Fail if the Rust table is outdated.
+    dev/gen-builtins.py --nix      Also compare the names with `nix __dump-builtins` of the Nix
+                                   in `PATH`, failing on builtins missing from `builtins.json`.
+"""
+import json
+import subprocess
+import sys
+from pathlib import Path
+
+ROOT = Path(__file__).resolve().parent.parent
+SOURCE = ROOT / "dev" / "builtins.json"
+TARGET = ROOT / "src" / "builtin" / "generated.rs"
+
+KINDS = {"const": "Const", "function": "Function", "attrset": "Attrset"}
+
+HEADER = """\
+//! The table of builtins.
+//! This file is generated by `dev/gen-builtins.py` from `dev/builtins.json`. Do not edit.
+use super::{BuiltinInfo, BuiltinKind};
+
+pub(super) static BUILTINS: phf::Map<&'static str, BuiltinInfo> = phf::phf_map! {
+"""
+
+
+def rust_str(s):
+    escaped = s.replace("\\", "\\\\").replace('"', '\\"').replace("\n", "\\n")
+    return f'"{escaped}"'
+
+
+def rust_opt_str(s):
+    return "None" if s is None else f"Some({rust_str(s)})"
+
+
+def generate(builtins):
+    out = [HEADER]
+    for name in sorted(builtins):
+        info = builtins[name]
+        out.append(
+            f"    {rust_str(name)} => BuiltinInfo {{\n"
+            f"        name: {rust_str(name)},\n"
+            f"        kind: BuiltinKind::{KINDS[info['kind']]},\n"
+            f"        global: {'true' if info['global'] else 'false'},\n"
+            f"        signature: {rust_opt_str(info.get('signature'))},\n"
+            f"        doc: {rust_str(info['doc'])},\n"
+            f"        since: {rust_opt_str(info.get('since'))},\n"
+            f"        deprecated: {rust_opt_str(info.get('deprecated'))},\n"
+            f"    }},\n"
+        )
+    out.append("};\n")
+    return "".join(out)
+
+
+def check_nix(builtins):
+    dump = json.loads(subprocess.check_output(["nix", "__dump-builtins"]))
+    missing = sorted(set(dump) - set(builtins))
+    # Constants and builtins gated behind experimental features may be absent from the dump.
+    unknown = sorted(
+        name
+        for name, info in builtins.items()
+        if info["kind"] == "function" and name not in dump
+    )
+    for name in unknown:
+        print(f"note: `{name}` is not reported by this Nix", file=sys.stderr)
+    for name in missing:
+        print(f"error: `{name}` is missing from {SOURCE.name}", file=sys.stderr)
+    return not missing
+
+
+def main(args):
+    builtins = json.loads(SOURCE.read_text())
+    text = generate(builtins)
+    ok = True
+    if "--nix" in args:
+        ok = check_nix(builtins)
+    if "--check" in args:
+        if TARGET.read_text() != text:
+            print(f"error: {TARGET.relative_to(ROOT)} is outdated", file=sys.stderr)
+            ok = False
+    else:
+        TARGET.parent.mkdir(exist_ok=True)
+        TARGET.write_text(text)
+    return 0 if ok else 1
+
+
+if __name__ == "__main__":
+    sys.exit(main(sys.argv[1:]))
@@ -34,6 +34,8 @@
             rustPkgs.rust-nightly_2022-08-01.availableComponents.rustfmt
             rustPkgs.rust
             pkgs.jq
+            # For `dev/gen-builtins.py`.
+            pkgs.python3
             (import ./neovim-env.nix { inherit pkgs; })
           ];
 
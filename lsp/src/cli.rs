@@ -0,0 +1,137 @@
+use crate::vfs::path_from_native;
+use crate::{handler, Config, PositionEncoding, Vfs};
+use anyhow::{Context, Result};
+use lsp_types::{DiagnosticSeverity, NumberOrString};
+use nil::{AnalysisHost, VfsPath};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Print diagnostics of `files` without an editor, one per line as
+/// `path:line:col: severity[code]: message`, with 1-based lines and columns in characters.
+/// `config_path` is a JSON file of the same settings as `initializationOptions` of the server.
+/// Returns whether any error is reported.
+pub fn run_diagnostics(
+    files: &[PathBuf],
+    config_path: Option<&Path>,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let inputs = files
+        .iter()
+        .map(|path| {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok((path.display().to_string(), vfs_path(path)?, text))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check(inputs, config_path, out)
+}
+
+/// Same as `run_diagnostics`, but for a source not on the disk, eg. from stdin.
+/// Relative paths in it are resolved from `path`, which defaults to `<stdin>` in the current
+/// directory.
+pub fn run_diagnostics_source(
+    text: String,
+    path: Option<&Path>,
+    config_path: Option<&Path>,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let path = path.unwrap_or_else(|| Path::new("<stdin>"));
+    let inputs = vec![(path.display().to_string(), vfs_path(path)?, text)];
+    check(inputs, config_path, out)
+}
+
+/// The absolute path in the VFS. The file does not need to exist.
+fn vfs_path(path: &Path) -> Result<VfsPath> {
+    let abs = std::env::current_dir()?.join(path);
+    path_from_native(&abs).with_context(|| format!("Invalid path {}", path.display()))
+}
+
+/// Check sources of `(display name, path, text)`.
+fn check(
+    inputs: Vec<(String, VfsPath, String)>,
+    config_path: Option<&Path>,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let mut config = Config::default();
+    if let Some(path) = config_path {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        config.update(serde_json::from_str(&text)?);
+    }
+    config.position_encoding = PositionEncoding::Utf32;
+
+    let mut vfs = Vfs::new(config.position_encoding);
+    let files = inputs
+        .into_iter()
+        .map(|(name, path, text)| (name, vfs.set_file_content(path, Some(text))))
+        .collect::<Vec<_>>();
+    let mut host = AnalysisHost::default();
+    let mut change = vfs.take_change();
+    change.set_max_file_size(config.max_file_size_bytes);
+    host.apply_change(change);
+    let analysis = host.snapshot();
+
+    let mut has_errors = false;
+    for (name, file) in files {
+        let diags =
+            handler::file_diagnostics(&analysis, &config, file).context("Analysis cancelled")?;
+        let diags = handler::to_lsp_diagnostics(&vfs, &config, file, diags, false);
+        for diag in diags {
+            let severity = match diag.severity {
+                Some(DiagnosticSeverity::ERROR) => {
+                    has_errors = true;
+                    "error"
+                }
+                Some(DiagnosticSeverity::WARNING) => "warning",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                _ => "hint",
+            };
+            let code = match &diag.code {
+                Some(NumberOrString::String(code)) => code.as_str(),
+                _ => "",
+            };
+            writeln!(
+                out,
+                "{}:{}:{}: {}[{}]: {}",
+                name,
+                diag.range.start.line + 1,
+                diag.range.start.character + 1,
+                severity,
+                code,
+                diag.message,
+            )?;
+        }
+    }
+    Ok(has_errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_diagnostics_source;
+    use std::path::Path;
+
+    #[test]
+    fn source() {
+        let mut out = Vec::new();
+        let has_errors = run_diagnostics_source(
+            "{ a = 1; a = 2; }".into(),
+            Some(Path::new("/foo/default.nix")),
+            None,
+            &mut out,
+        )
+        .unwrap();
+        assert!(has_errors);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "/foo/default.nix:1:3: error[duplicated_key]: Duplicated name definition\n\
+             /foo/default.nix:1:10: error[duplicated_key]: Duplicated name definition\n",
+        );
+
+        let mut out = Vec::new();
+        let has_errors =
+            run_diagnostics_source("let a = 1; in a".into(), None, None, &mut out).unwrap();
+        assert!(!has_errors);
+        assert!(out.is_empty());
+    }
+}
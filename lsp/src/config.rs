@@ -0,0 +1,347 @@
+use crate::vfs::PositionEncoding;
+use lsp_types::DiagnosticSeverity;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// Large enough for hand-written files, but not for generated ones like `hackage-packages.nix`.
+const DEFAULT_MAX_FILE_SIZE_BYTES: usize = 2 << 20;
+
+/// User configurations of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Codes of diagnostics which should not be reported, eg. `shadowed_name`.
+    pub diagnostics_ignored: HashSet<String>,
+    /// Codes of opt-in diagnostics which should be reported, eg. `duplicate_value`.
+    pub diagnostics_enabled: HashSet<String>,
+    /// Severities of diagnostics to report instead of the default ones, by codes.
+    pub diagnostics_severity_overrides: HashMap<String, DiagnosticSeverity>,
+    /// Whether the client accepts snippets in code action edits.
+    /// This comes from the client capabilities rather than user settings.
+    pub snippet_text_edit: bool,
+    /// Whether the client accepts snippets in completion items.
+    /// This comes from the client capabilities rather than user settings.
+    pub completion_snippets: bool,
+    /// The position encoding negotiated with the client.
+    pub position_encoding: PositionEncoding,
+    /// The `nix` binary to invoke for evaluation.
+    pub nix_binary: PathBuf,
+    /// Whether to evaluate flake inputs automatically, which may fetch them.
+    pub nix_flake_auto_eval_inputs: bool,
+    /// Entries like `nixpkgs=/path/to/nixpkgs` or `/path/to/dir`, to resolve `<nixpkgs>`.
+    /// It defaults to `NIX_PATH` of the server process.
+    pub nix_path: Vec<String>,
+    /// Whether to complete top-level nixpkgs attributes inside `with import <nixpkgs> { };`.
+    pub nix_complete_nixpkgs: bool,
+    /// A JSON file of the top-level nixpkgs attribute names, eg. generated by
+    /// `nix eval --impure --json --expr 'builtins.attrNames (import <nixpkgs> { })'`.
+    pub nix_nixpkgs_attr_names: Option<PathBuf>,
+    /// Files larger than this are only parsed for syntax errors, without semantic analysis.
+    pub max_file_size_bytes: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let nix_path = env::var("NIX_PATH").unwrap_or_default();
+        Self {
+            diagnostics_ignored: HashSet::new(),
+            diagnostics_enabled: HashSet::new(),
+            diagnostics_severity_overrides: HashMap::new(),
+            snippet_text_edit: false,
+            completion_snippets: false,
+            position_encoding: PositionEncoding::default(),
+            nix_binary: "nix".into(),
+            nix_flake_auto_eval_inputs: false,
+            nix_path: nix_path
+                .split(':')
+                .filter(|entry| !entry.is_empty())
+                .map(Into::into)
+                .collect(),
+            nix_complete_nixpkgs: false,
+            nix_nixpkgs_attr_names: None,
+            max_file_size_bytes: DEFAULT_MAX_FILE_SIZE_BYTES,
+        }
+    }
+}
+
+impl Config {
+    /// Hash the settings which affect reported diagnostics, so that results under different
+    /// settings are distinguished.
+    pub(crate) fn hash_diagnostics_settings(&self, state: &mut impl Hasher) {
+        let mut ignored = self.diagnostics_ignored.iter().collect::<Vec<_>>();
+        ignored.sort();
+        ignored.hash(state);
+        let mut enabled = self.diagnostics_enabled.iter().collect::<Vec<_>>();
+        enabled.sort();
+        enabled.hash(state);
+        // `DiagnosticSeverity` is not `Hash`.
+        let mut overrides = self
+            .diagnostics_severity_overrides
+            .iter()
+            .map(|(code, severity)| (code, format!("{:?}", severity)))
+            .collect::<Vec<_>>();
+        overrides.sort();
+        overrides.hash(state);
+        self.max_file_size_bytes.hash(state);
+    }
+
+    /// Update configurations from a JSON object like
+    /// `{ "diagnostics": { "ignored": ["shadowed_name"], "enabled": ["duplicate_value"] } }`.
+    /// Severities can be overridden by `{ "diagnostics": { "severityOverrides": { "empty_inherit": "hint" } } }`.
+    /// Nix settings are under `nix`, eg. `{ "nix": { "binary": "nix", "nixPath": [] } }`
+    /// and `{ "nix": { "flake": { "autoEvalInputs": false } } }`.
+    /// Nixpkgs completion is enabled by
+    /// `{ "nix": { "completeNixpkgs": true, "nixpkgsAttrNames": "/path/to/names.json" } }`.
+    /// Semantic analysis is skipped for files larger than `{ "maxFileSizeBytes": 2097152 }`.
+    /// Invalid or missing fields are ignored and keep their previous values.
+    pub fn update(&mut self, mut value: Value) {
+        if let Some(v) = value.pointer_mut("/diagnostics/ignored").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(ignored) => self.diagnostics_ignored = ignored,
+                Err(err) => log::warn!("Invalid config `diagnostics.ignored`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/diagnostics/enabled").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(enabled) => self.diagnostics_enabled = enabled,
+                Err(err) => log::warn!("Invalid config `diagnostics.enabled`: {}", err),
+            }
+        }
+        if let Some(v) = value
+            .pointer_mut("/diagnostics/severityOverrides")
+            .map(Value::take)
+        {
+            match parse_severity_overrides(v) {
+                Ok(overrides) => self.diagnostics_severity_overrides = overrides,
+                Err(err) => log::warn!("Invalid config `diagnostics.severityOverrides`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/nix/binary").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(binary) => self.nix_binary = binary,
+                Err(err) => log::warn!("Invalid config `nix.binary`: {}", err),
+            }
+        }
+        if let Some(v) = value
+            .pointer_mut("/nix/flake/autoEvalInputs")
+            .map(Value::take)
+        {
+            match serde_json::from_value(v) {
+                Ok(auto_eval) => self.nix_flake_auto_eval_inputs = auto_eval,
+                Err(err) => log::warn!("Invalid config `nix.flake.autoEvalInputs`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/nix/nixPath").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(nix_path) => self.nix_path = nix_path,
+                Err(err) => log::warn!("Invalid config `nix.nixPath`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/nix/completeNixpkgs").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(enabled) => self.nix_complete_nixpkgs = enabled,
+                Err(err) => log::warn!("Invalid config `nix.completeNixpkgs`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/nix/nixpkgsAttrNames").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(path) => self.nix_nixpkgs_attr_names = path,
+                Err(err) => log::warn!("Invalid config `nix.nixpkgsAttrNames`: {}", err),
+            }
+        }
+        if let Some(v) = value.pointer_mut("/maxFileSizeBytes").map(Value::take) {
+            match serde_json::from_value(v) {
+                Ok(size) => self.max_file_size_bytes = size,
+                Err(err) => log::warn!("Invalid config `maxFileSizeBytes`: {}", err),
+            }
+        }
+    }
+
+    /// Read the snapshot of top-level nixpkgs attribute names, which is a JSON array of strings.
+    /// It is empty unless `nix.completeNixpkgs` is enabled and `<nixpkgs>` can be resolved.
+    pub fn read_nixpkgs_attr_names(&self) -> Vec<String> {
+        let path = match &self.nix_nixpkgs_attr_names {
+            Some(path) if self.nix_complete_nixpkgs => path,
+            _ => return Vec::new(),
+        };
+        if self.resolve_search_path("nixpkgs").is_none() {
+            return Vec::new();
+        }
+        let names = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()));
+        match names {
+            Ok(names) => names,
+            Err(err) => {
+                log::warn!(
+                    "Failed to read nixpkgs attribute names from {}: {}",
+                    path.display(),
+                    err
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    /// Resolve a search path like `nixpkgs/lib` in `<nixpkgs/lib>` by `nix_path`,
+    /// returning the first existing path as Nix does.
+    pub fn resolve_search_path(&self, search: &str) -> Option<PathBuf> {
+        self.nix_path.iter().find_map(|entry| {
+            let path = match entry.split_once('=') {
+                Some((prefix, dir)) => {
+                    let rest = search.strip_prefix(prefix)?;
+                    if rest.is_empty() {
+                        PathBuf::from(dir)
+                    } else {
+                        PathBuf::from(dir).join(rest.strip_prefix('/')?)
+                    }
+                }
+                None => PathBuf::from(entry).join(search),
+            };
+            path.exists().then_some(path)
+        })
+    }
+}
+
+fn parse_severity_overrides(v: Value) -> Result<HashMap<String, DiagnosticSeverity>, String> {
+    let map =
+        serde_json::from_value::<HashMap<String, String>>(v).map_err(|err| err.to_string())?;
+    map.into_iter()
+        .map(|(code, severity)| {
+            let severity = match &*severity {
+                "error" => DiagnosticSeverity::ERROR,
+                "warning" => DiagnosticSeverity::WARNING,
+                "information" | "info" => DiagnosticSeverity::INFORMATION,
+                "hint" => DiagnosticSeverity::HINT,
+                _ => return Err(format!("Unknown severity {:?}", severity)),
+            };
+            Ok((code, severity))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use lsp_types::DiagnosticSeverity;
+    use serde_json::json;
+    use std::{fs, process};
+
+    #[test]
+    fn update() {
+        let mut config = Config::default();
+        config.update(json!({ "diagnostics": { "ignored": ["shadowed_name"] } }));
+        assert!(config.diagnostics_ignored.contains("shadowed_name"));
+
+        config.update(json!({ "diagnostics": { "ignored": 42 } }));
+        assert!(config.diagnostics_ignored.contains("shadowed_name"));
+
+        assert!(config.diagnostics_enabled.is_empty());
+        config.update(json!({ "diagnostics": { "enabled": ["duplicate_value"] } }));
+        assert!(config.diagnostics_enabled.contains("duplicate_value"));
+
+        config.update(json!({ "maxFileSizeBytes": 1024 }));
+        assert_eq!(config.max_file_size_bytes, 1024);
+        config.update(json!({ "maxFileSizeBytes": -1 }));
+        assert_eq!(config.max_file_size_bytes, 1024);
+    }
+
+    #[test]
+    fn severity_overrides() {
+        let mut config = Config::default();
+        let overrides = json!({ "shadowed_name": "hint" });
+        config.update(json!({ "diagnostics": { "severityOverrides": overrides } }));
+        assert_eq!(
+            config.diagnostics_severity_overrides.get("shadowed_name"),
+            Some(&DiagnosticSeverity::HINT),
+        );
+
+        let overrides = json!({ "shadowed_name": "fatal" });
+        config.update(json!({ "diagnostics": { "severityOverrides": overrides } }));
+        assert_eq!(
+            config.diagnostics_severity_overrides.get("shadowed_name"),
+            Some(&DiagnosticSeverity::HINT),
+        );
+    }
+
+    #[test]
+    fn nix_settings() {
+        let mut config = Config::default();
+        assert_eq!(config.nix_binary.to_str(), Some("nix"));
+        config.update(json!({
+            "nix": {
+                "binary": "/run/current-system/sw/bin/nix",
+                "flake": { "autoEvalInputs": true },
+                "nixPath": ["nixpkgs=/etc/nixpkgs"],
+            },
+        }));
+        assert_eq!(
+            config.nix_binary.to_str(),
+            Some("/run/current-system/sw/bin/nix")
+        );
+        assert!(config.nix_flake_auto_eval_inputs);
+        assert_eq!(config.nix_path, ["nixpkgs=/etc/nixpkgs"]);
+
+        config.update(json!({ "nix": { "nixPath": "nixpkgs=/tmp" } }));
+        assert_eq!(config.nix_path, ["nixpkgs=/etc/nixpkgs"]);
+    }
+
+    #[test]
+    fn resolve_search_path() {
+        let dir = std::env::temp_dir().join(format!("nil-search-path-{}", process::id()));
+        fs::create_dir_all(dir.join("nixpkgs/lib")).unwrap();
+        fs::create_dir_all(dir.join("channels/home-manager")).unwrap();
+
+        let mut config = Config::default();
+        config.update(json!({
+            "nix": {
+                "nixPath": [
+                    "nixpkgs=/nonexistent",
+                    format!("nixpkgs={}", dir.join("nixpkgs").display()),
+                    dir.join("channels").display().to_string(),
+                ],
+            },
+        }));
+        assert_eq!(
+            config.resolve_search_path("nixpkgs/lib"),
+            Some(dir.join("nixpkgs/lib"))
+        );
+        assert_eq!(
+            config.resolve_search_path("nixpkgs"),
+            Some(dir.join("nixpkgs"))
+        );
+        assert_eq!(
+            config.resolve_search_path("home-manager"),
+            Some(dir.join("channels/home-manager"))
+        );
+        assert_eq!(config.resolve_search_path("nixpkgs-unstable"), None);
+        assert_eq!(config.resolve_search_path("nixos"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nixpkgs_attr_names() {
+        let dir = std::env::temp_dir().join(format!("nil-nixpkgs-names-{}", process::id()));
+        fs::create_dir_all(dir.join("nixpkgs")).unwrap();
+        let names_path = dir.join("names.json");
+        fs::write(&names_path, r#"["hello", "lib"]"#).unwrap();
+
+        let mut config = Config::default();
+        config.update(json!({
+            "nix": {
+                "nixPath": [format!("nixpkgs={}", dir.join("nixpkgs").display())],
+                "nixpkgsAttrNames": names_path,
+            },
+        }));
+        assert!(config.read_nixpkgs_attr_names().is_empty());
+        config.update(json!({ "nix": { "completeNixpkgs": true } }));
+        assert_eq!(config.read_nixpkgs_attr_names(), ["hello", "lib"]);
+        config.update(json!({ "nix": { "nixPath": [] } }));
+        assert!(config.read_nixpkgs_attr_names().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
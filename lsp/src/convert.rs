@@ -1,23 +1,49 @@
-use crate::{LineMap, StateSnapshot, Vfs, VfsPath};
+use crate::lsp_ext::{DiagnosticData, SnippetTextEdit, RUN_COMMAND};
+use crate::vfs::{native_path, path_from_url, url_from_path};
+use crate::{Config, LineMap, StateSnapshot, Vfs};
 use lsp_types::{
-    self as lsp, DiagnosticSeverity, Location, Position, Range, TextDocumentPositionParams,
+    self as lsp, CodeDescription, CodeLens, Command, DiagnosticRelatedInformation,
+    DiagnosticSeverity, DiagnosticTag, DocumentSymbol, Hover, InsertTextFormat, Location,
+    MarkupContent, MarkupKind, NumberOrString, ParameterInformation, ParameterLabel, Position,
+    Range, SignatureHelp, SignatureInformation, TextDocumentIdentifier, TextDocumentPositionParams,
+    Url,
+};
+use nil::{
+    CallHierarchyItem, Diagnostic, DiagnosticKind, FileId, FilePos, FileRange, FixKind,
+    HoverResult, Runnable, Severity, SymbolKind, SymbolTree, TextEdit, VfsPath,
 };
-use nil::{Diagnostic, FilePos, InFile, Severity};
 use text_size::TextRange;
 
+/// The documentation of diagnostics, with sections named by codes.
+const DIAGNOSTICS_DOC_URL: &str = "https://github.com/sauricat/nil/blob/main/docs/diagnostics.md";
+
+pub(crate) fn from_file(snap: &StateSnapshot, doc: &TextDocumentIdentifier) -> Option<FileId> {
+    let path = path_from_url(&doc.uri)?;
+    let vfs = snap.vfs.read().unwrap();
+    let (file, _) = vfs.get(&path)?;
+    Some(file)
+}
+
 pub(crate) fn from_file_pos(
     snap: &StateSnapshot,
     params: &TextDocumentPositionParams,
 ) -> Option<FilePos> {
-    let path = VfsPath::try_from(&params.text_document.uri).ok()?;
+    let path = path_from_url(&params.text_document.uri)?;
     let vfs = snap.vfs.read().unwrap();
     let (file, line_map) = vfs.get(&path)?;
     let pos = line_map.pos(params.position.line, params.position.character);
     Some(FilePos::new(file, pos))
 }
 
-pub(crate) fn to_location(vfs: &Vfs, frange: InFile<TextRange>) -> Option<Location> {
-    let url = vfs.file_path(frange.file_id)?.try_into().ok()?;
+pub(crate) fn from_range(snap: &StateSnapshot, url: &Url, range: Range) -> Option<FileRange> {
+    let path = path_from_url(url)?;
+    let vfs = snap.vfs.read().unwrap();
+    let (file, line_map) = vfs.get(&path)?;
+    Some(FileRange::new(file, from_lsp_range(line_map, range)))
+}
+
+pub(crate) fn to_location(vfs: &Vfs, frange: FileRange) -> Option<Location> {
+    let url = url_from_path(vfs.file_path(frange.file_id)?)?;
     let line_map = vfs.file_line_map(frange.file_id)?;
     Some(Location::new(url, to_range(line_map, frange.value)))
 }
@@ -28,19 +54,374 @@ pub(crate) fn to_range(line_map: &LineMap, range: TextRange) -> Range {
     Range::new(Position::new(line1, col1), Position::new(line2, col2))
 }
 
-pub(crate) fn to_diagnostic(line_map: &LineMap, diag: Diagnostic) -> Option<lsp::Diagnostic> {
+pub(crate) fn to_diagnostic(
+    vfs: &Vfs,
+    config: &Config,
+    file: FileId,
+    diag: Diagnostic,
+    is_pull: bool,
+) -> Option<lsp::Diagnostic> {
+    let line_map = vfs.file_line_map(file)?;
+    let severity = to_severity(config, &diag, is_pull)?;
+    let message = diag.message();
+    let code = diag.kind.code();
+    let tags = match diag.kind {
+        DiagnosticKind::DeprecatedBuiltin(_) => Some(vec![DiagnosticTag::DEPRECATED]),
+        DiagnosticKind::RedundantUpdateKey | DiagnosticKind::UnusedBinding => {
+            Some(vec![DiagnosticTag::UNNECESSARY])
+        }
+        _ => None,
+    };
+    let code_description = Url::parse(&format!("{}#{}", DIAGNOSTICS_DOC_URL, code))
+        .ok()
+        .map(|href| CodeDescription { href });
+    let related_information = diag
+        .notes
+        .into_iter()
+        .filter_map(|(frange, message)| {
+            Some(DiagnosticRelatedInformation {
+                location: to_location(vfs, frange)?,
+                message,
+            })
+        })
+        .collect::<Vec<_>>();
     Some(lsp::Diagnostic {
-        severity: match diag.severity() {
-            Severity::Error => Some(DiagnosticSeverity::ERROR),
-            Severity::IncompleteSyntax => return None,
-        },
+        severity: Some(severity),
         range: to_range(line_map, diag.range),
-        code: None,
-        code_description: None,
+        code: Some(NumberOrString::String(code.into())),
+        code_description,
         source: None,
-        message: diag.message(),
-        related_information: None,
+        message,
+        related_information: Some(related_information),
+        tags,
+        data: diag
+            .fix
+            .and_then(|fix| serde_json::to_value(to_diagnostic_data(line_map, fix)).ok()),
+    })
+}
+
+pub(crate) fn to_diagnostic_data(line_map: &LineMap, fix: FixKind) -> DiagnosticData {
+    match fix {
+        FixKind::RemoveDuplicate { range } => DiagnosticData::RemoveDuplicate {
+            range: to_range(line_map, range),
+        },
+        FixKind::RemoveUnusedBinding { range } => DiagnosticData::RemoveUnusedBinding {
+            range: to_range(line_map, range),
+        },
+    }
+}
+
+pub(crate) fn from_diagnostic_data(line_map: &LineMap, data: DiagnosticData) -> FixKind {
+    match data {
+        DiagnosticData::RemoveDuplicate { range } => FixKind::RemoveDuplicate {
+            range: from_lsp_range(line_map, range),
+        },
+        DiagnosticData::RemoveUnusedBinding { range } => FixKind::RemoveUnusedBinding {
+            range: from_lsp_range(line_map, range),
+        },
+    }
+}
+
+fn from_lsp_range(line_map: &LineMap, range: Range) -> TextRange {
+    let start = line_map.pos(range.start.line, range.start.character);
+    let end = line_map.pos(range.end.line, range.end.character);
+    TextRange::new(start, end)
+}
+
+/// Map the severity of a diagnostic, respecting user overrides.
+/// Returns `None` if it should not be reported.
+/// `is_pull` is whether the client requested them, where incomplete code found by analysis,
+/// rather than by the parser, is reported as hints.
+pub(crate) fn to_severity(
+    config: &Config,
+    diag: &Diagnostic,
+    is_pull: bool,
+) -> Option<DiagnosticSeverity> {
+    let severity = match default_severity(diag.severity()) {
+        Some(severity) => severity,
+        None if is_pull && !matches!(diag.kind, DiagnosticKind::SyntaxError(_)) => {
+            DiagnosticSeverity::HINT
+        }
+        None => return None,
+    };
+    let severity = config
+        .diagnostics_severity_overrides
+        .get(diag.kind.code())
+        .copied()
+        .unwrap_or(severity);
+    Some(severity)
+}
+
+/// Incomplete syntax is not reported at all, since it is noisy during typing.
+fn default_severity(severity: Severity) -> Option<DiagnosticSeverity> {
+    Some(match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Hint => DiagnosticSeverity::HINT,
+        Severity::IncompleteSyntax => return None,
+    })
+}
+
+/// Convert an edit whose inserted text may be a snippet.
+/// The snippet is reduced to plain text if the client does not support it.
+pub(crate) fn to_snippet_text_edit(
+    config: &Config,
+    line_map: &LineMap,
+    edit: TextEdit,
+    is_snippet: bool,
+) -> SnippetTextEdit {
+    let range = to_range(line_map, edit.delete);
+    if !is_snippet {
+        return SnippetTextEdit {
+            range,
+            new_text: edit.insert.into(),
+            insert_text_format: None,
+        };
+    }
+    if config.snippet_text_edit {
+        SnippetTextEdit {
+            range,
+            new_text: edit.insert.into(),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+        }
+    } else {
+        SnippetTextEdit {
+            range,
+            new_text: snippet_to_plain(&edit.insert),
+            insert_text_format: None,
+        }
+    }
+}
+
+/// Remove tab stops and escapes from a snippet, keeping the text of placeholders.
+fn snippet_to_plain(snippet: &str) -> String {
+    let mut ret = String::with_capacity(snippet.len());
+    let mut placeholder_depth = 0usize;
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => ret.extend(chars.next()),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+                chars.next_if_eq(&':');
+                placeholder_depth += 1;
+            }
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            '}' if placeholder_depth > 0 => placeholder_depth -= 1,
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn to_symbol_kind(kind: SymbolKind) -> lsp::SymbolKind {
+    match kind {
+        SymbolKind::Namespace => lsp::SymbolKind::NAMESPACE,
+        SymbolKind::Function => lsp::SymbolKind::FUNCTION,
+        SymbolKind::Attribute => lsp::SymbolKind::FIELD,
+    }
+}
+
+pub(crate) fn to_document_symbol(line_map: &LineMap, sym: SymbolTree) -> DocumentSymbol {
+    let children = sym
+        .children
+        .into_iter()
+        .map(|child| to_document_symbol(line_map, child))
+        .collect();
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: sym.name.into(),
+        detail: None,
+        kind: to_symbol_kind(sym.kind),
+        tags: None,
+        deprecated: None,
+        range: to_range(line_map, sym.full_range),
+        selection_range: to_range(line_map, sym.focus_range),
+        children: Some(children),
+    }
+}
+
+pub(crate) fn to_call_hierarchy_item(
+    vfs: &Vfs,
+    item: CallHierarchyItem,
+) -> Option<lsp::CallHierarchyItem> {
+    let target = item.target;
+    let uri = url_from_path(vfs.file_path(target.file_id)?)?;
+    let line_map = vfs.file_line_map(target.file_id)?;
+    Some(lsp::CallHierarchyItem {
+        name: item.name.into(),
+        kind: to_symbol_kind(item.kind),
         tags: None,
+        detail: target.container_name.map(Into::into),
+        uri,
+        range: to_range(line_map, target.full_range),
+        selection_range: to_range(line_map, target.focus_range),
         data: None,
     })
 }
+
+/// Make a code lens running the command in `dir`, with `nix` replaced by the configured binary.
+pub(crate) fn to_code_lens(
+    config: &Config,
+    line_map: &LineMap,
+    dir: &VfsPath,
+    runnable: Runnable,
+) -> CodeLens {
+    let mut args = runnable.command;
+    if args[0] == "nix" {
+        args[0] = config.nix_binary.to_string_lossy().into_owned();
+    }
+    CodeLens {
+        range: to_range(line_map, runnable.range),
+        command: Some(Command {
+            title: runnable.label,
+            command: RUN_COMMAND.into(),
+            arguments: Some(vec![native_path(dir).to_string_lossy().into(), args.into()]),
+        }),
+        data: None,
+    }
+}
+
+pub(crate) fn to_signature_help(sig: nil::SignatureHelp) -> SignatureHelp {
+    // Parameter offsets are in UTF-16 code units of the label.
+    let utf16_offset = |pos| sig.label[..usize::from(pos)].encode_utf16().count() as u32;
+    let parameters = sig
+        .parameters
+        .iter()
+        .map(|range| ParameterInformation {
+            label: ParameterLabel::LabelOffsets([
+                utf16_offset(range.start()),
+                utf16_offset(range.end()),
+            ]),
+            documentation: None,
+        })
+        .collect();
+    let active_parameter = sig.active_parameter.map(|i| i as u32);
+    SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label: sig.label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter,
+        }],
+        active_signature: Some(0),
+        active_parameter,
+    }
+}
+
+pub(crate) fn to_hover(line_map: &LineMap, hover: HoverResult) -> Hover {
+    Hover {
+        contents: lsp::HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: hover.markup,
+        }),
+        range: Some(to_range(line_map, hover.range)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_severity, snippet_to_plain, to_severity};
+    use crate::Config;
+    use lsp_types::DiagnosticSeverity;
+    use nil::{Diagnostic, DiagnosticKind, Severity};
+    use text_size::TextRange;
+
+    #[test]
+    fn severity() {
+        let mut config = Config::default();
+        let diag = |kind| Diagnostic::new(TextRange::default(), kind);
+        let table = [
+            (DiagnosticKind::InvalidDynamic, DiagnosticSeverity::ERROR),
+            (
+                DiagnosticKind::DuplicatedKey(None),
+                DiagnosticSeverity::ERROR,
+            ),
+            (
+                DiagnosticKind::DuplicatedPatField,
+                DiagnosticSeverity::ERROR,
+            ),
+            (
+                DiagnosticKind::PatternBindCollision,
+                DiagnosticSeverity::ERROR,
+            ),
+            (DiagnosticKind::ShadowedName, DiagnosticSeverity::WARNING),
+            (DiagnosticKind::EmptyInherit, DiagnosticSeverity::WARNING),
+            (DiagnosticKind::InvalidOperand, DiagnosticSeverity::ERROR),
+            (
+                DiagnosticKind::UnnecessaryQuote,
+                DiagnosticSeverity::WARNING,
+            ),
+            (DiagnosticKind::WithNonAttrset, DiagnosticSeverity::WARNING),
+            (
+                DiagnosticKind::AssertAlwaysFails,
+                DiagnosticSeverity::WARNING,
+            ),
+            (
+                DiagnosticKind::InfiniteRecursion,
+                DiagnosticSeverity::WARNING,
+            ),
+            (DiagnosticKind::DuplicateValue, DiagnosticSeverity::HINT),
+            (DiagnosticKind::UnknownBuiltin, DiagnosticSeverity::WARNING),
+            (
+                DiagnosticKind::DeprecatedBuiltin("isNull"),
+                DiagnosticSeverity::HINT,
+            ),
+            (DiagnosticKind::RedundantUpdateKey, DiagnosticSeverity::HINT),
+            (DiagnosticKind::UnusedBinding, DiagnosticSeverity::WARNING),
+        ];
+        for (kind, severity) in table {
+            assert_eq!(to_severity(&config, &diag(kind), false), Some(severity));
+        }
+
+        for kind in [
+            DiagnosticKind::LetWithoutBody,
+            DiagnosticKind::EmptyInterpolation,
+        ] {
+            let incomplete = diag(kind);
+            assert_eq!(to_severity(&config, &incomplete, false), None);
+            assert_eq!(
+                to_severity(&config, &incomplete, true),
+                Some(DiagnosticSeverity::HINT),
+            );
+        }
+
+        config
+            .diagnostics_severity_overrides
+            .insert("shadowed_name".into(), DiagnosticSeverity::ERROR);
+        assert_eq!(
+            to_severity(&config, &diag(DiagnosticKind::ShadowedName), false),
+            Some(DiagnosticSeverity::ERROR),
+        );
+
+        config
+            .diagnostics_severity_overrides
+            .insert("unused_binding".into(), DiagnosticSeverity::ERROR);
+        assert_eq!(
+            to_severity(&config, &diag(DiagnosticKind::UnusedBinding), false),
+            Some(DiagnosticSeverity::ERROR),
+        );
+    }
+
+    #[test]
+    fn severity_levels() {
+        let table = [
+            (Severity::Error, Some(DiagnosticSeverity::ERROR)),
+            (Severity::Warning, Some(DiagnosticSeverity::WARNING)),
+            (Severity::Hint, Some(DiagnosticSeverity::HINT)),
+            (Severity::IncompleteSyntax, None),
+        ];
+        for (severity, expect) in table {
+            assert_eq!(default_severity(severity), expect);
+        }
+    }
+
+    #[test]
+    fn snippet_plain() {
+        assert_eq!(
+            snippet_to_plain(r#"let ${1:x} = "\${a\}"; in f ${1:x}$0"#),
+            r#"let x = "${a}"; in f x"#,
+        );
+    }
+}
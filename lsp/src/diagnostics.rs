@@ -0,0 +1,88 @@
+use crate::{handler, StateSnapshot};
+use crossbeam_channel::{Receiver, Sender};
+use lsp_types::{self as lsp, Url};
+use nil::FileId;
+use std::thread;
+
+/// The maximum number of threads computing diagnostics.
+const MAX_WORKERS: usize = 4;
+
+struct Task {
+    uri: Url,
+    file: FileId,
+    revision: u64,
+    snap: StateSnapshot,
+}
+
+/// Diagnostics of a file, computed from the snapshot at `revision`.
+#[derive(Debug)]
+pub struct DiagnosticsResult {
+    pub uri: Url,
+    pub file: FileId,
+    pub revision: u64,
+    pub diagnostics: Vec<lsp::Diagnostic>,
+}
+
+/// A bounded pool of threads computing diagnostics off the main loop.
+/// Results are posted back through `receiver` in the order they are finished.
+pub struct DiagnosticsPool {
+    task_sender: Sender<Task>,
+    pub receiver: Receiver<DiagnosticsResult>,
+}
+
+impl DiagnosticsPool {
+    pub fn new() -> Self {
+        let (task_sender, task_receiver) = crossbeam_channel::unbounded::<Task>();
+        let (result_sender, receiver) = crossbeam_channel::unbounded();
+        let workers = thread::available_parallelism().map_or(1, |n| n.get().min(MAX_WORKERS));
+        for _ in 0..workers {
+            let task_receiver = task_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::spawn(move || {
+                // Exit when the pool is dropped.
+                for task in task_receiver {
+                    if let Some(ret) = compute(task) {
+                        // The main loop may be already gone.
+                        let _ = result_sender.send(ret);
+                    }
+                }
+            });
+        }
+        Self {
+            task_sender,
+            receiver,
+        }
+    }
+
+    pub fn spawn(&self, uri: Url, file: FileId, revision: u64, snap: StateSnapshot) {
+        let task = Task {
+            uri,
+            file,
+            revision,
+            snap,
+        };
+        self.task_sender.send(task).unwrap();
+    }
+}
+
+/// Returns `None` if the analysis is cancelled by a newer change.
+fn compute(task: Task) -> Option<DiagnosticsResult> {
+    let snap = &task.snap;
+    // Closed files have their diagnostics cleared.
+    let is_open = snap.vfs.read().unwrap().file_content(task.file).is_some();
+    let diagnostics = if is_open {
+        // The VFS lock is released during the analysis, or changes on the main loop would wait
+        // for it to finish. Results from a changed file are discarded by their revisions.
+        let diags = handler::file_diagnostics(&snap.analysis, &snap.config, task.file)?;
+        let vfs = snap.vfs.read().unwrap();
+        handler::to_lsp_diagnostics(&vfs, &snap.config, task.file, diags, false)
+    } else {
+        Vec::new()
+    };
+    Some(DiagnosticsResult {
+        uri: task.uri,
+        file: task.file,
+        revision: task.revision,
+        diagnostics,
+    })
+}
@@ -1,13 +1,36 @@
-use crate::{convert, StateSnapshot};
+use crate::vfs::{native_path, path_from_url};
+use crate::{convert, lsp_ext, Config, PositionEncoding, StateSnapshot, Vfs};
 use lsp_types::{
-    self as lsp, CompletionItem, CompletionOptions, CompletionParams, CompletionResponse,
-    GotoDefinitionParams, GotoDefinitionResponse, Location, OneOf, ReferenceParams,
-    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    self as lsp, CallHierarchyItem, CallHierarchyPrepareParams, CodeActionKind, CodeActionParams,
+    CodeActionProviderCapability, CodeLens, CodeLensOptions, CodeLensParams, CompletionItem,
+    CompletionOptions, CompletionParams, CompletionResponse, DeclarationCapability,
+    DiagnosticOptions, DiagnosticServerCapabilities, DocumentDiagnosticParams,
+    DocumentDiagnosticReport, DocumentDiagnosticReportResult, DocumentHighlight,
+    DocumentHighlightKind, DocumentHighlightParams, DocumentOnTypeFormattingOptions,
+    DocumentOnTypeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
+    ExecuteCommandOptions, ExecuteCommandParams, FullDocumentDiagnosticReport,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverParams, HoverProviderCapability,
+    LinkedEditingRangeParams, LinkedEditingRangeServerCapabilities, LinkedEditingRanges, Location,
+    MarkupContent, MarkupKind, OneOf, ReferenceParams, RelatedFullDocumentDiagnosticReport,
+    RelatedUnchangedDocumentDiagnosticReport, RenameParams, ServerCapabilities, SignatureHelp,
+    SignatureHelpOptions, SignatureHelpParams, TextDocumentIdentifier, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TypeDefinitionProviderCapability, UnchangedDocumentDiagnosticReport, Url, WorkspaceEdit,
 };
-use nil::{CompletionItemKind, InFile};
+use nil::{
+    builtin, fuzzy_match, Analysis, CompletionItemKind, FileId, FilePos, NavigationTarget,
+    PathPrefix, VfsPath,
+};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::{env, fs};
 
-pub(crate) fn server_capabilities() -> ServerCapabilities {
+pub(crate) fn server_capabilities(position_encoding: PositionEncoding) -> ServerCapabilities {
     ServerCapabilities {
+        position_encoding: Some(position_encoding.to_kind()),
         text_document_sync: Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
                 open_close: Some(true),
@@ -16,27 +39,150 @@ pub(crate) fn server_capabilities() -> ServerCapabilities {
             },
         )),
         definition_provider: Some(OneOf::Left(true)),
+        declaration_provider: Some(DeclarationCapability::Simple(true)),
+        type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
         completion_provider: Some(CompletionOptions {
-            trigger_characters: Some(vec![".".into()]),
+            trigger_characters: Some(vec![".".into(), "$".into(), "/".into()]),
+            resolve_provider: Some(true),
             ..Default::default()
         }),
         references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_highlight_provider: Some(OneOf::Left(true)),
+        linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "\n".into(),
+            more_trigger_character: Some(vec![";".into()]),
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![
+                lsp_ext::REMOVE_UNUSED_BINDINGS.into(),
+                lsp_ext::SYNTAX_TREE.into(),
+                lsp_ext::SELECT_ENCLOSING_ATTRSET.into(),
+                lsp_ext::MATCHING_BRACE.into(),
+                lsp_ext::COMPLETION_ACCEPTED.into(),
+            ],
+            work_done_progress_options: Default::default(),
+        }),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec![" ".into()]),
+            ..Default::default()
+        }),
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+            identifier: None,
+            inter_file_dependencies: false,
+            workspace_diagnostics: false,
+            work_done_progress_options: Default::default(),
+        })),
+        // `callHierarchyProvider` is not advertised until incoming and outgoing calls are
+        // supported, though `prepareCallHierarchy` is already handled.
         ..Default::default()
     }
 }
 
+/// Compute syntax and semantic diagnostics of a file to be reported, skipping ignored ones.
+/// Semantic ones are empty for files too large to be analyzed.
+///
+/// No VFS lock should be held during the computation, since it blocks changes to be applied.
+pub(crate) fn file_diagnostics(
+    analysis: &Analysis,
+    config: &Config,
+    file: FileId,
+) -> Option<Vec<nil::Diagnostic>> {
+    let syntax_diags = analysis.syntax_diagnostics(file).ok()?;
+    let diags = analysis.diagnostics(file).ok()?;
+    let diags = syntax_diags
+        .into_iter()
+        .chain(diags)
+        .filter(|diag| {
+            let code = diag.kind.code();
+            !config.diagnostics_ignored.contains(code)
+                && (!diag.kind.is_opt_in() || config.diagnostics_enabled.contains(code))
+        })
+        .collect();
+    Some(diags)
+}
+
+/// Convert diagnostics of a file, dropping those not to be reported.
+/// `is_pull` is whether they are requested by the client rather than published.
+pub(crate) fn to_lsp_diagnostics(
+    vfs: &Vfs,
+    config: &Config,
+    file: FileId,
+    diags: Vec<nil::Diagnostic>,
+    is_pull: bool,
+) -> Vec<lsp::Diagnostic> {
+    diags
+        .into_iter()
+        .filter_map(|diag| convert::to_diagnostic(vfs, config, file, diag, is_pull))
+        .collect()
+}
+
 pub(crate) fn goto_definition(
     snap: StateSnapshot,
     params: GotoDefinitionParams,
 ) -> Option<GotoDefinitionResponse> {
     let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
-    let targets = snap.analysis.goto_definition(fpos).ok()??;
+    match snap.analysis.goto_definition(fpos).ok()? {
+        Some(targets) => Some(to_goto_response(&snap, targets)),
+        None => goto_search_path(&snap, fpos),
+    }
+}
+
+pub(crate) fn goto_declaration(
+    snap: StateSnapshot,
+    params: lsp::request::GotoDeclarationParams,
+) -> Option<lsp::request::GotoDeclarationResponse> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    match snap.analysis.goto_declaration(fpos).ok()? {
+        Some(targets) => Some(to_goto_response(&snap, targets)),
+        None => goto_search_path(&snap, fpos),
+    }
+}
+
+fn to_goto_response(
+    snap: &StateSnapshot,
+    targets: Vec<NavigationTarget>,
+) -> GotoDefinitionResponse {
     let vfs = snap.vfs.read().unwrap();
     let targets = targets
         .into_iter()
-        .filter_map(|target| {
-            convert::to_location(&vfs, InFile::new(target.file_id, target.focus_range))
-        })
+        .filter_map(|target| convert::to_location(&vfs, target.focus_file_range()))
+        .collect::<Vec<_>>();
+    GotoDefinitionResponse::Array(targets)
+}
+
+/// Search paths like `<nixpkgs>` depend on the configured `nix.nixPath`.
+fn goto_search_path(snap: &StateSnapshot, fpos: FilePos) -> Option<GotoDefinitionResponse> {
+    let search = snap.analysis.search_path_at(fpos).ok()??;
+    let mut path = snap.config.resolve_search_path(&search)?;
+    // Importing a directory imports the `default.nix` inside it.
+    if path.is_dir() {
+        path.push("default.nix");
+    }
+    let uri = Url::from_file_path(&path).ok()?;
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        uri,
+        lsp::Range::default(),
+    )))
+}
+
+pub(crate) fn goto_type_definition(
+    snap: StateSnapshot,
+    params: GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let targets = snap.analysis.goto_type_definition(fpos).ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    let targets = targets
+        .into_iter()
+        .filter_map(|target| convert::to_location(&vfs, target.focus_file_range()))
         .collect::<Vec<_>>();
     Some(GotoDefinitionResponse::Array(targets))
 }
@@ -44,6 +190,9 @@ pub(crate) fn goto_definition(
 pub(crate) fn references(snap: StateSnapshot, params: ReferenceParams) -> Option<Vec<Location>> {
     let fpos = convert::from_file_pos(&snap, &params.text_document_position)?;
     let refs = snap.analysis.references(fpos).ok()??;
+    if snap.is_cancelled() {
+        return None;
+    }
     let vfs = snap.vfs.read().unwrap();
     let locs = refs
         .iter()
@@ -52,12 +201,43 @@ pub(crate) fn references(snap: StateSnapshot, params: ReferenceParams) -> Option
     Some(locs)
 }
 
+pub(crate) fn rename(snap: StateSnapshot, params: RenameParams) -> Option<WorkspaceEdit> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position)?;
+    let edits = snap.analysis.rename(fpos, &params.new_name).ok()??;
+    if snap.is_cancelled() {
+        return None;
+    }
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(fpos.file_id)?;
+    let edits = edits
+        .into_iter()
+        .map(|edit| {
+            lsp::TextEdit::new(convert::to_range(line_map, edit.delete), edit.insert.into())
+        })
+        .collect();
+    let uri = params.text_document_position.text_document.uri;
+    Some(WorkspaceEdit::new(HashMap::from([(uri, edits)])))
+}
+
 pub(crate) fn completion(
     snap: StateSnapshot,
     params: CompletionParams,
 ) -> Option<CompletionResponse> {
     let fpos = convert::from_file_pos(&snap, &params.text_document_position)?;
-    let items = snap.analysis.completions(fpos).ok()??;
+    let trigger = params
+        .context
+        .filter(|ctx| ctx.trigger_kind == lsp::CompletionTriggerKind::TRIGGER_CHARACTER)
+        .and_then(|ctx| ctx.trigger_character?.chars().next());
+    let items = match snap.analysis.path_prefix_at(fpos).ok()? {
+        Some(prefix) => complete_path(&prefix),
+        None => snap.analysis.completions(fpos, trigger).ok()??,
+    };
+    if snap.is_cancelled() {
+        return None;
+    }
+    let uri = params.text_document_position.text_document.uri;
+    let uri_arg = serde_json::to_value(&uri).ok()?;
+    let recent = snap.recent_completions.lock().unwrap();
     let vfs = snap.vfs.read().unwrap();
     let line_map = vfs.file_line_map(fpos.file_id)?;
     let items = items
@@ -67,18 +247,66 @@ pub(crate) fn completion(
                 // FIXME: More specific?
                 CompletionItemKind::Builtin => lsp::CompletionItemKind::KEYWORD,
                 CompletionItemKind::Binding => lsp::CompletionItemKind::VARIABLE,
+                CompletionItemKind::Keyword => lsp::CompletionItemKind::KEYWORD,
+                CompletionItemKind::Constant => lsp::CompletionItemKind::CONSTANT,
+                CompletionItemKind::Path => lsp::CompletionItemKind::FILE,
+                CompletionItemKind::Snippet => lsp::CompletionItemKind::SNIPPET,
+            };
+            // Fallback to plain texts if the client does not support snippets.
+            let (new_text, insert_text_format) = match item.snippet {
+                Some(snippet) if snap.config.completion_snippets => {
+                    (snippet, lsp::InsertTextFormat::SNIPPET)
+                }
+                _ => (item.replace, lsp::InsertTextFormat::PLAIN_TEXT),
+            };
+            // Documentation of builtins is filled in by `completion_resolve`.
+            let data = match item.kind {
+                CompletionItemKind::Builtin | CompletionItemKind::Constant => {
+                    serde_json::to_value(lsp_ext::CompletionData::Builtin(item.label.to_string()))
+                        .ok()
+                }
+                _ => None,
+            };
+            let is_recent = recent.contains(&uri, &item.label);
+            // Keywords, paths and snippets are not identifiers to be remembered.
+            let command = match item.kind {
+                CompletionItemKind::Keyword
+                | CompletionItemKind::Path
+                | CompletionItemKind::Snippet => None,
+                _ => Some(lsp::Command::new(
+                    String::new(),
+                    lsp_ext::COMPLETION_ACCEPTED.into(),
+                    Some(vec![uri_arg.clone(), Value::String(item.label.to_string())]),
+                )),
             };
             CompletionItem {
                 label: item.label.into(),
                 kind: Some(kind),
+                detail: item.detail.map(Into::into),
+                sort_text: Some(completion_sort_text(item.sort_priority, is_recent)),
                 insert_text: None,
-                insert_text_format: Some(lsp::InsertTextFormat::PLAIN_TEXT),
+                insert_text_format: Some(insert_text_format),
                 // We don't support indentation yet.
                 insert_text_mode: Some(lsp::InsertTextMode::ADJUST_INDENTATION),
                 text_edit: Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit {
                     range: convert::to_range(line_map, item.source_range),
-                    new_text: item.replace.into(),
+                    new_text: new_text.into(),
                 })),
+                data,
+                documentation: item.documentation.map(|doc| {
+                    lsp::Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: doc,
+                    })
+                }),
+                filter_text: item.filter_text.map(Into::into),
+                commit_characters: (!item.commit_characters.is_empty()).then(|| {
+                    item.commit_characters
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect()
+                }),
+                command,
                 // TODO
                 ..Default::default()
             }
@@ -86,3 +314,853 @@ pub(crate) fn completion(
         .collect::<Vec<_>>();
     Some(CompletionResponse::Array(items))
 }
+
+/// Entries of the directory on the file system fuzzily matching the typed segment.
+/// Directories and Nix files come first, and hidden entries are only shown after a typed `.`.
+fn complete_path(prefix: &PathPrefix) -> Vec<nil::CompletionItem> {
+    let dir = match prefix.dir.strip_prefix('~') {
+        Some(rest) => match env::var("HOME") {
+            Ok(home) => PathBuf::from(format!("{}{}", home, rest)),
+            Err(_) => return Vec::new(),
+        },
+        None => match VfsPath::new(&prefix.dir) {
+            Some(dir) => native_path(&dir),
+            None => return Vec::new(),
+        },
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Failed to read directory {}: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            let score = fuzzy_match(&prefix.prefix, &name)?;
+            // Other characters cannot be written in path literals.
+            if (name.starts_with('.') && !prefix.prefix.starts_with('.'))
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c))
+            {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            let sort_priority = score.rank(if is_dir {
+                0
+            } else if name.ends_with(".nix") {
+                1
+            } else {
+                2
+            });
+            Some(nil::CompletionItem {
+                label: name.as_str().into(),
+                source_range: prefix.source_range,
+                replace: name.into(),
+                kind: CompletionItemKind::Path,
+                snippet: None,
+                detail: is_dir.then(|| "directory".into()),
+                sort_priority,
+                commit_characters: &[],
+                filter_text: None,
+                documentation: None,
+            })
+        })
+        .collect()
+}
+
+/// Recently accepted identifiers are ranked slightly higher, only above items of the same
+/// priority. Clients sort by labels among items with the same `sortText`.
+fn completion_sort_text(priority: u32, is_recent: bool) -> String {
+    format!("{:010}{}", priority, if is_recent { 0 } else { 1 })
+}
+
+pub(crate) fn completion_resolve(_snap: StateSnapshot, mut item: CompletionItem) -> CompletionItem {
+    let data = item
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value(data).ok());
+    match data {
+        Some(lsp_ext::CompletionData::Builtin(name)) => {
+            if let Some(info) = builtin::get(&name) {
+                item.detail = info.signature.map(Into::into);
+                item.documentation = Some(lsp::Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: info.doc.into(),
+                }));
+            }
+        }
+        None => {}
+    }
+    item
+}
+
+pub(crate) fn code_action(
+    snap: StateSnapshot,
+    params: CodeActionParams,
+) -> Option<Vec<lsp_ext::CodeAction>> {
+    let uri = params.text_document.uri;
+    let frange = convert::from_range(&snap, &uri, params.range)?;
+    let assists = snap.analysis.assists(frange).ok()?;
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(frange.file_id)?;
+    let to_workspace_edit = |edits: Vec<nil::TextEdit>, is_snippet: bool| {
+        let edits = edits
+            .into_iter()
+            .map(|edit| convert::to_snippet_text_edit(&snap.config, line_map, edit, is_snippet))
+            .collect();
+        lsp_ext::SnippetWorkspaceEdit {
+            changes: HashMap::from([(uri.clone(), edits)]),
+        }
+    };
+    // Quick fixes come from the data of the diagnostics, which are computed already.
+    let fixes = params
+        .context
+        .diagnostics
+        .into_iter()
+        .filter_map(|diag| serde_json::from_value::<lsp_ext::DiagnosticData>(diag.data?).ok())
+        .map(|data| {
+            let fix = convert::from_diagnostic_data(line_map, data);
+            lsp_ext::CodeAction {
+                title: fix.label().into(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(to_workspace_edit(fix.edits(), false)),
+            }
+        });
+    let actions = fixes
+        .chain(assists.into_iter().map(|assist| lsp_ext::CodeAction {
+            title: assist.label.into(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(to_workspace_edit(assist.edits, assist.is_snippet)),
+        }))
+        .collect();
+    Some(actions)
+}
+
+pub(crate) fn execute_command(snap: StateSnapshot, params: ExecuteCommandParams) -> Option<Value> {
+    match &*params.command {
+        lsp_ext::COMPLETION_ACCEPTED => {
+            let mut args = params.arguments.into_iter();
+            let uri = serde_json::from_value::<Url>(args.next()?).ok()?;
+            let label = serde_json::from_value::<String>(args.next()?).ok()?;
+            snap.recent_completions.lock().unwrap().record(uri, label);
+            None
+        }
+        lsp_ext::REMOVE_UNUSED_BINDINGS => {
+            let uri = serde_json::from_value::<Url>(params.arguments.into_iter().next()?).ok()?;
+            let file = convert::from_file(&snap, &TextDocumentIdentifier::new(uri.clone()))?;
+            let edits = snap.analysis.remove_unused_bindings(file).ok()?;
+            if snap.is_cancelled() {
+                return None;
+            }
+            let vfs = snap.vfs.read().unwrap();
+            let line_map = vfs.file_line_map(file)?;
+            let edits = edits
+                .into_iter()
+                .map(|edit| {
+                    lsp::TextEdit::new(convert::to_range(line_map, edit.delete), edit.insert.into())
+                })
+                .collect();
+            let edit = WorkspaceEdit::new(HashMap::from([(uri, edits)]));
+            serde_json::to_value(edit).ok()
+        }
+        lsp_ext::SYNTAX_TREE => {
+            let mut args = params.arguments.into_iter();
+            let uri = serde_json::from_value::<Url>(args.next()?).ok()?;
+            let range = args
+                .next()
+                .and_then(|v| serde_json::from_value::<lsp::Range>(v).ok());
+            let (file, range) = match range {
+                Some(range) => {
+                    let frange = convert::from_range(&snap, &uri, range)?;
+                    (frange.file_id, Some(frange.value))
+                }
+                None => (
+                    convert::from_file(&snap, &TextDocumentIdentifier::new(uri))?,
+                    None,
+                ),
+            };
+            let tree = snap.analysis.syntax_tree(file, range).ok()?;
+            Some(Value::String(tree))
+        }
+        lsp_ext::SELECT_ENCLOSING_ATTRSET => {
+            let mut args = params.arguments.into_iter();
+            let uri = serde_json::from_value::<Url>(args.next()?).ok()?;
+            let position = serde_json::from_value::<lsp::Position>(args.next()?).ok()?;
+            let fpos = convert::from_file_pos(
+                &snap,
+                &TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri), position),
+            )?;
+            let range = snap
+                .analysis
+                .expand_selection_to_matching_delimiter(fpos)
+                .ok()?;
+            let vfs = snap.vfs.read().unwrap();
+            let line_map = vfs.file_line_map(fpos.file_id)?;
+            let range = range.map(|range| convert::to_range(line_map, range));
+            serde_json::to_value(range).ok()
+        }
+        lsp_ext::MATCHING_BRACE => {
+            let mut args = params.arguments.into_iter();
+            let uri = serde_json::from_value::<Url>(args.next()?).ok()?;
+            let position = serde_json::from_value::<lsp::Position>(args.next()?).ok()?;
+            let fpos = convert::from_file_pos(
+                &snap,
+                &TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri), position),
+            )?;
+            let pos = snap.analysis.matching_brace(fpos).ok()?;
+            let vfs = snap.vfs.read().unwrap();
+            let line_map = vfs.file_line_map(fpos.file_id)?;
+            let pos = pos.map(|pos| {
+                let (line, col) = line_map.line_col(pos);
+                lsp::Position::new(line, col)
+            });
+            serde_json::to_value(pos).ok()
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn hover(snap: StateSnapshot, params: HoverParams) -> Option<Hover> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let ret = snap.analysis.hover(fpos).ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(fpos.file_id)?;
+    Some(convert::to_hover(line_map, ret))
+}
+
+pub(crate) fn document_highlight(
+    snap: StateSnapshot,
+    params: DocumentHighlightParams,
+) -> Option<Vec<DocumentHighlight>> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let ranges = snap.analysis.highlight_related(fpos).ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(fpos.file_id)?;
+    let highlights = ranges
+        .into_iter()
+        .map(|range| DocumentHighlight {
+            range: convert::to_range(line_map, range),
+            kind: Some(DocumentHighlightKind::TEXT),
+        })
+        .collect();
+    Some(highlights)
+}
+
+/// Valid contents of linked editing ranges, which are identifiers.
+const IDENT_PATTERN: &str = r"[a-zA-Z_][a-zA-Z0-9_'-]*";
+
+pub(crate) fn linked_editing_range(
+    snap: StateSnapshot,
+    params: LinkedEditingRangeParams,
+) -> Option<LinkedEditingRanges> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let ranges = snap.analysis.linked_editing_ranges(fpos).ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(fpos.file_id)?;
+    Some(LinkedEditingRanges {
+        ranges: ranges
+            .into_iter()
+            .map(|range| convert::to_range(line_map, range))
+            .collect(),
+        word_pattern: Some(IDENT_PATTERN.into()),
+    })
+}
+
+pub(crate) fn on_type_formatting(
+    snap: StateSnapshot,
+    params: DocumentOnTypeFormattingParams,
+) -> Option<Vec<lsp::TextEdit>> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position)?;
+    let indent_unit = if params.options.insert_spaces {
+        " ".repeat(params.options.tab_size as usize)
+    } else {
+        "\t".into()
+    };
+    let edit = snap
+        .analysis
+        .on_type_formatting(fpos, &indent_unit)
+        .ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(fpos.file_id)?;
+    Some(vec![lsp::TextEdit::new(
+        convert::to_range(line_map, edit.delete),
+        edit.insert.into(),
+    )])
+}
+
+pub(crate) fn prepare_call_hierarchy(
+    snap: StateSnapshot,
+    params: CallHierarchyPrepareParams,
+) -> Option<Vec<CallHierarchyItem>> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let item = snap.analysis.prepare_call_hierarchy(fpos).ok()??;
+    let vfs = snap.vfs.read().unwrap();
+    Some(vec![convert::to_call_hierarchy_item(&vfs, item)?])
+}
+
+pub(crate) fn document_symbol(
+    snap: StateSnapshot,
+    params: DocumentSymbolParams,
+) -> Option<DocumentSymbolResponse> {
+    let file = convert::from_file(&snap, &params.text_document)?;
+    let syms = snap.analysis.symbol_hierarchy(file).ok()?;
+    if snap.is_cancelled() {
+        return None;
+    }
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(file)?;
+    let syms = syms
+        .into_iter()
+        .map(|sym| convert::to_document_symbol(line_map, sym))
+        .collect();
+    Some(DocumentSymbolResponse::Nested(syms))
+}
+
+pub(crate) fn code_lens(snap: StateSnapshot, params: CodeLensParams) -> Option<Vec<CodeLens>> {
+    let file = convert::from_file(&snap, &params.text_document)?;
+    let runnables = snap.analysis.runnables(file).ok()?;
+    if snap.is_cancelled() {
+        return None;
+    }
+    let vfs = snap.vfs.read().unwrap();
+    let line_map = vfs.file_line_map(file)?;
+    let dir = vfs.file_path(file)?.parent()?;
+    let lenses = runnables
+        .into_iter()
+        .map(|runnable| convert::to_code_lens(&snap.config, line_map, &dir, runnable))
+        .collect();
+    Some(lenses)
+}
+
+pub(crate) fn signature_help(
+    snap: StateSnapshot,
+    params: SignatureHelpParams,
+) -> Option<SignatureHelp> {
+    let fpos = convert::from_file_pos(&snap, &params.text_document_position_params)?;
+    let sig = snap.analysis.signature_help(fpos).ok()??;
+    Some(convert::to_signature_help(sig))
+}
+
+pub(crate) fn document_diagnostic(
+    snap: StateSnapshot,
+    params: DocumentDiagnosticParams,
+) -> DocumentDiagnosticReportResult {
+    let report = document_diagnostic_report(&snap, params).unwrap_or_else(|| {
+        DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport::default())
+    });
+    DocumentDiagnosticReportResult::Report(report)
+}
+
+fn document_diagnostic_report(
+    snap: &StateSnapshot,
+    params: DocumentDiagnosticParams,
+) -> Option<DocumentDiagnosticReport> {
+    let (file, result_id) = {
+        let vfs = snap.vfs.read().unwrap();
+        let (file, _) = vfs.get(&path_from_url(&params.text_document.uri)?)?;
+        // Diagnostics only depend on the file content and the diagnostics settings, so their
+        // hash identifies the result.
+        let mut hasher = DefaultHasher::new();
+        vfs.file_content(file)?.hash(&mut hasher);
+        snap.config.hash_diagnostics_settings(&mut hasher);
+        let result_id = format!("{:016x}", hasher.finish());
+        (file, result_id)
+    };
+
+    if params.previous_result_id.as_ref() == Some(&result_id) {
+        return Some(DocumentDiagnosticReport::Unchanged(
+            RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                    result_id,
+                },
+            },
+        ));
+    }
+
+    if snap.is_cancelled() {
+        return None;
+    }
+    // The VFS lock is not held during the analysis, which would block changes.
+    let diags = file_diagnostics(&snap.analysis, &snap.config, file)?;
+    if snap.is_cancelled() {
+        return None;
+    }
+    let vfs = snap.vfs.read().unwrap();
+    let items = to_lsp_diagnostics(&vfs, &snap.config, file, diags, true);
+    Some(DocumentDiagnosticReport::Full(
+        RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                result_id: Some(result_id),
+                items,
+            },
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        code_action, completion, completion_resolve, completion_sort_text, document_diagnostic,
+        document_symbol, execute_command, goto_definition,
+    };
+    use crate::vfs::path_from_url;
+    use crate::{convert, lsp_ext, Config, StateSnapshot, Vfs};
+    use lsp_types::{
+        CodeActionContext, CodeActionKind, CodeActionParams, CompletionItem, CompletionParams,
+        CompletionResponse, CompletionTextEdit, DiagnosticSeverity, DiagnosticTag,
+        DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
+        DocumentSymbolParams, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
+        InsertTextFormat, NumberOrString, Position, Range, TextDocumentIdentifier,
+        TextDocumentPositionParams, Url, WorkspaceEdit,
+    };
+    use nil::{AnalysisHost, Change};
+    use std::fs;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, RwLock};
+
+    fn snapshot(host: &AnalysisHost, vfs: &Arc<RwLock<Vfs>>, cancelled: bool) -> StateSnapshot {
+        StateSnapshot {
+            analysis: host.snapshot(),
+            vfs: Arc::clone(vfs),
+            config: Arc::new(Config::default()),
+            recent_completions: Default::default(),
+            cancelled: Arc::new(AtomicBool::new(cancelled)),
+        }
+    }
+
+    fn single_file(text: &str) -> (AnalysisHost, Arc<RwLock<Vfs>>, Url) {
+        let uri = Url::from_file_path(std::env::temp_dir().join("default.nix")).unwrap();
+        let mut vfs = Vfs::default();
+        vfs.set_file_content(path_from_url(&uri).unwrap(), Some(text.into()));
+        let mut host = AnalysisHost::default();
+        host.apply_change(vfs.take_change());
+        (host, Arc::new(RwLock::new(vfs)), uri)
+    }
+
+    fn diagnose(
+        host: &AnalysisHost,
+        vfs: &Arc<RwLock<Vfs>>,
+        uri: &Url,
+        previous_result_id: Option<String>,
+    ) -> DocumentDiagnosticReport {
+        let snap = snapshot(host, vfs, false);
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            identifier: None,
+            previous_result_id,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        match document_diagnostic(snap, params) {
+            DocumentDiagnosticReportResult::Report(report) => report,
+            DocumentDiagnosticReportResult::Partial(_) => panic!("Unexpected partial result"),
+        }
+    }
+
+    #[test]
+    fn unchanged_report() {
+        let uri = Url::from_file_path(std::env::temp_dir().join("default.nix")).unwrap();
+        let path = path_from_url(&uri).unwrap();
+        let vfs = Arc::new(RwLock::new(Vfs::default()));
+        let mut host = AnalysisHost::default();
+        let set_content = |host: &mut AnalysisHost, text: &str| {
+            let mut vfs = vfs.write().unwrap();
+            vfs.set_file_content(path.clone(), Some(text.into()));
+            host.apply_change(vfs.take_change());
+        };
+
+        set_content(&mut host, "let a = 1; in { b = a; b = 2; }");
+        let result_id = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => {
+                let report = report.full_document_diagnostic_report;
+                assert!(!report.items.is_empty());
+                report.result_id.expect("Missing result id")
+            }
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+
+        match diagnose(&host, &vfs, &uri, Some(result_id.clone())) {
+            DocumentDiagnosticReport::Unchanged(report) => assert_eq!(
+                report.unchanged_document_diagnostic_report.result_id,
+                result_id,
+            ),
+            DocumentDiagnosticReport::Full(_) => panic!("Expect an unchanged report"),
+        }
+
+        set_content(&mut host, "let a = 1; in { b = a; }");
+        match diagnose(&host, &vfs, &uri, Some(result_id.clone())) {
+            DocumentDiagnosticReport::Full(report) => {
+                let report = report.full_document_diagnostic_report;
+                assert!(report.items.is_empty());
+                assert_ne!(report.result_id, Some(result_id));
+            }
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        }
+    }
+
+    #[test]
+    fn report_after_config_change() {
+        let (host, vfs, uri) = single_file("{ a = 1; a = 2; }");
+        let result_id = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report
+                .full_document_diagnostic_report
+                .result_id
+                .expect("Missing result id"),
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+
+        let mut snap = snapshot(&host, &vfs, false);
+        let mut config = Config::default();
+        config.diagnostics_ignored.insert("duplicated_key".into());
+        snap.config = Arc::new(config);
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            identifier: None,
+            previous_result_id: Some(result_id.clone()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        match document_diagnostic(snap, params) {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                let report = report.full_document_diagnostic_report;
+                assert!(report.items.is_empty());
+                assert_ne!(report.result_id, Some(result_id));
+            }
+            _ => panic!("Expect a full report"),
+        }
+    }
+
+    #[test]
+    fn pulled_incomplete_let() {
+        let (host, vfs, uri) = single_file("let a = 1; in");
+        let items = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        // The missing expression from the parser is still suppressed.
+        let codes = items
+            .iter()
+            .map(|diag| (diag.code.clone(), diag.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            [(
+                Some(NumberOrString::String("let_without_body".into())),
+                Some(DiagnosticSeverity::HINT),
+            )],
+        );
+    }
+
+    #[test]
+    fn deprecated_tag() {
+        let (host, vfs, uri) = single_file("x: isNull x");
+        let items = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(items[0].tags, Some(vec![DiagnosticTag::DEPRECATED]));
+    }
+
+    #[test]
+    fn leading_shebang() {
+        let src = "#!/usr/bin/env nix-instantiate --eval\nlet a = 1;\nin { b = a; b = 2; }";
+        let (host, vfs, uri) = single_file(src);
+
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                Position::new(2, 9),
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let targets = match goto_definition(snapshot(&host, &vfs, false), params) {
+            Some(GotoDefinitionResponse::Array(targets)) => targets,
+            _ => panic!("Expect definition targets"),
+        };
+        let ranges = targets.iter().map(|loc| loc.range).collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [Range::new(Position::new(1, 4), Position::new(1, 5))]
+        );
+
+        let items = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        let ranges = items.iter().map(|diag| diag.range).collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                Range::new(Position::new(2, 5), Position::new(2, 11)),
+                Range::new(Position::new(2, 12), Position::new(2, 18)),
+            ],
+        );
+    }
+
+    #[test]
+    fn large_file() {
+        let src = "let a = 1; in { b = a; b = 2; c = ./c/; }";
+        let (mut host, vfs, uri) = single_file(src);
+        let goto_params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                Position::new(0, 20),
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        assert!(goto_definition(snapshot(&host, &vfs, false), goto_params.clone()).is_some());
+
+        let mut change = Change::new();
+        change.set_max_file_size(src.len() - 1);
+        host.apply_change(change);
+        let items = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        let codes = items
+            .iter()
+            .map(|diag| diag.code.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, [Some(NumberOrString::String("syntax_error".into()))]);
+        assert!(goto_definition(snapshot(&host, &vfs, false), goto_params).is_none());
+    }
+
+    #[test]
+    fn diagnostic_fix_data() {
+        let (host, vfs, uri) = single_file("{ a = 1; a = 2; }");
+        let diags = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        assert_eq!(
+            diags[1].data,
+            Some(serde_json::json!({
+                "fix": "removeDuplicate",
+                "range": {
+                    "start": { "line": 0, "character": 8 },
+                    "end": { "line": 0, "character": 15 },
+                },
+            })),
+        );
+
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            range: diags[1].range,
+            context: CodeActionContext {
+                diagnostics: vec![diags[1].clone()],
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let actions = code_action(snapshot(&host, &vfs, false), params).unwrap();
+        assert_eq!(actions[0].title, "Remove this duplicated definition");
+        assert_eq!(actions[0].kind, Some(CodeActionKind::QUICKFIX));
+        let edits = &actions[0].edit.as_ref().unwrap().changes[&uri];
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (edit.range, edit.new_text.as_str()))
+                .collect::<Vec<_>>(),
+            [(Range::new(Position::new(0, 8), Position::new(0, 15)), "")],
+        );
+    }
+
+    #[test]
+    fn unused_binding_fix_data() {
+        let (host, vfs, uri) = single_file("let a = 1; in 2");
+        let diags = match diagnose(&host, &vfs, &uri, None) {
+            DocumentDiagnosticReport::Full(report) => report.full_document_diagnostic_report.items,
+            DocumentDiagnosticReport::Unchanged(_) => panic!("Expect a full report"),
+        };
+        assert_eq!(diags.len(), 1);
+        let data = diags[0].data.clone().unwrap();
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "fix": "removeUnusedBinding",
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 14 },
+                },
+            }),
+        );
+        let data = serde_json::from_value::<lsp_ext::DiagnosticData>(data).unwrap();
+        {
+            let vfs = vfs.read().unwrap();
+            let (file, _) = vfs.get(&path_from_url(&uri).unwrap()).unwrap();
+            let line_map = vfs.file_line_map(file).unwrap();
+            let fix = convert::from_diagnostic_data(line_map, data.clone());
+            assert_eq!(convert::to_diagnostic_data(line_map, fix), data);
+        }
+
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            range: diags[0].range,
+            context: CodeActionContext {
+                diagnostics: vec![diags[0].clone()],
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let actions = code_action(snapshot(&host, &vfs, false), params).unwrap();
+        assert_eq!(actions[0].title, "Remove this unused binding");
+        assert_eq!(actions[0].kind, Some(CodeActionKind::QUICKFIX));
+        let edits = &actions[0].edit.as_ref().unwrap().changes[&uri];
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (edit.range, edit.new_text.as_str()))
+                .collect::<Vec<_>>(),
+            [(Range::new(Position::new(0, 0), Position::new(0, 14)), "")],
+        );
+    }
+
+    #[test]
+    fn cancelled_request() {
+        let (host, vfs, uri) = single_file("{ a = 1; }");
+        let params = || DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        assert!(document_symbol(snapshot(&host, &vfs, false), params()).is_some());
+        assert!(document_symbol(snapshot(&host, &vfs, true), params()).is_none());
+    }
+
+    fn complete_at(
+        host: &AnalysisHost,
+        vfs: &Arc<RwLock<Vfs>>,
+        uri: &Url,
+        col: u32,
+    ) -> Vec<CompletionItem> {
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                Position::new(0, col),
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+        match completion(snapshot(host, vfs, false), params) {
+            Some(CompletionResponse::Array(items)) => items,
+            _ => panic!("Expect completion items"),
+        }
+    }
+
+    #[test]
+    fn path_completion() {
+        let dir = std::env::temp_dir().join(format!("nil-path-completion-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for file in ["b.nix", "a.txt", ".hidden", "with space.nix"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let uri = Url::from_file_path(dir.join("default.nix")).unwrap();
+        let mut vfs = Vfs::default();
+        vfs.set_file_content(path_from_url(&uri).unwrap(), Some("import ./".into()));
+        let mut host = AnalysisHost::default();
+        host.apply_change(vfs.take_change());
+        let vfs = Arc::new(RwLock::new(vfs));
+
+        let mut items = complete_at(&host, &vfs, &uri, 9);
+        fs::remove_dir_all(&dir).unwrap();
+        items.sort_by_key(|item| (item.sort_text.clone(), item.label.clone()));
+        let labels = items
+            .iter()
+            .map(|item| item.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["src", "b.nix", "a.txt"]);
+        assert_eq!(items[0].detail.as_deref(), Some("directory"));
+    }
+
+    #[test]
+    fn remove_unused_bindings() {
+        let (host, vfs, uri) = single_file("let b = 1; a = b; c = 2; in c");
+        let params = ExecuteCommandParams {
+            command: crate::lsp_ext::REMOVE_UNUSED_BINDINGS.into(),
+            arguments: vec![serde_json::to_value(&uri).unwrap()],
+            work_done_progress_params: Default::default(),
+        };
+        let ret = execute_command(snapshot(&host, &vfs, false), params).unwrap();
+        let edit = serde_json::from_value::<WorkspaceEdit>(ret).unwrap();
+        let edits = &edit.changes.unwrap()[&uri];
+        let ranges = edits
+            .iter()
+            .map(|edit| (edit.range.start.character, edit.range.end.character))
+            .collect::<Vec<_>>();
+        // Removing `a` makes `b` unused.
+        assert_eq!(ranges, [(3, 10), (10, 17)]);
+    }
+
+    #[test]
+    fn sort_by_scope() {
+        let src = "let a = 1; in let ab = 2; in a";
+        let (host, vfs, uri) = single_file(src);
+        let items = complete_at(&host, &vfs, &uri, src.len() as u32);
+        let sort_text = |label: &str| {
+            items
+                .iter()
+                .find(|item| item.label == label)
+                .and_then(|item| item.sort_text.clone())
+                .expect("Missing sort text")
+        };
+        assert!(sort_text("ab") < sort_text("a"));
+        assert!(sort_text("a") < sort_text("map"));
+    }
+
+    #[test]
+    fn plain_keyword_completion() {
+        let src = "a: i";
+        let (host, vfs, uri) = single_file(src);
+        let items = complete_at(&host, &vfs, &uri, src.len() as u32);
+        let item = items
+            .into_iter()
+            .find(|item| item.label == "if")
+            .expect("Missing keyword completion");
+        assert_eq!(item.insert_text_format, Some(InsertTextFormat::PLAIN_TEXT));
+        match item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => {
+                assert_eq!(edit.new_text, "if");
+            }
+            _ => panic!("Expect a text edit"),
+        }
+    }
+
+    #[test]
+    fn sort_recent_completions() {
+        assert!(completion_sort_text(10, true) < completion_sort_text(10, false));
+        // Priorities still take precedence.
+        assert!(completion_sort_text(10, false) < completion_sort_text(11, true));
+        assert!(completion_sort_text(9, false) < completion_sort_text(10, true));
+    }
+
+    #[test]
+    fn resolve_builtin_docs() {
+        let (host, vfs, uri) = single_file("toStr");
+        let items = complete_at(&host, &vfs, &uri, 5);
+        let item = items
+            .into_iter()
+            .find(|item| item.label == "toString")
+            .expect("Missing builtin completion");
+        assert_eq!(item.documentation, None);
+        assert!(item.data.is_some());
+
+        let item = completion_resolve(snapshot(&host, &vfs, false), item);
+        assert!(item.detail.is_some());
+        assert!(item.documentation.is_some());
+    }
+}
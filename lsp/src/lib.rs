@@ -1,20 +1,59 @@
+mod cli;
+mod config;
 mod convert;
+mod diagnostics;
 mod handler;
+mod lsp_ext;
+mod recent;
 mod state;
 mod vfs;
 
+pub use cli::{run_diagnostics, run_diagnostics_source};
+pub(crate) use config::Config;
 pub(crate) use state::{State, StateSnapshot};
-pub(crate) use vfs::{LineMap, Vfs, VfsPath};
+pub(crate) use vfs::{LineMap, PositionEncoding, Vfs};
 
 use anyhow::Result;
 use lsp_server::Connection;
 
 pub fn main_loop(conn: Connection) -> Result<()> {
-    let init_params =
-        conn.initialize(serde_json::to_value(&handler::server_capabilities()).unwrap())?;
+    let (init_id, init_params) = conn.initialize_start()?;
     log::info!("Init params: {}", init_params);
 
-    let mut state = State::new(conn.sender.clone());
+    let position_encodings = init_params
+        .pointer("/capabilities/general/positionEncodings")
+        .and_then(|v| v.as_array())
+        .map(|kinds| kinds.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let position_encoding = PositionEncoding::negotiate(position_encodings);
+    let init_result = serde_json::json!({
+        "capabilities": handler::server_capabilities(position_encoding),
+    });
+    conn.initialize_finish(init_id, init_result)?;
+
+    let mut config = Config::default();
+    if let Some(options) = init_params.get("initializationOptions") {
+        config.update(options.clone());
+    }
+    config.position_encoding = position_encoding;
+    config.snippet_text_edit = init_params
+        .pointer("/capabilities/experimental/snippetTextEdit")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    config.completion_snippets = init_params
+        .pointer("/capabilities/textDocument/completion/completionItem/snippetSupport")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let watch_files = init_params
+        .pointer("/capabilities/workspace/didChangeWatchedFiles/dynamicRegistration")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let mut state = State::new(conn.sender.clone(), config);
+    if watch_files {
+        state.register_file_watchers();
+    }
     state.run(conn.receiver)?;
 
     log::info!("Leaving main loop");
@@ -0,0 +1,85 @@
+//! LSP extensions which are not in the specification.
+use lsp_types::{CodeActionKind, CodeActionParams, InsertTextFormat, Range, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// `textDocument/codeAction` returning edits which may contain snippets.
+/// Snippets are only sent if the client has the experimental capability `snippetTextEdit`,
+/// which is also used by rust-analyzer.
+pub enum CodeActionRequest {}
+
+impl lsp_types::request::Request for CodeActionRequest {
+    type Params = CodeActionParams;
+    type Result = Option<Vec<CodeAction>>;
+    const METHOD: &'static str = "textDocument/codeAction";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeAction {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<CodeActionKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit: Option<SnippetWorkspaceEdit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetWorkspaceEdit {
+    pub changes: HashMap<Url, Vec<SnippetTextEdit>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetTextEdit {
+    pub range: Range,
+    pub new_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insert_text_format: Option<InsertTextFormat>,
+}
+
+/// The payload of `CompletionItem::data`, to fill in heavy fields lazily on
+/// `completionItem/resolve`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "name")]
+pub enum CompletionData {
+    Builtin(String),
+}
+
+/// The payload of `Diagnostic::data`, to build its quick fix in `textDocument/codeAction`
+/// without re-analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "fix")]
+pub enum DiagnosticData {
+    RemoveDuplicate { range: Range },
+    RemoveUnusedBinding { range: Range },
+}
+
+/// The client command of code lenses from `textDocument/codeLens`.
+/// Its arguments are the working directory and the command line to execute there.
+pub const RUN_COMMAND: &str = "nil.runCommand";
+
+/// The `workspace/executeCommand` command attached to completion items, sent by the client when
+/// an item is accepted. Its arguments are the URI of the file and the label of the item,
+/// which is ranked higher in later completions of the same file.
+pub const COMPLETION_ACCEPTED: &str = "nil.completionAccepted";
+
+/// The `workspace/executeCommand` command to remove all unused `let` bindings of a file.
+/// Its argument is the URI of the file, and it returns a `WorkspaceEdit` to be applied.
+pub const REMOVE_UNUSED_BINDINGS: &str = "nil.removeUnusedBindings";
+
+/// The `workspace/executeCommand` command to dump the syntax tree of a file for debugging.
+/// Its arguments are the URI of the file and an optional range to show only the covering node,
+/// and it returns the dump as a string.
+pub const SYNTAX_TREE: &str = "nil.syntaxTree";
+
+/// The `workspace/executeCommand` command to get the range of the nearest enclosing `{ }`, `[ ]`
+/// or `( )`. Its arguments are the URI of the file and a position,
+/// and it returns the `Range` including the delimiters, or null if there is none.
+pub const SELECT_ENCLOSING_ATTRSET: &str = "nil.selectEnclosingAttrset";
+
+/// The `workspace/executeCommand` command to find the delimiter matching the one at a position.
+/// Its arguments are the URI of the file and a position,
+/// and it returns the `Position` of the matching delimiter, or null if there is none.
+pub const MATCHING_BRACE: &str = "nil.matchingBrace";
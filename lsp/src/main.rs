@@ -1,8 +1,70 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use lsp_server::Connection;
+use std::path::PathBuf;
+use std::{io, process};
+use tracing_subscriber::EnvFilter;
 
 fn main() -> Result<()> {
-    env_logger::Builder::from_env("NIL_LOG").init();
+    let mut log_level = None;
+    let mut config_path = None;
+    // `nil diagnostics [--config <path>] <files>...` checks files without an editor,
+    // or `nil diagnostics --stdin [--path <path>]` checks the source from stdin.
+    let mut diagnostics_files = None;
+    let mut stdin = false;
+    let mut stdin_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log-level" => {
+                log_level = Some(args.next().context("Missing value of `--log-level`")?);
+            }
+            "--config" => {
+                config_path = Some(PathBuf::from(
+                    args.next().context("Missing value of `--config`")?,
+                ));
+            }
+            "--stdin" => stdin = true,
+            "--path" => {
+                stdin_path = Some(PathBuf::from(
+                    args.next().context("Missing value of `--path`")?,
+                ));
+            }
+            "diagnostics" if diagnostics_files.is_none() => diagnostics_files = Some(Vec::new()),
+            _ => {
+                // Other arguments, eg. `--stdio` passed by some clients, are ignored.
+                if let Some(files) = &mut diagnostics_files {
+                    files.push(PathBuf::from(arg));
+                }
+            }
+        }
+    }
+
+    // The flag takes precedence over `NIL_LOG` and `RUST_LOG`.
+    let filter = match log_level {
+        Some(level) => EnvFilter::try_new(level)?,
+        None => EnvFilter::try_from_env("NIL_LOG")
+            .or_else(|_| EnvFilter::try_from_default_env())
+            .unwrap_or_else(|_| EnvFilter::new("error")),
+    };
+    // Stdout is the LSP channel.
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(false)
+        .init();
+
+    if let Some(files) = diagnostics_files {
+        let config_path = config_path.as_deref();
+        let out = &mut io::stdout().lock();
+        let has_errors = if stdin {
+            let text = io::read_to_string(io::stdin())?;
+            lsp::run_diagnostics_source(text, stdin_path.as_deref(), config_path, out)?
+        } else {
+            lsp::run_diagnostics(&files, config_path, out)?
+        };
+        process::exit(i32::from(has_errors));
+    }
+
     let (conn, io_threads) = Connection::stdio();
     lsp::main_loop(conn)?;
     io_threads.join()?;
@@ -0,0 +1,58 @@
+use lsp_types::Url;
+use std::collections::{HashMap, VecDeque};
+
+/// The maximum number of accepted identifiers remembered for each file.
+const MAX_RECENT_PER_FILE: usize = 16;
+
+/// Identifiers accepted from completion recently, most recent first, for each file.
+/// They are ranked higher among completion items of the same priority.
+#[derive(Debug, Default)]
+pub struct RecentCompletions {
+    files: HashMap<Url, VecDeque<String>>,
+}
+
+impl RecentCompletions {
+    /// Record an accepted identifier, evicting the least recently used one if it is full.
+    pub fn record(&mut self, uri: Url, label: String) {
+        let labels = self.files.entry(uri).or_default();
+        labels.retain(|l| *l != label);
+        labels.push_front(label);
+        labels.truncate(MAX_RECENT_PER_FILE);
+    }
+
+    pub fn contains(&self, uri: &Url, label: &str) -> bool {
+        self.files
+            .get(uri)
+            .map_or(false, |labels| labels.iter().any(|l| l == label))
+    }
+
+    pub fn remove_file(&mut self, uri: &Url) {
+        self.files.remove(uri);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecentCompletions, MAX_RECENT_PER_FILE};
+    use lsp_types::Url;
+
+    #[test]
+    fn evict_least_recent() {
+        let uri = Url::parse("file:///default.nix").unwrap();
+        let other = Url::parse("file:///other.nix").unwrap();
+        let mut recent = RecentCompletions::default();
+        for i in 0..MAX_RECENT_PER_FILE {
+            recent.record(uri.clone(), format!("a{}", i));
+        }
+        // Using it again makes it the most recent.
+        recent.record(uri.clone(), "a0".into());
+        recent.record(uri.clone(), "b".into());
+        assert!(recent.contains(&uri, "a0"));
+        assert!(!recent.contains(&uri, "a1"));
+        assert!(recent.contains(&uri, "b"));
+        assert!(!recent.contains(&other, "b"));
+
+        recent.remove_file(&uri);
+        assert!(!recent.contains(&uri, "b"));
+    }
+}
@@ -1,43 +1,121 @@
-use crate::{convert, handler, Vfs, VfsPath};
+use crate::diagnostics::{DiagnosticsPool, DiagnosticsResult};
+use crate::recent::RecentCompletions;
+use crate::vfs::{native_path, path_from_url};
+use crate::{handler, lsp_ext, Config, Vfs};
 use anyhow::{bail, Result};
-use crossbeam_channel::{Receiver, Sender};
-use lsp_server::{ErrorCode, Message, Notification, Request, Response};
+use crossbeam_channel::{select, Receiver, Sender};
+use lsp_server::{ErrorCode, Message, Notification, Request, RequestId, Response};
 use lsp_types::notification::Notification as _;
-use lsp_types::{notification as notif, request as req, PublishDiagnosticsParams, Url};
-use nil::{Analysis, AnalysisHost};
-use std::sync::{Arc, RwLock};
+use lsp_types::request::Request as _;
+use lsp_types::{
+    notification as notif, request as req, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileSystemWatcher, GlobPattern,
+    MessageType, NumberOrString, PublishDiagnosticsParams, Registration, RegistrationParams,
+    ShowMessageParams, Url,
+};
+use nil::{Analysis, AnalysisHost, Change, FileId};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use std::{fs, thread};
 
 pub struct State {
     host: AnalysisHost,
     vfs: Arc<RwLock<Vfs>>,
     sender: Sender<Message>,
+    config: Arc<Config>,
+    /// Files opened in the client, whose contents are managed by the client instead of the disk.
+    opened_files: HashSet<Url>,
+    /// Cancellation flags of requests being handled in background threads.
+    pending_requests: Arc<Mutex<HashMap<RequestId, Arc<AtomicBool>>>>,
+    /// Identifiers accepted from completion in opened files, recorded by request handlers.
+    recent_completions: Arc<Mutex<RecentCompletions>>,
+    /// Opened files which are told to be too large for semantic analysis, to tell only once.
+    large_files_notified: HashSet<Url>,
+    diagnostics_pool: DiagnosticsPool,
+    /// The revision of the latest diagnostics computation of each file.
+    /// Results of older revisions are stale and discarded.
+    diagnostics_revisions: HashMap<FileId, u64>,
+    revision: u64,
     is_shutdown: bool,
 }
 
 impl State {
-    pub fn new(responder: Sender<Message>) -> Self {
-        Self {
+    pub fn new(responder: Sender<Message>, config: Config) -> Self {
+        let mut this = Self {
             host: Default::default(),
-            vfs: Default::default(),
+            vfs: Arc::new(RwLock::new(Vfs::new(config.position_encoding))),
             sender: responder,
+            config: Arc::new(config),
+            opened_files: HashSet::new(),
+            pending_requests: Default::default(),
+            recent_completions: Default::default(),
+            large_files_notified: HashSet::new(),
+            diagnostics_pool: DiagnosticsPool::new(),
+            diagnostics_revisions: HashMap::new(),
+            revision: 0,
             is_shutdown: false,
-        }
+        };
+        this.apply_analysis_config();
+        this
+    }
+
+    /// Pass configurations to the analysis, including the size limit of analyzed files and the
+    /// configured snapshot of nixpkgs attribute names for completion.
+    fn apply_analysis_config(&mut self) {
+        let names = self.config.read_nixpkgs_attr_names();
+        let mut change = Change::new();
+        change.set_nixpkgs_attr_names(names.iter().map(|name| name.as_str().into()).collect());
+        change.set_max_file_size(self.config.max_file_size_bytes);
+        self.host.apply_change(change);
+    }
+
+    /// Ask the client to notify us about changes of Nix files on the disk.
+    pub fn register_file_watchers(&self) {
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.nix".into()),
+                kind: None,
+            }],
+        };
+        let params = RegistrationParams {
+            registrations: vec![Registration {
+                id: notif::DidChangeWatchedFiles::METHOD.into(),
+                method: notif::DidChangeWatchedFiles::METHOD.into(),
+                register_options: Some(serde_json::to_value(options).unwrap()),
+            }],
+        };
+        let req = Request::new(
+            RequestId::from("register-file-watchers".to_owned()),
+            req::RegisterCapability::METHOD.into(),
+            params,
+        );
+        self.sender.send(req.into()).unwrap();
     }
 
     pub fn run(&mut self, lsp_receiver: Receiver<Message>) -> Result<()> {
-        for msg in &lsp_receiver {
-            match msg {
-                Message::Request(req) => self.dispatch_request(req),
-                Message::Notification(notif) => {
-                    if notif.method == notif::Exit::METHOD {
-                        return Ok(());
+        let diagnostics_receiver = self.diagnostics_pool.receiver.clone();
+        loop {
+            select! {
+                recv(lsp_receiver) -> msg => match msg {
+                    Ok(Message::Request(req)) => self.dispatch_request(req),
+                    Ok(Message::Notification(notif)) => {
+                        if notif.method == notif::Exit::METHOD {
+                            return Ok(());
+                        }
+                        self.dispatch_notification(notif)
+                    }
+                    Ok(Message::Response(_)) => {}
+                    Err(_) => bail!("Channel closed"),
+                },
+                recv(diagnostics_receiver) -> ret => {
+                    if let Ok(ret) = ret {
+                        self.on_diagnostics_computed(ret);
                     }
-                    self.dispatch_notification(notif)
                 }
-                Message::Response(_) => {}
             }
         }
-        bail!("Channel closed")
     }
 
     fn dispatch_request(&mut self, req: Request) {
@@ -56,24 +134,68 @@ impl State {
                 st.is_shutdown = true;
             })
             .on::<req::GotoDefinition>(handler::goto_definition)
+            .on::<req::GotoDeclaration>(handler::goto_declaration)
+            .on::<req::GotoTypeDefinition>(handler::goto_type_definition)
             .on::<req::References>(handler::references)
+            .on::<req::Rename>(handler::rename)
             .on::<req::Completion>(handler::completion)
+            .on::<req::ResolveCompletionItem>(handler::completion_resolve)
+            .on::<lsp_ext::CodeActionRequest>(handler::code_action)
+            .on::<req::HoverRequest>(handler::hover)
+            .on::<req::DocumentHighlightRequest>(handler::document_highlight)
+            .on::<req::LinkedEditingRange>(handler::linked_editing_range)
+            .on::<req::OnTypeFormatting>(handler::on_type_formatting)
+            .on::<req::DocumentSymbolRequest>(handler::document_symbol)
+            .on::<req::CodeLensRequest>(handler::code_lens)
+            .on::<req::ExecuteCommand>(handler::execute_command)
+            .on::<req::CallHierarchyPrepare>(handler::prepare_call_hierarchy)
+            .on::<req::SignatureHelpRequest>(handler::signature_help)
+            .on::<req::DocumentDiagnosticRequest>(handler::document_diagnostic)
             .finish();
     }
 
     fn dispatch_notification(&mut self, notif: Notification) {
         NotificationDispatcher(self, Some(notif))
             .on_sync_mut::<notif::DidOpenTextDocument>(|st, params| {
-                st.set_vfs_file_content(&params.text_document.uri, Some(params.text_document.text));
+                let uri = params.text_document.uri;
+                let len = params.text_document.text.len();
+                st.set_vfs_file_content(&uri, Some(params.text_document.text));
+                st.notify_large_file(&uri, len);
+                st.opened_files.insert(uri);
             })
             .on_sync_mut::<notif::DidCloseTextDocument>(|st, params| {
-                st.set_vfs_file_content(&params.text_document.uri, None);
+                let uri = params.text_document.uri;
+                st.opened_files.remove(&uri);
+                st.recent_completions.lock().unwrap().remove_file(&uri);
+                st.set_vfs_file_content(&uri, None);
             })
             .on_sync_mut::<notif::DidChangeTextDocument>(|st, params| {
                 if let Some(chg) = params.content_changes.into_iter().next() {
                     st.set_vfs_file_content(&params.text_document.uri, Some(chg.text));
                 }
             })
+            .on_sync_mut::<notif::DidChangeWatchedFiles>(State::on_did_change_watched_files)
+            .on_sync_mut::<notif::DidChangeConfiguration>(|st, params| {
+                // Settings may be either the section of this server or the whole client settings.
+                let settings = match params.settings.get("nil") {
+                    Some(v) => v.clone(),
+                    None => params.settings,
+                };
+                let mut config = Config::clone(&st.config);
+                config.update(settings);
+                st.config = Arc::new(config);
+                st.apply_analysis_config();
+            })
+            .on_sync_mut::<notif::Cancel>(|st, params| {
+                let id = match params.id {
+                    NumberOrString::Number(id) => RequestId::from(id),
+                    NumberOrString::String(id) => RequestId::from(id),
+                };
+                // The request may be already finished.
+                if let Some(cancelled) = st.pending_requests.lock().unwrap().get(&id) {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+            })
             .finish();
     }
 
@@ -83,42 +205,126 @@ impl State {
             .unwrap();
     }
 
-    fn snapshot(&self) -> StateSnapshot {
+    /// Tell the user once that semantic analysis is skipped for the file.
+    fn notify_large_file(&mut self, uri: &Url, len: usize) {
+        let max = self.config.max_file_size_bytes;
+        if len <= max || !self.large_files_notified.insert(uri.clone()) {
+            return;
+        }
+        let message = format!(
+            "{} is larger than `nil.maxFileSizeBytes` ({} bytes), only syntax errors are reported.",
+            uri, max,
+        );
+        self.send_notification::<notif::ShowMessage>(ShowMessageParams {
+            typ: MessageType::INFO,
+            message,
+        });
+    }
+
+    fn snapshot(&self, cancelled: Arc<AtomicBool>) -> StateSnapshot {
         StateSnapshot {
             analysis: self.host.snapshot(),
             vfs: Arc::clone(&self.vfs),
+            config: Arc::clone(&self.config),
+            recent_completions: Arc::clone(&self.recent_completions),
+            cancelled,
         }
     }
 
     fn set_vfs_file_content(&mut self, uri: &Url, text: Option<String>) {
-        if let Ok(path) = VfsPath::try_from(uri) {
+        if let Some(path) = path_from_url(uri) {
             let mut vfs = self.vfs.write().unwrap();
             vfs.set_file_content(path, text);
 
             let change = vfs.take_change();
             log::debug!("Files changed: {:?}", change);
+            drop(vfs);
             self.host.apply_change(change.clone());
 
             // Currently we push down changes immediately.
             assert_eq!(change.file_changes.len(), 1);
-            let (file, text) = &change.file_changes[0];
-            let line_map = vfs.file_line_map(*file).unwrap();
-            let diagnostics = text
-                .as_ref()
-                .and_then(|_| self.host.snapshot().diagnostics(*file).ok())
-                .map(|diags| {
-                    diags
-                        .into_iter()
-                        .filter_map(|diag| convert::to_diagnostic(line_map, diag))
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
-            self.send_notification::<notif::PublishDiagnostics>(PublishDiagnosticsParams {
-                uri: uri.clone(),
-                diagnostics,
-                version: None,
-            });
+            let (file, _) = change.file_changes[0];
+            self.update_diagnostics(uri.clone(), file);
+        }
+    }
+
+    fn on_did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {
+        let mut vfs = self.vfs.write().unwrap();
+        for event in params.changes {
+            // Contents of opened files are synchronized by the client.
+            if self.opened_files.contains(&event.uri) {
+                continue;
+            }
+            let path = match path_from_url(&event.uri) {
+                Some(path) => path,
+                None => continue,
+            };
+            let text = if event.typ == FileChangeType::DELETED {
+                None
+            } else {
+                match fs::read_to_string(native_path(&path)) {
+                    Ok(text) => Some(text),
+                    Err(err) => {
+                        log::warn!("Failed to read {}: {}", path, err);
+                        None
+                    }
+                }
+            };
+            vfs.set_file_content(path, text);
+        }
+
+        let change = vfs.take_change();
+        log::debug!("Files changed: {:?}", change);
+        if change.is_empty() {
+            return;
+        }
+        let changed_files = change
+            .file_changes
+            .iter()
+            .map(|&(file, _)| file)
+            .collect::<Vec<_>>();
+        drop(vfs);
+        self.host.apply_change(change);
+
+        // Opened files importing changed ones may be affected.
+        let analysis = self.host.snapshot();
+        let vfs = self.vfs.read().unwrap();
+        let affected = self
+            .opened_files
+            .iter()
+            .filter_map(|uri| {
+                let (file, _) = vfs.get(&path_from_url(uri)?)?;
+                let imported = analysis.imported_files(file).ok()?;
+                imported
+                    .iter()
+                    .any(|file| changed_files.contains(file))
+                    .then(|| (uri.clone(), file))
+            })
+            .collect::<Vec<_>>();
+        drop(vfs);
+        for (uri, file) in affected {
+            self.update_diagnostics(uri, file);
+        }
+    }
+
+    /// Recompute diagnostics of a file in the background, superseding previous computations.
+    fn update_diagnostics(&mut self, uri: Url, file: FileId) {
+        self.revision += 1;
+        self.diagnostics_revisions.insert(file, self.revision);
+        let snap = self.snapshot(Default::default());
+        self.diagnostics_pool.spawn(uri, file, self.revision, snap);
+    }
+
+    fn on_diagnostics_computed(&mut self, ret: DiagnosticsResult) {
+        if self.diagnostics_revisions.get(&ret.file) != Some(&ret.revision) {
+            return;
         }
+        self.diagnostics_revisions.remove(&ret.file);
+        self.send_notification::<notif::PublishDiagnostics>(PublishDiagnosticsParams {
+            uri: ret.uri,
+            diagnostics: ret.diagnostics,
+            version: None,
+        });
     }
 }
 
@@ -137,14 +343,48 @@ impl<'s> RequestDispatcher<'s> {
         self
     }
 
+    /// Handle the request in a background thread, which can be cancelled by `$/cancelRequest`.
     // TODO: Error handling?
-    fn on<R: req::Request>(mut self, f: fn(StateSnapshot, R::Params) -> R::Result) -> Self {
+    fn on<R: req::Request>(mut self, f: fn(StateSnapshot, R::Params) -> R::Result) -> Self
+    where
+        R::Params: Send + 'static,
+        R::Result: 'static,
+    {
         if matches!(&self.1, Some(notif) if notif.method == R::METHOD) {
             let req = self.1.take().unwrap();
+            let span = tracing::info_span!(
+                "request",
+                method = R::METHOD,
+                file = req
+                    .params
+                    .pointer("/textDocument/uri")
+                    .and_then(|uri| uri.as_str())
+                    .unwrap_or_default(),
+                revision = self.0.revision,
+            );
             let params = serde_json::from_value::<R::Params>(req.params).unwrap();
-            let resp = f(self.0.snapshot(), params);
-            let resp = Response::new_ok(req.id, serde_json::to_value(resp).unwrap());
-            self.0.sender.send(resp.into()).unwrap();
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let pending = Arc::clone(&self.0.pending_requests);
+            pending
+                .lock()
+                .unwrap()
+                .insert(req.id.clone(), Arc::clone(&cancelled));
+            let snap = self.0.snapshot(Arc::clone(&cancelled));
+            let sender = self.0.sender.clone();
+            thread::spawn(move || {
+                let ret = in_request_span(span, || f(snap, params));
+                pending.lock().unwrap().remove(&req.id);
+                let resp = if cancelled.load(Ordering::Relaxed) {
+                    Response::new_err(
+                        req.id,
+                        ErrorCode::RequestCanceled as i32,
+                        "Request cancelled.".into(),
+                    )
+                } else {
+                    Response::new_ok(req.id, serde_json::to_value(ret).unwrap())
+                };
+                sender.send(resp.into()).unwrap();
+            });
         }
         self
     }
@@ -157,6 +397,15 @@ impl<'s> RequestDispatcher<'s> {
     }
 }
 
+/// Run a request handler inside its span, and log the time it takes.
+fn in_request_span<T>(span: tracing::Span, f: impl FnOnce() -> T) -> T {
+    let _guard = span.enter();
+    let start = Instant::now();
+    let ret = f();
+    tracing::info!(elapsed = ?start.elapsed(), "Request handled");
+    ret
+}
+
 #[must_use = "NotificationDispatcher::finish not called"]
 struct NotificationDispatcher<'s>(&'s mut State, Option<Notification>);
 
@@ -179,4 +428,121 @@ impl<'s> NotificationDispatcher<'s> {
 pub struct StateSnapshot {
     pub(crate) analysis: Analysis,
     pub(crate) vfs: Arc<RwLock<Vfs>>,
+    pub(crate) config: Arc<Config>,
+    pub(crate) recent_completions: Arc<Mutex<RecentCompletions>>,
+    pub(crate) cancelled: Arc<AtomicBool>,
+}
+
+impl StateSnapshot {
+    /// Whether the client cancelled the request, or the files are changed since the snapshot.
+    /// Handlers should check it and return early.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.is_stale()
+    }
+
+    /// Whether the snapshot is superseded by newer changes.
+    pub(crate) fn is_stale(&self) -> bool {
+        self.analysis.is_stale()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{in_request_span, State};
+    use crate::vfs::path_from_url;
+    use crate::Config;
+    use lsp_server::Message;
+    use lsp_types::notification::{Notification, PublishDiagnostics};
+    use lsp_types::{DidChangeWatchedFilesParams, FileChangeType, FileEvent, Url};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn watched_file_changed() {
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let mut state = State::new(sender, Config::default());
+        let path = std::env::temp_dir().join(format!("nil-watched-{}.nix", std::process::id()));
+        let uri = Url::from_file_path(&path).unwrap();
+        let vfs_path = path_from_url(&uri).unwrap();
+
+        state.set_vfs_file_content(&uri, Some("abc".into()));
+        {
+            let vfs = state.vfs.read().unwrap();
+            let (_, line_map) = vfs.get(&vfs_path).unwrap();
+            assert_eq!(line_map.line_col(2.into()), (0, 2));
+        }
+
+        fs::write(&path, "a\nbc").unwrap();
+        state.on_did_change_watched_files(DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(uri.clone(), FileChangeType::CHANGED)],
+        });
+        fs::remove_file(&path).unwrap();
+        {
+            let vfs = state.vfs.read().unwrap();
+            let (file, line_map) = vfs.get(&vfs_path).unwrap();
+            assert_eq!(vfs.file_content(file), Some("a\nbc"));
+            assert_eq!(line_map.line_col(2.into()), (1, 0));
+        }
+
+        state.on_did_change_watched_files(DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(uri, FileChangeType::DELETED)],
+        });
+        assert!(state.vfs.read().unwrap().get(&vfs_path).is_none());
+    }
+
+    #[test]
+    fn parallel_diagnostics() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut state = State::new(sender, Config::default());
+        let dir = std::env::temp_dir();
+        let uri1 = Url::from_file_path(dir.join("a.nix")).unwrap();
+        let uri2 = Url::from_file_path(dir.join("b.nix")).unwrap();
+        state.set_vfs_file_content(&uri1, Some("{ a = 1; a = 2; }".into()));
+        state.set_vfs_file_content(&uri2, Some("{ }".into()));
+        // Supersede the first computation.
+        state.set_vfs_file_content(&uri2, Some("{ b = 1; b = 2; }".into()));
+
+        // The superseded computation may be either cancelled or discarded.
+        let timeout = Duration::from_secs(10);
+        while !state.diagnostics_revisions.is_empty() {
+            let ret = state
+                .diagnostics_pool
+                .receiver
+                .recv_timeout(timeout)
+                .unwrap();
+            state.on_diagnostics_computed(ret);
+        }
+        let published = receiver
+            .try_iter()
+            .map(|msg| match msg {
+                Message::Notification(notif) if notif.method == PublishDiagnostics::METHOD => {
+                    serde_json::from_value::<<PublishDiagnostics as Notification>::Params>(
+                        notif.params,
+                    )
+                    .unwrap()
+                }
+                msg => panic!("Unexpected message: {:?}", msg),
+            })
+            .map(|params| (params.uri, params.diagnostics.len()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(published.len(), 2);
+        assert_ne!(published[&uri1], 0);
+        assert_ne!(published[&uri2], 0);
+    }
+
+    #[test]
+    fn request_span_result() {
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(std::io::sink)
+            .finish();
+        let ret = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", method = "textDocument/hover");
+            in_request_span(span, || vec![1, 2, 3])
+        });
+        assert_eq!(ret, [1, 2, 3]);
+        // Without any subscriber.
+        assert_eq!(in_request_span(tracing::Span::none(), || 42), 42);
+    }
 }
@@ -1,32 +1,71 @@
 use indexmap::IndexMap;
-use lsp_types::Url;
-use nil::{Change, FileId};
+use lsp_types::{PositionEncodingKind, Url};
+use nil::{Change, FileId, FileSet, VfsPath};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::{fmt, mem};
 use text_size::TextSize;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct VfsPath(PathBuf);
+pub fn path_from_url(url: &Url) -> Option<VfsPath> {
+    path_from_native(&url.to_file_path().ok()?)
+}
+
+pub fn url_from_path(path: &VfsPath) -> Option<Url> {
+    Url::from_file_path(native_path(path)).ok()
+}
+
+/// Convert an absolute path of the OS. On Windows, `C:\a\b` becomes `/C:/a/b`.
+pub fn path_from_native(path: &Path) -> Option<VfsPath> {
+    let path = path.to_str()?;
+    if cfg!(windows) {
+        VfsPath::new(from_windows_path(path)?)
+    } else {
+        VfsPath::new(path)
+    }
+}
 
-impl<'a> TryFrom<&'a Url> for VfsPath {
-    type Error = ();
-    fn try_from(url: &'a Url) -> Result<Self, Self::Error> {
-        url.to_file_path().map(Self)
+/// Convert to a path of the OS. On Windows, `/C:/a/b` becomes `C:\a\b`.
+pub fn native_path(path: &VfsPath) -> PathBuf {
+    if cfg!(windows) {
+        to_windows_path(path.as_str()).into()
+    } else {
+        path.as_str().into()
     }
 }
 
-impl<'a> From<&'a VfsPath> for Url {
-    fn from(path: &'a VfsPath) -> Self {
-        Url::from_file_path(&path.0).unwrap()
+/// `C:\a\b` to `/C:/a/b`, with the drive letter uppercased since it is case-insensitive
+/// but clients disagree on it. Paths without a drive letter are rejected.
+fn from_windows_path(path: &str) -> Option<String> {
+    match path.as_bytes() {
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => Some(format!(
+            "/{}:{}",
+            letter.to_ascii_uppercase() as char,
+            path[2..].replace('\\', "/")
+        )),
+        _ => None,
     }
 }
 
+/// `/C:/a/b` to `C:\a\b`.
+fn to_windows_path(path: &str) -> String {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let mut ret = path.replace('/', "\\");
+    // The root of a drive needs a trailing separator, otherwise it is relative to the current
+    // directory of the drive.
+    if ret.len() == 2 {
+        ret.push('\\');
+    }
+    ret
+}
+
 #[derive(Default)]
 pub struct Vfs {
+    /// Line maps are computed once when the content is set, and reused by all position
+    /// conversions until the next change of that file.
     files: IndexMap<VfsPath, Option<(Arc<str>, LineMap)>>,
     change: Change,
+    encoding: PositionEncoding,
 }
 
 impl fmt::Debug for Vfs {
@@ -48,6 +87,13 @@ impl fmt::Debug for Vfs {
 }
 
 impl Vfs {
+    pub fn new(encoding: PositionEncoding) -> Self {
+        Self {
+            encoding,
+            ..Self::default()
+        }
+    }
+
     pub fn file_path(&self, file_id: FileId) -> Option<&VfsPath> {
         self.files.get_index(file_id.0 as _).map(|(path, _)| path)
     }
@@ -58,21 +104,39 @@ impl Vfs {
 
     pub fn set_file_content(&mut self, path: VfsPath, content: Option<String>) -> FileId {
         let text_with_map = content
-            .and_then(LineMap::normalize)
+            .and_then(|text| LineMap::normalize(text, self.encoding))
             .map(|(text, map)| (text.into(), map));
         let text = text_with_map.as_ref().map(|(text, _)| Arc::clone(text));
-        let id = self.files.insert_full(path, text_with_map).0;
+        let (id, old) = self.files.insert_full(path, text_with_map);
         let file_id = FileId(u32::try_from(id).unwrap());
+        // A new path is added.
+        if old.is_none() {
+            self.change.set_file_set(self.file_set());
+        }
         self.change.change_file(file_id, text);
         file_id
     }
 
+    fn file_set(&self) -> FileSet {
+        let mut file_set = FileSet::default();
+        for (i, path) in self.files.keys().enumerate() {
+            file_set.insert(FileId(i as u32), path.clone());
+        }
+        file_set
+    }
+
     pub fn get(&self, path: &VfsPath) -> Option<(FileId, &LineMap)> {
         let (id, _, inner) = self.files.get_full(path)?;
         let (_, line_map) = inner.as_ref()?;
         Some((FileId(id as u32), line_map))
     }
 
+    pub fn file_content(&self, file_id: FileId) -> Option<&str> {
+        let (_, inner) = self.files.get_index(file_id.0 as usize)?;
+        let (text, _) = inner.as_ref()?;
+        Some(text)
+    }
+
     pub fn file_line_map(&self, file_id: FileId) -> Option<&LineMap> {
         let (_, inner) = self.files.get_index(file_id.0 as usize)?;
         let (_, line_map) = inner.as_ref()?;
@@ -80,9 +144,44 @@ impl Vfs {
     }
 }
 
+/// The unit of columns in LSP positions, negotiated with the client.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Choose the first encoding we support from the client's preference list.
+    /// UTF-16 is mandatory and is the fallback.
+    pub fn negotiate<'a>(client_encodings: impl IntoIterator<Item = &'a str>) -> Self {
+        client_encodings
+            .into_iter()
+            .find_map(|kind| match kind {
+                "utf-8" => Some(Self::Utf8),
+                "utf-16" => Some(Self::Utf16),
+                "utf-32" => Some(Self::Utf32),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn to_kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct LineMap {
+    encoding: PositionEncoding,
     line_starts: Vec<u32>,
+    /// Differences between UTF-8 lengths and encoded lengths of non-ASCII chars, by lines.
     char_diffs: HashMap<u32, Vec<(u32, CodeUnitsDiff)>>,
 }
 
@@ -90,16 +189,19 @@ pub struct LineMap {
 enum CodeUnitsDiff {
     One = 1,
     Two = 2,
+    Three = 3,
 }
 
 impl LineMap {
-    fn normalize(text: String) -> Option<(String, Self)> {
+    fn normalize(text: String, encoding: PositionEncoding) -> Option<(String, Self)> {
         // Too large for `TextSize`.
         if text.len() > u32::MAX as usize {
             return None;
         }
 
-        let text = text.replace('\r', "");
+        // Line breaks can be `\n`, `\r\n` or a lone `\r` in LSP. Normalize them all to `\n`,
+        // so that line numbers agree with the client.
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
         let bytes = text.as_bytes();
 
         let mut line_starts = Some(0)
@@ -115,24 +217,30 @@ impl LineMap {
         line_starts.push(text.len() as u32);
 
         let mut char_diffs = HashMap::new();
-        for ((&start, &end), i) in line_starts.iter().zip(&line_starts[1..]).zip(0u32..) {
-            let mut diffs = Vec::new();
-            for (&b, pos) in bytes[start as usize..end as usize].iter().zip(0u32..) {
-                let diff = match b {
-                    0b0000_0000..=0b0111_1111 |                      // utf8_len == 1, utf16_len == 1
-                    0b1000_0000..=0b1011_1111 => continue,           // Continuation bytes.
-                    0b1100_0000..=0b1101_1111 => CodeUnitsDiff::One, // utf8_len == 2, utf16_len == 1
-                    0b1110_0000..=0b1110_1111 => CodeUnitsDiff::Two, // utf8_len == 3, utf16_len == 1
-                    0b1111_0000.. => CodeUnitsDiff::Two,             // utf8_len == 4, utf16_len == 2
-                };
-                diffs.push((pos, diff));
-            }
-            if !diffs.is_empty() {
-                char_diffs.insert(i, diffs);
+        // Columns are exactly byte offsets in UTF-8.
+        if encoding != PositionEncoding::Utf8 {
+            for ((&start, &end), i) in line_starts.iter().zip(&line_starts[1..]).zip(0u32..) {
+                let mut diffs = Vec::new();
+                for (&b, pos) in bytes[start as usize..end as usize].iter().zip(0u32..) {
+                    let diff = match b {
+                        0b0000_0000..=0b0111_1111 |                      // utf8_len == 1, encoded_len == 1
+                        0b1000_0000..=0b1011_1111 => continue,           // Continuation bytes.
+                        0b1100_0000..=0b1101_1111 => CodeUnitsDiff::One, // utf8_len == 2, encoded_len == 1
+                        0b1110_0000..=0b1110_1111 => CodeUnitsDiff::Two, // utf8_len == 3, encoded_len == 1
+                        // utf8_len == 4, utf16_len == 2, utf32_len == 1
+                        0b1111_0000.. if encoding == PositionEncoding::Utf16 => CodeUnitsDiff::Two,
+                        0b1111_0000.. => CodeUnitsDiff::Three,
+                    };
+                    diffs.push((pos, diff));
+                }
+                if !diffs.is_empty() {
+                    char_diffs.insert(i, diffs);
+                }
             }
         }
 
         let this = Self {
+            encoding,
             line_starts,
             char_diffs,
         };
@@ -171,12 +279,70 @@ impl LineMap {
 
 #[cfg(test)]
 mod tests {
-    use super::{CodeUnitsDiff, LineMap};
+    use super::{
+        from_windows_path, path_from_url, to_windows_path, url_from_path, CodeUnitsDiff, LineMap,
+        PositionEncoding, Vfs,
+    };
+    use lsp_types::Url;
+    use nil::VfsPath;
     use std::collections::HashMap;
+    use std::ptr;
+
+    #[test]
+    #[cfg(unix)]
+    fn url_round_trip() {
+        for (url, path) in [
+            (
+                "file:///home/user/my%20project/flake.nix",
+                "/home/user/my project/flake.nix",
+            ),
+            (
+                "file:///home/user/%E6%96%87%E6%A1%A3/default.nix",
+                "/home/user/文档/default.nix",
+            ),
+        ] {
+            let url = Url::parse(url).unwrap();
+            let vfs_path = path_from_url(&url).unwrap();
+            assert_eq!(vfs_path.as_str(), path);
+            assert_eq!(url_from_path(&vfs_path).unwrap(), url);
+        }
+    }
+
+    #[test]
+    fn windows_path() {
+        assert_eq!(
+            from_windows_path(r"c:\Users\my project\文档\flake.nix").unwrap(),
+            "/C:/Users/my project/文档/flake.nix",
+        );
+        assert_eq!(from_windows_path(r"C:\").unwrap(), "/C:/");
+        assert_eq!(from_windows_path(r"\\server\share"), None);
+        assert_eq!(from_windows_path("/a"), None);
+
+        assert_eq!(
+            to_windows_path("/C:/Users/my project/flake.nix"),
+            r"C:\Users\my project\flake.nix",
+        );
+        assert_eq!(to_windows_path("/C:"), r"C:\");
+    }
+
+    #[test]
+    fn line_map_reused() {
+        let mut vfs = Vfs::default();
+        let path = VfsPath::new("/default.nix").unwrap();
+        let file = vfs.set_file_content(path.clone(), Some("a\nb".into()));
+        let map = vfs.file_line_map(file).unwrap();
+        assert!(ptr::eq(map, vfs.file_line_map(file).unwrap()));
+        assert!(ptr::eq(map, vfs.get(&path).unwrap().1));
+
+        vfs.set_file_content(path, Some("a\nb\nc".into()));
+        let map = vfs.file_line_map(file).unwrap();
+        assert_eq!(map.line_starts, [0, 2, 4, 5]);
+    }
 
     #[test]
     fn line_map_ascii() {
-        let (s, map) = LineMap::normalize("hello\nworld\nend".into()).unwrap();
+        let (s, map) =
+            LineMap::normalize("hello\nworld\nend".into(), PositionEncoding::Utf16).unwrap();
         assert_eq!(s, "hello\nworld\nend");
         assert_eq!(&map.line_starts, &[0, 6, 12, 15]);
 
@@ -194,9 +360,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn line_map_crlf() {
+        let (s, map) =
+            LineMap::normalize("a\r\nbc\r\rd\ne".into(), PositionEncoding::Utf16).unwrap();
+        assert_eq!(s, "a\nbc\n\nd\ne");
+        assert_eq!(&map.line_starts, &[0, 2, 5, 6, 8, 9]);
+
+        // `\r\n` is a single line break.
+        assert_eq!(map.pos(1, 1), 3.into());
+        assert_eq!(map.line_col(3.into()), (1, 1));
+        // A lone `\r` is also a line break.
+        assert_eq!(map.pos(2, 0), 5.into());
+        assert_eq!(map.pos(3, 0), 6.into());
+        assert_eq!(map.line_col(8.into()), (4, 0));
+    }
+
     #[test]
     fn line_map_unicode() {
-        let (s, map) = LineMap::normalize("_A_ß_ℝ_💣_".into()).unwrap();
+        let (s, map) = LineMap::normalize("_A_ß_ℝ_💣_".into(), PositionEncoding::Utf16).unwrap();
         assert_eq!(s, "_A_ß_ℝ_💣_");
         assert_eq!(&map.line_starts, &[0, 15]);
         assert_eq!(
@@ -227,4 +409,32 @@ mod tests {
             assert_eq!(map.pos(line, col), pos.into());
         }
     }
+
+    #[test]
+    fn line_map_encodings() {
+        let text = "x\na💣b";
+        // Positions of `a`, `💣` and `b` in bytes.
+        let positions = [2, 3, 7];
+        let table = [
+            (PositionEncoding::Utf8, [0, 1, 5]),
+            (PositionEncoding::Utf16, [0, 1, 3]),
+            (PositionEncoding::Utf32, [0, 1, 2]),
+        ];
+        for (encoding, cols) in table {
+            let (_, map) = LineMap::normalize(text.into(), encoding).unwrap();
+            for (pos, col) in positions.into_iter().zip(cols) {
+                assert_eq!(map.line_col(pos.into()), (1, col), "{:?}", encoding);
+                assert_eq!(map.pos(1, col), pos.into(), "{:?}", encoding);
+            }
+        }
+    }
+
+    #[test]
+    fn negotiate_encoding() {
+        let negotiate = |kinds: &[&str]| PositionEncoding::negotiate(kinds.iter().copied());
+        assert_eq!(negotiate(&[]), PositionEncoding::Utf16);
+        assert_eq!(negotiate(&["utf-8", "utf-16"]), PositionEncoding::Utf8);
+        assert_eq!(negotiate(&["utf-32", "utf-8"]), PositionEncoding::Utf32);
+        assert_eq!(negotiate(&["utf-7", "utf-16"]), PositionEncoding::Utf16);
+    }
 }
@@ -0,0 +1,35 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn diagnostics() {
+    let dir = std::env::temp_dir().join(format!("nil-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let bad = dir.join("bad.nix");
+    let good = dir.join("good.nix");
+    fs::write(&bad, "{\n  a = 1;\n  a = 2;\n}\n").unwrap();
+    fs::write(&good, "{ a = 1; }\n").unwrap();
+
+    let run = |args: &[&std::path::Path]| {
+        Command::new(env!("CARGO_BIN_EXE_nil"))
+            .arg("diagnostics")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let bad_output = run(&[&bad, &good]);
+    let good_output = run(&[&good]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(bad_output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(bad_output.stdout).unwrap(),
+        format!(
+            "{bad}:2:3: error[duplicated_key]: Duplicated name definition\n\
+             {bad}:3:3: error[duplicated_key]: Duplicated name definition\n",
+            bad = bad.display(),
+        ),
+    );
+    assert_eq!(good_output.status.code(), Some(0));
+    assert!(good_output.stdout.is_empty());
+}
@@ -1,5 +1,7 @@
 use rowan::{TextRange, TextSize};
 use salsa::Durability;
+use smol_str::SmolStr;
+use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use syntax::Parse;
@@ -18,6 +20,11 @@ impl<T> InFile<T> {
         Self { file_id, value }
     }
 
+    /// Attach another value to the same file.
+    pub fn with_value<U>(&self, value: U) -> InFile<U> {
+        InFile::new(self.file_id, value)
+    }
+
     pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> InFile<U> {
         InFile {
             file_id: self.file_id,
@@ -29,11 +36,142 @@ impl<T> InFile<T> {
 pub type FilePos = InFile<TextSize>;
 pub type FileRange = InFile<TextRange>;
 
+/// A single text replacement in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub delete: TextRange,
+    pub insert: SmolStr,
+}
+
+impl TextEdit {
+    pub fn apply(&self, text: &mut String) {
+        let range = std::ops::Range::<usize>::from(self.delete);
+        text.replace_range(range, &self.insert);
+    }
+}
+
+/// An absolute, normalized path in the virtual file system, separated by `/`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VfsPath(String);
+
+impl VfsPath {
+    /// Create a path from an absolute path string, normalizing `.` and `..` segments.
+    /// Returns `None` if the path is not absolute.
+    pub fn new(path: impl AsRef<str>) -> Option<Self> {
+        let path = path.as_ref();
+        if !path.starts_with('/') {
+            return None;
+        }
+        let mut this = Self(String::new());
+        for seg in path.split('/') {
+            this.push_segment(seg);
+        }
+        Some(this)
+    }
+
+    pub fn as_str(&self) -> &str {
+        if self.0.is_empty() {
+            "/"
+        } else {
+            &self.0
+        }
+    }
+
+    /// Get the last segment, or `None` for the root.
+    pub fn file_name(&self) -> Option<&str> {
+        let idx = self.0.rfind('/')?;
+        Some(&self.0[idx + 1..])
+    }
+
+    /// Get the parent directory, or `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let idx = self.0.rfind('/')?;
+        Some(Self(self.0[..idx].into()))
+    }
+
+    /// Append a segment. `.` and empty segments are ignored, and `..` pops the last segment.
+    pub fn push_segment(&mut self, seg: &str) {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                let idx = self.0.rfind('/').unwrap_or(0);
+                self.0.truncate(idx);
+            }
+            _ => {
+                self.0.push('/');
+                self.0.push_str(seg);
+            }
+        }
+    }
+}
+
+impl fmt::Display for VfsPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A bidirectional mapping between files and their paths.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct FileSet {
+    files: HashMap<VfsPath, FileId>,
+    paths: HashMap<FileId, VfsPath>,
+}
+
+impl FileSet {
+    pub fn insert(&mut self, file_id: FileId, path: VfsPath) {
+        self.files.insert(path.clone(), file_id);
+        self.paths.insert(file_id, path);
+    }
+
+    pub fn remove(&mut self, file_id: FileId) -> Option<VfsPath> {
+        let path = self.paths.remove(&file_id)?;
+        self.files.remove(&path);
+        Some(path)
+    }
+
+    /// A `FileId` not used by any file in this set.
+    pub fn next_file_id(&self) -> FileId {
+        FileId(self.paths.keys().map(|f| f.0 + 1).max().unwrap_or(0))
+    }
+
+    pub fn file_for_path(&self, path: &VfsPath) -> Option<FileId> {
+        self.files.get(path).copied()
+    }
+
+    pub fn path_for_file(&self, file_id: FileId) -> Option<&VfsPath> {
+        self.paths.get(&file_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (FileId, &'_ VfsPath)> + '_ {
+        self.paths.iter().map(|(&file, path)| (file, path))
+    }
+}
+
+impl fmt::Debug for FileSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(&self.paths).finish()
+    }
+}
+
 #[salsa::query_group(SourceDatabaseStorage)]
 pub trait SourceDatabase {
     #[salsa::input]
     fn file_content(&self, file_id: FileId) -> Arc<str>;
 
+    #[salsa::input]
+    fn file_set(&self) -> Arc<FileSet>;
+
+    /// Top-level attribute names of nixpkgs from a pre-generated snapshot, used to complete
+    /// names in `with import <nixpkgs> { };`. It is empty if not configured.
+    #[salsa::input]
+    fn nixpkgs_attr_names(&self) -> Arc<[SmolStr]>;
+
+    /// Files larger than this in bytes are only parsed for syntax errors. They are lowered as
+    /// empty modules, so semantic analysis on them gives empty results.
+    #[salsa::input]
+    fn max_file_size(&self) -> usize;
+
     fn parse(&self, file_id: FileId) -> InFile<Parse>;
 }
 
@@ -45,7 +183,13 @@ fn parse(db: &dyn SourceDatabase, file_id: FileId) -> InFile<Parse> {
 
 #[derive(Default, Clone, PartialEq, Eq)]
 pub struct Change {
+    pub file_set: Option<FileSet>,
     pub file_changes: Vec<(FileId, Option<Arc<str>>)>,
+    /// Files to add by path, or to overwrite if the path already exists.
+    pub created_files: Vec<(VfsPath, Arc<str>)>,
+    pub deleted_files: Vec<VfsPath>,
+    pub nixpkgs_attr_names: Option<Arc<[SmolStr]>>,
+    pub max_file_size: Option<usize>,
 }
 
 impl Change {
@@ -54,15 +198,76 @@ impl Change {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.file_changes.is_empty()
+        self.file_set.is_none()
+            && self.file_changes.is_empty()
+            && self.created_files.is_empty()
+            && self.deleted_files.is_empty()
+            && self.nixpkgs_attr_names.is_none()
+            && self.max_file_size.is_none()
+    }
+
+    pub fn set_file_set(&mut self, file_set: FileSet) {
+        self.file_set = Some(file_set);
     }
 
     pub fn change_file(&mut self, file_id: FileId, content: Option<Arc<str>>) {
         self.file_changes.push((file_id, content));
     }
 
+    pub fn set_nixpkgs_attr_names(&mut self, names: Arc<[SmolStr]>) {
+        self.nixpkgs_attr_names = Some(names);
+    }
+
+    pub fn set_max_file_size(&mut self, size: usize) {
+        self.max_file_size = Some(size);
+    }
+
+    /// Add a file by path. A new `FileId` is allocated if the path is not known yet.
+    pub fn create_file(&mut self, path: VfsPath, content: Arc<str>) {
+        self.created_files.push((path, content));
+    }
+
+    /// Remove a file by path from the file set, clearing its content.
+    pub fn delete_file(&mut self, path: VfsPath) {
+        self.deleted_files.push(path);
+    }
+
     pub(crate) fn apply(self, db: &mut dyn SourceDatabase) {
-        for (file_id, content) in self.file_changes {
+        let mut file_changes = self.file_changes;
+        let file_set = if self.created_files.is_empty() && self.deleted_files.is_empty() {
+            self.file_set
+        } else {
+            let mut file_set = self.file_set.unwrap_or_else(|| (*db.file_set()).clone());
+            for (path, content) in self.created_files {
+                let file_id = match file_set.file_for_path(&path) {
+                    Some(file_id) => file_id,
+                    None => {
+                        let file_id = file_set.next_file_id();
+                        file_set.insert(file_id, path);
+                        file_id
+                    }
+                };
+                file_changes.push((file_id, Some(content)));
+            }
+            for path in self.deleted_files {
+                if let Some(file_id) = file_set.file_for_path(&path) {
+                    file_set.remove(file_id);
+                    file_changes.push((file_id, None));
+                }
+            }
+            Some(file_set)
+        };
+
+        if let Some(file_set) = file_set {
+            db.set_file_set_with_durability(Arc::new(file_set), Durability::MEDIUM);
+        }
+        if let Some(names) = self.nixpkgs_attr_names {
+            db.set_nixpkgs_attr_names_with_durability(names, Durability::HIGH);
+        }
+        if let Some(size) = self.max_file_size {
+            db.set_max_file_size_with_durability(size, Durability::HIGH);
+        }
+        for (file_id, content) in file_changes {
             let content = content.unwrap_or_else(|| String::new().into());
             // TODO: Better guess of durability?
             db.set_file_content_with_durability(file_id, content, Durability::HIGH);
@@ -79,8 +284,18 @@ impl fmt::Debug for Change {
             .count();
         let cleared = self.file_changes.len() - modified;
         f.debug_struct("Change")
+            .field(
+                "file_set",
+                &self.file_set.as_ref().map(|set| set.paths.len()),
+            )
             .field("modified", &modified)
             .field("cleared", &cleared)
+            .field("created", &self.created_files.len())
+            .field("deleted", &self.deleted_files.len())
+            .field(
+                "nixpkgs_attr_names",
+                &self.nixpkgs_attr_names.as_ref().map(|names| names.len()),
+            )
             .finish_non_exhaustive()
     }
 }
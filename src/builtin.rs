@@ -1,113 +1,100 @@
-pub static NAMES: phf::Set<&'static str> = phf::phf_set! {
-    "__addErrorContext",
-    "__all",
-    "__any",
-    "__appendContext",
-    "__attrNames",
-    "__attrValues",
-    "__bitAnd",
-    "__bitOr",
-    "__bitXor",
-    "__catAttrs",
-    "__ceil",
-    "__compareVersions",
-    "__concatLists",
-    "__concatMap",
-    "__concatStringsSep",
-    "__currentSystem",
-    "__currentTime",
-    "__deepSeq",
-    "__div",
-    "__elem",
-    "__elemAt",
-    "__fetchurl",
-    "__filter",
-    "__filterSource",
-    "__findFile",
-    "__floor",
-    "__foldl'",
-    "__fromJSON",
-    "__functionArgs",
-    "__genList",
-    "__genericClosure",
-    "__getAttr",
-    "__getContext",
-    "__getEnv",
-    "__getFlake",
-    "__groupBy",
-    "__hasAttr",
-    "__hasContext",
-    "__hashFile",
-    "__hashString",
-    "__head",
-    "__intersectAttrs",
-    "__isAttrs",
-    "__isBool",
-    "__isFloat",
-    "__isFunction",
-    "__isInt",
-    "__isList",
-    "__isPath",
-    "__isString",
-    "__langVersion",
-    "__length",
-    "__lessThan",
-    "__listToAttrs",
-    "__mapAttrs",
-    "__match",
-    "__mul",
-    "__nixPath",
-    "__nixVersion",
-    "__parseDrvName",
-    "__partition",
-    "__path",
-    "__pathExists",
-    "__readDir",
-    "__readFile",
-    "__replaceStrings",
-    "__seq",
-    "__sort",
-    "__split",
-    "__splitVersion",
-    "__storeDir",
-    "__storePath",
-    "__stringLength",
-    "__sub",
-    "__substring",
-    "__tail",
-    "__toFile",
-    "__toJSON",
-    "__toPath",
-    "__toXML",
-    "__trace",
-    "__traceVerbose",
-    "__tryEval",
-    "__typeOf",
-    "__unsafeDiscardOutputDependency",
-    "__unsafeDiscardStringContext",
-    "__unsafeGetAttrPos",
-    "__zipAttrsWith",
-    "abort",
-    "baseNameOf",
-    "break",
-    "builtins",
-    "derivation",
-    "derivationStrict",
-    "dirOf",
-    "false",
-    "fetchGit",
-    "fetchMercurial",
-    "fetchTarball",
-    "fetchTree",
-    "fromTOML",
-    "import",
-    "isNull",
-    "map",
-    "null",
-    "placeholder",
-    "removeAttrs",
-    "scopedImport",
-    "throw",
-    "toString",
-    "true",
-};
+//! Static information about builtins of Nix.
+//! The table is generated from `dev/builtins.json`, see `dev/gen-builtins.py`.
+mod generated;
+
+use generated::BUILTINS;
+
+/// The kind of a builtin value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinKind {
+    Const,
+    Function,
+    Attrset,
+}
+
+/// Static information about a builtin, used for hover, completion and signature help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinInfo {
+    /// The attribute name under `builtins`.
+    pub name: &'static str,
+    pub kind: BuiltinKind,
+    /// Whether it is available as a global name, like `map`. Others are only global with a `__`
+    /// prefix, like `__length`.
+    pub global: bool,
+    /// The type signature in the style of the Nix manual, for functions.
+    pub signature: Option<&'static str>,
+    pub doc: &'static str,
+    /// The first Nix version providing this builtin, if it is not available since the beginning.
+    pub since: Option<&'static str>,
+    /// The suggested replacement if this builtin is deprecated.
+    pub deprecated: Option<&'static str>,
+}
+
+/// Get the information of a builtin by its attribute name or its `__`-prefixed global alias.
+pub fn get(name: &str) -> Option<&'static BuiltinInfo> {
+    BUILTINS
+        .get(name)
+        .or_else(|| BUILTINS.get(name.strip_prefix("__")?))
+}
+
+/// Resolve a global name, which is either a global builtin like `map`, or a `__`-prefixed alias
+/// of another one like `__length`.
+pub fn get_global(name: &str) -> Option<&'static BuiltinInfo> {
+    match name.strip_prefix("__") {
+        Some(attr) => BUILTINS.get(attr).filter(|info| !info.global),
+        None => BUILTINS.get(name).filter(|info| info.global),
+    }
+}
+
+/// Whether `builtins.<name>` exists. Unlike `get`, `__`-prefixed global aliases are not
+/// attributes of `builtins`.
+pub fn is_builtins_attr(name: &str) -> bool {
+    BUILTINS.contains_key(name)
+}
+
+/// Iterate over all known builtins, in no particular order.
+pub fn all() -> impl Iterator<Item = &'static BuiltinInfo> {
+    BUILTINS.values()
+}
+
+impl BuiltinInfo {
+    /// The name to refer to it without `builtins`, eg. `map` or `__length`.
+    pub fn global_name(&self) -> String {
+        if self.global {
+            self.name.to_owned()
+        } else {
+            format!("__{}", self.name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuiltinKind;
+
+    #[test]
+    fn lookup() {
+        let info = super::get("length").unwrap();
+        assert_eq!(info.name, "length");
+        assert_eq!(info.kind, BuiltinKind::Function);
+        assert_eq!(info.signature, Some("length :: [a] -> Int"));
+        assert_eq!(super::get("__length"), Some(info));
+        assert_eq!(super::get("true").unwrap().kind, BuiltinKind::Const);
+        assert_eq!(super::get("builtins").unwrap().kind, BuiltinKind::Attrset);
+        assert_eq!(super::get("nonexistent"), None);
+        assert!(super::is_builtins_attr("length"));
+        assert!(!super::is_builtins_attr("__length"));
+    }
+
+    #[test]
+    fn globals() {
+        assert_eq!(super::get_global("map").unwrap().name, "map");
+        assert_eq!(super::get_global("__length").unwrap().name, "length");
+        assert_eq!(super::get_global("__add").unwrap().name, "add");
+        assert_eq!(super::get_global("length"), None);
+        assert_eq!(super::get_global("__map"), None);
+        for info in super::all() {
+            assert_eq!(super::get_global(&info.global_name()), Some(info));
+        }
+    }
+}
@@ -0,0 +1,1050 @@
+//! The table of builtins.
+//! This file is generated by `dev/gen-builtins.py` from `dev/builtins.json`. Do not edit.
+use super::{BuiltinInfo, BuiltinKind};
+
+pub(super) static BUILTINS: phf::Map<&'static str, BuiltinInfo> = phf::phf_map! {
+    "abort" => BuiltinInfo {
+        name: "abort",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("abort :: String -> a"),
+        doc: "Abort evaluation with the given error message.",
+        since: None,
+        deprecated: None,
+    },
+    "add" => BuiltinInfo {
+        name: "add",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("add :: Number -> Number -> Number"),
+        doc: "Return the sum of two numbers.",
+        since: None,
+        deprecated: None,
+    },
+    "addErrorContext" => BuiltinInfo {
+        name: "addErrorContext",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("addErrorContext :: String -> a -> a"),
+        doc: "Attach a message to the error trace when evaluating the second argument fails. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "all" => BuiltinInfo {
+        name: "all",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("all :: (a -> Bool) -> [a] -> Bool"),
+        doc: "Return `true` if the predicate holds for all elements of the list.",
+        since: None,
+        deprecated: None,
+    },
+    "any" => BuiltinInfo {
+        name: "any",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("any :: (a -> Bool) -> [a] -> Bool"),
+        doc: "Return `true` if the predicate holds for at least one element of the list.",
+        since: None,
+        deprecated: None,
+    },
+    "appendContext" => BuiltinInfo {
+        name: "appendContext",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("appendContext :: String -> AttrSet -> String"),
+        doc: "Append string context to a string. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "attrNames" => BuiltinInfo {
+        name: "attrNames",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("attrNames :: AttrSet -> [String]"),
+        doc: "Return the names of the attributes in the set, sorted alphabetically.",
+        since: None,
+        deprecated: None,
+    },
+    "attrValues" => BuiltinInfo {
+        name: "attrValues",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("attrValues :: AttrSet -> [a]"),
+        doc: "Return the values of the attributes in the set, sorted by attribute names.",
+        since: None,
+        deprecated: None,
+    },
+    "baseNameOf" => BuiltinInfo {
+        name: "baseNameOf",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("baseNameOf :: String -> String"),
+        doc: "Return the last component of a path, or everything after the final slash of a string.",
+        since: None,
+        deprecated: None,
+    },
+    "bitAnd" => BuiltinInfo {
+        name: "bitAnd",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("bitAnd :: Int -> Int -> Int"),
+        doc: "Return the bitwise AND of two integers.",
+        since: None,
+        deprecated: None,
+    },
+    "bitOr" => BuiltinInfo {
+        name: "bitOr",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("bitOr :: Int -> Int -> Int"),
+        doc: "Return the bitwise OR of two integers.",
+        since: None,
+        deprecated: None,
+    },
+    "bitXor" => BuiltinInfo {
+        name: "bitXor",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("bitXor :: Int -> Int -> Int"),
+        doc: "Return the bitwise XOR of two integers.",
+        since: None,
+        deprecated: None,
+    },
+    "break" => BuiltinInfo {
+        name: "break",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("break :: a -> a"),
+        doc: "Pause evaluation in the debugger when it is enabled, otherwise return the argument.",
+        since: Some("2.9"),
+        deprecated: None,
+    },
+    "builtins" => BuiltinInfo {
+        name: "builtins",
+        kind: BuiltinKind::Attrset,
+        global: true,
+        signature: None,
+        doc: "The set of all builtin functions and values.",
+        since: None,
+        deprecated: None,
+    },
+    "catAttrs" => BuiltinInfo {
+        name: "catAttrs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("catAttrs :: String -> [AttrSet] -> [a]"),
+        doc: "Collect the attribute with the given name from each set in the list which has it.",
+        since: None,
+        deprecated: None,
+    },
+    "ceil" => BuiltinInfo {
+        name: "ceil",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("ceil :: Number -> Int"),
+        doc: "Round a number up to the nearest integer.",
+        since: Some("2.4"),
+        deprecated: None,
+    },
+    "compareVersions" => BuiltinInfo {
+        name: "compareVersions",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("compareVersions :: String -> String -> Int"),
+        doc: "Compare two version strings, returning -1, 0 or 1.",
+        since: None,
+        deprecated: None,
+    },
+    "concatLists" => BuiltinInfo {
+        name: "concatLists",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("concatLists :: [[a]] -> [a]"),
+        doc: "Concatenate a list of lists into a single list.",
+        since: None,
+        deprecated: None,
+    },
+    "concatMap" => BuiltinInfo {
+        name: "concatMap",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("concatMap :: (a -> [b]) -> [a] -> [b]"),
+        doc: "Map a function over a list and concatenate the resulting lists.",
+        since: None,
+        deprecated: None,
+    },
+    "concatStringsSep" => BuiltinInfo {
+        name: "concatStringsSep",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("concatStringsSep :: String -> [String] -> String"),
+        doc: "Concatenate a list of strings with a separator between each element.",
+        since: None,
+        deprecated: None,
+    },
+    "convertHash" => BuiltinInfo {
+        name: "convertHash",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("convertHash :: AttrSet -> String"),
+        doc: "Convert a hash in the attribute `hash` to the format `toHashFormat`, eg. `\"sri\"` or `\"base16\"`. `hashAlgo` is required if it is not in the SRI format.",
+        since: Some("2.19"),
+        deprecated: None,
+    },
+    "currentSystem" => BuiltinInfo {
+        name: "currentSystem",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The platform string of the running evaluator, eg. `x86_64-linux`.",
+        since: None,
+        deprecated: None,
+    },
+    "currentTime" => BuiltinInfo {
+        name: "currentTime",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The Unix time at the start of evaluation.",
+        since: None,
+        deprecated: None,
+    },
+    "deepSeq" => BuiltinInfo {
+        name: "deepSeq",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("deepSeq :: a -> b -> b"),
+        doc: "Evaluate the first argument deeply, then return the second.",
+        since: None,
+        deprecated: None,
+    },
+    "derivation" => BuiltinInfo {
+        name: "derivation",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("derivation :: AttrSet -> Derivation"),
+        doc: "Construct a derivation from a set of attributes.",
+        since: None,
+        deprecated: None,
+    },
+    "derivationStrict" => BuiltinInfo {
+        name: "derivationStrict",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("derivationStrict :: AttrSet -> AttrSet"),
+        doc: "The primitive underlying `derivation`. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "dirOf" => BuiltinInfo {
+        name: "dirOf",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("dirOf :: String -> String"),
+        doc: "Return the directory part of a path or string.",
+        since: None,
+        deprecated: None,
+    },
+    "div" => BuiltinInfo {
+        name: "div",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("div :: Number -> Number -> Number"),
+        doc: "Return the quotient of two numbers.",
+        since: None,
+        deprecated: None,
+    },
+    "elem" => BuiltinInfo {
+        name: "elem",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("elem :: a -> [a] -> Bool"),
+        doc: "Return `true` if the value occurs in the list.",
+        since: None,
+        deprecated: None,
+    },
+    "elemAt" => BuiltinInfo {
+        name: "elemAt",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("elemAt :: [a] -> Int -> a"),
+        doc: "Return the element of the list at the given zero-based index.",
+        since: None,
+        deprecated: None,
+    },
+    "false" => BuiltinInfo {
+        name: "false",
+        kind: BuiltinKind::Const,
+        global: true,
+        signature: None,
+        doc: "The boolean value `false`.",
+        since: None,
+        deprecated: None,
+    },
+    "fetchClosure" => BuiltinInfo {
+        name: "fetchClosure",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("fetchClosure :: AttrSet -> StorePath"),
+        doc: "Fetch a store path closure from the binary cache `fromStore`, optionally rewriting it to be content-addressed. Requires the experimental feature `fetch-closure`.",
+        since: Some("2.8"),
+        deprecated: None,
+    },
+    "fetchGit" => BuiltinInfo {
+        name: "fetchGit",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("fetchGit :: (String | AttrSet) -> AttrSet"),
+        doc: "Fetch a Git repository at evaluation time.",
+        since: None,
+        deprecated: None,
+    },
+    "fetchMercurial" => BuiltinInfo {
+        name: "fetchMercurial",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("fetchMercurial :: (String | AttrSet) -> AttrSet"),
+        doc: "Fetch a Mercurial repository at evaluation time.",
+        since: None,
+        deprecated: None,
+    },
+    "fetchTarball" => BuiltinInfo {
+        name: "fetchTarball",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("fetchTarball :: (String | AttrSet) -> Path"),
+        doc: "Download and unpack a tarball at evaluation time.",
+        since: None,
+        deprecated: None,
+    },
+    "fetchTree" => BuiltinInfo {
+        name: "fetchTree",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("fetchTree :: (String | AttrSet) -> AttrSet"),
+        doc: "Fetch a source tree described by a flake reference.",
+        since: Some("2.4"),
+        deprecated: None,
+    },
+    "fetchurl" => BuiltinInfo {
+        name: "fetchurl",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("fetchurl :: (String | AttrSet) -> Path"),
+        doc: "Download a file at evaluation time.",
+        since: None,
+        deprecated: None,
+    },
+    "filter" => BuiltinInfo {
+        name: "filter",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("filter :: (a -> Bool) -> [a] -> [a]"),
+        doc: "Return the elements of the list for which the predicate holds.",
+        since: None,
+        deprecated: None,
+    },
+    "filterSource" => BuiltinInfo {
+        name: "filterSource",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("filterSource :: (Path -> String -> Bool) -> Path -> Path"),
+        doc: "Copy a path to the store, keeping only files accepted by the predicate.",
+        since: None,
+        deprecated: None,
+    },
+    "findFile" => BuiltinInfo {
+        name: "findFile",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("findFile :: [AttrSet] -> String -> Path"),
+        doc: "Look up a path in a search path list, as used by `<name>` syntax.",
+        since: None,
+        deprecated: None,
+    },
+    "floor" => BuiltinInfo {
+        name: "floor",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("floor :: Number -> Int"),
+        doc: "Round a number down to the nearest integer.",
+        since: Some("2.4"),
+        deprecated: None,
+    },
+    "foldl'" => BuiltinInfo {
+        name: "foldl'",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("foldl' :: (b -> a -> b) -> b -> [a] -> b"),
+        doc: "Reduce a list from the left with a strict accumulator.",
+        since: None,
+        deprecated: None,
+    },
+    "fromJSON" => BuiltinInfo {
+        name: "fromJSON",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("fromJSON :: String -> a"),
+        doc: "Parse a JSON string into a Nix value.",
+        since: None,
+        deprecated: None,
+    },
+    "fromTOML" => BuiltinInfo {
+        name: "fromTOML",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("fromTOML :: String -> AttrSet"),
+        doc: "Parse a TOML string into a Nix value.",
+        since: None,
+        deprecated: None,
+    },
+    "functionArgs" => BuiltinInfo {
+        name: "functionArgs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("functionArgs :: (AttrSet -> a) -> AttrSet"),
+        doc: "Return the formal arguments of a function, mapped to whether they have defaults.",
+        since: None,
+        deprecated: None,
+    },
+    "genList" => BuiltinInfo {
+        name: "genList",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("genList :: (Int -> a) -> Int -> [a]"),
+        doc: "Generate a list of the given length by applying the function to each index.",
+        since: None,
+        deprecated: None,
+    },
+    "genericClosure" => BuiltinInfo {
+        name: "genericClosure",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("genericClosure :: AttrSet -> [AttrSet]"),
+        doc: "Compute the transitive closure of a start set under an operator.",
+        since: None,
+        deprecated: None,
+    },
+    "getAttr" => BuiltinInfo {
+        name: "getAttr",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("getAttr :: String -> AttrSet -> a"),
+        doc: "Return the attribute with the given name from the set.",
+        since: None,
+        deprecated: None,
+    },
+    "getContext" => BuiltinInfo {
+        name: "getContext",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("getContext :: String -> AttrSet"),
+        doc: "Return the string context of a string. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "getEnv" => BuiltinInfo {
+        name: "getEnv",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("getEnv :: String -> String"),
+        doc: "Return the value of an environment variable, or an empty string if unset.",
+        since: None,
+        deprecated: None,
+    },
+    "getFlake" => BuiltinInfo {
+        name: "getFlake",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("getFlake :: String -> AttrSet"),
+        doc: "Fetch a flake and return its outputs.",
+        since: Some("2.4"),
+        deprecated: None,
+    },
+    "groupBy" => BuiltinInfo {
+        name: "groupBy",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("groupBy :: (a -> String) -> [a] -> AttrSet"),
+        doc: "Group the elements of a list into a set by the key returned from the function.",
+        since: Some("2.5"),
+        deprecated: None,
+    },
+    "hasAttr" => BuiltinInfo {
+        name: "hasAttr",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("hasAttr :: String -> AttrSet -> Bool"),
+        doc: "Return `true` if the set has an attribute with the given name.",
+        since: None,
+        deprecated: None,
+    },
+    "hasContext" => BuiltinInfo {
+        name: "hasContext",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("hasContext :: String -> Bool"),
+        doc: "Return `true` if the string has a non-empty context.",
+        since: None,
+        deprecated: None,
+    },
+    "hashFile" => BuiltinInfo {
+        name: "hashFile",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("hashFile :: String -> Path -> String"),
+        doc: "Return the base-16 hash of a file with the given algorithm.",
+        since: None,
+        deprecated: None,
+    },
+    "hashString" => BuiltinInfo {
+        name: "hashString",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("hashString :: String -> String -> String"),
+        doc: "Return the base-16 hash of a string with the given algorithm.",
+        since: None,
+        deprecated: None,
+    },
+    "head" => BuiltinInfo {
+        name: "head",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("head :: [a] -> a"),
+        doc: "Return the first element of a list.",
+        since: None,
+        deprecated: None,
+    },
+    "import" => BuiltinInfo {
+        name: "import",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("import :: Path -> a"),
+        doc: "Load, parse and evaluate the Nix expression in a file.",
+        since: None,
+        deprecated: None,
+    },
+    "intersectAttrs" => BuiltinInfo {
+        name: "intersectAttrs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("intersectAttrs :: AttrSet -> AttrSet -> AttrSet"),
+        doc: "Return the attributes of the second set whose names also occur in the first.",
+        since: None,
+        deprecated: None,
+    },
+    "isAttrs" => BuiltinInfo {
+        name: "isAttrs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isAttrs :: a -> Bool"),
+        doc: "Return `true` if the value is a set.",
+        since: None,
+        deprecated: None,
+    },
+    "isBool" => BuiltinInfo {
+        name: "isBool",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isBool :: a -> Bool"),
+        doc: "Return `true` if the value is a boolean.",
+        since: None,
+        deprecated: None,
+    },
+    "isFloat" => BuiltinInfo {
+        name: "isFloat",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isFloat :: a -> Bool"),
+        doc: "Return `true` if the value is a float.",
+        since: None,
+        deprecated: None,
+    },
+    "isFunction" => BuiltinInfo {
+        name: "isFunction",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isFunction :: a -> Bool"),
+        doc: "Return `true` if the value is a function.",
+        since: None,
+        deprecated: None,
+    },
+    "isInt" => BuiltinInfo {
+        name: "isInt",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isInt :: a -> Bool"),
+        doc: "Return `true` if the value is an integer.",
+        since: None,
+        deprecated: None,
+    },
+    "isList" => BuiltinInfo {
+        name: "isList",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isList :: a -> Bool"),
+        doc: "Return `true` if the value is a list.",
+        since: None,
+        deprecated: None,
+    },
+    "isNull" => BuiltinInfo {
+        name: "isNull",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("isNull :: a -> Bool"),
+        doc: "Return `true` if the value is `null`. Deprecated, use `x == null` instead.",
+        since: None,
+        deprecated: Some("`x == null`"),
+    },
+    "isPath" => BuiltinInfo {
+        name: "isPath",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isPath :: a -> Bool"),
+        doc: "Return `true` if the value is a path.",
+        since: None,
+        deprecated: None,
+    },
+    "isString" => BuiltinInfo {
+        name: "isString",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("isString :: a -> Bool"),
+        doc: "Return `true` if the value is a string.",
+        since: None,
+        deprecated: None,
+    },
+    "langVersion" => BuiltinInfo {
+        name: "langVersion",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The current version of the Nix language.",
+        since: None,
+        deprecated: None,
+    },
+    "length" => BuiltinInfo {
+        name: "length",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("length :: [a] -> Int"),
+        doc: "Return the length of a list.",
+        since: None,
+        deprecated: None,
+    },
+    "lessThan" => BuiltinInfo {
+        name: "lessThan",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("lessThan :: a -> a -> Bool"),
+        doc: "Return `true` if the first value is less than the second.",
+        since: None,
+        deprecated: None,
+    },
+    "listToAttrs" => BuiltinInfo {
+        name: "listToAttrs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("listToAttrs :: [{ name :: String; value :: a; }] -> AttrSet"),
+        doc: "Construct a set from a list of name-value pairs.",
+        since: None,
+        deprecated: None,
+    },
+    "map" => BuiltinInfo {
+        name: "map",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("map :: (a -> b) -> [a] -> [b]"),
+        doc: "Apply a function to each element of a list.",
+        since: None,
+        deprecated: None,
+    },
+    "mapAttrs" => BuiltinInfo {
+        name: "mapAttrs",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("mapAttrs :: (String -> a -> b) -> AttrSet -> AttrSet"),
+        doc: "Apply a function to each attribute of a set, passing the name and the value.",
+        since: None,
+        deprecated: None,
+    },
+    "match" => BuiltinInfo {
+        name: "match",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("match :: String -> String -> Null | [String]"),
+        doc: "Match a string against a POSIX regular expression, returning the capture groups.",
+        since: None,
+        deprecated: None,
+    },
+    "mul" => BuiltinInfo {
+        name: "mul",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("mul :: Number -> Number -> Number"),
+        doc: "Return the product of two numbers.",
+        since: None,
+        deprecated: None,
+    },
+    "nixPath" => BuiltinInfo {
+        name: "nixPath",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The search path list used to resolve `<name>` paths.",
+        since: None,
+        deprecated: None,
+    },
+    "nixVersion" => BuiltinInfo {
+        name: "nixVersion",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The version of the running Nix evaluator.",
+        since: None,
+        deprecated: None,
+    },
+    "null" => BuiltinInfo {
+        name: "null",
+        kind: BuiltinKind::Const,
+        global: true,
+        signature: None,
+        doc: "The null value.",
+        since: None,
+        deprecated: None,
+    },
+    "outputOf" => BuiltinInfo {
+        name: "outputOf",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("outputOf :: String -> String -> String"),
+        doc: "Return the placeholder of an output of a derivation, which may itself be an output of another derivation. Requires the experimental feature `dynamic-derivations`.",
+        since: Some("2.18"),
+        deprecated: None,
+    },
+    "parseDrvName" => BuiltinInfo {
+        name: "parseDrvName",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("parseDrvName :: String -> AttrSet"),
+        doc: "Split a package name into its `name` and `version` parts.",
+        since: None,
+        deprecated: None,
+    },
+    "partition" => BuiltinInfo {
+        name: "partition",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("partition :: (a -> Bool) -> [a] -> { right :: [a]; wrong :: [a]; }"),
+        doc: "Split a list into the elements which satisfy the predicate and those which do not.",
+        since: None,
+        deprecated: None,
+    },
+    "path" => BuiltinInfo {
+        name: "path",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("path :: AttrSet -> Path"),
+        doc: "Copy a path to the store with an optional name, filter and hash.",
+        since: None,
+        deprecated: None,
+    },
+    "pathExists" => BuiltinInfo {
+        name: "pathExists",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("pathExists :: Path -> Bool"),
+        doc: "Return `true` if the path exists at evaluation time.",
+        since: None,
+        deprecated: None,
+    },
+    "placeholder" => BuiltinInfo {
+        name: "placeholder",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("placeholder :: String -> String"),
+        doc: "Return a placeholder string for the given output of the current derivation.",
+        since: None,
+        deprecated: None,
+    },
+    "readDir" => BuiltinInfo {
+        name: "readDir",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("readDir :: Path -> AttrSet"),
+        doc: "Return the entries of a directory mapped to their file types.",
+        since: None,
+        deprecated: None,
+    },
+    "readFile" => BuiltinInfo {
+        name: "readFile",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("readFile :: Path -> String"),
+        doc: "Return the contents of a file as a string.",
+        since: None,
+        deprecated: None,
+    },
+    "readFileType" => BuiltinInfo {
+        name: "readFileType",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("readFileType :: Path -> String"),
+        doc: "Return the type of a directory entry: `\"regular\"`, `\"directory\"`, `\"symlink\"` or `\"unknown\"`.",
+        since: Some("2.14"),
+        deprecated: None,
+    },
+    "removeAttrs" => BuiltinInfo {
+        name: "removeAttrs",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("removeAttrs :: AttrSet -> [String] -> AttrSet"),
+        doc: "Remove the attributes with the given names from a set.",
+        since: None,
+        deprecated: None,
+    },
+    "replaceStrings" => BuiltinInfo {
+        name: "replaceStrings",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("replaceStrings :: [String] -> [String] -> String -> String"),
+        doc: "Replace occurrences of each string in the first list by the corresponding one in the second.",
+        since: None,
+        deprecated: None,
+    },
+    "scopedImport" => BuiltinInfo {
+        name: "scopedImport",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("scopedImport :: AttrSet -> Path -> a"),
+        doc: "Like `import`, but evaluate the file with extra names in scope. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "seq" => BuiltinInfo {
+        name: "seq",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("seq :: a -> b -> b"),
+        doc: "Evaluate the first argument shallowly, then return the second.",
+        since: None,
+        deprecated: None,
+    },
+    "sort" => BuiltinInfo {
+        name: "sort",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("sort :: (a -> a -> Bool) -> [a] -> [a]"),
+        doc: "Sort a list with a strict less-than comparator.",
+        since: None,
+        deprecated: None,
+    },
+    "split" => BuiltinInfo {
+        name: "split",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("split :: String -> String -> [String | [String]]"),
+        doc: "Split a string by a POSIX regular expression, keeping the capture groups.",
+        since: None,
+        deprecated: None,
+    },
+    "splitVersion" => BuiltinInfo {
+        name: "splitVersion",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("splitVersion :: String -> [String]"),
+        doc: "Split a version string into its components.",
+        since: None,
+        deprecated: None,
+    },
+    "storeDir" => BuiltinInfo {
+        name: "storeDir",
+        kind: BuiltinKind::Const,
+        global: false,
+        signature: None,
+        doc: "The path of the Nix store, usually `/nix/store`.",
+        since: None,
+        deprecated: None,
+    },
+    "storePath" => BuiltinInfo {
+        name: "storePath",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("storePath :: Path -> Path"),
+        doc: "Turn a store path into a dependency of the string context.",
+        since: None,
+        deprecated: None,
+    },
+    "stringLength" => BuiltinInfo {
+        name: "stringLength",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("stringLength :: String -> Int"),
+        doc: "Return the length of a string in bytes.",
+        since: None,
+        deprecated: None,
+    },
+    "sub" => BuiltinInfo {
+        name: "sub",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("sub :: Number -> Number -> Number"),
+        doc: "Return the difference of two numbers.",
+        since: None,
+        deprecated: None,
+    },
+    "substring" => BuiltinInfo {
+        name: "substring",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("substring :: Int -> Int -> String -> String"),
+        doc: "Return the substring starting at the given offset with at most the given length.",
+        since: None,
+        deprecated: None,
+    },
+    "tail" => BuiltinInfo {
+        name: "tail",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("tail :: [a] -> [a]"),
+        doc: "Return a list without its first element.",
+        since: None,
+        deprecated: None,
+    },
+    "throw" => BuiltinInfo {
+        name: "throw",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("throw :: String -> a"),
+        doc: "Throw a catchable error with the given message.",
+        since: None,
+        deprecated: None,
+    },
+    "toFile" => BuiltinInfo {
+        name: "toFile",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("toFile :: String -> String -> Path"),
+        doc: "Write a string to a file in the store and return its path.",
+        since: None,
+        deprecated: None,
+    },
+    "toJSON" => BuiltinInfo {
+        name: "toJSON",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("toJSON :: a -> String"),
+        doc: "Serialize a value to a JSON string.",
+        since: None,
+        deprecated: None,
+    },
+    "toPath" => BuiltinInfo {
+        name: "toPath",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("toPath :: String -> Path"),
+        doc: "Convert a string to an absolute path. Deprecated.",
+        since: None,
+        deprecated: Some("`/. + \"/path\"`"),
+    },
+    "toString" => BuiltinInfo {
+        name: "toString",
+        kind: BuiltinKind::Function,
+        global: true,
+        signature: Some("toString :: a -> String"),
+        doc: "Convert a value to a string.",
+        since: None,
+        deprecated: None,
+    },
+    "toXML" => BuiltinInfo {
+        name: "toXML",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("toXML :: a -> String"),
+        doc: "Serialize a value to an XML string.",
+        since: None,
+        deprecated: None,
+    },
+    "trace" => BuiltinInfo {
+        name: "trace",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("trace :: a -> b -> b"),
+        doc: "Print the first argument to stderr, then return the second.",
+        since: None,
+        deprecated: None,
+    },
+    "traceVerbose" => BuiltinInfo {
+        name: "traceVerbose",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("traceVerbose :: a -> b -> b"),
+        doc: "Like `trace`, but only prints when `--trace-verbose` is enabled.",
+        since: Some("2.10"),
+        deprecated: None,
+    },
+    "true" => BuiltinInfo {
+        name: "true",
+        kind: BuiltinKind::Const,
+        global: true,
+        signature: None,
+        doc: "The boolean value `true`.",
+        since: None,
+        deprecated: None,
+    },
+    "tryEval" => BuiltinInfo {
+        name: "tryEval",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("tryEval :: a -> { success :: Bool; value :: a; }"),
+        doc: "Evaluate a value, catching errors thrown by `throw` or `assert`.",
+        since: None,
+        deprecated: None,
+    },
+    "typeOf" => BuiltinInfo {
+        name: "typeOf",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("typeOf :: a -> String"),
+        doc: "Return the name of the type of a value.",
+        since: None,
+        deprecated: None,
+    },
+    "unsafeDiscardOutputDependency" => BuiltinInfo {
+        name: "unsafeDiscardOutputDependency",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("unsafeDiscardOutputDependency :: String -> String"),
+        doc: "Turn output dependencies of a string context into plain ones. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "unsafeDiscardStringContext" => BuiltinInfo {
+        name: "unsafeDiscardStringContext",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("unsafeDiscardStringContext :: String -> String"),
+        doc: "Remove the string context of a string. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "unsafeGetAttrPos" => BuiltinInfo {
+        name: "unsafeGetAttrPos",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("unsafeGetAttrPos :: String -> AttrSet -> Null | AttrSet"),
+        doc: "Return the source position of an attribute definition. Mostly internal.",
+        since: None,
+        deprecated: None,
+    },
+    "zipAttrsWith" => BuiltinInfo {
+        name: "zipAttrsWith",
+        kind: BuiltinKind::Function,
+        global: false,
+        signature: Some("zipAttrsWith :: (String -> [a] -> b) -> [AttrSet] -> AttrSet"),
+        doc: "Merge a list of sets, combining the values of each name with the function.",
+        since: Some("2.6"),
+        deprecated: None,
+    },
+};
@@ -0,0 +1,150 @@
+use super::{DefDatabase, NameDefId};
+use crate::FileId;
+use rowan::NodeOrToken;
+use syntax::{SyntaxKind, SyntaxNode};
+
+pub(crate) fn binding_doc_query(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    def: NameDefId,
+) -> Option<String> {
+    let root = db.parse(file_id).value.syntax_node();
+    let name = db
+        .source_map(file_id)
+        .node_for_name_def(def)?
+        .to_node(&root);
+    // Only the first attr of a binding owns the comments, not names in lambda patterns inside.
+    let parent = name.parent()?;
+    let entry = match parent.kind() {
+        SyntaxKind::INHERIT => parent,
+        SyntaxKind::ATTR_PATH => parent.parent()?,
+        _ => return None,
+    };
+    doc_comment(&entry)
+}
+
+/// The comments right above a binding `a = 1;` or `inherit a;`, with comment markers stripped.
+/// They end at a blank line, and a comment after code on the same line belongs to that code.
+fn doc_comment(entry: &SyntaxNode) -> Option<String> {
+    if !matches!(
+        entry.kind(),
+        SyntaxKind::ATTR_PATH_VALUE | SyntaxKind::INHERIT
+    ) {
+        return None;
+    }
+    let mut comments = Vec::new();
+    let mut cur = entry.prev_sibling_or_token();
+    while let Some(elem) = cur {
+        match &elem {
+            NodeOrToken::Token(tok) if tok.kind() == SyntaxKind::SPACE => {
+                if tok.text().matches('\n').count() >= 2 {
+                    break;
+                }
+            }
+            NodeOrToken::Token(tok) if tok.kind() == SyntaxKind::COMMENT => {
+                let is_trailing = match tok.prev_sibling_or_token() {
+                    Some(NodeOrToken::Token(space)) if space.kind() == SyntaxKind::SPACE => {
+                        !space.text().contains('\n')
+                            && matches!(space.prev_sibling_or_token(), Some(NodeOrToken::Node(_)))
+                    }
+                    Some(NodeOrToken::Node(_)) => true,
+                    _ => false,
+                };
+                if is_trailing {
+                    break;
+                }
+                comments.push(strip_comment(tok.text()));
+            }
+            _ => break,
+        }
+        cur = elem.prev_sibling_or_token();
+    }
+    if comments.is_empty() {
+        return None;
+    }
+    comments.reverse();
+    Some(comments.join("\n"))
+}
+
+fn strip_comment(text: &str) -> String {
+    if let Some(line) = text.strip_prefix('#') {
+        return line.strip_prefix(' ').unwrap_or(line).trim_end().to_owned();
+    }
+    let body = text
+        .trim_start_matches("/*")
+        .trim_start_matches('*')
+        .trim_end_matches("*/");
+    body.lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line)
+        })
+        .skip_while(|line| line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::def::DefDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let root = db.parse(file_id).value.syntax_node();
+        let tok = root.token_at_offset(pos).right_biased().unwrap();
+        let def = tok
+            .parent_ancestors()
+            .find_map(|node| db.source_map(file_id).name_def_for_node(&node))
+            .expect("No definition");
+        let doc = db.binding_doc(file_id, def);
+        expect.assert_eq(doc.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn line_comments() {
+        check(
+            "
+let
+  # Say hello.
+  #
+  # To someone.
+  $0greet = name: name;
+in greet
+            ",
+            expect![[r#"
+                Say hello.
+
+                To someone."#]],
+        );
+        check("let a = 1; # a\n  $0b = 2; in b", expect!["None"]);
+        check("let # a\n  # b\n\n  $0b = 2; in b", expect!["None"]);
+        check("let\n  # a\n  inherit ({ }) x $0y; in y", expect!["a"]);
+        check("# a\n{ $0x }: x", expect!["None"]);
+    }
+
+    #[test]
+    fn block_comments() {
+        check(
+            "
+rec {
+  /**
+   * Say hello.
+   * To someone.
+   */
+  $0greet = name: name;
+}
+            ",
+            expect![[r#"
+                Say hello.
+                To someone."#]],
+        );
+        check("rec { /* a */ $0b = 1; }", expect!["a"]);
+    }
+}
@@ -4,12 +4,15 @@ use super::{
 };
 use crate::{Diagnostic, DiagnosticKind, FileId, InFile};
 use indexmap::IndexMap;
-use la_arena::Arena;
+use la_arena::{Arena, ArenaMap};
 use rowan::ast::AstNode;
 use smol_str::SmolStr;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::{mem, str};
 use syntax::ast::{self, HasBindings, HasStringParts, LiteralKind};
-use syntax::{Parse, TextRange};
+use syntax::lexer::is_valid_ident;
+use syntax::{match_ast, Parse, SyntaxNode, TextRange};
 
 pub(super) fn lower(parse: InFile<Parse>) -> (Module, ModuleSourceMap) {
     let mut ctx = LowerCtx {
@@ -17,6 +20,7 @@ pub(super) fn lower(parse: InFile<Parse>) -> (Module, ModuleSourceMap) {
         module: Module {
             exprs: Arena::new(),
             name_defs: Arena::new(),
+            name_def_values: ArenaMap::default(),
             // Placeholder.
             entry_expr: ExprId::from_raw(0.into()),
             diagnostics: Vec::new(),
@@ -27,6 +31,17 @@ pub(super) fn lower(parse: InFile<Parse>) -> (Module, ModuleSourceMap) {
     let entry = ctx.lower_expr_opt(parse.value.root().expr());
     let mut module = ctx.module;
     module.entry_expr = entry;
+    for (_, expr) in module.exprs.iter() {
+        if let Expr::Attrset(bindings) | Expr::LetAttrset(bindings) | Expr::LetIn(bindings, _) =
+            expr
+        {
+            for (key, value) in bindings.entries.iter() {
+                if let (&BindingKey::NameDef(def), &BindingValue::Expr(value)) = (key, value) {
+                    module.name_def_values.insert(def, value);
+                }
+            }
+        }
+    }
     (module, ctx.source_map)
 }
 
@@ -52,7 +67,60 @@ impl LowerCtx {
     }
 
     fn diagnostic(&mut self, range: TextRange, kind: DiagnosticKind) {
-        self.module.diagnostics.push(Diagnostic { range, kind });
+        self.module.diagnostics.push(Diagnostic::new(range, kind));
+    }
+
+    /// Report pattern fields with the same name as a previous field or the `@` binding.
+    fn check_duplicated_pat_fields(&mut self, param: &ast::Param) {
+        let fields = param
+            .pat()
+            .into_iter()
+            .flat_map(|pat| pat.fields())
+            .filter_map(|field| field.name())
+            .filter_map(|name| Some((SmolStr::from(name.token()?.text()), name)))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashMap::new();
+        for (text, name) in &fields {
+            let range = name.syntax().text_range();
+            match seen.entry(text) {
+                Entry::Vacant(entry) => {
+                    entry.insert(range);
+                }
+                Entry::Occupied(entry) => {
+                    let diag = Diagnostic::new(range, DiagnosticKind::DuplicatedPatField)
+                        .with_note(
+                            InFile::new(self.file_id, *entry.get()),
+                            "Previously defined here",
+                        );
+                    self.module.diagnostics.push(diag);
+                }
+            }
+        }
+
+        // `args@{ args }: ...` binds the same name twice, which is an error in Nix.
+        let bind_name = match param.name() {
+            Some(name) => name,
+            None => return,
+        };
+        let bind_text = match bind_name.token() {
+            Some(tok) => SmolStr::from(tok.text()),
+            None => return,
+        };
+        if let Some((_, field)) = fields.iter().find(|(text, _)| *text == bind_text) {
+            let bind_range = bind_name.syntax().text_range();
+            let field_range = field.syntax().text_range();
+            // The `@` binding can be either before or after the pattern. Report the latter one.
+            let range = if bind_range.start() < field_range.start() {
+                field_range
+            } else {
+                bind_range
+            };
+            let diag = Diagnostic::new(range, DiagnosticKind::PatternBindCollision)
+                .with_note(InFile::new(self.file_id, bind_range), "The `@` binding")
+                .with_note(InFile::new(self.file_id, field_range), "The pattern field");
+            self.module.diagnostics.push(diag);
+        }
     }
 
     fn lower_name(&mut self, node: ast::Name) -> NameDefId {
@@ -79,10 +147,21 @@ impl LowerCtx {
                 self.alloc_expr(lit.map_or(Expr::Missing, Expr::Literal), ptr)
             }
             ast::Expr::Ref(e) => {
-                let name = e
+                let name: SmolStr = e
                     .token()
                     .map_or_else(Default::default, |tok| tok.text().into());
-                self.alloc_expr(Expr::Reference(name), ptr)
+                // Builtin constants, unless they are shadowed.
+                let lit = match &*name {
+                    "true" => Some(Literal::Bool(true)),
+                    "false" => Some(Literal::Bool(false)),
+                    "null" => Some(Literal::Null),
+                    _ => None,
+                };
+                let expr = match lit {
+                    Some(lit) if !is_locally_bound(e.syntax(), &name) => Expr::Literal(lit),
+                    _ => Expr::Reference(name),
+                };
+                self.alloc_expr(expr, ptr)
             }
             ast::Expr::Apply(e) => {
                 let func = self.lower_expr_opt(e.function());
@@ -91,6 +170,9 @@ impl LowerCtx {
             }
             ast::Expr::Paren(e) => self.lower_expr_opt(e.expr()),
             ast::Expr::Lambda(e) => {
+                if let Some(param) = e.param() {
+                    self.check_duplicated_pat_fields(&param);
+                }
                 let (param, pat) = e.param().map_or((None, None), |param| {
                     let name = param.name().map(|n| self.lower_name(n));
                     let pat = param.pat().map(|pat| {
@@ -179,7 +261,7 @@ impl LowerCtx {
                     .path_parts()
                     .filter_map(|part| match part {
                         ast::PathPart::Fragment(_) => None,
-                        ast::PathPart::Dynamic(d) => Some(self.lower_expr_opt(d.expr())),
+                        ast::PathPart::Dynamic(d) => Some(self.lower_dynamic(&d)),
                     })
                     .collect();
                 self.alloc_expr(Expr::PathInterpolation(parts), ptr)
@@ -204,7 +286,7 @@ impl LowerCtx {
                 } else if ret.is_empty() {
                     supers += 1;
                 } else {
-                    let last_slash = ret.bytes().rposition(|c| c != b'/').unwrap_or(0);
+                    let last_slash = ret.bytes().rposition(|c| c == b'/').unwrap_or(0);
                     ret.truncate(last_slash);
                 }
             }
@@ -254,7 +336,15 @@ impl LowerCtx {
             .into_iter()
             .flat_map(|attrpath| attrpath.attrs())
             .map(|attr| match attr {
-                ast::Attr::Dynamic(d) => self.lower_expr_opt(d.expr()),
+                ast::Attr::Dynamic(d) => {
+                    let id = self.lower_dynamic(&d);
+                    // Also map the whole `${}`, so every segment is found by its attr node.
+                    // An empty `${}` has no other text for the missing expression.
+                    let ptr = AstPtr::new(d.syntax());
+                    self.source_map.expr_map.insert(ptr.clone(), id);
+                    self.source_map.expr_map_rev.entry(id).or_insert(ptr);
+                    id
+                }
                 ast::Attr::Name(n) => {
                     let name = n
                         .token()
@@ -267,6 +357,18 @@ impl LowerCtx {
             .collect()
     }
 
+    /// `${expr}` in strings, paths and attrpaths. An empty `${}` lowers to `Expr::Missing`.
+    fn lower_dynamic(&mut self, d: &ast::Dynamic) -> ExprId {
+        self.check_empty_dynamic(d);
+        self.lower_expr_opt(d.expr())
+    }
+
+    fn check_empty_dynamic(&mut self, d: &ast::Dynamic) {
+        if d.expr().is_none() {
+            self.diagnostic(d.syntax().text_range(), DiagnosticKind::EmptyInterpolation);
+        }
+    }
+
     fn lower_string(&mut self, n: &impl HasStringParts) -> ExprId {
         let ptr = AstPtr::new(n.syntax());
         // Here we don't need to special case literal strings.
@@ -275,7 +377,7 @@ impl LowerCtx {
             .string_parts()
             .filter_map(|part| {
                 match part {
-                    ast::StringPart::Dynamic(d) => Some(self.lower_expr_opt(d.expr())),
+                    ast::StringPart::Dynamic(d) => Some(self.lower_dynamic(&d)),
                     // Currently we don't encode literal fragments.
                     ast::StringPart::Fragment(_) | ast::StringPart::Escape(_) => None,
                 }
@@ -284,61 +386,169 @@ impl LowerCtx {
         self.alloc_expr(Expr::StringInterpolation(parts), ptr)
     }
 
-    fn lower_key(&mut self, is_rec: bool, attr: ast::Attr) -> BindingKey {
+    /// `rec_names` is the names already defined in the `rec` set or `let`, or `None` if the
+    /// bindings are not recursive. The same name always lowers to the same `NameDef`,
+    /// so that merged bindings like `let a.b = 1; a.c = 2; in a` share a definition.
+    fn lower_key(
+        &mut self,
+        rec_names: Option<&mut HashMap<SmolStr, NameDefId>>,
+        attr: ast::Attr,
+    ) -> BindingKey {
+        let is_quoted = matches!(attr, ast::Attr::String(_));
         let ast_string = match attr {
-            ast::Attr::Name(n) if is_rec => return BindingKey::NameDef(self.lower_name(n)),
             ast::Attr::Name(n) => {
-                return BindingKey::Name(
-                    n.token()
-                        .map_or_else(Default::default, |tok| tok.text().into()),
-                )
+                let name = n
+                    .token()
+                    .map_or_else(Default::default, |tok| tok.text().into());
+                return self.static_key(rec_names, name, AstPtr::new(n.syntax()));
             }
             ast::Attr::String(s) => s,
             ast::Attr::Dynamic(d) => {
+                self.check_empty_dynamic(&d);
                 let mut e = d.expr();
                 loop {
                     match e {
                         Some(ast::Expr::String(s)) => break s,
                         Some(ast::Expr::Paren(p)) => e = p.expr(),
+                        // Concatenation of literal strings, eg. `${"a" + "b"}`.
+                        Some(ast::Expr::BinaryOp(op)) => {
+                            let ptr = AstPtr::new(op.syntax());
+                            return match static_concat_content(&op) {
+                                Some(content) => self.static_key(rec_names, content.into(), ptr),
+                                None => {
+                                    BindingKey::Dynamic(self.lower_expr(ast::Expr::BinaryOp(op)))
+                                }
+                            };
+                        }
                         _ => return BindingKey::Dynamic(self.lower_expr_opt(e)),
                     }
                 }
             }
         };
 
-        if ast_string
-            .string_parts()
-            .all(|part| !matches!(part, ast::StringPart::Dynamic(_)))
-        {
+        if let Some(content) = static_string_content(&ast_string) {
             let ptr = AstPtr::new(ast_string.syntax());
-            let content = ast_string
-                .string_parts()
-                .fold(String::new(), |prev, part| match part {
-                    ast::StringPart::Dynamic(_) => unreachable!(),
-                    ast::StringPart::Fragment(tok) => prev + tok.text(),
-                    ast::StringPart::Escape(tok) => match tok.text().as_bytes() {
-                        b"\\n" => prev + "\n",
-                        b"\\r" => prev + "\r",
-                        b"\\t" => prev + "\t",
-                        [b'\\', bytes @ ..] => {
-                            prev + str::from_utf8(bytes).expect("Verified by the lexer")
-                        }
-                        _ => unreachable!("Verified by the lexer"),
-                    },
-                });
-            if is_rec {
-                return BindingKey::NameDef(self.alloc_name_def(content.into(), ptr));
-            } else {
-                return BindingKey::Name(content.into());
+            if is_quoted && is_valid_ident(&content) {
+                self.diagnostic(ptr.text_range(), DiagnosticKind::UnnecessaryQuote);
             }
+            return self.static_key(rec_names, content.into(), ptr);
         }
 
         BindingKey::Dynamic(self.lower_string(&ast_string))
     }
+
+    fn static_key(
+        &mut self,
+        rec_names: Option<&mut HashMap<SmolStr, NameDefId>>,
+        name: SmolStr,
+        ptr: AstPtr,
+    ) -> BindingKey {
+        match rec_names {
+            Some(names) => BindingKey::NameDef(self.rec_name_def(names, name, ptr)),
+            None => BindingKey::Name(name),
+        }
+    }
+
+    fn rec_name_def(
+        &mut self,
+        names: &mut HashMap<SmolStr, NameDefId>,
+        name: SmolStr,
+        ptr: AstPtr,
+    ) -> NameDefId {
+        match names.entry(name) {
+            // Later definition sites map to the first one.
+            Entry::Occupied(entry) => {
+                let id = *entry.get();
+                self.source_map.name_def_map.insert(ptr, id);
+                id
+            }
+            Entry::Vacant(entry) => {
+                let id = self.alloc_name_def(entry.key().clone(), ptr);
+                entry.insert(id);
+                id
+            }
+        }
+    }
+}
+
+/// Whether `name` is defined by a `let`, `rec` or lambda enclosing `node`.
+/// This is checked syntactically, since names are resolved only after lowering.
+fn is_locally_bound(node: &SyntaxNode, name: &str) -> bool {
+    fn is_name(attr: ast::Attr, name: &str) -> bool {
+        match attr {
+            ast::Attr::Name(n) => n.token().map_or(false, |tok| tok.text() == name),
+            ast::Attr::String(s) => static_string_content(&s).map_or(false, |s| s == name),
+            ast::Attr::Dynamic(_) => false,
+        }
+    }
+    fn defines(n: &impl HasBindings, name: &str) -> bool {
+        n.bindings().any(|binding| match binding {
+            ast::Binding::AttrpathValue(b) => b
+                .attrpath()
+                .and_then(|path| path.attrs().next())
+                .map_or(false, |attr| is_name(attr, name)),
+            ast::Binding::Inherit(i) => i.attrs().any(|attr| is_name(attr, name)),
+        })
+    }
+    let is_param = |n: Option<ast::Name>| {
+        n.and_then(|n| n.token())
+            .map_or(false, |tok| tok.text() == name)
+    };
+    node.ancestors().any(|node| {
+        match_ast! {
+            match node {
+                ast::LetIn(n) => defines(&n, name),
+                ast::AttrSet(n) => {
+                    (n.rec_token().is_some() || n.let_token().is_some()) && defines(&n, name)
+                },
+                ast::Lambda(n) => n.param().map_or(false, |param| {
+                    is_param(param.name())
+                        || param.pat().map_or(false, |pat| pat.fields().any(|f| is_param(f.name())))
+                }),
+                _ => false,
+            }
+        }
+    })
+}
+
+/// The decoded content of a string without interpolations.
+pub(crate) fn static_string_content(s: &impl HasStringParts) -> Option<String> {
+    s.string_parts()
+        .try_fold(String::new(), |prev, part| match part {
+            ast::StringPart::Dynamic(_) => None,
+            ast::StringPart::Fragment(tok) => Some(prev + tok.text()),
+            ast::StringPart::Escape(tok) => Some(match tok.text().as_bytes() {
+                b"\\n" => prev + "\n",
+                b"\\r" => prev + "\r",
+                b"\\t" => prev + "\t",
+                [b'\\', bytes @ ..] => prev + str::from_utf8(bytes).expect("Verified by the lexer"),
+                _ => unreachable!("Verified by the lexer"),
+            }),
+        })
+}
+
+/// The content of a concatenation of literal strings, like `"a" + ("b" + "c")`.
+fn static_concat_content(op: &ast::BinaryOp) -> Option<String> {
+    fn go(e: ast::Expr) -> Option<String> {
+        match e {
+            ast::Expr::Paren(p) => go(p.expr()?),
+            ast::Expr::String(s) => static_string_content(&s),
+            ast::Expr::BinaryOp(op) => static_concat_content(&op),
+            _ => None,
+        }
+    }
+    match op.op_details()? {
+        (_, ast::BinaryOpKind::Add) => Some(go(op.lhs()?)? + &go(op.rhs()?)?),
+        _ => None,
+    }
 }
 
 struct MergingSet {
     is_rec: bool,
+    /// Name definitions of a recursive set, see `LowerCtx::lower_key`.
+    rec_names: HashMap<SmolStr, NameDefId>,
+    /// The static attrpath of this set from the outermost bindings, for reporting.
+    path: Vec<SmolStr>,
     entries: IndexMap<BindingKey, MergingEntry>,
     inherit_froms: Vec<ExprId>,
 }
@@ -346,6 +556,10 @@ struct MergingSet {
 struct MergingEntry {
     /// The definition location of this entry.
     def_ptr: AstPtr,
+    /// The range to report when this entry is duplicated.
+    key_range: TextRange,
+    /// The static attrpath of this entry from the outermost bindings, for reporting.
+    path: Vec<SmolStr>,
     /// Whether this entry is considered duplicated and the error is already reported.
     is_duplicated: bool,
     value: MergingValue,
@@ -371,18 +585,37 @@ impl From<BindingValue> for MergingValue {
 
 impl MergingSet {
     fn new(is_rec: bool) -> Self {
+        Self::new_nested(is_rec, Vec::new())
+    }
+
+    fn new_nested(is_rec: bool, path: Vec<SmolStr>) -> Self {
         Self {
             is_rec,
+            rec_names: HashMap::new(),
+            path,
             entries: Default::default(),
             inherit_froms: Vec::new(),
         }
     }
 
+    /// The static attrpath of a key in this set.
+    fn key_path(&self, ctx: &LowerCtx, key: &BindingKey) -> Vec<SmolStr> {
+        let name = key
+            .name(&ctx.module)
+            .cloned()
+            .unwrap_or_else(|| "${...}".into());
+        let mut path = self.path.clone();
+        path.push(name);
+        path
+    }
+
     // Place an orphaned Expr as dynamic-attrs for error recovery.
     fn recover_error(&mut self, ctx: &mut LowerCtx, expr: ExprId, ptr: AstPtr) {
         let key = BindingKey::Dynamic(ctx.alloc_expr(Expr::Missing, ptr.clone()));
         let ent = MergingEntry {
+            key_range: ptr.text_range(),
             def_ptr: ptr,
+            path: Vec::new(),
             // This doesn't matter since dynamic keys can never be duplicated.
             is_duplicated: true,
             value: BindingValue::Expr(expr).into(),
@@ -402,6 +635,10 @@ impl MergingSet {
     }
 
     fn merge_inherit(&mut self, ctx: &mut LowerCtx, i: ast::Inherit) {
+        if i.attrs().next().is_none() {
+            ctx.diagnostic(i.syntax().text_range(), DiagnosticKind::EmptyInherit);
+        }
+
         let from_expr = i.from_expr().map(|e| {
             let expr = ctx.lower_expr_opt(e.expr());
             let from_id = self.inherit_froms.len() as u32;
@@ -411,7 +648,8 @@ impl MergingSet {
 
         for attr in i.attrs() {
             let ptr = AstPtr::new(attr.syntax());
-            let key = match ctx.lower_key(self.is_rec, attr) {
+            let rec_names = self.is_rec.then_some(&mut self.rec_names);
+            let key = match ctx.lower_key(rec_names, attr) {
                 // `inherit ${expr}` or `inherit (expr) ${expr}` is invalid.
                 BindingKey::Dynamic(expr) => {
                     ctx.diagnostic(ptr.text_range(), DiagnosticKind::InvalidDynamic);
@@ -423,7 +661,7 @@ impl MergingSet {
 
             // Inherited names never merge other values. It must be an error.
             if let Some(v) = self.entries.get_mut(&key) {
-                v.emit_duplicated_key(ctx, &ptr);
+                v.emit_duplicated_key(ctx, ptr.text_range());
                 continue;
             }
 
@@ -440,7 +678,9 @@ impl MergingSet {
                 }
             };
             let entry = MergingEntry {
+                key_range: ptr.text_range(),
                 def_ptr: ptr,
+                path: self.key_path(ctx, &key),
                 is_duplicated: false,
                 value: value.into(),
             };
@@ -466,25 +706,36 @@ impl MergingSet {
             }
         };
 
+        let path_start = next_attr.syntax().text_range().start();
         loop {
             let attr_ptr = AstPtr::new(next_attr.syntax());
-            let key = ctx.lower_key(self.is_rec, next_attr);
+            let following_attr = attrs.next();
+            // Duplicated keys are reported on the attrpath prefix up to the conflicting segment,
+            // or the whole binding if it is the last segment.
+            let key_range = match following_attr {
+                Some(_) => TextRange::new(path_start, attr_ptr.text_range().end()),
+                None => entry.syntax().text_range(),
+            };
+            let key = ctx.lower_key(self.is_rec.then_some(&mut self.rec_names), next_attr);
             if let (false, BindingKey::Dynamic(_)) = (allow_dynamic, &key) {
                 ctx.diagnostic(attr_ptr.text_range(), DiagnosticKind::InvalidDynamic);
                 // We don't skip the RHS but still process it as a recovery.
             }
+            let path = self.key_path(ctx, &key);
             let deep = self.entries.entry(key).or_insert_with(|| MergingEntry {
                 def_ptr: attr_ptr.clone(),
+                key_range,
+                path,
                 is_duplicated: false,
                 value: MergingValue::Placeholder,
             });
-            match attrs.next() {
+            match following_attr {
                 Some(attr) => {
-                    self = deep.make_attrset(ctx, false, &attr_ptr);
+                    self = deep.make_attrset(ctx, false, &attr_ptr, key_range);
                     next_attr = attr;
                 }
                 None => {
-                    deep.merge_ast(ctx, &attr_ptr, entry.value());
+                    deep.merge_ast(ctx, key_range, entry.value());
                     return;
                 }
             }
@@ -509,21 +760,23 @@ impl MergingEntry {
         ctx: &mut LowerCtx,
         is_rec: bool,
         def_ptr: &AstPtr,
+        key_range: TextRange,
     ) -> &mut MergingSet {
         match &mut self.value {
             MergingValue::Placeholder => {
-                self.value = MergingSet::new(is_rec).into();
+                self.value = MergingSet::new_nested(is_rec, self.path.clone()).into();
             }
             // TODO: Report warnings if set.is_rec
             MergingValue::Attrset(_) => {}
             // We prefer to become a Attrset as a guess, which allows further merging.
             MergingValue::Final(value) => {
-                let mut set = MergingSet::new(is_rec);
+                let mut set = MergingSet::new_nested(is_rec, self.path.clone());
                 if let BindingValue::Expr(expr) = value {
                     set.recover_error(ctx, *expr, self.def_ptr.clone());
                 }
-                self.emit_duplicated_key(ctx, def_ptr);
+                self.emit_duplicated_key(ctx, key_range);
                 self.def_ptr = def_ptr.clone();
+                self.key_range = key_range;
                 self.value = set.into();
             }
         }
@@ -533,13 +786,18 @@ impl MergingEntry {
         }
     }
 
-    fn merge_ast(&mut self, ctx: &mut LowerCtx, def_ptr: &AstPtr, mut e: Option<ast::Expr>) {
+    fn merge_ast(&mut self, ctx: &mut LowerCtx, key_range: TextRange, mut e: Option<ast::Expr>) {
         loop {
             match e {
                 Some(ast::Expr::Paren(p)) => e = p.expr(),
                 Some(ast::Expr::AttrSet(e)) if e.let_token().is_none() => {
                     return self
-                        .make_attrset(ctx, e.rec_token().is_some(), &AstPtr::new(e.syntax()))
+                        .make_attrset(
+                            ctx,
+                            e.rec_token().is_some(),
+                            &AstPtr::new(e.syntax()),
+                            key_range,
+                        )
                         .merge_bindings(ctx, &e, true);
                 }
                 _ => break,
@@ -553,18 +811,32 @@ impl MergingEntry {
             MergingValue::Attrset(_) | MergingValue::Final { .. } => {
                 // Suppress errors when there is no RHS, which happens during typing.
                 if e.is_some() {
-                    self.emit_duplicated_key(ctx, def_ptr);
+                    self.emit_duplicated_key(ctx, key_range);
                 }
             }
         }
     }
 
-    fn emit_duplicated_key(&mut self, ctx: &mut LowerCtx, ptr: &AstPtr) {
+    fn emit_duplicated_key(&mut self, ctx: &mut LowerCtx, range: TextRange) {
+        // Conflicts after merging attrpaths are not obvious from the key itself.
+        let path = (self.path.len() > 1).then(|| self.path.join("."));
+        let mut prev = Diagnostic::new(self.key_range, DiagnosticKind::DuplicatedKey(path.clone()));
+        let mut cur = Diagnostic::new(range, DiagnosticKind::DuplicatedKey(path.clone()));
+        if let Some(path) = &path {
+            prev = prev.with_note(
+                InFile::new(ctx.file_id, range),
+                format!("Attribute `{}` is redefined here", path),
+            );
+            cur = cur.with_note(
+                InFile::new(ctx.file_id, self.key_range),
+                format!("Attribute `{}` is already defined here", path),
+            );
+        }
         if !mem::replace(&mut self.is_duplicated, true) {
             // Don't emit twice at previouse key.
-            ctx.diagnostic(self.def_ptr.text_range(), DiagnosticKind::DuplicatedKey);
+            ctx.module.diagnostics.push(prev);
         }
-        ctx.diagnostic(ptr.text_range(), DiagnosticKind::DuplicatedKey);
+        ctx.module.diagnostics.push(cur);
     }
 
     fn finish(self, ctx: &mut LowerCtx) -> BindingValue {
@@ -584,6 +856,7 @@ impl MergingEntry {
 mod tests {
     use super::lower;
     use crate::base::{FileId, InFile};
+    use crate::Severity;
     use expect_test::{expect, Expect};
     use std::fmt::Write;
     use syntax::parse_file;
@@ -601,7 +874,19 @@ mod tests {
         for (i, def) in module.name_defs.iter() {
             writeln!(got, "{}: {:?}", i.into_raw(), def).unwrap();
         }
-        assert!(module.diagnostics().is_empty());
+        // Only warnings are allowed here. Errors should be tested by `check_error`.
+        if !module.diagnostics().is_empty() {
+            writeln!(got).unwrap();
+        }
+        for diag in module.diagnostics() {
+            assert_eq!(
+                diag.severity(),
+                Severity::Warning,
+                "Unexpected error: {}",
+                diag
+            );
+            writeln!(got, "{}", diag).unwrap();
+        }
         expect.assert_eq(&got);
     }
 
@@ -615,6 +900,28 @@ mod tests {
         expect.assert_eq(&got);
     }
 
+    #[test]
+    fn name_def_values() {
+        let parse = parse_file("x: let a = 1; inherit x; in rec { b = a; c.d = b; }");
+        let (module, _source_map) = lower(InFile::new(FileId(0), parse));
+        let got = module
+            .name_defs
+            .iter()
+            .map(|(def, nd)| {
+                let value = module.name_def_value(def).map(|e| e.into_raw());
+                format!("{} = {:?}\n", nd.name, value)
+            })
+            .collect::<String>();
+        expect![[r#"
+            x = None
+            a = Some(0)
+            x = None
+            b = Some(2)
+            c = Some(4)
+        "#]]
+        .assert_eq(&got);
+    }
+
     #[test]
     fn literal() {
         check_lower(
@@ -637,6 +944,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn constant_literal() {
+        check_lower(
+            "[ true false ]",
+            expect![[r#"
+                0: Literal(Bool(true))
+                1: Literal(Bool(false))
+                2: List([Idx::<Expr>(0), Idx::<Expr>(1)])
+            "#]],
+        );
+        check_lower(
+            "let true = 1; in [ true false ]",
+            expect![[r#"
+                0: Literal(Int(1))
+                1: Reference("true")
+                2: Literal(Bool(false))
+                3: List([Idx::<Expr>(1), Idx::<Expr>(2)])
+                4: LetIn(Bindings { entries: [(NameDef(Idx::<NameDef>(0)), Expr(Idx::<Expr>(0)))], inherit_froms: [] }, Idx::<Expr>(3))
+
+                0: NameDef { name: "true" }
+            "#]],
+        );
+        check_lower(
+            "[ null (null: null) ]",
+            expect![[r#"
+                0: Literal(Null)
+                1: Reference("null")
+                2: Lambda(Some(Idx::<NameDef>(0)), None, Idx::<Expr>(1))
+                3: List([Idx::<Expr>(0), Idx::<Expr>(2)])
+
+                0: NameDef { name: "null" }
+            "#]],
+        );
+        check_lower(
+            "{ false ? 1 }: [ true false ]",
+            expect![[r#"
+                0: Literal(Int(1))
+                1: Literal(Bool(true))
+                2: Reference("false")
+                3: List([Idx::<Expr>(1), Idx::<Expr>(2)])
+                4: Lambda(None, Some(Pat { fields: [(Some(Idx::<NameDef>(0)), Some(Idx::<Expr>(0)))], ellipsis: false }), Idx::<Expr>(3))
+
+                0: NameDef { name: "false" }
+            "#]],
+        );
+    }
+
     #[test]
     fn path() {
         check_lower(
@@ -675,6 +1029,12 @@ mod tests {
                 0: Literal(Path(Path { anchor: Search("p"), supers: 2, raw_segments: ".b/c" }))
             "#]],
         );
+        check_lower(
+            "./a/b/../c",
+            expect![[r#"
+                0: Literal(Path(Path { anchor: Relative(FileId(0)), supers: 0, raw_segments: "a/c" }))
+            "#]],
+        );
     }
 
     #[test]
@@ -771,6 +1131,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn empty_interpolation() {
+        check_error(
+            r#""foo${}bar""#,
+            expect![[r#"
+                Diagnostic { range: 4..7, kind: EmptyInterpolation, notes: [], fix: None }
+            "#]],
+        );
+        check_error(
+            "/foo/${}",
+            expect![[r#"
+                Diagnostic { range: 5..8, kind: EmptyInterpolation, notes: [], fix: None }
+            "#]],
+        );
+        check_error(
+            "{ a.${} = 1; }.${}",
+            expect![[r#"
+                Diagnostic { range: 4..7, kind: EmptyInterpolation, notes: [], fix: None }
+                Diagnostic { range: 15..18, kind: EmptyInterpolation, notes: [], fix: None }
+            "#]],
+        );
+    }
+
     #[test]
     fn trivial_expr() {
         check_lower(
@@ -857,6 +1240,8 @@ mod tests {
                 3: Literal(Int(3))
                 4: Literal(Int(4))
                 5: Attrset(Bindings { entries: [(Name("a"), Expr(Idx::<Expr>(0))), (Dynamic(Idx::<Expr>(1)), Expr(Idx::<Expr>(2))), (Name("c"), Expr(Idx::<Expr>(3))), (Name("\n"), Expr(Idx::<Expr>(4)))], inherit_froms: [] })
+
+                Unnecessary quotes around the name at 19..22
             "#]],
         );
     }
@@ -872,6 +1257,27 @@ mod tests {
                 3: Reference("d")
                 4: Reference("e")
                 5: Attrset(Bindings { entries: [(Name("a"), Inherit(Idx::<Expr>(0))), (Name("b"), Inherit(Idx::<Expr>(1))), (Name("c"), Inherit(Idx::<Expr>(2))), (Name("f"), InheritFrom(1)), (Name("g"), InheritFrom(1))], inherit_froms: [Idx::<Expr>(3), Idx::<Expr>(4)] })
+
+                Nothing is inherited at 2..10
+                Unnecessary quotes around the name at 21..24
+                Nothing is inherited at 35..47
+            "#]],
+        );
+    }
+
+    #[test]
+    fn empty_inherit() {
+        check_error(
+            "{ inherit; inherit (a); inherit b; }",
+            expect![[r#"
+                Diagnostic { range: 2..10, kind: EmptyInherit, notes: [], fix: None }
+                Diagnostic { range: 11..23, kind: EmptyInherit, notes: [], fix: None }
+            "#]],
+        );
+        check_error(
+            "let inherit; in 1",
+            expect![[r#"
+                Diagnostic { range: 4..12, kind: EmptyInherit, notes: [], fix: None }
             "#]],
         );
     }
@@ -959,6 +1365,21 @@ mod tests {
                 0: NameDef { name: "a" }
             "#]],
         );
+        // Merged string keys share one definition.
+        check_lower(
+            r#"rec { "a".x = 1; "a".y = 2; }"#,
+            expect![[r#"
+                0: Literal(Int(1))
+                1: Literal(Int(2))
+                2: Attrset(Bindings { entries: [(Name("x"), Expr(Idx::<Expr>(0))), (Name("y"), Expr(Idx::<Expr>(1)))], inherit_froms: [] })
+                3: Attrset(Bindings { entries: [(NameDef(Idx::<NameDef>(0)), Expr(Idx::<Expr>(2)))], inherit_froms: [] })
+
+                0: NameDef { name: "a" }
+
+                Unnecessary quotes around the name at 6..9
+                Unnecessary quotes around the name at 17..20
+            "#]],
+        );
     }
 
     #[test]
@@ -998,64 +1419,178 @@ mod tests {
         check_error(
             "let ${a} = 1; in 1",
             expect![[r#"
-                Diagnostic { range: 4..8, kind: InvalidDynamic }
+                Diagnostic { range: 4..8, kind: InvalidDynamic, notes: [], fix: None }
             "#]],
         );
         check_error(
             "{ inherit ${a}; }",
             expect![[r#"
-                Diagnostic { range: 10..14, kind: InvalidDynamic }
+                Diagnostic { range: 10..14, kind: InvalidDynamic, notes: [], fix: None }
             "#]],
         );
         check_error(
             "{ inherit (a) ${a}; }",
             expect![[r#"
-                Diagnostic { range: 14..18, kind: InvalidDynamic }
+                Diagnostic { range: 14..18, kind: InvalidDynamic, notes: [], fix: None }
             "#]],
         );
     }
 
-    // FIXME: The location is not quite right currently.
     #[test]
     fn attrset_merge_error() {
         // Value and value.
         check_error(
             "{ a = 1; a = 2; }",
             expect![[r#"
-                Diagnostic { range: 2..3, kind: DuplicatedKey }
-                Diagnostic { range: 9..10, kind: DuplicatedKey }
+                Diagnostic { range: 2..8, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 9..15, kind: DuplicatedKey(None), notes: [], fix: None }
             "#]],
         );
         // Set and value.
         check_error(
             "{ a.b = 1; a = 2; }",
             expect![[r#"
-                Diagnostic { range: 2..3, kind: DuplicatedKey }
-                Diagnostic { range: 11..12, kind: DuplicatedKey }
+                Diagnostic { range: 2..3, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 11..17, kind: DuplicatedKey(None), notes: [], fix: None }
             "#]],
         );
         // Value and set.
         check_error(
             "{ a = 1; a.b = 2; }",
             expect![[r#"
-                Diagnostic { range: 2..3, kind: DuplicatedKey }
-                Diagnostic { range: 9..10, kind: DuplicatedKey }
+                Diagnostic { range: 2..8, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 9..10, kind: DuplicatedKey(None), notes: [], fix: None }
             "#]],
         );
         // Inherit and value.
         check_error(
             "{ inherit a; a = 1; }",
             expect![[r#"
-                Diagnostic { range: 10..11, kind: DuplicatedKey }
-                Diagnostic { range: 13..14, kind: DuplicatedKey }
+                Diagnostic { range: 10..11, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 13..19, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+        // Nested paths.
+        check_error(
+            "{ a.b = 1; a.b.c = 2; a.d = 3; }",
+            expect![[r#"
+                Diagnostic { range: 2..10, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 11..14 }, "Attribute `a.b` is redefined here")], fix: None }
+                Diagnostic { range: 11..14, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 2..10 }, "Attribute `a.b` is already defined here")], fix: None }
+            "#]],
+        );
+        check_error(
+            "{ a = { b = 1; }; a.b = 2; }",
+            expect![[r#"
+                Diagnostic { range: 8..14, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 18..26 }, "Attribute `a.b` is redefined here")], fix: None }
+                Diagnostic { range: 18..26, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 8..14 }, "Attribute `a.b` is already defined here")], fix: None }
             "#]],
         );
         // Inherit-from and value.
         check_error(
             "{ inherit (1) a; a = 1; }",
             expect![[r#"
-                Diagnostic { range: 14..15, kind: DuplicatedKey }
-                Diagnostic { range: 17..18, kind: DuplicatedKey }
+                Diagnostic { range: 14..15, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 17..23, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn static_concat_key() {
+        check_lower(
+            r#"{ ${"a" + "b"} = 1; ${("c" + ("d" + "e"))}.f = 2; }"#,
+            expect![[r#"
+                0: Literal(Int(1))
+                1: Literal(Int(2))
+                2: Attrset(Bindings { entries: [(Name("f"), Expr(Idx::<Expr>(1)))], inherit_froms: [] })
+                3: Attrset(Bindings { entries: [(Name("ab"), Expr(Idx::<Expr>(0))), (Name("cde"), Expr(Idx::<Expr>(2)))], inherit_froms: [] })
+            "#]],
+        );
+        check_lower(
+            r#"{ ${"a" + b} = 1; ${"a" + "${b}"} = 2; ${"a" ++ "b"} = 3; }"#,
+            expect![[r#"
+                0: StringInterpolation([])
+                1: Reference("b")
+                2: Binary(Some(Add), Idx::<Expr>(0), Idx::<Expr>(1))
+                3: Literal(Int(1))
+                4: StringInterpolation([])
+                5: Reference("b")
+                6: StringInterpolation([Idx::<Expr>(5)])
+                7: Binary(Some(Add), Idx::<Expr>(4), Idx::<Expr>(6))
+                8: Literal(Int(2))
+                9: StringInterpolation([])
+                10: StringInterpolation([])
+                11: Binary(Some(Concat), Idx::<Expr>(9), Idx::<Expr>(10))
+                12: Literal(Int(3))
+                13: Attrset(Bindings { entries: [(Dynamic(Idx::<Expr>(2)), Expr(Idx::<Expr>(3))), (Dynamic(Idx::<Expr>(7)), Expr(Idx::<Expr>(8))), (Dynamic(Idx::<Expr>(11)), Expr(Idx::<Expr>(12)))], inherit_froms: [] })
+            "#]],
+        );
+        check_error(
+            r#"{ ab = 1; ${"a" + "b"} = 2; }"#,
+            expect![[r#"
+                Diagnostic { range: 2..9, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 10..27, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn rec_merge() {
+        check_error("let a.x = 1; a.y = 2; in a", expect![""]);
+        check_error(
+            "rec { a = 1; a.x = 2; }",
+            expect![[r#"
+                Diagnostic { range: 6..12, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 13..14, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+        check_error(
+            "let a = 1; a = 2; in a",
+            expect![[r#"
+                Diagnostic { range: 4..10, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 11..17, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn unnecessary_quote() {
+        check_error(
+            r#"{ "foo" = 1; "foo bar" = 2; a."b" = 3; "in" = 4; ${"c"} = 5; }"#,
+            expect![[r#"
+                Diagnostic { range: 2..7, kind: UnnecessaryQuote, notes: [], fix: None }
+                Diagnostic { range: 30..33, kind: UnnecessaryQuote, notes: [], fix: None }
+            "#]],
+        );
+        check_error(
+            r#"rec { inherit "a"; }"#,
+            expect![[r#"
+                Diagnostic { range: 14..17, kind: UnnecessaryQuote, notes: [], fix: None }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn attrset_deep_merge_error() {
+        check_error(
+            "{ a.b = 1; a.b = 2; }",
+            expect![[r#"
+                Diagnostic { range: 2..10, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 11..19 }, "Attribute `a.b` is redefined here")], fix: None }
+                Diagnostic { range: 11..19, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 2..10 }, "Attribute `a.b` is already defined here")], fix: None }
+            "#]],
+        );
+        check_error(
+            "{ a = { b = 1; }; a = { b = 2; }; }",
+            expect![[r#"
+                Diagnostic { range: 8..14, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 24..30 }, "Attribute `a.b` is redefined here")], fix: None }
+                Diagnostic { range: 24..30, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 8..14 }, "Attribute `a.b` is already defined here")], fix: None }
+            "#]],
+        );
+        check_error(
+            "{ a = { inherit b; }; a.b = 1; }",
+            expect![[r#"
+                Diagnostic { range: 16..17, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 22..30 }, "Attribute `a.b` is redefined here")], fix: None }
+                Diagnostic { range: 22..30, kind: DuplicatedKey(Some("a.b")), notes: [(InFile { file_id: FileId(0), value: 16..17 }, "Attribute `a.b` is already defined here")], fix: None }
             "#]],
         );
     }
@@ -1065,11 +1600,39 @@ mod tests {
         check_error(
             "{ a = 1; a = 2; a = 3; }",
             expect![[r#"
-                Diagnostic { range: 2..3, kind: DuplicatedKey }
-                Diagnostic { range: 9..10, kind: DuplicatedKey }
-                Diagnostic { range: 16..17, kind: DuplicatedKey }
+                Diagnostic { range: 2..8, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 9..15, kind: DuplicatedKey(None), notes: [], fix: None }
+                Diagnostic { range: 16..22, kind: DuplicatedKey(None), notes: [], fix: None }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn duplicated_pat_field() {
+        check_error(
+            "{ a, b, a ? 1 }: a",
+            expect![[r#"
+                Diagnostic { range: 8..9, kind: DuplicatedPatField, notes: [(InFile { file_id: FileId(0), value: 2..3 }, "Previously defined here")], fix: None }
+            "#]],
+        );
+        check_error("{ a, b }: a", expect![""]);
+    }
+
+    #[test]
+    fn pattern_bind_collision() {
+        check_error(
+            "x@{ x, ... }: x",
+            expect![[r#"
+                Diagnostic { range: 4..5, kind: PatternBindCollision, notes: [(InFile { file_id: FileId(0), value: 0..1 }, "The `@` binding"), (InFile { file_id: FileId(0), value: 4..5 }, "The pattern field")], fix: None }
+            "#]],
+        );
+        check_error(
+            "{ x, y }@x: x",
+            expect![[r#"
+                Diagnostic { range: 9..10, kind: PatternBindCollision, notes: [(InFile { file_id: FileId(0), value: 9..10 }, "The `@` binding"), (InFile { file_id: FileId(0), value: 2..3 }, "The pattern field")], fix: None }
             "#]],
         );
+        check_error("x@{ y, z }: x", expect![""]);
     }
 
     #[test]
@@ -1,18 +1,23 @@
+mod doc;
 mod lower;
+mod pretty;
 mod scope;
+mod structural;
 
 #[cfg(test)]
 mod tests;
 
 use crate::base::SourceDatabase;
-use crate::{Diagnostic, FileId};
+use crate::{Diagnostic, FileId, InFile, VfsPath};
 use la_arena::{Arena, ArenaMap, Idx};
 use ordered_float::OrderedFloat;
 use smol_str::SmolStr;
 use std::collections::HashMap;
 use std::ops;
 use std::sync::Arc;
+use syntax::{ast, SyntaxNode};
 
+pub(crate) use self::lower::static_string_content;
 pub use self::scope::{ModuleScopes, NameReferenceMap, ResolveResult, ScopeData, ScopeId};
 pub use syntax::ast::{BinaryOpKind as BinaryOp, UnaryOpKind as UnaryOp};
 
@@ -32,13 +37,21 @@ pub trait DefDatabase: SourceDatabase {
 
     #[salsa::invoke(NameReferenceMap::name_reference_map_query)]
     fn name_reference_map(&self, file_id: FileId) -> Arc<NameReferenceMap>;
+
+    #[salsa::invoke(doc::binding_doc_query)]
+    fn binding_doc(&self, file_id: FileId, def: NameDefId) -> Option<String>;
 }
 
 fn module_with_source_map(
     db: &dyn DefDatabase,
     file_id: FileId,
 ) -> (Arc<Module>, Arc<ModuleSourceMap>) {
-    let parse = db.parse(file_id);
+    // Large files are not analyzed, so nothing is resolved in them.
+    let parse = if db.file_content(file_id).len() > db.max_file_size() {
+        InFile::new(file_id, syntax::parse_file(""))
+    } else {
+        db.parse(file_id)
+    };
     let (module, source_map) = lower::lower(parse);
     (Arc::new(module), Arc::new(source_map))
 }
@@ -55,6 +68,8 @@ fn source_map(db: &dyn DefDatabase, file_id: FileId) -> Arc<ModuleSourceMap> {
 pub struct Module {
     exprs: Arena<Expr>,
     name_defs: Arena<NameDef>,
+    /// The value expressions bound to name definitions by `let` or `rec`.
+    name_def_values: ArenaMap<NameDefId, ExprId>,
     entry_expr: ExprId,
     diagnostics: Vec<Diagnostic>,
 }
@@ -77,6 +92,10 @@ impl ops::Index<NameDefId> for Module {
 }
 
 impl Module {
+    pub fn entry_expr(&self) -> ExprId {
+        self.entry_expr
+    }
+
     pub fn diagnostics(&self) -> &[Diagnostic] {
         &self.diagnostics
     }
@@ -97,19 +116,25 @@ pub struct ModuleSourceMap {
 }
 
 impl ModuleSourceMap {
-    pub fn node_expr(&self, node: AstPtr) -> Option<ExprId> {
-        self.expr_map.get(&node).copied()
+    pub(crate) fn expr_for_node(&self, node: &SyntaxNode) -> Option<ExprId> {
+        self.expr_map.get(&AstPtr::new(node)).copied()
     }
 
-    pub fn expr_node(&self, expr_id: ExprId) -> Option<AstPtr> {
+    pub(crate) fn node_for_expr(&self, expr_id: ExprId) -> Option<AstPtr> {
         self.expr_map_rev.get(&expr_id).cloned()
     }
 
-    pub fn node_name_def(&self, node: AstPtr) -> Option<NameDefId> {
-        self.name_def_map.get(&node).copied()
+    /// The expression of a segment of a selection or `?` attrpath, like `b` in `a.b.c`.
+    /// Its range is available by `node_for_expr`.
+    pub(crate) fn expr_for_attr(&self, attr: &ast::Attr) -> Option<ExprId> {
+        self.expr_for_node(rowan::ast::AstNode::syntax(attr))
+    }
+
+    pub(crate) fn name_def_for_node(&self, node: &SyntaxNode) -> Option<NameDefId> {
+        self.name_def_map.get(&AstPtr::new(node)).copied()
     }
 
-    pub fn name_def_node(&self, def_id: NameDefId) -> Option<AstPtr> {
+    pub(crate) fn node_for_name_def(&self, def_id: NameDefId) -> Option<AstPtr> {
         self.name_def_map_rev.get(def_id).cloned()
     }
 }
@@ -137,6 +162,11 @@ pub enum Expr {
 }
 
 impl Expr {
+    /// Whether this is a placeholder for an expression absent from incomplete syntax.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Self::Missing)
+    }
+
     pub(crate) fn walk_child_exprs(&self, mut f: impl FnMut(ExprId)) {
         match self {
             Self::Missing | Self::Reference(_) | Self::Literal(_) => {}
@@ -195,6 +225,20 @@ pub enum Literal {
     Float(OrderedFloat<f64>),
     String(SmolStr),
     Path(Path),
+    /// `true` or `false` which are not shadowed by local definitions.
+    Bool(bool),
+    /// `null` which is not shadowed by local definitions.
+    Null,
+}
+
+impl Literal {
+    /// The human-readable form of a path literal, like `./foo`, `~/foo` or `<nixpkgs>/lib`.
+    pub fn path_display(&self) -> Option<String> {
+        match self {
+            Self::Path(path) => Some(path.display()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -207,7 +251,47 @@ pub struct Path {
 
 impl Path {
     pub fn segments(&self) -> impl Iterator<Item = &str> + '_ {
-        self.raw_segments.split(' ').filter(|s| !s.is_empty())
+        self.raw_segments.split('/').filter(|s| !s.is_empty())
+    }
+
+    /// Reconstruct the path from its anchor, `..` segments and normalized segments.
+    /// Search paths show the search name and the relative part separately, as `<nixpkgs>/lib`.
+    pub fn display(&self) -> String {
+        let parts = std::iter::repeat("..")
+            .take(self.supers)
+            .chain(self.segments())
+            .collect::<Vec<_>>()
+            .join("/");
+        let prefix = match &self.anchor {
+            PathAnchor::Relative(_) if self.supers != 0 => return parts,
+            PathAnchor::Relative(_) => ".".to_owned(),
+            PathAnchor::Absolute => String::new(),
+            PathAnchor::Home => "~".to_owned(),
+            PathAnchor::Search(name) => format!("<{}>", name),
+        };
+        match (&self.anchor, parts.is_empty()) {
+            (PathAnchor::Absolute, true) => "/".to_owned(),
+            (PathAnchor::Relative(_), true) => "./.".to_owned(),
+            (_, true) => prefix,
+            (_, false) => format!("{}/{}", prefix, parts),
+        }
+    }
+
+    /// Resolve the path in the virtual file system.
+    /// Returns `None` for home-relative or search paths, which depend on the environment.
+    pub fn resolve(&self, db: &dyn SourceDatabase) -> Option<VfsPath> {
+        let mut path = match self.anchor {
+            PathAnchor::Relative(file_id) => db.file_set().path_for_file(file_id)?.parent()?,
+            PathAnchor::Absolute => VfsPath::new("/")?,
+            PathAnchor::Home | PathAnchor::Search(_) => return None,
+        };
+        for _ in 0..self.supers {
+            path = path.parent()?;
+        }
+        for seg in self.segments() {
+            path.push_segment(seg);
+        }
+        Some(path)
     }
 }
 
@@ -247,7 +331,66 @@ pub enum BindingValue {
     Expr(ExprId),
 }
 
+impl Module {
+    /// Get the merged bindings of an attrset or `let`, then follow the static attrpath into
+    /// nested attrsets.
+    pub fn bindings_at_attrpath(&self, expr: ExprId, attrpath: &[SmolStr]) -> Option<&Bindings> {
+        let mut bindings = match &self[expr] {
+            Expr::Attrset(bindings) | Expr::LetAttrset(bindings) | Expr::LetIn(bindings, _) => {
+                bindings
+            }
+            _ => return None,
+        };
+        for name in attrpath {
+            bindings = match bindings.get(self, name)? {
+                BindingValue::Expr(expr) => match &self[expr] {
+                    Expr::Attrset(bindings) => bindings,
+                    _ => return None,
+                },
+                BindingValue::Inherit(_) | BindingValue::InheritFrom(_) => return None,
+            };
+        }
+        Some(bindings)
+    }
+
+    /// Whether the expression or any of its descendants is missing from incomplete syntax.
+    pub fn contains_missing(&self, expr: ExprId) -> bool {
+        let mut found = self[expr].is_missing();
+        self[expr].walk_child_exprs(|e| found = found || self.contains_missing(e));
+        found
+    }
+
+    /// Find the value expression bound to a name definition by `let` or `rec`.
+    pub fn name_def_value(&self, def: NameDefId) -> Option<ExprId> {
+        self.name_def_values.get(def).copied()
+    }
+}
+
+impl BindingKey {
+    /// The static name of this key, or `None` for dynamic keys.
+    pub fn name<'a>(&'a self, module: &'a Module) -> Option<&'a SmolStr> {
+        match self {
+            Self::NameDef(def) => Some(&module[*def].name),
+            Self::Name(name) => Some(name),
+            Self::Dynamic(_) => None,
+        }
+    }
+}
+
 impl Bindings {
+    /// Get the value of a static key.
+    pub fn get(&self, module: &Module, name: &str) -> Option<BindingValue> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.name(module).map_or(false, |n| n == name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Get the `e` of `inherit (e)` referred by `BindingValue::InheritFrom`.
+    pub fn inherit_from_expr(&self, id: u32) -> ExprId {
+        self.inherit_froms[id as usize]
+    }
+
     pub(crate) fn walk_child_exprs(&self, mut f: impl FnMut(ExprId)) {
         for (key, kind) in self.entries.iter() {
             match key {
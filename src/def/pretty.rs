@@ -0,0 +1,367 @@
+//! Render lowered expressions back to approximate Nix syntax.
+//!
+//! The output is not byte-identical to the source: comments, whitespaces and parentheses are
+//! not kept, and merged attrpaths like `{ a.b = 1; a.c = 2; }` become nested attrsets.
+//! Fragments of strings and interpolated paths are not lowered, so they are omitted, and a
+//! string without interpolations is shown as `"…"`.
+use super::{
+    BinaryOp, BindingKey, BindingValue, Bindings, Expr, ExprId, Literal, Module, Pat, PathAnchor,
+    UnaryOp,
+};
+use std::fmt::Write;
+use syntax::lexer::is_valid_ident;
+use syntax::precedence as prec;
+
+const MISSING: &str = "…";
+
+impl Module {
+    /// Render the expression in Nix syntax on a single line, with parentheses only where
+    /// needed by the precedence.
+    pub fn expr_to_string(&self, expr: ExprId) -> String {
+        let mut buf = String::new();
+        Printer {
+            module: self,
+            buf: &mut buf,
+        }
+        .expr(expr, prec::LOWEST);
+        buf
+    }
+}
+
+struct Printer<'a> {
+    module: &'a Module,
+    buf: &'a mut String,
+}
+
+impl Printer<'_> {
+    fn expr(&mut self, expr: ExprId, min_prec: u8) {
+        let prec = precedence(&self.module[expr]);
+        if prec < min_prec {
+            self.buf.push('(');
+            self.expr_inner(expr);
+            self.buf.push(')');
+        } else {
+            self.expr_inner(expr);
+        }
+    }
+
+    fn expr_inner(&mut self, expr: ExprId) {
+        match &self.module[expr] {
+            Expr::Missing => self.buf.push_str(MISSING),
+            Expr::Reference(name) => self.buf.push_str(name),
+            Expr::Literal(lit) => self.literal(lit),
+            Expr::Lambda(param, pat, body) => {
+                if let Some(pat) = pat {
+                    self.pat(pat);
+                }
+                if let Some(param) = param {
+                    if pat.is_some() {
+                        self.buf.push('@');
+                    }
+                    self.buf.push_str(&self.module[*param].name);
+                }
+                self.buf.push_str(": ");
+                self.expr(*body, prec::LOWEST);
+            }
+            &Expr::With(env, body) => {
+                self.buf.push_str("with ");
+                self.expr(env, prec::LOWEST);
+                self.buf.push_str("; ");
+                self.expr(body, prec::LOWEST);
+            }
+            &Expr::Assert(cond, body) => {
+                self.buf.push_str("assert ");
+                self.expr(cond, prec::LOWEST);
+                self.buf.push_str("; ");
+                self.expr(body, prec::LOWEST);
+            }
+            &Expr::IfThenElse(cond, then_body, else_body) => {
+                self.buf.push_str("if ");
+                self.expr(cond, prec::LOWEST);
+                self.buf.push_str(" then ");
+                self.expr(then_body, prec::LOWEST);
+                self.buf.push_str(" else ");
+                self.expr(else_body, prec::LOWEST);
+            }
+            &Expr::Binary(op, lhs, rhs) => {
+                let (lhs_prec, rhs_prec) =
+                    op.map_or((prec::LOWEST + 1, prec::LOWEST + 1), prec::binary_operands);
+                self.expr(lhs, lhs_prec);
+                self.buf.push(' ');
+                self.buf.push_str(op.map_or(MISSING, binary_op_str));
+                self.buf.push(' ');
+                self.expr(rhs, rhs_prec);
+            }
+            &Expr::Apply(func, arg) => {
+                self.expr(func, prec::APPLY);
+                self.buf.push(' ');
+                self.expr(arg, prec::SELECT);
+            }
+            &Expr::Unary(op, arg) => {
+                let prec = match op {
+                    Some(UnaryOp::Not) => {
+                        self.buf.push('!');
+                        prec::NOT
+                    }
+                    Some(UnaryOp::Negate) => {
+                        self.buf.push('-');
+                        prec::NEGATE
+                    }
+                    None => prec::ATOM,
+                };
+                self.expr(arg, prec);
+            }
+            Expr::HasAttr(set, path) => {
+                self.expr(*set, prec::HAS_ATTR + 1);
+                self.buf.push_str(" ? ");
+                self.attrpath(path);
+            }
+            Expr::Select(set, path, default_expr) => {
+                self.expr(*set, prec::SELECT);
+                self.buf.push('.');
+                self.attrpath(path);
+                if let Some(default_expr) = *default_expr {
+                    self.buf.push_str(" or ");
+                    self.expr(default_expr, prec::SELECT);
+                }
+            }
+            Expr::StringInterpolation(parts) => {
+                self.buf.push('"');
+                self.interpolation_parts(parts);
+                self.buf.push('"');
+            }
+            Expr::PathInterpolation(parts) => {
+                self.buf.push_str("./");
+                self.interpolation_parts(parts);
+            }
+            Expr::List(elems) => {
+                self.buf.push('[');
+                for &elem in elems.iter() {
+                    self.buf.push(' ');
+                    self.expr(elem, prec::SELECT);
+                }
+                self.buf.push_str(" ]");
+            }
+            Expr::LetIn(bindings, body) => {
+                self.buf.push_str("let");
+                self.bindings(bindings);
+                self.buf.push_str(" in ");
+                self.expr(*body, prec::LOWEST);
+            }
+            Expr::Attrset(bindings) => {
+                let is_rec = bindings
+                    .entries
+                    .iter()
+                    .any(|(key, _)| matches!(key, BindingKey::NameDef(_)));
+                if is_rec {
+                    self.buf.push_str("rec ");
+                }
+                self.buf.push('{');
+                self.bindings(bindings);
+                self.buf.push_str(" }");
+            }
+            Expr::LetAttrset(bindings) => {
+                self.buf.push_str("let {");
+                self.bindings(bindings);
+                self.buf.push_str(" }");
+            }
+        }
+    }
+
+    fn literal(&mut self, lit: &Literal) {
+        match lit {
+            Literal::Int(x) => write!(self.buf, "{}", x).unwrap(),
+            Literal::Float(x) => write!(self.buf, "{:?}", x.0).unwrap(),
+            Literal::String(s) => self.string(s),
+            // Unlike `Path::display`, the relative part stays inside `<>` to be valid syntax.
+            Literal::Path(path) => match &path.anchor {
+                PathAnchor::Search(name) => {
+                    self.buf.push('<');
+                    self.buf.push_str(name);
+                    for seg in std::iter::repeat("..")
+                        .take(path.supers)
+                        .chain(path.segments())
+                    {
+                        self.buf.push('/');
+                        self.buf.push_str(seg);
+                    }
+                    self.buf.push('>');
+                }
+                _ => self.buf.push_str(&path.display()),
+            },
+            Literal::Bool(b) => write!(self.buf, "{}", b).unwrap(),
+            Literal::Null => self.buf.push_str("null"),
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.buf.push('"');
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => self.buf.push_str("\\\""),
+                '\\' => self.buf.push_str("\\\\"),
+                '\n' => self.buf.push_str("\\n"),
+                '\r' => self.buf.push_str("\\r"),
+                '\t' => self.buf.push_str("\\t"),
+                '$' if chars.peek() == Some(&'{') => self.buf.push_str("\\$"),
+                c => self.buf.push(c),
+            }
+        }
+        self.buf.push('"');
+    }
+
+    /// Fragments between interpolations are not lowered, so they are elided.
+    fn interpolation_parts(&mut self, parts: &[ExprId]) {
+        if parts.is_empty() {
+            self.buf.push_str(MISSING);
+        }
+        for &part in parts {
+            self.buf.push_str("${");
+            self.expr(part, prec::LOWEST);
+            self.buf.push('}');
+        }
+    }
+
+    fn pat(&mut self, pat: &Pat) {
+        self.buf.push('{');
+        let mut first = true;
+        for &(name, default_expr) in pat.fields.iter() {
+            self.buf.push_str(if first { " " } else { ", " });
+            first = false;
+            match name {
+                Some(name) => self.buf.push_str(&self.module[name].name),
+                None => self.buf.push_str(MISSING),
+            }
+            if let Some(default_expr) = default_expr {
+                self.buf.push_str(" ? ");
+                self.expr(default_expr, prec::LOWEST);
+            }
+        }
+        if pat.ellipsis {
+            self.buf.push_str(if first { " ..." } else { ", ..." });
+            first = false;
+        }
+        self.buf.push_str(if first { "}" } else { " }" });
+    }
+
+    fn attrpath(&mut self, path: &[ExprId]) {
+        for (i, &attr) in path.iter().enumerate() {
+            if i != 0 {
+                self.buf.push('.');
+            }
+            self.attr(attr);
+        }
+    }
+
+    /// Static names are lowered as string literals.
+    fn attr(&mut self, attr: ExprId) {
+        match &self.module[attr] {
+            Expr::Literal(Literal::String(name)) => self.name(name),
+            Expr::StringInterpolation(_) => self.expr_inner(attr),
+            _ => {
+                self.buf.push_str("${");
+                self.expr(attr, prec::LOWEST);
+                self.buf.push('}');
+            }
+        }
+    }
+
+    fn name(&mut self, name: &str) {
+        if is_valid_ident(name) {
+            self.buf.push_str(name);
+        } else {
+            self.string(name);
+        }
+    }
+
+    /// Consecutive `inherit`s of the same source are joined, like `inherit (e) a b;`.
+    fn bindings(&mut self, bindings: &Bindings) {
+        let mut entries = bindings.entries.iter().peekable();
+        while let Some((key, value)) = entries.next() {
+            self.buf.push(' ');
+            match *value {
+                BindingValue::Inherit(_) | BindingValue::InheritFrom(_) => {
+                    self.buf.push_str("inherit");
+                    if let BindingValue::InheritFrom(id) = *value {
+                        self.buf.push_str(" (");
+                        self.expr(bindings.inherit_from_expr(id), prec::LOWEST);
+                        self.buf.push(')');
+                    }
+                    self.buf.push(' ');
+                    self.key(key);
+                    while let Some((key, _)) = entries.next_if(|(_, next)| match (value, next) {
+                        (BindingValue::Inherit(_), BindingValue::Inherit(_)) => true,
+                        (BindingValue::InheritFrom(a), BindingValue::InheritFrom(b)) => a == b,
+                        _ => false,
+                    }) {
+                        self.buf.push(' ');
+                        self.key(key);
+                    }
+                }
+                BindingValue::Expr(value) => {
+                    self.key(key);
+                    self.buf.push_str(" = ");
+                    self.expr(value, prec::LOWEST);
+                }
+            }
+            self.buf.push(';');
+        }
+    }
+
+    fn key(&mut self, key: &BindingKey) {
+        match key {
+            BindingKey::NameDef(def) => self.name(&self.module[*def].name),
+            BindingKey::Name(name) => self.name(name),
+            &BindingKey::Dynamic(expr) => self.attr(expr),
+        }
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Missing
+        | Expr::Reference(_)
+        | Expr::StringInterpolation(_)
+        | Expr::PathInterpolation(_)
+        | Expr::List(_)
+        | Expr::Attrset(_)
+        | Expr::LetAttrset(_) => prec::ATOM,
+        // Negative numbers need parentheses as arguments, like `f (-1)`.
+        Expr::Literal(Literal::Int(x)) if *x < 0 => prec::NEGATE,
+        Expr::Literal(Literal::Float(x)) if x.0 < 0.0 => prec::NEGATE,
+        Expr::Literal(_) => prec::ATOM,
+        Expr::Select(..) => prec::SELECT,
+        Expr::Apply(..) => prec::APPLY,
+        Expr::Unary(Some(UnaryOp::Negate), _) => prec::NEGATE,
+        Expr::Unary(Some(UnaryOp::Not), _) => prec::NOT,
+        Expr::Unary(None, _) => prec::ATOM,
+        Expr::HasAttr(..) => prec::HAS_ATTR,
+        Expr::Binary(op, ..) => op.map_or(prec::LOWEST, prec::binary),
+        Expr::Lambda(..)
+        | Expr::With(..)
+        | Expr::Assert(..)
+        | Expr::IfThenElse(..)
+        | Expr::LetIn(..) => prec::LOWEST,
+    }
+}
+
+fn binary_op_str(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Imply => "->",
+        BinaryOp::Or => "||",
+        BinaryOp::And => "&&",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Less => "<",
+        BinaryOp::Greater => ">",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::Update => "//",
+        BinaryOp::Concat => "++",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+    }
+}
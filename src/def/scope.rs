@@ -50,6 +50,10 @@ impl ModuleScopes {
         self.scope_by_expr.get(expr_id).copied()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (ScopeId, &'_ ScopeData)> + '_ {
+        self.scopes.iter()
+    }
+
     pub fn ancestors(&self, scope_id: ScopeId) -> impl Iterator<Item = &'_ ScopeData> + '_ {
         iter::successors(Some(scope_id), |&i| self[i].parent).map(|i| &self[i])
     }
@@ -64,8 +68,8 @@ impl ModuleScopes {
             return Some(ResolveResult::NameDef(*def));
         }
         // 2. Builtin names.
-        if let Some(name) = builtin::NAMES.get_key(name) {
-            return Some(ResolveResult::Builtin(name));
+        if let Some(info) = builtin::get_global(name) {
+            return Some(ResolveResult::Builtin(info.name));
         }
         // 3. "with" exprs.
         let withs = self
@@ -175,6 +179,7 @@ impl ModuleScopes {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResolveResult {
     NameDef(NameDefId),
+    /// A builtin by its attribute name under `builtins`, eg. `length` for `__length`.
     Builtin(&'static str),
     WithExprs(Vec<ExprId>),
 }
@@ -201,6 +206,10 @@ enum ScopeKind {
 }
 
 impl ScopeData {
+    pub fn parent(&self) -> Option<ScopeId> {
+        self.parent
+    }
+
     pub fn as_name_defs(&self) -> Option<&HashMap<SmolStr, NameDefId>> {
         match &self.kind {
             ScopeKind::NameDefs(defs) => Some(defs),
@@ -289,7 +298,11 @@ mod tests {
                     let mut names = defs
                         .iter()
                         .map(|(name, def)| {
-                            let pos = source_map.name_def_node(*def).unwrap().text_range().start();
+                            let pos = source_map
+                                .node_for_name_def(*def)
+                                .unwrap()
+                                .text_range()
+                                .start();
                             format!("{}@{}", name, u32::from(pos))
                         })
                         .collect::<Vec<_>>();
@@ -297,7 +310,7 @@ mod tests {
                     names.join(" ")
                 }
                 &ScopeKind::WithExpr(expr) => {
-                    let pos = source_map.expr_node(expr).unwrap().text_range().start();
+                    let pos = source_map.node_for_expr(expr).unwrap().text_range().start();
                     format!("with@{}", u32::from(pos))
                 }
             })
@@ -329,14 +342,14 @@ mod tests {
         let expr_id = source_map.expr_map[&ptr];
         let got = db.resolve_name(file_id, expr_id).map(|ret| match ret {
             ResolveResult::NameDef(def) => source_map
-                .name_def_node(def)
+                .node_for_name_def(def)
                 .unwrap()
                 .to_node(&parse.syntax_node())
                 .text_range()
                 .start(),
             ResolveResult::WithExprs(expr) => source_map
                 // Test the innermost one.
-                .expr_node(expr[0])
+                .node_for_expr(expr[0])
                 .unwrap()
                 .to_node(&parse.syntax_node())
                 .text_range()
@@ -426,8 +439,8 @@ mod tests {
 
     #[test]
     fn builtin() {
-        check_resolve("let $1true = 1; in with x; $0true + false + falsie");
-        check_resolve("let true = 1; in with x; true + $0$1false + falsie");
-        check_resolve("let true = 1; in $1with x; true + false + $0falsie");
+        check_resolve("let $1true = 1; in with x; $0true + map + mapie");
+        check_resolve("let true = 1; in with x; true + $0$1map + mapie");
+        check_resolve("let true = 1; in $1with x; true + map + $0mapie");
     }
 }
@@ -0,0 +1,178 @@
+use super::{
+    BinaryOp, BindingKey, BindingValue, Bindings, Expr, ExprId, Literal, Module, NameDefId, UnaryOp,
+};
+use std::mem::{self, Discriminant};
+
+/// The normalized form of an expression subtree, with ids replaced by what they refer to.
+/// Two subtrees are structurally identical iff their normalized forms are equal,
+/// so it can be used as a hash key to find duplicated expressions.
+///
+/// Names are compared textually, without resolving references.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct NormalizedExpr<'a>(Vec<Atom<'a>>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Atom<'a> {
+    Expr(Discriminant<Expr>),
+    Key(Discriminant<BindingKey>),
+    Value(Discriminant<BindingValue>),
+    Name(&'a str),
+    Literal(&'a Literal),
+    BinaryOp(Option<BinaryOp>),
+    UnaryOp(Option<UnaryOp>),
+    /// The length of a following sequence, or the index of `inherit (e)`.
+    Len(usize),
+    Flag(bool),
+}
+
+impl Module {
+    pub(crate) fn normalize_expr(&self, expr: ExprId) -> NormalizedExpr<'_> {
+        let mut out = Vec::new();
+        self.normalize_into(expr, &mut out);
+        NormalizedExpr(out)
+    }
+
+    fn normalize_into<'a>(&'a self, expr: ExprId, out: &mut Vec<Atom<'a>>) {
+        let kind = &self[expr];
+        out.push(Atom::Expr(mem::discriminant(kind)));
+        match kind {
+            Expr::Missing => {}
+            Expr::Reference(name) => out.push(Atom::Name(name)),
+            Expr::Literal(lit) => out.push(Atom::Literal(lit)),
+            Expr::Lambda(param, pat, body) => {
+                self.normalize_name_opt(*param, out);
+                out.push(Atom::Flag(pat.is_some()));
+                if let Some(pat) = pat {
+                    out.push(Atom::Flag(pat.ellipsis));
+                    out.push(Atom::Len(pat.fields.len()));
+                    for &(name, default_expr) in pat.fields.iter() {
+                        self.normalize_name_opt(name, out);
+                        self.normalize_opt(default_expr, out);
+                    }
+                }
+                self.normalize_into(*body, out);
+            }
+            Expr::Unary(op, a) => {
+                out.push(Atom::UnaryOp(*op));
+                self.normalize_into(*a, out);
+            }
+            Expr::Binary(op, a, b) => {
+                out.push(Atom::BinaryOp(*op));
+                self.normalize_into(*a, out);
+                self.normalize_into(*b, out);
+            }
+            Expr::With(a, b) | Expr::Assert(a, b) | Expr::Apply(a, b) => {
+                self.normalize_into(*a, out);
+                self.normalize_into(*b, out);
+            }
+            Expr::IfThenElse(a, b, c) => {
+                self.normalize_into(*a, out);
+                self.normalize_into(*b, out);
+                self.normalize_into(*c, out);
+            }
+            Expr::HasAttr(set, path) => {
+                self.normalize_into(*set, out);
+                self.normalize_seq(path, out);
+            }
+            Expr::Select(set, path, default_expr) => {
+                self.normalize_into(*set, out);
+                self.normalize_seq(path, out);
+                self.normalize_opt(*default_expr, out);
+            }
+            Expr::StringInterpolation(xs) | Expr::PathInterpolation(xs) | Expr::List(xs) => {
+                self.normalize_seq(xs, out);
+            }
+            Expr::LetIn(bindings, body) => {
+                self.normalize_bindings(bindings, out);
+                self.normalize_into(*body, out);
+            }
+            Expr::Attrset(bindings) | Expr::LetAttrset(bindings) => {
+                self.normalize_bindings(bindings, out);
+            }
+        }
+    }
+
+    fn normalize_opt<'a>(&'a self, expr: Option<ExprId>, out: &mut Vec<Atom<'a>>) {
+        out.push(Atom::Flag(expr.is_some()));
+        if let Some(expr) = expr {
+            self.normalize_into(expr, out);
+        }
+    }
+
+    fn normalize_seq<'a>(&'a self, exprs: &[ExprId], out: &mut Vec<Atom<'a>>) {
+        out.push(Atom::Len(exprs.len()));
+        for &expr in exprs {
+            self.normalize_into(expr, out);
+        }
+    }
+
+    fn normalize_name_opt<'a>(&'a self, name: Option<NameDefId>, out: &mut Vec<Atom<'a>>) {
+        out.push(Atom::Flag(name.is_some()));
+        if let Some(name) = name {
+            out.push(Atom::Name(&self[name].name));
+        }
+    }
+
+    fn normalize_bindings<'a>(&'a self, bindings: &'a Bindings, out: &mut Vec<Atom<'a>>) {
+        self.normalize_seq(&bindings.inherit_froms, out);
+        out.push(Atom::Len(bindings.entries.len()));
+        for (key, value) in bindings.entries.iter() {
+            out.push(Atom::Key(mem::discriminant(key)));
+            match key {
+                BindingKey::NameDef(def) => out.push(Atom::Name(&self[*def].name)),
+                BindingKey::Name(name) => out.push(Atom::Name(name)),
+                BindingKey::Dynamic(expr) => self.normalize_into(*expr, out),
+            }
+            out.push(Atom::Value(mem::discriminant(value)));
+            match *value {
+                BindingValue::Inherit(expr) | BindingValue::Expr(expr) => {
+                    self.normalize_into(expr, out)
+                }
+                BindingValue::InheritFrom(idx) => out.push(Atom::Len(idx as usize)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::def::DefDatabase;
+    use crate::tests::TestDB;
+
+    #[track_caller]
+    fn is_same(lhs: &str, rhs: &str) -> bool {
+        let (db, file_id, []) = TestDB::single_file(&format!("[ ({}) ({}) ]", lhs, rhs)).unwrap();
+        let module = db.module(file_id);
+        let elems = match &module[module.entry_expr()] {
+            crate::def::Expr::List(elems) => elems.clone(),
+            _ => unreachable!(),
+        };
+        module.normalize_expr(elems[0]) == module.normalize_expr(elems[1])
+    }
+
+    #[test]
+    fn identical() {
+        assert!(is_same("f 1", "f  (1)"));
+        assert!(is_same("a.b or c", "a.b or c"));
+        assert!(is_same(
+            "x: { inherit x; y = x; }",
+            "x: { inherit x; y = x; }"
+        ));
+        assert!(is_same(
+            "rec { a.b = 1; a.c = 2; }",
+            "rec { a = { b = 1; c = 2; }; }"
+        ));
+    }
+
+    #[test]
+    fn different() {
+        assert!(!is_same("f 1", "f 2"));
+        assert!(!is_same("f 1", "g 1"));
+        assert!(!is_same("a + b", "a - b"));
+        assert!(!is_same("a.b", "a.b or c"));
+        assert!(!is_same("x: x", "y: y"));
+        assert!(!is_same("{ a = 1; }", "rec { a = 1; }"));
+        assert!(!is_same("[ a b ]", "[ (a b) ]"));
+        assert!(!is_same("{ inherit a; }", "{ a = a; }"));
+    }
+}
@@ -1,6 +1,9 @@
 use super::DefDatabase;
+use crate::base::SourceDatabase;
 use crate::tests::TestDB;
 use expect_test::expect;
+use rowan::ast::AstNode;
+use syntax::ast;
 
 #[test]
 fn source_map() {
@@ -28,3 +31,119 @@ fn source_map() {
     "#]]
     .assert_debug_eq(&ptrs);
 }
+
+#[test]
+fn source_map_round_trip() {
+    let (db, root, []) = TestDB::single_file("let a = 1; in a").unwrap();
+    let module = db.module(root);
+    let source_map = db.source_map(root);
+    let root_node = db.parse(root).value.syntax_node();
+
+    for (expr, _) in module.exprs() {
+        let ptr = source_map.node_for_expr(expr).unwrap();
+        let node = ptr.to_node(&root_node);
+        assert_eq!(source_map.expr_for_node(&node), Some(expr));
+    }
+
+    let def = module.name_defs.iter().next().unwrap().0;
+    let ptr = source_map.node_for_name_def(def).unwrap();
+    let node = ptr.to_node(&root_node);
+    assert_eq!(node.text(), "a");
+    assert_eq!(source_map.name_def_for_node(&node), Some(def));
+}
+
+#[test]
+fn attrpath_segments() {
+    let (db, root, []) = TestDB::single_file(r#"x.a."b".${c} ? d.${}"#).unwrap();
+    let module = db.module(root);
+    let source_map = db.source_map(root);
+    let root_node = db.parse(root).value.syntax_node();
+    let src = db.file_content(root);
+
+    let mut got = Vec::new();
+    for path in root_node.descendants().filter_map(ast::Attrpath::cast) {
+        for attr in path.attrs() {
+            let expr = source_map.expr_for_attr(&attr).unwrap();
+            let range = source_map.node_for_expr(expr).unwrap().text_range();
+            got.push(format!("{:?}: {}", module[expr], &src[range]));
+        }
+    }
+    expect![[r#"
+        [
+            "Literal(String(\"a\")): a",
+            "StringInterpolation([]): \"b\"",
+            "Reference(\"c\"): c",
+            "Literal(String(\"d\")): d",
+            "Missing: ${}",
+        ]
+    "#]]
+    .assert_debug_eq(&got);
+}
+
+#[test]
+fn path_display() {
+    let src = "[ ./. ./a/./b ../../c ./a/../.. /. /a/./b /../c ~/a ~/../a <nixpkgs> <nixpkgs/lib> <nixpkgs/../a> ]";
+    let (db, root, []) = TestDB::single_file(src).unwrap();
+    let module = db.module(root);
+    let got = module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            super::Expr::Literal(lit) => lit.path_display(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    expect![[r#"
+        [
+            "./.",
+            "./a/b",
+            "../../c",
+            "..",
+            "/",
+            "/a/b",
+            "/c",
+            "~/a",
+            "~/../a",
+            "<nixpkgs>",
+            "<nixpkgs>/lib",
+            "<nixpkgs>/../a",
+        ]
+    "#]]
+    .assert_debug_eq(&got);
+}
+
+#[test]
+fn expr_to_string() {
+    let srcs = [
+        r"a: { b, c ? 1, ... }@d: { }: x: a + b * c",
+        "(a + b) * c - (d - e) - f",
+        "(a ++ b) ++ c ++ d // (e // f) // g",
+        "!(a && b) || !c == d -> e -> (f -> g)",
+        "-(f x) + f (-1) (g x) y.z (h.i or (j 1)) [ (k 1) l.m ]",
+        r#"a ? b.${c}."d e" && (x: x) 1"#,
+        "with a; assert b; if c then d else e",
+        "let a = 1; inherit b c; inherit (d) e f; in rec { x = 1; y = x; }",
+        r#"{ a.b = 1; a.c = 2; "d e" = "f${g}h"; ${x} = [ 1 2.5 /a ~/b <c/d> http://e ]; }"#,
+        "let { body = [ null true false ./${a}/b ]; }",
+    ];
+    let got = srcs
+        .iter()
+        .map(|src| {
+            let (db, root, []) = TestDB::single_file(src).unwrap();
+            let module = db.module(root);
+            module.expr_to_string(module.entry_expr()) + "\n"
+        })
+        .collect::<String>();
+    expect![[r#"
+        a: { b, c ? 1, ... }@d: {}: x: a + b * c
+        (a + b) * c - (d - e) - f
+        (a ++ b) ++ c ++ d // (e // f) // g
+        !(a && b) || !c == d -> e -> f -> g
+        -f x + f (-1) (g x) y.z h.i or (j 1) [ (k 1) l.m ]
+        a ? b.${c}."…" && (x: x) 1
+        with a; assert b; if c then d else e
+        let a = 1; inherit b c; inherit (d) e f; in rec { x = 1; y = x; }
+        { a = { b = 1; c = 2; }; "d e" = "${g}"; ${x} = [ 1 2.5 /a ~/b <c/d> "http://e" ]; }
+        let { body = [ null true false ./${a} ]; }
+    "#]]
+    .assert_eq(&got);
+}
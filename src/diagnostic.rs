@@ -1,26 +1,132 @@
+use crate::{FileRange, TextEdit};
 use std::fmt;
 use syntax::{ErrorKind as SynErrorKind, TextRange};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Diagnostic {
     pub range: TextRange,
     pub kind: DiagnosticKind,
+    /// Related locations with explanations.
+    pub notes: Vec<(FileRange, String)>,
+    /// The quick fix, which can be applied from the diagnostic alone.
+    pub fix: Option<FixKind>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A machine-readable quick fix of a diagnostic, which needs no further analysis to apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixKind {
+    /// Remove a duplicated definition with the whitespace before it.
+    RemoveDuplicate { range: TextRange },
+    /// Remove an unused `let` binding, or the whole `let` if it is the only binding.
+    RemoveUnusedBinding { range: TextRange },
+}
+
+impl FixKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::RemoveDuplicate { .. } => "Remove this duplicated definition",
+            Self::RemoveUnusedBinding { .. } => "Remove this unused binding",
+        }
+    }
+
+    pub fn edits(&self) -> Vec<TextEdit> {
+        match *self {
+            Self::RemoveDuplicate { range } | Self::RemoveUnusedBinding { range } => {
+                vec![TextEdit {
+                    delete: range,
+                    insert: "".into(),
+                }]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DiagnosticKind {
     SyntaxError(SynErrorKind),
     InvalidDynamic,
-    DuplicatedKey,
+    /// A duplicated key, with the full dotted path if it is nested by merging attrpaths.
+    DuplicatedKey(Option<String>),
+    DuplicatedPatField,
+    PatternBindCollision,
+    ShadowedName,
+    EmptyInherit,
+    InvalidOperand,
+    UnnecessaryQuote,
+    WithNonAttrset,
+    AssertAlwaysFails,
+    InfiniteRecursion,
+    DuplicateValue,
+    LetWithoutBody,
+    EmptyInterpolation,
+    UnknownBuiltin,
+    /// A deprecated builtin, by its name in `builtins`.
+    DeprecatedBuiltin(&'static str),
+    RedundantUpdateKey,
+    UnusedBinding,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Severity {
     Error,
+    Warning,
+    /// Style suggestions which do not indicate a problem.
+    Hint,
     IncompleteSyntax,
 }
 
+impl DiagnosticKind {
+    /// The stable identifier of this kind, used for configuration and reporting.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SyntaxError(_) => "syntax_error",
+            Self::InvalidDynamic => "invalid_dynamic",
+            Self::DuplicatedKey(_) => "duplicated_key",
+            Self::DuplicatedPatField => "duplicated_pat_field",
+            Self::PatternBindCollision => "pattern_bind_collision",
+            Self::ShadowedName => "shadowed_name",
+            Self::EmptyInherit => "empty_inherit",
+            Self::InvalidOperand => "invalid_operand",
+            Self::UnnecessaryQuote => "unnecessary_quote",
+            Self::WithNonAttrset => "with_non_attrset",
+            Self::AssertAlwaysFails => "assert_always_fails",
+            Self::InfiniteRecursion => "infinite_recursion",
+            Self::DuplicateValue => "duplicate_value",
+            Self::LetWithoutBody => "let_without_body",
+            Self::EmptyInterpolation => "empty_interpolation",
+            Self::UnknownBuiltin => "unknown_builtin",
+            Self::DeprecatedBuiltin(_) => "deprecated_builtin",
+            Self::RedundantUpdateKey => "redundant_update_key",
+            Self::UnusedBinding => "unused_binding",
+        }
+    }
+
+    /// Whether this kind is only reported when enabled explicitly, since it can be noisy.
+    pub fn is_opt_in(&self) -> bool {
+        matches!(self, Self::DuplicateValue)
+    }
+}
+
 impl Diagnostic {
+    pub fn new(range: TextRange, kind: DiagnosticKind) -> Self {
+        Self {
+            range,
+            kind,
+            notes: Vec::new(),
+            fix: None,
+        }
+    }
+
+    pub fn with_note(mut self, frange: FileRange, message: impl Into<String>) -> Self {
+        self.notes.push((frange, message.into()));
+        self
+    }
+
+    pub fn with_fix(mut self, fix: FixKind) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
     pub fn severity(&self) -> Severity {
         match self.kind {
             DiagnosticKind::SyntaxError(kind) => match kind {
@@ -33,25 +139,75 @@ impl Diagnostic {
                 | SynErrorKind::MissingExpr
                 | SynErrorKind::MissingAttr => Severity::IncompleteSyntax,
             },
-            DiagnosticKind::InvalidDynamic | DiagnosticKind::DuplicatedKey => Severity::Error,
+            DiagnosticKind::LetWithoutBody | DiagnosticKind::EmptyInterpolation => {
+                Severity::IncompleteSyntax
+            }
+            DiagnosticKind::InvalidDynamic
+            | DiagnosticKind::DuplicatedKey(_)
+            | DiagnosticKind::DuplicatedPatField
+            | DiagnosticKind::PatternBindCollision
+            | DiagnosticKind::InvalidOperand => Severity::Error,
+            DiagnosticKind::ShadowedName
+            | DiagnosticKind::EmptyInherit
+            | DiagnosticKind::UnnecessaryQuote
+            | DiagnosticKind::WithNonAttrset
+            | DiagnosticKind::AssertAlwaysFails
+            | DiagnosticKind::InfiniteRecursion
+            | DiagnosticKind::UnknownBuiltin
+            | DiagnosticKind::UnusedBinding => Severity::Warning,
+            DiagnosticKind::DuplicateValue
+            | DiagnosticKind::DeprecatedBuiltin(_)
+            | DiagnosticKind::RedundantUpdateKey => Severity::Hint,
         }
     }
 
     pub fn message(&self) -> String {
-        match self.kind {
+        match &self.kind {
             DiagnosticKind::SyntaxError(kind) => kind.to_string(),
             DiagnosticKind::InvalidDynamic => "Invalid location of dynamic attribute".into(),
-            DiagnosticKind::DuplicatedKey => "Duplicated name definition".into(),
+            DiagnosticKind::DuplicatedKey(None) => "Duplicated name definition".into(),
+            DiagnosticKind::DuplicatedKey(Some(path)) => {
+                format!("Attribute `{}` is already defined", path)
+            }
+            DiagnosticKind::DuplicatedPatField => "Duplicated function argument".into(),
+            DiagnosticKind::PatternBindCollision => {
+                "The `@` binding has the same name as a pattern field".into()
+            }
+            DiagnosticKind::ShadowedName => "This name shadows an outer definition".into(),
+            DiagnosticKind::EmptyInherit => "Nothing is inherited".into(),
+            DiagnosticKind::InvalidOperand => "Invalid operand types for this operator".into(),
+            DiagnosticKind::UnnecessaryQuote => "Unnecessary quotes around the name".into(),
+            DiagnosticKind::WithNonAttrset => "The environment of `with` is not an attrset".into(),
+            DiagnosticKind::AssertAlwaysFails => "This assertion always fails".into(),
+            DiagnosticKind::InfiniteRecursion => {
+                "This binding is defined as itself, which recurses infinitely".into()
+            }
+            DiagnosticKind::DuplicateValue => {
+                "This value is identical to a sibling binding, consider factoring it out with `let`"
+                    .into()
+            }
+            DiagnosticKind::LetWithoutBody => "This `let` has no body after `in`".into(),
+            DiagnosticKind::EmptyInterpolation => "Empty interpolation".into(),
+            DiagnosticKind::UnknownBuiltin => "This attribute does not exist in `builtins`".into(),
+            DiagnosticKind::DeprecatedBuiltin(name) => {
+                match crate::builtin::get(name).and_then(|info| info.deprecated) {
+                    Some(replacement) => {
+                        format!("`{}` is deprecated, use {} instead", name, replacement)
+                    }
+                    None => format!("`{}` is deprecated", name),
+                }
+            }
+            DiagnosticKind::RedundantUpdateKey => {
+                "This attribute is always overridden by the right operand of `//`".into()
+            }
+            DiagnosticKind::UnusedBinding => "This binding is never used".into(),
         }
     }
 }
 
 impl From<syntax::Error> for Diagnostic {
     fn from(err: syntax::Error) -> Self {
-        Self {
-            range: err.range,
-            kind: DiagnosticKind::SyntaxError(err.kind),
-        }
+        Self::new(err.range, DiagnosticKind::SyntaxError(err.kind))
     }
 }
 
@@ -66,3 +222,38 @@ impl fmt::Display for Diagnostic {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DiagnosticKind;
+    use std::collections::HashSet;
+    use syntax::ErrorKind as SynErrorKind;
+
+    #[test]
+    fn unique_codes() {
+        let kinds = [
+            DiagnosticKind::SyntaxError(SynErrorKind::MissingExpr),
+            DiagnosticKind::InvalidDynamic,
+            DiagnosticKind::DuplicatedKey(None),
+            DiagnosticKind::DuplicatedPatField,
+            DiagnosticKind::PatternBindCollision,
+            DiagnosticKind::ShadowedName,
+            DiagnosticKind::EmptyInherit,
+            DiagnosticKind::InvalidOperand,
+            DiagnosticKind::UnnecessaryQuote,
+            DiagnosticKind::WithNonAttrset,
+            DiagnosticKind::AssertAlwaysFails,
+            DiagnosticKind::InfiniteRecursion,
+            DiagnosticKind::DuplicateValue,
+            DiagnosticKind::LetWithoutBody,
+            DiagnosticKind::EmptyInterpolation,
+            DiagnosticKind::UnknownBuiltin,
+            DiagnosticKind::DeprecatedBuiltin("isNull"),
+            DiagnosticKind::RedundantUpdateKey,
+            DiagnosticKind::UnusedBinding,
+        ];
+        let codes = kinds.iter().map(|kind| kind.code()).collect::<HashSet<_>>();
+        assert_eq!(codes.len(), kinds.len());
+        assert!(codes.iter().all(|code| !code.is_empty()));
+    }
+}
@@ -0,0 +1,143 @@
+//! Expand `inherit` into explicit bindings.
+//!
+//! ```nix
+//! { inherit (e) a b; inherit c; }
+//! ```
+//! =>
+//! ```nix
+//! { a = e.a; b = e.b; c = c; }
+//! ```
+use super::AssistsCtx;
+use crate::def::Expr;
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use syntax::lexer::is_valid_ident;
+use syntax::{ast, SyntaxKind};
+
+pub(super) fn expand_inherit(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let inherit = ctx.covering_node::<ast::Inherit>()?;
+
+    let from_text = match inherit.from_expr() {
+        None => None,
+        Some(paren) => {
+            let expr = paren.expr()?;
+            // Atoms can be selected without parentheses.
+            let text = match expr {
+                ast::Expr::Ref(_) | ast::Expr::Select(_) | ast::Expr::AttrSet(_) => {
+                    expr.syntax().to_string()
+                }
+                _ => paren.syntax().to_string(),
+            };
+            Some(text)
+        }
+    };
+
+    let module = ctx.db.module(ctx.file_id);
+    let source_map = ctx.db.source_map(ctx.file_id);
+    let mut bindings = Vec::new();
+    for attr in inherit.attrs() {
+        if matches!(attr, ast::Attr::Dynamic(_)) {
+            return None;
+        }
+        let key = attr.syntax().to_string();
+        let value = match &from_text {
+            Some(from) => format!("{}.{}", from, key),
+            // Inherited names are lowered into references. Use the resolved name,
+            // which must be representable as an identifier.
+            None => {
+                let expr = source_map.expr_for_node(attr.syntax())?;
+                match &module[expr] {
+                    Expr::Reference(name) if is_valid_ident(name) => name.to_string(),
+                    _ => return None,
+                }
+            }
+        };
+        bindings.push(format!("{} = {};", key, value));
+    }
+    if bindings.is_empty() {
+        return None;
+    }
+
+    // Keep one binding per line if the `inherit` is on its own line.
+    let sep = match inherit
+        .syntax()
+        .prev_sibling_or_token()
+        .and_then(|elem| elem.into_token())
+    {
+        Some(tok) if tok.kind() == SyntaxKind::SPACE && tok.text().contains('\n') => {
+            let indent = tok.text().rsplit('\n').next().unwrap_or_default();
+            format!("\n{}", indent)
+        }
+        _ => " ".into(),
+    };
+
+    let range = inherit.syntax().text_range();
+    ctx.add(
+        "expand_inherit",
+        "Expand inherit",
+        range,
+        vec![TextEdit {
+            delete: range,
+            insert: bindings.join(&sep).into(),
+        }],
+    );
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::expand_inherit, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::expand_inherit, fixture);
+    }
+
+    #[test]
+    fn inherit_from() {
+        check("{ inherit (e) $0a b; }", expect!["{ a = e.a; b = e.b; }"]);
+        check("{ inh$0erit (a.b) c; }", expect!["{ c = a.b.c; }"]);
+        check(
+            r#"{ inherit (f x) "a b"$0; }"#,
+            expect![[r#"{ "a b" = (f x)."a b"; }"#]],
+        );
+    }
+
+    #[test]
+    fn inherit_plain() {
+        check(
+            "let a = 1; in { inherit $0a; }",
+            expect!["let a = 1; in { a = a; }"],
+        );
+        check(
+            r#"let a = 1; in { inherit $0"a"; }"#,
+            expect![[r#"let a = 1; in { "a" = a; }"#]],
+        );
+        check_no(r#"let "a b" = 1; in { inherit $0"a b"; }"#);
+    }
+
+    #[test]
+    fn indentation() {
+        check(
+            "{\n  x = 1;\n  inherit (e) $0a b;\n}",
+            expect![[r#"
+                {
+                  x = 1;
+                  a = e.a;
+                  b = e.b;
+                }"#]],
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        check_no("{ inherit$0; }");
+        check_no("{ inherit ${a}$0; }");
+        check_no("{ a = $0b; }");
+    }
+}
@@ -0,0 +1,227 @@
+//! Extract the selected expression into a `let` binding.
+//!
+//! ```nix
+//! x: f (g x)
+//! ```
+//! =>
+//! ```nix
+//! x: let _name = g x; in f _name
+//! ```
+//!
+//! If the enclosing expression is already a `let` body or binding value, the new binding is
+//! appended to that `let` instead.
+use super::AssistsCtx;
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use std::collections::HashSet;
+use syntax::ast::{self, HasBindings};
+use syntax::{SyntaxKind, SyntaxNode, TextRange, TextSize};
+
+const PLACEHOLDER_NAME: &str = "_name";
+
+pub(super) fn extract_to_let(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    if ctx.range.is_empty() {
+        return None;
+    }
+    let selected = ctx
+        .covering_node::<ast::Expr>()?
+        .syntax()
+        .ancestors()
+        .take_while(|node| node.text_range().start() == ctx.range.start())
+        .filter(|node| ast::Expr::can_cast(node.kind()))
+        .find(|node| trimmed_range(node) == ctx.range)?;
+
+    // The nearest enclosing expression in a position accepting any expression.
+    let target = selected
+        .ancestors()
+        .filter(|node| ast::Expr::can_cast(node.kind()))
+        .find(|node| {
+            node.parent().map_or(false, |parent| {
+                matches!(
+                    parent.kind(),
+                    SyntaxKind::SOURCE_FILE
+                        | SyntaxKind::LAMBDA
+                        | SyntaxKind::LET_IN
+                        | SyntaxKind::WITH
+                        | SyntaxKind::ASSERT
+                        | SyntaxKind::IF_THEN_ELSE
+                        | SyntaxKind::ATTR_PATH_VALUE
+                        | SyntaxKind::PAT_FIELD
+                )
+            })
+        })?;
+
+    let root = ctx.db.parse(ctx.file_id).value.syntax_node();
+    let name = fresh_name(&root);
+    let placeholder = format!("${{1:{}}}", name);
+    let selected_range = trimmed_range(&selected);
+    let selected_text = escape_snippet(&selected.to_string()[..usize::from(selected_range.len())]);
+
+    let enclosing_let = target.parent().and_then(|parent| match parent.kind() {
+        SyntaxKind::LET_IN => ast::LetIn::cast(parent),
+        SyntaxKind::ATTR_PATH_VALUE => ast::LetIn::cast(parent.parent()?),
+        _ => None,
+    });
+
+    let (range, insert) = match enclosing_let.and_then(|let_in| {
+        let last = let_in.bindings().last()?;
+        Some((let_in, last))
+    }) {
+        // Append a binding after the last one of the existing `let`.
+        Some((let_in, last)) => {
+            let last = last.syntax();
+            let sep = match last
+                .prev_sibling_or_token()
+                .and_then(|elem| elem.into_token())
+            {
+                Some(tok) if tok.kind() == SyntaxKind::SPACE && tok.text().contains('\n') => {
+                    let indent = tok.text().rsplit('\n').next().unwrap_or_default();
+                    format!("\n{}", indent)
+                }
+                _ => " ".into(),
+            };
+            let binding = format!("{}{} = {};", sep, placeholder, selected_text);
+            let let_range = let_in.syntax().text_range();
+            let insert = rewrite(
+                &root,
+                let_range,
+                &mut [
+                    (TextRange::empty(last.text_range().end()), binding),
+                    (selected_range, placeholder.clone()),
+                ],
+            );
+            (let_range, insert)
+        }
+        // Wrap the target expression with a new `let`.
+        None => {
+            let target_range = trimmed_range(&target);
+            let body = rewrite(
+                &root,
+                target_range,
+                &mut [(selected_range, placeholder.clone())],
+            );
+            let insert = format!("let {} = {}; in {}", placeholder, selected_text, body);
+            (target_range, insert)
+        }
+    };
+
+    ctx.add_snippet(
+        "extract_to_let",
+        "Extract to let binding",
+        selected_range,
+        vec![TextEdit {
+            delete: range,
+            insert: insert.into(),
+        }],
+    );
+    Some(())
+}
+
+/// The range of a node without trailing whitespaces and comments.
+fn trimmed_range(node: &SyntaxNode) -> TextRange {
+    let text = node.to_string();
+    let len = text.trim_end().len();
+    TextRange::at(node.text_range().start(), TextSize::of(&text[..len]))
+}
+
+/// Pick a name which does not appear in the file, to avoid shadowing or being shadowed.
+fn fresh_name(root: &SyntaxNode) -> String {
+    let used = root
+        .descendants_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .filter(|tok| tok.kind() == SyntaxKind::IDENT)
+        .map(|tok| tok.text().to_owned())
+        .collect::<HashSet<_>>();
+    std::iter::once(PLACEHOLDER_NAME.to_owned())
+        .chain((1..).map(|i| format!("{}{}", PLACEHOLDER_NAME, i)))
+        .find(|name| !used.contains(name))
+        .unwrap()
+}
+
+/// Get the escaped text of `range` with some subranges replaced by snippets.
+fn rewrite(root: &SyntaxNode, range: TextRange, replaces: &mut [(TextRange, String)]) -> String {
+    replaces.sort_by_key(|(r, _)| (r.start(), r.end()));
+    let src = root.to_string();
+    let mut ret = String::new();
+    let mut pos = range.start();
+    for (r, snippet) in replaces.iter() {
+        ret += &escape_snippet(&src[TextRange::new(pos, r.start())]);
+        ret += snippet;
+        pos = r.end();
+    }
+    ret += &escape_snippet(&src[TextRange::new(pos, range.end())]);
+    ret
+}
+
+fn escape_snippet(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '$' | '}') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check_range(super::extract_to_let, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no_range(super::extract_to_let, fixture);
+    }
+
+    #[test]
+    fn fresh_let() {
+        check(
+            "x: f ($0g x$1)",
+            expect!["x: let ${1:_name} = g x; in f (${1:_name})"],
+        );
+        check(
+            "{ a = $0b + 1$1 + c; }",
+            expect!["{ a = let ${1:_name} = b + 1; in ${1:_name} + c; }"],
+        );
+        check(
+            r#"$0"${a}"$1 + b"#,
+            expect![[r#"let ${1:_name} = "\${a\}"; in ${1:_name} + b"#]],
+        );
+    }
+
+    #[test]
+    fn append() {
+        check(
+            "let a = 1; in f $0a.b$1",
+            expect!["let a = 1; ${1:_name} = a.b; in f ${1:_name}"],
+        );
+        check(
+            "let\n  a = f $0[ 1 ]$1;\n  b = 2;\nin a",
+            expect![[r#"
+                let
+                  a = f ${1:_name};
+                  b = 2;
+                  ${1:_name} = [ 1 ];
+                in a"#]],
+        );
+    }
+
+    #[test]
+    fn fresh_name() {
+        check(
+            "_name: $0_name + 1$1",
+            expect!["_name: let ${1:_name1} = _name + 1; in ${1:_name1}"],
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        check_no("f $0a +$1 b");
+        check_no("{ $0a = 1;$1 }");
+    }
+}
@@ -0,0 +1,235 @@
+//! Inline a `let` binding which is used exactly once.
+//!
+//! ```nix
+//! let x = a + b; in f x
+//! ```
+//! =>
+//! ```nix
+//! f (a + b)
+//! ```
+use super::AssistsCtx;
+use crate::def::{BindingKey, BindingValue, Expr, ExprId, Module};
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use syntax::ast::{self, HasBindings};
+use syntax::precedence as prec;
+use syntax::{match_ast, SyntaxKind, SyntaxNode};
+
+pub(super) fn inline_variable(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let db = ctx.db;
+    let file_id = ctx.file_id;
+    let root = db.parse(file_id).value.syntax_node();
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+
+    // The cursor can be either on the binding name or on the use.
+    let def = match ctx.covering_node::<ast::Ref>() {
+        Some(use_ref) => {
+            let expr = source_map.expr_for_node(use_ref.syntax())?;
+            db.resolve_name(file_id, expr)?.as_name_def()?
+        }
+        None => source_map.name_def_for_node(ctx.covering_node::<ast::Attr>()?.syntax())?,
+    };
+
+    let attr = source_map.node_for_name_def(def)?.to_node(&root);
+    let attrpath = ast::Attrpath::cast(attr.parent()?)?;
+    if attrpath.attrs().count() != 1 {
+        return None;
+    }
+    let entry = ast::AttrpathValue::cast(attrpath.syntax().parent()?)?;
+    let let_in = ast::LetIn::cast(entry.syntax().parent()?)?;
+    let value = entry.value()?;
+    let value_expr = match &module[source_map.expr_for_node(let_in.syntax())?] {
+        Expr::LetIn(bindings, _) => bindings.entries.iter().find_map(|(k, v)| match (k, v) {
+            (BindingKey::NameDef(d), &BindingValue::Expr(e)) if *d == def => Some(e),
+            _ => None,
+        })?,
+        _ => return None,
+    };
+
+    let use_expr = match db.name_reference_map(file_id).def_references(def)? {
+        &[use_expr] => use_expr,
+        _ => return None,
+    };
+    // Inherited names have no expression to be replaced.
+    let use_ref = ast::Ref::cast(source_map.node_for_expr(use_expr)?.to_node(&root))?;
+    let use_range = use_ref.syntax().text_range();
+    if entry.syntax().text_range().contains_range(use_range) {
+        return None;
+    }
+
+    // Free names in the inlined expression must still resolve to the same things at the use site.
+    let scopes = db.scopes(file_id);
+    let value_range = value.syntax().text_range();
+    let mut captured = false;
+    walk_exprs(&module, value_expr, &mut |e| {
+        let name = match &module[e] {
+            Expr::Reference(name) => name,
+            _ => return,
+        };
+        let resolved = db.resolve_name(file_id, e);
+        let is_local = resolved
+            .as_ref()
+            .and_then(|ret| ret.as_name_def())
+            .and_then(|def| source_map.node_for_name_def(def))
+            .map_or(false, |ptr| value_range.contains_range(ptr.text_range()));
+        captured |= !is_local && scopes.resolve_name(use_expr, name) != resolved;
+    });
+    if captured {
+        return None;
+    }
+
+    let value_text = value.syntax().to_string();
+    let value_text = value_text.trim_end();
+    let insert = if needs_parens(use_ref.syntax(), precedence(&value)) {
+        format!("({})", value_text)
+    } else {
+        value_text.to_owned()
+    };
+
+    let edits = if let_in.bindings().count() == 1 {
+        // Removing the only binding leaves an empty `let`. Replace it by its body.
+        let body = let_in.body()?;
+        let body_range = body.syntax().text_range();
+        let src = root.to_string();
+        let mut text = src[body_range].to_owned();
+        let use_range = use_range - body_range.start();
+        text.replace_range(std::ops::Range::<usize>::from(use_range), &insert);
+        vec![TextEdit {
+            delete: let_in.syntax().text_range().cover(body_range),
+            insert: text.into(),
+        }]
+    } else {
+        // Also remove the whitespace before the binding, so no blank line is left.
+        let entry_range = entry.syntax().text_range();
+        let delete = match entry
+            .syntax()
+            .prev_sibling_or_token()
+            .and_then(|elem| elem.into_token())
+        {
+            Some(tok) if tok.kind() == SyntaxKind::SPACE => tok.text_range().cover(entry_range),
+            _ => entry_range,
+        };
+        vec![
+            TextEdit {
+                delete,
+                insert: "".into(),
+            },
+            TextEdit {
+                delete: use_range,
+                insert: insert.into(),
+            },
+        ]
+    };
+
+    ctx.add(
+        "inline_variable",
+        "Inline variable",
+        entry.syntax().text_range(),
+        edits,
+    );
+    Some(())
+}
+
+fn walk_exprs(module: &Module, expr: ExprId, f: &mut impl FnMut(ExprId)) {
+    f(expr);
+    module[expr].walk_child_exprs(|e| walk_exprs(module, e, f));
+}
+
+/// The binding power of an expression. Higher values bind tighter.
+fn precedence(e: &ast::Expr) -> u8 {
+    match e {
+        ast::Expr::Lambda(_)
+        | ast::Expr::Assert(_)
+        | ast::Expr::With(_)
+        | ast::Expr::LetIn(_)
+        | ast::Expr::IfThenElse(_) => prec::LOWEST,
+        ast::Expr::BinaryOp(op) => op.op_kind().map_or(prec::LOWEST, prec::binary),
+        ast::Expr::UnaryOp(op) => op.op_kind().map_or(prec::LOWEST, prec::unary),
+        ast::Expr::HasAttr(_) => prec::HAS_ATTR,
+        ast::Expr::Apply(_) => prec::APPLY,
+        ast::Expr::Select(_) => prec::SELECT,
+        _ => prec::ATOM,
+    }
+}
+
+/// Whether an expression with precedence `expr_prec` needs parentheses to replace `node`.
+fn needs_parens(node: &SyntaxNode, expr_prec: u8) -> bool {
+    let parent = match node.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let is_first_child = parent.first_child().as_ref() == Some(node);
+    let min_prec = match_ast! {
+        match parent {
+            ast::BinaryOp(op) => {
+                let (lhs_prec, rhs_prec) = match op.op_kind() {
+                    Some(kind) => prec::binary_operands(kind),
+                    None => return true,
+                };
+                if is_first_child { lhs_prec } else { rhs_prec }
+            },
+            ast::UnaryOp(op) => precedence(&ast::Expr::UnaryOp(op)),
+            ast::HasAttr(_) => prec::HAS_ATTR + 1,
+            ast::Apply(_) => if is_first_child { prec::APPLY } else { prec::SELECT },
+            ast::Select(_) => if is_first_child { prec::ATOM } else { prec::SELECT },
+            ast::List(_) => prec::SELECT,
+            _ => prec::LOWEST,
+        }
+    };
+    expr_prec < min_prec
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::inline_variable, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::inline_variable, fixture);
+    }
+
+    #[test]
+    fn no_parens() {
+        check("let $0x = 1; in x", expect!["1"]);
+        check("let x = a b; in $0x c", expect!["a b c"]);
+        check("let $0x = a * b; in x + c", expect!["a * b + c"]);
+        check("let x = f a; in [ ($0x) ]", expect!["[ (f a) ]"]);
+        check(
+            "let\n  $0x = a.b;\n  y = 2;\nin f x y",
+            expect![[r#"
+                let
+                  y = 2;
+                in f a.b y"#]],
+        );
+    }
+
+    #[test]
+    fn needs_parens() {
+        check("let $0x = a + b; in f x", expect!["f (a + b)"]);
+        check("let $0x = a + b; in x * c", expect!["(a + b) * c"]);
+        check("let $0x = a - b; in c - x", expect!["c - (a - b)"]);
+        check("let $0x = -1; in f x", expect!["f (-1)"]);
+        check("let $0x = a: a; in [ x ]", expect!["[ (a: a) ]"]);
+        check("let $0x = a.b; in f x", expect!["f a.b"]);
+        check("let $0x = f a; in x.b", expect!["(f a).b"]);
+        check("let $0x = a ++ b; in x ++ c", expect!["(a ++ b) ++ c"]);
+        check("let $0x = a ++ b; in c ++ x", expect!["c ++ a ++ b"]);
+        check("let $0x = a ? b; in x ? c", expect!["(a ? b) ? c"]);
+    }
+
+    #[test]
+    fn invalid() {
+        check_no("let $0x = 1; in x + x");
+        check_no("let $0x = 1; in 2");
+        check_no("let $0x = y; in { inherit x; }");
+        check_no("let $0x = x; in 1");
+        check_no("rec { $0x = 1; y = x; }");
+        check_no("let $0x = a; in a: x");
+    }
+}
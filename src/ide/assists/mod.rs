@@ -0,0 +1,191 @@
+//! Assists are code actions attached to a cursor position or selection,
+//! which rewrite the source without changing the semantics.
+mod expand_inherit;
+mod extract_to_let;
+mod inline_variable;
+mod remove_empty_inherit;
+mod sort_attrs;
+mod unquote_attr;
+mod wrap_to_string;
+
+use crate::def::DefDatabase;
+use crate::{FileId, FileRange, TextEdit};
+use rowan::ast::AstNode;
+use smol_str::SmolStr;
+use syntax::{NixLanguage, SyntaxKind, SyntaxNode, TextRange};
+
+/// A refactor which can be applied to the selected range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assist {
+    /// The unique identifier of this kind of assist.
+    pub id: &'static str,
+    /// The label to show in the code action menu.
+    pub label: SmolStr,
+    /// The range this assist is applicable to.
+    pub target: TextRange,
+    /// The edits to apply in the file.
+    pub edits: Vec<TextEdit>,
+    /// Whether the inserted texts of `edits` are in the LSP snippet syntax.
+    pub is_snippet: bool,
+}
+
+type AssistHandler = fn(&mut AssistsCtx<'_>) -> Option<()>;
+
+const ASSIST_HANDLERS: &[AssistHandler] = &[
+    expand_inherit::expand_inherit,
+    extract_to_let::extract_to_let,
+    inline_variable::inline_variable,
+    remove_empty_inherit::remove_empty_inherit,
+    sort_attrs::sort_attrs,
+    unquote_attr::unquote_attr,
+    wrap_to_string::wrap_to_string,
+];
+
+pub(crate) fn assists(db: &dyn DefDatabase, frange: FileRange) -> Vec<Assist> {
+    let mut ctx = AssistsCtx::new(db, frange);
+    for handler in ASSIST_HANDLERS {
+        handler(&mut ctx);
+    }
+    ctx.assists
+}
+
+pub(crate) struct AssistsCtx<'a> {
+    assists: Vec<Assist>,
+    pub(crate) db: &'a dyn DefDatabase,
+    pub(crate) file_id: FileId,
+    pub(crate) range: TextRange,
+    covering_node: SyntaxNode,
+}
+
+impl<'a> AssistsCtx<'a> {
+    fn new(db: &'a dyn DefDatabase, frange: FileRange) -> Self {
+        let root = db.parse(frange.file_id).value.syntax_node();
+        let covering_node = if frange.value.is_empty() {
+            // For a cursor position, prefer the adjacent token which is not whitespace.
+            root.token_at_offset(frange.value.start())
+                .max_by_key(|tok| tok.kind() != SyntaxKind::SPACE)
+                .and_then(|tok| tok.parent())
+                .unwrap_or(root)
+        } else {
+            match root.covering_element(frange.value) {
+                rowan::NodeOrToken::Node(n) => n,
+                rowan::NodeOrToken::Token(t) => t.parent().unwrap_or(root),
+            }
+        };
+        Self {
+            assists: Vec::new(),
+            db,
+            file_id: frange.file_id,
+            range: frange.value,
+            covering_node,
+        }
+    }
+
+    /// Find the innermost node of type `N` covering the selected range.
+    pub(crate) fn covering_node<N: AstNode<Language = NixLanguage>>(&self) -> Option<N> {
+        self.covering_node.ancestors().find_map(N::cast)
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        id: &'static str,
+        label: impl Into<SmolStr>,
+        target: TextRange,
+        edits: Vec<TextEdit>,
+    ) {
+        self.assists.push(Assist {
+            id,
+            label: label.into(),
+            target,
+            edits,
+            is_snippet: false,
+        });
+    }
+
+    /// Same as `add`, but the inserted texts are snippets with placeholders.
+    pub(crate) fn add_snippet(
+        &mut self,
+        id: &'static str,
+        label: impl Into<SmolStr>,
+        target: TextRange,
+        edits: Vec<TextEdit>,
+    ) {
+        self.add(id, label, target, edits);
+        self.assists.last_mut().unwrap().is_snippet = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssistHandler;
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use crate::FileRange;
+    use expect_test::Expect;
+    use syntax::TextRange;
+
+    #[track_caller]
+    pub(crate) fn check(handler: AssistHandler, fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        check_impl(
+            handler,
+            &db,
+            FileRange::new(file_id, TextRange::empty(pos)),
+            expect,
+        );
+    }
+
+    /// Same as `check`, but with a selection from marker 0 to marker 1.
+    #[track_caller]
+    pub(crate) fn check_range(handler: AssistHandler, fixture: &str, expect: Expect) {
+        let (db, file_id, [start, end]) = TestDB::single_file(fixture).unwrap();
+        check_impl(
+            handler,
+            &db,
+            FileRange::new(file_id, TextRange::new(start, end)),
+            expect,
+        );
+    }
+
+    #[track_caller]
+    fn check_impl(handler: AssistHandler, db: &TestDB, frange: FileRange, expect: Expect) {
+        let file_id = frange.file_id;
+        let mut ctx = super::AssistsCtx::new(db, frange);
+        handler(&mut ctx).expect("No assist");
+        assert_eq!(ctx.assists.len(), 1, "Multiple assists");
+        let mut edits = ctx.assists.pop().unwrap().edits;
+        let mut src = db.file_content(file_id).to_string();
+        edits.sort_by_key(|edit| edit.delete.start());
+        for edit in edits.iter().rev() {
+            edit.apply(&mut src);
+        }
+        expect.assert_eq(&src);
+    }
+
+    #[track_caller]
+    pub(crate) fn check_no(handler: AssistHandler, fixture: &str) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        check_no_impl(handler, &db, FileRange::new(file_id, TextRange::empty(pos)));
+    }
+
+    #[track_caller]
+    pub(crate) fn check_no_range(handler: AssistHandler, fixture: &str) {
+        let (db, file_id, [start, end]) = TestDB::single_file(fixture).unwrap();
+        check_no_impl(
+            handler,
+            &db,
+            FileRange::new(file_id, TextRange::new(start, end)),
+        );
+    }
+
+    #[track_caller]
+    fn check_no_impl(handler: AssistHandler, db: &TestDB, frange: FileRange) {
+        let mut ctx = super::AssistsCtx::new(db, frange);
+        handler(&mut ctx);
+        assert!(
+            ctx.assists.is_empty(),
+            "Unexpected assists: {:?}",
+            ctx.assists
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Remove an `inherit` without any names, which has no effect.
+//!
+//! ```nix
+//! { inherit (e); a = 1; }
+//! ```
+//! =>
+//! ```nix
+//! { a = 1; }
+//! ```
+use super::AssistsCtx;
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use syntax::{ast, SyntaxKind};
+
+pub(super) fn remove_empty_inherit(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let inherit = ctx.covering_node::<ast::Inherit>()?;
+    if inherit.attrs().next().is_some() {
+        return None;
+    }
+
+    // Also remove the whitespace before it, so no blank line is left.
+    let target = inherit.syntax().text_range();
+    let delete = match inherit
+        .syntax()
+        .prev_sibling_or_token()
+        .and_then(|elem| elem.into_token())
+    {
+        Some(tok) if tok.kind() == SyntaxKind::SPACE => tok.text_range().cover(target),
+        _ => target,
+    };
+    ctx.add(
+        "remove_empty_inherit",
+        "Remove empty inherit",
+        target,
+        vec![TextEdit {
+            delete,
+            insert: "".into(),
+        }],
+    );
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::remove_empty_inherit, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::remove_empty_inherit, fixture);
+    }
+
+    #[test]
+    fn simple() {
+        check("{ inherit$0; }", expect!["{ }"]);
+        check("{ a = 1; $0inherit (e); }", expect!["{ a = 1; }"]);
+        check(
+            "let\n  a = 1;\n  inherit$0;\nin a",
+            expect![[r#"
+                let
+                  a = 1;
+                in a"#]],
+        );
+    }
+
+    #[test]
+    fn non_empty() {
+        check_no("{ inherit $0a; }");
+        check_no("{ inherit (e) $0a; }");
+    }
+}
@@ -0,0 +1,205 @@
+//! Sort the bindings of an attrset, or the selected ones inside it, by their names.
+//! Comments right above a binding or after it on the same line are moved along.
+//! Bindings with dynamic names are kept in the source order after all others.
+//!
+//! ```nix
+//! { b = 1; a = 2; ${c} = 3; }
+//! ```
+//! =>
+//! ```nix
+//! { a = 2; b = 1; ${c} = 3; }
+//! ```
+use super::AssistsCtx;
+use crate::def::static_string_content;
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use rowan::NodeOrToken;
+use std::cmp::Ordering;
+use syntax::ast::{self, HasBindings};
+use syntax::{SyntaxKind, SyntaxNode, TextRange};
+
+pub(super) fn sort_attrs(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let set = ctx.covering_node::<ast::AttrSet>()?;
+    let bindings = set
+        .bindings()
+        .filter(|b| ctx.range.is_empty() || ctx.range.contains_range(b.syntax().text_range()))
+        .collect::<Vec<_>>();
+    if bindings.len() < 2 {
+        return None;
+    }
+
+    let keys = bindings.iter().map(binding_key).collect::<Vec<_>>();
+    let mut order = (0..bindings.len()).collect::<Vec<_>>();
+    // The sort is stable, so dynamic names keep their order.
+    order.sort_by(|&i, &j| match (&keys[i], &keys[j]) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    if order.iter().enumerate().all(|(slot, &i)| slot == i) {
+        return None;
+    }
+
+    // Bindings with their comments are swapped, while whitespaces between them are kept in place.
+    let src = ctx.db.file_content(ctx.file_id);
+    let ranges = bindings
+        .iter()
+        .map(|b| binding_range(b.syntax()))
+        .collect::<Vec<_>>();
+    let mut text = String::new();
+    for (slot, &i) in order.iter().enumerate() {
+        if slot != 0 {
+            text += &src[TextRange::new(ranges[slot - 1].end(), ranges[slot].start())];
+        }
+        text += &src[ranges[i]];
+    }
+    ctx.add(
+        "sort_attrs",
+        "Sort attributes alphabetically",
+        set.syntax().text_range(),
+        vec![TextEdit {
+            delete: TextRange::new(ranges[0].start(), ranges[ranges.len() - 1].end()),
+            insert: text.into(),
+        }],
+    );
+    Some(())
+}
+
+/// The leading static names of a binding, or `None` if it starts with a dynamic one.
+/// `inherit` is sorted by its first name.
+fn binding_key(binding: &ast::Binding) -> Option<Vec<String>> {
+    let attrs = match binding {
+        ast::Binding::AttrpathValue(entry) => entry.attrpath()?.attrs().collect::<Vec<_>>(),
+        ast::Binding::Inherit(inherit) => inherit.attrs().take(1).collect(),
+    };
+    let names = attrs
+        .iter()
+        .map_while(|attr| match attr {
+            ast::Attr::Name(name) => Some(name.token()?.text().to_owned()),
+            ast::Attr::String(s) => static_string_content(s),
+            ast::Attr::Dynamic(_) => None,
+        })
+        .collect::<Vec<_>>();
+    (!names.is_empty()).then_some(names)
+}
+
+/// The range of a binding extended to the comments attached to it.
+fn binding_range(node: &SyntaxNode) -> TextRange {
+    let mut range = node.text_range();
+
+    // Comments on the lines right above, but not trailing the previous binding.
+    let mut cur = node.prev_sibling_or_token();
+    while let Some(elem) = cur {
+        match &elem {
+            NodeOrToken::Token(tok) if tok.kind() == SyntaxKind::SPACE => {
+                if tok.text().matches('\n').count() >= 2 {
+                    break;
+                }
+            }
+            NodeOrToken::Token(tok) if tok.kind() == SyntaxKind::COMMENT => {
+                if is_trailing_comment(&elem) {
+                    break;
+                }
+                range = range.cover(tok.text_range());
+            }
+            _ => break,
+        }
+        cur = elem.prev_sibling_or_token();
+    }
+
+    // A comment after it on the same line.
+    let mut next = node.next_sibling_or_token();
+    if let Some(NodeOrToken::Token(tok)) = &next {
+        if tok.kind() == SyntaxKind::SPACE && !tok.text().contains('\n') {
+            next = tok.next_sibling_or_token();
+        }
+    }
+    if let Some(NodeOrToken::Token(tok)) = next {
+        if tok.kind() == SyntaxKind::COMMENT {
+            range = range.cover(tok.text_range());
+        }
+    }
+    range
+}
+
+fn is_trailing_comment(elem: &syntax::SyntaxElement) -> bool {
+    match elem.prev_sibling_or_token() {
+        Some(NodeOrToken::Token(space)) if space.kind() == SyntaxKind::SPACE => {
+            !space.text().contains('\n')
+                && matches!(space.prev_sibling_or_token(), Some(NodeOrToken::Node(_)))
+        }
+        Some(NodeOrToken::Node(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::sort_attrs, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_range(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check_range(super::sort_attrs, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::sort_attrs, fixture);
+    }
+
+    #[test]
+    fn simple() {
+        check("{ b = 1; $0a = 2; }", expect!["{ a = 2; b = 1; }"]);
+        check(
+            "rec { inherit (x) c; $0a.y = 1; a.x = 2; \"b\" = 3; }",
+            expect![[r#"rec { a.x = 2; a.y = 1; "b" = 3; inherit (x) c; }"#]],
+        );
+        check(
+            "{ ${b} = 1; c = 2; ${a} = 3; $0b = 4; }",
+            expect!["{ b = 4; c = 2; ${b} = 1; ${a} = 3; }"],
+        );
+        check_no("{ a = 1; $0b = 2; }");
+        check_no("{ $0a = 1; }");
+    }
+
+    #[test]
+    fn comments() {
+        check(
+            "{
+  # About b.
+  b = 1; # One.
+
+  /* About a. */
+  $0a = 2;
+  # Unrelated.
+
+  c = 3;
+}",
+            expect![[r#"
+                {
+                  /* About a. */
+                  a = 2;
+
+                  # About b.
+                  b = 1; # One.
+                  # Unrelated.
+
+                  c = 3;
+                }"#]],
+        );
+    }
+
+    #[test]
+    fn selection() {
+        check_range(
+            "{ d = 1; $0c = 2; b = 3;$1 a = 4; }",
+            expect!["{ d = 1; b = 3; c = 2; a = 4; }"],
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Remove the quotes of an attribute name which is a valid identifier.
+//!
+//! ```nix
+//! { "foo" = 1; }
+//! ```
+//! =>
+//! ```nix
+//! { foo = 1; }
+//! ```
+use super::AssistsCtx;
+use crate::def::static_string_content;
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use syntax::lexer::is_valid_ident;
+use syntax::{ast, SyntaxKind};
+
+pub(super) fn unquote_attr(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let string = ctx.covering_node::<ast::String>()?;
+    // Only names in attrpaths or `inherit`, not `${"foo"}`.
+    let parent = string.syntax().parent()?;
+    if !matches!(parent.kind(), SyntaxKind::ATTR_PATH | SyntaxKind::INHERIT) {
+        return None;
+    }
+    let content = static_string_content(&string)?;
+    if !is_valid_ident(&content) {
+        return None;
+    }
+    let range = string.syntax().text_range();
+    ctx.add(
+        "unquote_attr",
+        format!("Unquote attribute name `{}`", content),
+        range,
+        vec![TextEdit {
+            delete: range,
+            insert: content.into(),
+        }],
+    );
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::unquote_attr, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::unquote_attr, fixture);
+    }
+
+    #[test]
+    fn simple() {
+        check(r#"{ "fo$0o" = 1; }"#, expect!["{ foo = 1; }"]);
+        check(r#"{ a."$0b" = 1; }"#, expect!["{ a.b = 1; }"]);
+        check(r#"{ inherit "$0a"; }"#, expect!["{ inherit a; }"]);
+        check(
+            r#"let "a\-b$0" = 1; in a-b"#,
+            expect!["let a-b = 1; in a-b"],
+        );
+    }
+
+    #[test]
+    fn need_quote() {
+        check_no(r#"{ "foo $0bar" = 1; }"#);
+        check_no(r#"{ "$0in" = 1; }"#);
+        check_no(r#"{ "$0${a}" = 1; }"#);
+        check_no(r#"{ ${"$0a"} = 1; }"#);
+        check_no(r#"{ a = "$0b"; }"#);
+    }
+}
@@ -0,0 +1,93 @@
+//! Convert a number or a path interpolated into a string with `toString`.
+//! Numbers cannot be interpolated directly, and `toString` on a path uses the path itself
+//! instead of copying it into the store.
+//!
+//! ```nix
+//! "port ${8080}"
+//! ```
+//! =>
+//! ```nix
+//! "port ${toString 8080}"
+//! ```
+use super::AssistsCtx;
+use crate::def::{Expr, Literal, ResolveResult};
+use crate::TextEdit;
+use rowan::ast::AstNode;
+use smol_str::SmolStr;
+use syntax::{ast, SyntaxKind, TextRange};
+
+pub(super) fn wrap_to_string(ctx: &mut AssistsCtx<'_>) -> Option<()> {
+    let dynamic = ctx.covering_node::<ast::Dynamic>()?;
+    // Only interpolations in strings, not in paths or attrpaths.
+    let parent_kind = dynamic.syntax().parent()?.kind();
+    if !matches!(parent_kind, SyntaxKind::STRING | SyntaxKind::INDENT_STRING) {
+        return None;
+    }
+    let inner = dynamic.expr()?;
+    let source_map = ctx.db.source_map(ctx.file_id);
+    let expr = source_map.expr_for_node(inner.syntax())?;
+    let module = ctx.db.module(ctx.file_id);
+    if !matches!(
+        &module[expr],
+        Expr::Literal(Literal::Int(_) | Literal::Float(_) | Literal::Path(_))
+            | Expr::PathInterpolation(_)
+    ) {
+        return None;
+    }
+
+    // Use the global `toString` unless it is shadowed.
+    let scopes = ctx.db.scopes(ctx.file_id);
+    let func = match scopes.resolve_name(expr, &SmolStr::from("toString")) {
+        Some(ResolveResult::Builtin("toString")) => "toString ",
+        _ => "builtins.toString ",
+    };
+    // Literals are atoms and need no parentheses.
+    let range = inner.syntax().text_range();
+    ctx.add(
+        "wrap_to_string",
+        "Wrap with toString",
+        range,
+        vec![TextEdit {
+            delete: TextRange::empty(range.start()),
+            insert: func.into(),
+        }],
+    );
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: expect_test::Expect) {
+        super::super::tests::check(super::wrap_to_string, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        super::super::tests::check_no(super::wrap_to_string, fixture);
+    }
+
+    #[test]
+    fn literal() {
+        check(r#""${$01}""#, expect![[r#""${toString 1}""#]]);
+        check(
+            r#""port ${8080$0}""#,
+            expect![[r#""port ${toString 8080}""#]],
+        );
+        check("''${$0./a.nix}''", expect!["''${toString ./a.nix}''"]);
+        check(
+            r#"let toString = x: x; in "${$01.5}""#,
+            expect![[r#"let toString = x: x; in "${builtins.toString 1.5}""#]],
+        );
+    }
+
+    #[test]
+    fn not_applicable() {
+        check_no(r#""${$0x}""#);
+        check_no(r#""${$0"a"}""#);
+        check_no("./${$01}");
+        check_no(r#"{ ${$01} = 1; }"#);
+    }
+}
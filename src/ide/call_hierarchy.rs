@@ -0,0 +1,118 @@
+use super::{container_name, NavigationTarget, SymbolKind};
+use crate::def::{DefDatabase, Expr, NameDefId, ResolveResult};
+use crate::FileId;
+use rowan::ast::AstNode;
+use rowan::TextSize;
+use smol_str::SmolStr;
+use syntax::{ast, match_ast, SyntaxKind, T};
+
+/// A function which can be the root of a call hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallHierarchyItem {
+    pub name: SmolStr,
+    pub kind: SymbolKind,
+    /// The focus range is the name of the function,
+    /// and the full range is the whole binding.
+    pub target: NavigationTarget,
+}
+
+pub(crate) fn prepare_call_hierarchy(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<CallHierarchyItem> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).right_biased()?;
+    if !matches!(tok.kind(), T![or] | SyntaxKind::IDENT) {
+        return None;
+    }
+
+    let source_map = db.source_map(file_id);
+    let def = tok.parent_ancestors().find_map(|node| {
+        match_ast! {
+            match node {
+                ast::Ref(n) => {
+                    let expr = source_map.expr_for_node(n.syntax())?;
+                    match db.resolve_name(file_id, expr)? {
+                        ResolveResult::NameDef(def) => Some(def),
+                        ResolveResult::Builtin(_) | ResolveResult::WithExprs(_) => None,
+                    }
+                },
+                ast::Attr(n) => source_map.name_def_for_node(n.syntax()),
+                _ => None,
+            }
+        }
+    })?;
+    call_hierarchy_item(db, file_id, def)
+}
+
+/// Returns `None` if the name is not bound to a lambda.
+fn call_hierarchy_item(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    def: NameDefId,
+) -> Option<CallHierarchyItem> {
+    let module = db.module(file_id);
+    let value = module.name_def_value(def)?;
+    if !matches!(module[value], Expr::Lambda(..)) {
+        return None;
+    }
+
+    let parse = db.parse(file_id).value;
+    let source_map = db.source_map(file_id);
+    let name_node = source_map
+        .node_for_name_def(def)?
+        .to_node(&parse.syntax_node());
+    let full_node = name_node
+        .ancestors()
+        .find(|n| n.kind() == SyntaxKind::ATTR_PATH_VALUE)?;
+    Some(CallHierarchyItem {
+        name: module[def].name.clone(),
+        kind: SymbolKind::Function,
+        target: NavigationTarget {
+            file_id,
+            focus_range: name_node.text_range(),
+            full_range: full_node.text_range(),
+            container_name: container_name(&name_node),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = super::prepare_call_hierarchy(&db, file_id, pos).map(|item| {
+            let src = db.file_content(file_id);
+            let target = item.target;
+            format!(
+                "{} {:?} <{}> {}",
+                item.name, item.kind, &src[target.focus_range], &src[target.full_range],
+            )
+        });
+        expect.assert_eq(got.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn lambda_binding() {
+        check("let f = x: x; in $0f", expect!["f Function <f> f = x: x;"]);
+        check("let $0f = x: x; in f", expect!["f Function <f> f = x: x;"]);
+        check(
+            "rec { f = x: x; g = $0f 1; }",
+            expect!["f Function <f> f = x: x;"],
+        );
+    }
+
+    #[test]
+    fn not_callable() {
+        check("let f = 1; in $0f", expect!["None"]);
+        check("let f = x: x; in f $01", expect!["None"]);
+        check("x: $0x", expect!["None"]);
+        check("$0map", expect!["None"]);
+    }
+}
@@ -1,8 +1,10 @@
-use crate::def::{AstPtr, DefDatabase};
+use super::imports::is_nixpkgs_import;
+use super::{fuzzy_match, is_flake_file, resolve_attrset};
+use crate::def::{BindingValue, DefDatabase, Expr, ExprId, ResolveResult, ScopeId};
 use crate::{builtin, FileId};
 use rowan::ast::AstNode;
 use smol_str::SmolStr;
-use syntax::{ast, match_ast, SyntaxKind, TextRange, TextSize, T};
+use syntax::{ast, match_ast, SyntaxKind, SyntaxNode, SyntaxToken, TextRange, TextSize, T};
 
 /// A single completion variant in the editor pop-up.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +17,19 @@ pub struct CompletionItem {
     pub replace: SmolStr,
     /// What item (struct, function, etc) are we completing.
     pub kind: CompletionItemKind,
+    /// A replacement with placeholders in the LSP snippet syntax, to use instead of `replace`
+    /// if the client supports snippets.
+    pub snippet: Option<SmolStr>,
+    /// Additional information to show beside the label.
+    pub detail: Option<SmolStr>,
+    /// Items with lower priorities are shown first, eg. names from inner scopes.
+    pub sort_priority: u32,
+    /// Characters which accept this item and are then typed, eg. `.` to continue an attrpath.
+    pub commit_characters: &'static [char],
+    /// The text to match against instead of `label`, eg. the full attrpath `a.b.c` for `c`.
+    pub filter_text: Option<SmolStr>,
+    /// The documentation in Markdown, eg. the comments above a binding.
+    pub documentation: Option<String>,
 }
 
 /// The type of the completion item.
@@ -22,22 +37,164 @@ pub struct CompletionItem {
 pub enum CompletionItemKind {
     Builtin,
     Binding,
+    /// A keyword construct like `if` or `let`.
+    Keyword,
+    /// A builtin constant, ie. `true`, `false` or `null`.
+    Constant,
+    /// A file or directory path.
+    Path,
+    /// Other text which is mostly useful with placeholders, eg. `${}` in strings.
+    Snippet,
 }
 
+/// Accepting an attribute key with `.` continues the attrpath, and with `=` starts its value.
+const ATTRPATH_COMMIT_CHARS: &[char] = &['.', '='];
+/// Accepting an attribute being selected with `.` continues the attrpath.
+const SELECT_COMMIT_CHARS: &[char] = &['.'];
+
+/// Snippets of keyword constructs which are valid in any full expression position.
+const KEYWORD_SNIPPETS: &[(&str, &str)] = &[
+    ("assert", "assert $1; $0"),
+    ("if", "if $1 then $2 else $0"),
+    ("let", "let $1 in $0"),
+    ("with", "with $1; $0"),
+];
+
+/// Keyword snippets come after all local names but before builtins.
+const SNIPPET_PRIORITY: u32 = 1000;
+const BUILTIN_PRIORITY: u32 = 2000;
+/// Names provided by `with` are shadowed by all lexical names, including builtins.
+const WITH_PRIORITY: u32 = 3000;
+const NIXPKGS_PRIORITY: u32 = 4000;
+/// The penalty of names starting with `_`, which are usually internal.
+const UNDERSCORE_PRIORITY: u32 = 10000;
+
+/// `trigger` is the character typed which triggers the completion, if any.
+///
+/// Items are filtered by fuzzily matching the identifier before the cursor, and better matches
+/// are ranked higher among items of the same priority.
 pub(crate) fn completions(
     db: &dyn DefDatabase,
     file_id: FileId,
     pos: TextSize,
+    trigger: Option<char>,
+) -> Option<Vec<CompletionItem>> {
+    let items = unranked_completions(db, file_id, pos, trigger)?;
+    let parse = db.parse(file_id).value;
+    let query = match parse.syntax_node().token_at_offset(pos).left_biased() {
+        Some(tok) if tok.kind() == SyntaxKind::IDENT => {
+            tok.text()[..usize::from(pos - tok.text_range().start())].to_owned()
+        }
+        _ => String::new(),
+    };
+    let items = items
+        .into_iter()
+        .filter_map(|mut item| {
+            let score = fuzzy_match(&query, &item.label)?;
+            item.sort_priority = score.rank(item.sort_priority);
+            Some(item)
+        })
+        .collect();
+    Some(items)
+}
+
+fn unranked_completions(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    trigger: Option<char>,
 ) -> Option<Vec<CompletionItem>> {
     let parse = db.parse(file_id).value;
 
-    let tok = parse.syntax_node().token_at_offset(pos).left_biased()?;
+    let tok = match parse.syntax_node().token_at_offset(pos).left_biased() {
+        Some(tok) => tok,
+        // An empty file expects an expression.
+        None => return Some(keyword_snippets(TextRange::empty(pos)).collect()),
+    };
+    // A `.` typed in a float or a path, eg. `1.` or `./`, is not an attribute access.
+    if trigger == Some('.') && tok.kind() != T![.] {
+        return None;
+    }
+    // Names to inherit, eg. `{ inherit |; }` or `{ inherit (e) |; }`.
+    if let Some(inherit) = inherit_context(&tok) {
+        let source_range = match tok.kind() {
+            SyntaxKind::IDENT => tok.text_range(),
+            _ => TextRange::empty(pos),
+        };
+        return complete_inherit(db, file_id, pos, source_range, inherit);
+    }
+
+    // The missing body of a `with`, eg. `with pkgs; |`.
+    if let Some(with) = with_missing_body(&tok) {
+        return complete_with_body(db, file_id, pos, with);
+    }
+
     let source_range = match tok.kind() {
         T![.] => TextRange::empty(pos),
         SyntaxKind::IDENT => tok.text_range(),
+        SyntaxKind::STRING_FRAGMENT => {
+            return complete_interpolation(tok.text_range(), tok.text(), pos)
+        }
+        // After a complete select, eg. `a.b |`.
+        SyntaxKind::SPACE => {
+            let mut prev = tok.prev_token()?;
+            while matches!(prev.kind(), SyntaxKind::SPACE | SyntaxKind::COMMENT) {
+                prev = prev.prev_token()?;
+            }
+            // Trailing spaces are inside the select node.
+            let select = prev
+                .parent_ancestors()
+                .take_while(|node| node.text_range().end() <= pos)
+                .find_map(ast::Select::cast)?;
+            return select_default_item(&select, TextRange::empty(pos)).map(|item| vec![item]);
+        }
         _ => return None,
     };
 
+    // Attribute members of a selected expression, eg. `let a.b = 1; in a.|`.
+    if let Some(select) = tok
+        .parent_ancestors()
+        .take_while(|node| !ast::Ref::can_cast(node.kind()))
+        .take_while(|node| !ast::AttrpathValue::can_cast(node.kind()))
+        .find_map(ast::Select::cast)
+    {
+        return complete_select(db, file_id, source_range, select);
+    }
+
+    // Inside an attrpath being defined, eg. `{ a.b = 1; a.| }`.
+    if let Some(path) = tok
+        .parent_ancestors()
+        .take_while(|node| !ast::Ref::can_cast(node.kind()))
+        .find_map(ast::Attrpath::cast)
+    {
+        return complete_attrpath(db, file_id, source_range, path);
+    }
+
+    // Field names of a lambda pattern. Only flake inputs are known currently.
+    let grandparent_kind = tok
+        .parent()
+        .and_then(|node| node.parent())
+        .map(|node| node.kind());
+    if grandparent_kind == Some(SyntaxKind::PAT_FIELD) {
+        let mut items = flake_input_names(db, file_id, pos)
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name.clone(),
+                source_range,
+                sort_priority: name_priority(&name, 0),
+                replace: name,
+                kind: CompletionItemKind::Binding,
+                snippet: None,
+                commit_characters: &[],
+                filter_text: None,
+                documentation: None,
+                detail: None,
+            })
+            .collect::<Vec<_>>();
+        items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+        return Some(items);
+    }
+
     let ref_node = tok.parent_ancestors().find_map(|node| {
         match_ast! {
             match node {
@@ -47,30 +204,1046 @@ pub(crate) fn completions(
         }
     })?;
     let source_map = db.source_map(file_id);
-    let expr_id = source_map.node_expr(AstPtr::new(ref_node.syntax()))?;
+    let expr_id = source_map.expr_for_node(ref_node.syntax())?;
     let scopes = db.scopes(file_id);
     let scope_id = scopes.scope_by_expr(expr_id)?;
 
-    // TODO: Better sorting.
-    let mut items = scopes
+    let mut items = scope_name_items(db, file_id, pos, scope_id, source_range)
+        .into_iter()
+        .chain(
+            is_full_expr_position(ref_node.syntax())
+                .then(|| keyword_snippets(source_range))
+                .into_iter()
+                .flatten(),
+        )
+        .chain(
+            select_before_ref(&ref_node)
+                .and_then(|select| select_default_item(&select, source_range)),
+        )
+        .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+    items.dedup_by(|lhs, rhs| lhs.label == rhs.label);
+
+    Some(items)
+}
+
+fn with_missing_body(tok: &SyntaxToken) -> Option<ast::With> {
+    if tok.kind() == SyntaxKind::COMMENT {
+        return None;
+    }
+    let mut semicolon = tok.clone();
+    while matches!(semicolon.kind(), SyntaxKind::SPACE | SyntaxKind::COMMENT) {
+        semicolon = semicolon.prev_token()?;
+    }
+    if semicolon.kind() != T![;] {
+        return None;
+    }
+    let with = ast::With::cast(semicolon.parent()?)?;
+    with.body().is_none().then_some(with)
+}
+
+fn complete_with_body(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    with: ast::With,
+) -> Option<Vec<CompletionItem>> {
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+    let body = match module[source_map.expr_for_node(with.syntax())?] {
+        Expr::With(_, body) => body,
+        _ => return None,
+    };
+    let scope_id = db.scopes(file_id).scope_by_expr(body)?;
+    let source_range = TextRange::empty(pos);
+    let mut items = scope_name_items(db, file_id, pos, scope_id, source_range)
+        .into_iter()
+        .chain(keyword_snippets(source_range))
+        .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+    items.dedup_by(|lhs, rhs| lhs.label == rhs.label);
+    Some(items)
+}
+
+/// Names visible in the scope, including flake inputs and global builtins.
+fn scope_name_items(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    scope_id: ScopeId,
+    source_range: TextRange,
+) -> Vec<CompletionItem> {
+    let scopes = db.scopes(file_id);
+    // Names in inner scopes are preferred. Flake inputs are outside of all scopes.
+    let name_scopes = scopes
         .ancestors(scope_id)
         .filter_map(|scope| scope.as_name_defs())
-        .flat_map(|scope| scope.keys())
+        .collect::<Vec<_>>();
+    let flake_inputs_depth = name_scopes.len() as u32;
+    name_scopes
+        .into_iter()
+        .zip(0..)
+        .flat_map(|(scope, depth)| {
+            scope
+                .iter()
+                .map(move |(name, &def)| (name.clone(), depth, Some(def)))
+        })
+        .chain(
+            flake_input_names(db, file_id, pos)
+                .into_iter()
+                .map(|name| (name, flake_inputs_depth, None)),
+        )
+        .map(|(name, depth, def)| CompletionItem {
+            label: name.clone(),
+            source_range,
+            sort_priority: name_priority(&name, depth),
+            replace: name,
+            kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: &[],
+            filter_text: None,
+            detail: None,
+            documentation: def.and_then(|def| db.binding_doc(file_id, def)),
+        })
+        .chain(builtin::all().map(|info| builtin_item(&info.global_name(), source_range)))
+        .chain(with_env_items(db, file_id, scope_id, source_range))
+        .chain(nixpkgs_items(db, file_id, scope_id, source_range))
+        .collect()
+}
+
+/// Attributes of statically known environments of enclosing `with`s, eg. `a` in
+/// `with { a = 1; }; |`. Inner `with`s are preferred.
+fn with_env_items(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    scope_id: ScopeId,
+    source_range: TextRange,
+) -> Vec<CompletionItem> {
+    let scopes = db.scopes(file_id);
+    let module = db.module(file_id);
+    scopes
+        .ancestors(scope_id)
+        .filter_map(|scope| scope.as_with())
+        .zip(0..)
+        .filter_map(|(with_expr, depth)| {
+            let env = match module[with_expr] {
+                Expr::With(env, _) => resolve_attrset(db, file_id, env)?,
+                _ => return None,
+            };
+            match &module[env] {
+                Expr::Attrset(bindings) => Some((bindings, depth)),
+                _ => None,
+            }
+        })
+        .flat_map(|(bindings, depth)| {
+            bindings
+                .entries
+                .iter()
+                .filter_map(|(key, _)| key.name(&module).cloned())
+                .map(move |name| (name, depth))
+        })
+        .map(|(name, depth)| CompletionItem {
+            label: name.clone(),
+            source_range,
+            replace: name.clone(),
+            kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: &[],
+            filter_text: None,
+            documentation: None,
+            detail: Some("with".into()),
+            sort_priority: name_priority(&name, WITH_PRIORITY + depth),
+        })
+        .collect()
+}
+
+/// Top-level attributes of nixpkgs inside `with import <nixpkgs> { };`, if available.
+/// Builtins and lexical names take precedence over them.
+fn nixpkgs_items(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    scope_id: ScopeId,
+    source_range: TextRange,
+) -> Vec<CompletionItem> {
+    let names = db.nixpkgs_attr_names();
+    if names.is_empty() {
+        return Vec::new();
+    }
+    let scopes = db.scopes(file_id);
+    let module = db.module(file_id);
+    let in_nixpkgs = scopes
+        .ancestors(scope_id)
+        .filter_map(|scope| scope.as_with())
+        .any(|with_expr| match module[with_expr] {
+            Expr::With(env, _) => is_nixpkgs_import(db, file_id, env),
+            _ => false,
+        });
+    if !in_nixpkgs {
+        return Vec::new();
+    }
+    names
+        .iter()
         .map(|name| CompletionItem {
             label: name.clone(),
             source_range,
             replace: name.clone(),
             kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: &[],
+            filter_text: None,
+            documentation: None,
+            detail: Some("nixpkgs".into()),
+            sort_priority: name_priority(name, NIXPKGS_PRIORITY),
+        })
+        .collect()
+}
+
+/// Names of flake inputs, which are available in the `outputs` lambda, including `self`.
+fn flake_input_names(db: &dyn DefDatabase, file_id: FileId, pos: TextSize) -> Vec<SmolStr> {
+    if !is_flake_file(db, file_id) {
+        return Vec::new();
+    }
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+    let entry = module.entry_expr();
+    let in_outputs = module
+        .bindings_at_attrpath(entry, &[])
+        .and_then(|bindings| {
+            bindings
+                .entries
+                .iter()
+                .find(|(key, _)| key.name(&module).map_or(false, |name| name == "outputs"))
+        })
+        .and_then(|&(_, value)| match value {
+            BindingValue::Expr(e) if matches!(module[e], Expr::Lambda(..)) => {
+                source_map.node_for_expr(e)
+            }
+            _ => None,
         })
-        .chain(builtin::NAMES.iter().map(|name| CompletionItem {
-            label: name.into(),
+        .map_or(false, |ptr| ptr.text_range().contains_inclusive(pos));
+    if !in_outputs {
+        return Vec::new();
+    }
+
+    let mut names = vec![SmolStr::from("self")];
+    if let Some(inputs) = module.bindings_at_attrpath(entry, &["inputs".into()]) {
+        names.extend(
+            inputs
+                .entries
+                .iter()
+                .filter_map(|(key, _)| key.name(&module).cloned()),
+        );
+    }
+    names
+}
+
+/// Complete `$` in a string into an interpolation `${}`.
+fn complete_interpolation(
+    fragment_range: TextRange,
+    fragment: &str,
+    pos: TextSize,
+) -> Option<Vec<CompletionItem>> {
+    let before = &fragment[..usize::from(pos - fragment_range.start())];
+    // `$$` is consumed as a pair, so only an odd run of `$` starts an interpolation.
+    let dollars = before.len() - before.trim_end_matches('$').len();
+    if dollars % 2 == 0 {
+        return None;
+    }
+    let dollar_range = TextRange::at(pos - TextSize::of('$'), TextSize::of('$'));
+    Some(vec![CompletionItem {
+        label: "${}".into(),
+        source_range: dollar_range,
+        replace: "${}".into(),
+        kind: CompletionItemKind::Snippet,
+        snippet: Some("\\${$0}".into()),
+        commit_characters: &[],
+        filter_text: None,
+        documentation: None,
+        detail: None,
+        sort_priority: 0,
+    }])
+}
+
+fn builtin_item(name: &str, source_range: TextRange) -> CompletionItem {
+    CompletionItem {
+        label: name.into(),
+        source_range,
+        replace: name.into(),
+        kind: match name {
+            "true" | "false" | "null" => CompletionItemKind::Constant,
+            _ => CompletionItemKind::Builtin,
+        },
+        snippet: builtin::get(name).and_then(|info| builtin_snippet(name, info)),
+        commit_characters: &[],
+        filter_text: None,
+        documentation: None,
+        detail: None,
+        sort_priority: name_priority(name, BUILTIN_PRIORITY),
+    }
+}
+
+fn name_priority(name: &str, base: u32) -> u32 {
+    if name.starts_with('_') {
+        base + UNDERSCORE_PRIORITY
+    } else {
+        base
+    }
+}
+
+/// A call with placeholders for all parameters of a builtin function, eg.
+/// `map (${1:f}) ${2:xs}`, driven by its signature.
+fn builtin_snippet(name: &str, info: &builtin::BuiltinInfo) -> Option<SmolStr> {
+    if info.kind != builtin::BuiltinKind::Function {
+        return None;
+    }
+    let (_, ty) = info.signature?.split_once(" :: ")?;
+    let mut params = split_arrows(ty);
+    params.pop();
+    if params.is_empty() {
+        return None;
+    }
+    let mut snippet = name.to_owned();
+    for (i, param) in params.iter().enumerate() {
+        let i = i + 1;
+        // Function arguments need parentheses around lambdas.
+        if split_arrows(param.trim_start_matches('(').trim_end_matches(')')).len() > 1 {
+            snippet += &format!(" (${{{}:f}})", i);
+        } else {
+            let placeholder = match *param {
+                "AttrSet" => "attrs",
+                "Int" | "Number" => "n",
+                "String" => "s",
+                "Path" => "path",
+                "Bool" => "b",
+                _ if param.starts_with('[') => "xs",
+                _ => "x",
+            };
+            snippet += &format!(" ${{{}:{}}}", i, placeholder);
+        }
+    }
+    Some(snippet.into())
+}
+
+/// Split a type in the signature by top-level arrows.
+fn split_arrows(ty: &str) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let bytes = ty.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+            b'-' if depth == 0 && bytes.get(i + 1) == Some(&b'>') => {
+                ret.push(ty[start..i].trim());
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    ret.push(ty[start..].trim());
+    ret
+}
+
+fn keyword_snippets(source_range: TextRange) -> impl Iterator<Item = CompletionItem> {
+    KEYWORD_SNIPPETS
+        .iter()
+        .map(move |&(label, snippet)| CompletionItem {
+            label: label.into(),
             source_range,
-            replace: name.into(),
-            kind: CompletionItemKind::Builtin,
-        }))
+            replace: label.into(),
+            kind: CompletionItemKind::Keyword,
+            snippet: Some(snippet.into()),
+            commit_characters: &[],
+            filter_text: None,
+            documentation: None,
+            detail: None,
+            sort_priority: SNIPPET_PRIORITY,
+        })
+}
+
+/// The `or` default for a select which may fail, eg. `a.b or ${1:default}`.
+fn select_default_item(select: &ast::Select, source_range: TextRange) -> Option<CompletionItem> {
+    if select.or_token().is_some() {
+        return None;
+    }
+    Some(CompletionItem {
+        label: "or".into(),
+        source_range,
+        replace: "or ".into(),
+        kind: CompletionItemKind::Keyword,
+        snippet: Some("or ${1:default}".into()),
+        commit_characters: &[],
+        filter_text: None,
+        documentation: None,
+        detail: None,
+        sort_priority: SNIPPET_PRIORITY,
+    })
+}
+
+/// A partially typed `or` is parsed as an argument, eg. `a.b o` is `(a.b) o`.
+fn select_before_ref(ref_node: &ast::Ref) -> Option<ast::Select> {
+    let apply = ast::Apply::cast(ref_node.syntax().parent()?)?;
+    if apply.argument()?.syntax() != ref_node.syntax() {
+        return None;
+    }
+    match apply.function()? {
+        ast::Expr::Select(select) => Some(select),
+        _ => None,
+    }
+}
+
+/// Whether the node can be replaced by any expression without parentheses,
+/// eg. the body of a lambda, but not an operand of binary operators.
+fn is_full_expr_position(node: &SyntaxNode) -> bool {
+    node.parent().map_or(false, |parent| {
+        matches!(
+            parent.kind(),
+            SyntaxKind::SOURCE_FILE
+                | SyntaxKind::LAMBDA
+                | SyntaxKind::LET_IN
+                | SyntaxKind::WITH
+                | SyntaxKind::ASSERT
+                | SyntaxKind::IF_THEN_ELSE
+                | SyntaxKind::ATTR_PATH_VALUE
+                | SyntaxKind::PAT_FIELD
+                | SyntaxKind::PAREN
+                | SyntaxKind::DYNAMIC
+        )
+    })
+}
+
+fn complete_attrpath(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    source_range: TextRange,
+    path: ast::Attrpath,
+) -> Option<Vec<CompletionItem>> {
+    let entry = ast::AttrpathValue::cast(path.syntax().parent()?)?;
+    let container = entry.syntax().parent()?;
+    let prefix = path
+        .attrs()
+        .take_while(|attr| attr.syntax().text_range().end() <= source_range.start())
+        .map(|attr| match attr {
+            ast::Attr::Name(name) => Some(name.token()?.text().into()),
+            _ => None,
+        })
+        .collect::<Option<Vec<SmolStr>>>()?;
+    if prefix.is_empty() {
+        return None;
+    }
+
+    let source_map = db.source_map(file_id);
+    let container_expr = source_map.expr_for_node(&container)?;
+    let module = db.module(file_id);
+    let bindings = module.bindings_at_attrpath(container_expr, &prefix)?;
+    let mut items = bindings
+        .entries
+        .iter()
+        // Skip incomplete keys without values, including the one being typed.
+        .filter(|(_, value)| !matches!(value, BindingValue::Expr(e) if module[*e].is_missing()))
+        .filter_map(|(key, _)| key.name(&module))
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            source_range,
+            replace: name.clone(),
+            kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: ATTRPATH_COMMIT_CHARS,
+            filter_text: attrpath_filter_text(&prefix, name),
+            documentation: None,
+            detail: None,
+            sort_priority: name_priority(name, 0),
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+    Some(items)
+}
+
+/// The full dotted path of a completed attribute, to match the whole typed path like `a.b.c`.
+fn attrpath_filter_text(prefix: &[SmolStr], name: &SmolStr) -> Option<SmolStr> {
+    if prefix.is_empty() {
+        return None;
+    }
+    let mut text = prefix.join(".");
+    text.push('.');
+    text.push_str(name);
+    Some(text.into())
+}
+
+fn complete_select(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    source_range: TextRange,
+    select: ast::Select,
+) -> Option<Vec<CompletionItem>> {
+    let prefix = match select.attrpath() {
+        Some(path) => path
+            .attrs()
+            .take_while(|attr| attr.syntax().text_range().end() <= source_range.start())
+            .map(|attr| match attr {
+                ast::Attr::Name(name) => Some(name.token()?.text().into()),
+                _ => None,
+            })
+            .collect::<Option<Vec<SmolStr>>>()?,
+        None => Vec::new(),
+    };
+
+    let source_map = db.source_map(file_id);
+    let set_expr = source_map.expr_for_node(select.set()?.syntax())?;
+    member_items(db, file_id, source_range, set_expr, &prefix)
+}
+
+/// Attributes of `set_expr` under `prefix`, if statically known.
+fn member_items(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    source_range: TextRange,
+    set_expr: ExprId,
+    prefix: &[SmolStr],
+) -> Option<Vec<CompletionItem>> {
+    // Members of `builtins` are not defined in source, but come from the same registry checked
+    // by the `unknown_builtin` diagnostic. A locally rebound `builtins` is a normal attrset.
+    if prefix.is_empty()
+        && db.resolve_name(file_id, set_expr) == Some(ResolveResult::Builtin("builtins"))
+    {
+        let mut items = builtin::all()
+            .map(|info| CompletionItem {
+                detail: info.signature.map(Into::into),
+                ..builtin_item(info.name, source_range)
+            })
+            .collect::<Vec<_>>();
+        items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+        return Some(items);
+    }
+    if prefix.is_empty() {
+        if let Some(items) = complete_pat_fields(db, file_id, source_range, set_expr) {
+            return Some(items);
+        }
+    }
+    let set_expr = resolve_attrset(db, file_id, set_expr)?;
+    let module = db.module(file_id);
+    let bindings = module.bindings_at_attrpath(set_expr, prefix)?;
+    let mut items = bindings
+        .entries
+        .iter()
+        .filter_map(|(key, _)| key.name(&module))
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            source_range,
+            replace: name.clone(),
+            kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: SELECT_COMMIT_CHARS,
+            filter_text: attrpath_filter_text(prefix, name),
+            documentation: None,
+            detail: None,
+            sort_priority: name_priority(name, 0),
+        })
         .collect::<Vec<_>>();
     items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
+    Some(items)
+}
+
+/// The `inherit` whose names are being typed at the token, either an identifier or spaces after
+/// `inherit`, `(e)` or another name.
+fn inherit_context(tok: &SyntaxToken) -> Option<ast::Inherit> {
+    let tok = match tok.kind() {
+        SyntaxKind::IDENT => tok.clone(),
+        SyntaxKind::SPACE => {
+            let mut prev = tok.prev_token()?;
+            while matches!(prev.kind(), SyntaxKind::SPACE | SyntaxKind::COMMENT) {
+                prev = prev.prev_token()?;
+            }
+            prev
+        }
+        _ => return None,
+    };
+    // After a complete `inherit a;`.
+    if tok.kind() == T![;] {
+        return None;
+    }
+    let inherit = tok.parent_ancestors().find_map(ast::Inherit::cast)?;
+    // Names inside `(e)` are expressions, except the closing parenthesis itself.
+    let in_from_expr = inherit.from_expr().map_or(false, |from| {
+        tok.text_range().end() < from.syntax().text_range().end()
+    });
+    (!in_from_expr).then_some(inherit)
+}
+
+fn complete_inherit(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    source_range: TextRange,
+    inherit: ast::Inherit,
+) -> Option<Vec<CompletionItem>> {
+    let source_map = db.source_map(file_id);
+    let mut items = match inherit.from_expr() {
+        Some(from) => {
+            let set_expr = source_map.expr_for_node(from.expr()?.syntax())?;
+            let mut items = member_items(db, file_id, source_range, set_expr, &[])?;
+            for item in &mut items {
+                item.commit_characters = &[];
+            }
+            items
+        }
+        // Names are inherited from the scope outside the attrset or `let`.
+        None => {
+            let container = source_map.expr_for_node(&inherit.syntax().parent()?)?;
+            let scope_id = db.scopes(file_id).scope_by_expr(container)?;
+            scope_name_items(db, file_id, pos, scope_id, source_range)
+        }
+    };
+    items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
     items.dedup_by(|lhs, rhs| lhs.label == rhs.label);
+    Some(items)
+}
 
+/// Fields of the pattern of a lambda, selected from its `@` binding, eg. `args@{ a, b }: args.|`.
+fn complete_pat_fields(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    source_range: TextRange,
+    set_expr: ExprId,
+) -> Option<Vec<CompletionItem>> {
+    let def = match db.resolve_name(file_id, set_expr)? {
+        ResolveResult::NameDef(def) => def,
+        ResolveResult::Builtin(_) | ResolveResult::WithExprs(_) => return None,
+    };
+    let module = db.module(file_id);
+    let pat = module.exprs().find_map(|(_, expr)| match expr {
+        Expr::Lambda(Some(param), Some(pat), _) if *param == def => Some(pat),
+        _ => None,
+    })?;
+    // Unknown fields may be passed with `...`.
+    let detail = pat
+        .ellipsis
+        .then(|| SmolStr::from("(this set may have more attrs)"));
+    let mut items = pat
+        .fields
+        .iter()
+        .filter_map(|&(name, _)| Some(&module[name?].name))
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            source_range,
+            replace: name.clone(),
+            kind: CompletionItemKind::Binding,
+            snippet: None,
+            commit_characters: SELECT_COMMIT_CHARS,
+            filter_text: None,
+            documentation: None,
+            detail: detail.clone(),
+            sort_priority: name_priority(name, 0),
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.label.cmp(&rhs.label));
     Some(items)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CompletionItemKind;
+    use crate::tests::TestDB;
+    use crate::Change;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (_, _, [_]) = TestDB::single_file(fixture).unwrap();
+        check_fixture(fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_fixture(fixture: &str, expect: Expect) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [pos] = f.markers().unwrap();
+        let items = super::completions(&db, pos.file_id, pos.value, None).unwrap_or_default();
+        // Builtins are omitted for simplicity.
+        let labels = items
+            .iter()
+            .filter(|item| {
+                !matches!(
+                    item.kind,
+                    CompletionItemKind::Builtin | CompletionItemKind::Constant
+                )
+            })
+            .map(|item| item.label.as_str())
+            .collect::<Vec<_>>();
+        expect.assert_eq(&labels.join(" "));
+    }
+
+    #[test]
+    fn keyword_snippets() {
+        check("$0", expect!["assert if let with"]);
+        check("a: l$0", expect!["let"]);
+        check("{ b = l$0; }", expect!["let"]);
+        check("f l$0", expect![""]);
+        check("1 + l$0", expect![""]);
+
+        let (db, file_id, [pos]) = TestDB::single_file("l$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let kind_of = |label: &str| items.iter().find(|item| item.label == label).unwrap().kind;
+        assert_eq!(kind_of("let"), CompletionItemKind::Keyword);
+        let let_item = items.iter().find(|item| item.label == "let").unwrap();
+        assert_eq!(let_item.replace, "let");
+        assert_eq!(let_item.snippet.as_deref(), Some("let $1 in $0"));
+        assert_eq!(kind_of("null"), CompletionItemKind::Constant);
+        assert_eq!(kind_of("placeholder"), CompletionItemKind::Builtin);
+    }
+
+    #[test]
+    fn flake_inputs() {
+        let flake = |outputs: &str| {
+            format!(
+                r#"
+#- /flake.nix
+{{
+  inputs = {{ nixpkgs.url = "github:NixOS/nixpkgs"; }};
+  inputs.flake-utils.url = "github:numtide/flake-utils";
+  outputs = {};
+}}
+                "#,
+                outputs,
+            )
+        };
+        check_fixture(
+            &flake("{ self, ... }: { x = s$0; }"),
+            expect!["assert flake-utils nixpkgs self"],
+        );
+        check_fixture(
+            &flake("{ self, s$0 }: { }"),
+            expect!["flake-utils nixpkgs self"],
+        );
+        check_fixture(
+            "
+#- /default.nix
+{ inputs.nixpkgs = 1; outputs = { self, ... }: s$0; }
+            ",
+            expect!["assert self"],
+        );
+    }
+
+    #[test]
+    fn attrpath_key() {
+        check(
+            "{ services.nginx.enable = true; services.$0 }",
+            expect!["nginx"],
+        );
+        check(
+            "{ services.nginx.enable = true; services.n$0 }",
+            expect!["nginx"],
+        );
+        check(
+            "let a.b.c = 1; a.b = { d = 2; }; in { a.b.$0 }",
+            expect![""],
+        );
+        check("{ a.b.c = 1; a.b = { d = 2; }; a.b.$0 }", expect!["c d"]);
+        check("let a.b = 1; a.$0 in a", expect!["b"]);
+        check("{ a = 1; a.$0 }", expect![""]);
+    }
+
+    #[track_caller]
+    fn check_trigger(fixture: &str, trigger: char, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let items = super::completions(&db, file_id, pos, Some(trigger)).unwrap_or_default();
+        let labels = items
+            .iter()
+            .filter(|item| {
+                !matches!(
+                    item.kind,
+                    CompletionItemKind::Builtin | CompletionItemKind::Constant
+                )
+            })
+            .map(|item| item.label.as_str())
+            .collect::<Vec<_>>();
+        expect.assert_eq(&labels.join(" "));
+    }
+
+    #[test]
+    fn select_member() {
+        check("let a = { b = 1; c = 2; }; in a.$0", expect!["b c"]);
+        check("let a = { b = 1; c = 2; }; in a.b$0", expect!["b"]);
+        check("let a = { b.c = 1; b.d = 2; }; in a.b.$0", expect!["c d"]);
+        check("let a = b; b = { c = 1; }; in a.$0 or 1", expect!["c"]);
+        check("{ x = { y = 1; }.$0; }", expect!["y"]);
+        check("let a = a; in a.$0", expect![""]);
+    }
+
+    #[test]
+    fn interpolation_expr() {
+        check(r#"let pkgs = 1; in "${pk$0}""#, expect!["pkgs"]);
+        check(r#"let pkgs = 1; in "${pk$0""#, expect!["pkgs"]);
+        check("let pkgs = 1; in ''${pk$0}''", expect!["pkgs"]);
+        check("let pkgs = 1; in ''${pk$0", expect!["pkgs"]);
+        check(r#"let pkgs = 1; in "a${pk$0 ""#, expect!["pkgs"]);
+    }
+
+    #[test]
+    fn select_default() {
+        check("let a = { }; in a.b $0", expect!["or"]);
+        check("let a = { }; in a.b o$0", expect!["or"]);
+        check("let a = { }; in a.b or 1 $0", expect![""]);
+        check("let a = { }; in a $0", expect![""]);
+        check("let a = { }; in a o$0", expect![""]);
+
+        let (db, file_id, [pos]) = TestDB::single_file("let a = { }; in a.b $0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let item = items.iter().find(|item| item.label == "or").unwrap();
+        assert_eq!(item.replace, "or ");
+        assert_eq!(item.snippet.as_deref(), Some("or ${1:default}"));
+    }
+
+    #[test]
+    fn pat_fields() {
+        check("args@{ b, a, ... }: args.$0", expect!["a b"]);
+        check("{ a ? 1 }@args: args.$0", expect!["a"]);
+        check("args@{ a }: args.a.$0", expect![""]);
+
+        let details = |fixture: &str| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            super::completions(&db, file_id, pos, None)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.detail)
+                .collect::<Vec<_>>()
+        };
+        let more = Some("(this set may have more attrs)".into());
+        assert_eq!(details("args@{ a, b, ... }: args.$0"), [more.clone(), more]);
+        assert_eq!(details("args@{ a, b }: args.$0"), [None, None]);
+    }
+
+    #[test]
+    fn dot_trigger() {
+        check_trigger("let a = { b = 1; }; in a.$0", '.', expect!["b"]);
+        check_trigger("{ a.b = 1; a.$0 }", '.', expect!["b"]);
+        check_trigger("let a = 1; in 1.$0", '.', expect![""]);
+        check_trigger("let a = 1; in ./$0", '.', expect![""]);
+    }
+
+    #[test]
+    fn builtin_snippets() {
+        let snippet = |fixture: &str, label: &str| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            super::completions(&db, file_id, pos, None)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|item| item.label == label)
+                .expect("Missing completion item")
+                .snippet
+        };
+        assert_eq!(
+            snippet("builtins.gen$0", "genList").as_deref(),
+            Some("genList (${1:f}) ${2:n}"),
+        );
+        assert_eq!(
+            snippet("ma$0", "map").as_deref(),
+            Some("map (${1:f}) ${2:xs}"),
+        );
+        assert_eq!(
+            snippet("builtins.$0", "listToAttrs").as_deref(),
+            Some("listToAttrs ${1:xs}"),
+        );
+        assert_eq!(snippet("builtins.$0", "currentSystem"), None);
+        assert_eq!(snippet("tr$0", "true"), None);
+    }
+
+    #[test]
+    fn builtins_members() {
+        let complete = |fixture: &str| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            super::completions(&db, file_id, pos, None).unwrap_or_default()
+        };
+        let items = complete("builtins.$0");
+        let item = items
+            .iter()
+            .find(|item| item.label == "currentSystem")
+            .expect("Missing completion item");
+        assert_eq!(item.kind, CompletionItemKind::Builtin);
+        assert!(items.iter().all(|item| item.label != "fooBar"));
+        assert!(items.iter().all(|item| !item.label.starts_with("__")));
+        let item = items.iter().find(|item| item.label == "length").unwrap();
+        assert_eq!(item.detail.as_deref(), Some("length :: [a] -> Int"));
+
+        check("let builtins = { x = 1; }; in builtins.$0", expect!["x"]);
+    }
+
+    #[test]
+    fn scope_priority() {
+        let (db, file_id, [pos]) =
+            TestDB::single_file("let a = 1; _ca = 1; in let ba = 2; in a: a$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let priority = |label: &str| {
+            items
+                .iter()
+                .find(|item| item.label == label)
+                .expect("Missing completion item")
+                .sort_priority
+        };
+        assert!(priority("a") < priority("ba"));
+        assert!(priority("ba") < priority("assert"));
+        assert!(priority("assert") < priority("map"));
+        assert!(priority("map") < priority("_ca"));
+        assert!(priority("_ca") < priority("__attrNames"));
+    }
+
+    #[test]
+    fn attr_commit_characters() {
+        let (db, file_id, [pos]) =
+            TestDB::single_file("{ services.nginx.enable = true; services.n$0 }").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        assert_eq!(items[0].label, "nginx");
+        assert_eq!(items[0].commit_characters, ['.', '=']);
+        assert_eq!(items[0].filter_text.as_deref(), Some("services.nginx"));
+
+        let (db, file_id, [pos]) = TestDB::single_file("let a = { b = 1; }; in a.$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        assert_eq!(items[0].label, "b");
+        assert_eq!(items[0].commit_characters, ['.']);
+        assert_eq!(items[0].filter_text, None);
+
+        let (db, file_id, [pos]) = TestDB::single_file("let a = 1; in a$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        assert!(items.iter().all(|item| item.commit_characters.is_empty()));
+    }
+
+    #[test]
+    fn binding_documentation() {
+        let (db, file_id, [pos]) =
+            TestDB::single_file("let\n  # Say hello.\n  greet = x: x;\nin gre$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let item = items.iter().find(|item| item.label == "greet").unwrap();
+        assert_eq!(item.documentation.as_deref(), Some("Say hello."));
+    }
+
+    #[test]
+    fn inherit_names() {
+        check("let pkgs = { }; in { inherit pk$0 }", expect!["pkgs"]);
+        check("x: let y = 1; in { inherit $0; }", expect!["x y"]);
+        check("x: let inherit $0; y = 1; in y", expect!["x"]);
+    }
+
+    #[test]
+    fn inherit_from() {
+        check(
+            "let lib = { foo = 1; bar = 2; }; in { inherit (lib) $0; }",
+            expect!["bar foo"],
+        );
+        check(
+            "let lib = { foo = 1; bar = 2; }; in { inherit (lib) foo b$0 }",
+            expect!["bar"],
+        );
+        check(
+            "let lib = { foo = 1; }; in { inherit (l$0) foo; }",
+            expect!["let lib"],
+        );
+    }
+
+    #[test]
+    fn inherit_from_builtins() {
+        let (db, file_id, [pos]) = TestDB::single_file("{ inherit (builtins) toStr$0; }").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let item = items.iter().find(|item| item.label == "toString").unwrap();
+        assert_eq!(item.detail.as_deref(), Some("toString :: a -> String"));
+        assert!(item.commit_characters.is_empty());
+    }
+
+    #[test]
+    fn nixpkgs_attrs() {
+        let check = |fixture: &str, expect: Expect| {
+            let (mut db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            let mut change = Change::new();
+            change.set_nixpkgs_attr_names(["hello", "lib", "map", "stdenv"].map(Into::into).into());
+            change.apply(&mut db);
+            let items = super::completions(&db, file_id, pos, None).unwrap_or_default();
+            let labels = items
+                .iter()
+                .filter(|item| item.detail.as_deref() == Some("nixpkgs"))
+                .map(|item| item.label.as_str())
+                .collect::<Vec<_>>();
+            expect.assert_eq(&labels.join(" "));
+        };
+        check("with import <nixpkgs> { }; [ l$0 ]", expect!["hello lib"]);
+        check(
+            "let pkgs = import <nixpkgs> { }; lib = 1; in with pkgs; l$0",
+            expect!["hello"],
+        );
+        check("with import <nixpkgs/lib> { }; h$0", expect![""]);
+        check("with import ./nixpkgs { }; h$0", expect![""]);
+    }
+
+    #[test]
+    fn with_names() {
+        let check = |fixture: &str, expect: Expect| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            let items = super::completions(&db, file_id, pos, None).unwrap_or_default();
+            let labels = items
+                .iter()
+                .filter(|item| item.detail.as_deref() == Some("with"))
+                .map(|item| item.label.as_str())
+                .collect::<Vec<_>>();
+            expect.assert_eq(&labels.join(" "));
+        };
+        check("with { a = 1; }; $0", expect!["a"]);
+        check("with { a = 1; };$0", expect!["a"]);
+        check("with { a = 1; }; # Comment.\n  $0", expect!["a"]);
+        check(
+            "let set = { a = 1; \"b\" = 2; ${c} = 3; }; in with set; $0",
+            expect!["a b"],
+        );
+        // Shadowed by lexical names.
+        check("let a = 1; in with { a = 1; b = 2; }; $0", expect!["b"]);
+        check("with { map = 1; }; $0", expect![""]);
+        check("with x; $0", expect![""]);
+
+        let (db, file_id, [pos]) =
+            TestDB::single_file("let b = 1; in with { a = 1; }; with { c = 1; }; $0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let priority = |label: &str| {
+            items
+                .iter()
+                .find(|item| item.label == label)
+                .expect("Missing completion item")
+                .sort_priority
+        };
+        assert!(priority("b") < priority("map"));
+        assert!(priority("map") < priority("c"));
+        assert!(priority("c") < priority("a"));
+    }
+
+    #[test]
+    fn fuzzy_ranking() {
+        check(
+            "let fooBar = 1; feedback = 2; abc = 3; in fb$0",
+            expect!["feedback fooBar"],
+        );
+        check("let abc = 1; in xy$0", expect![""]);
+
+        let (db, file_id, [pos]) =
+            TestDB::single_file("let fooBar = 1; feedback = 2; in fb$0").unwrap();
+        let items = super::completions(&db, file_id, pos, None).unwrap();
+        let priority = |label: &str| {
+            items
+                .iter()
+                .find(|item| item.label == label)
+                .expect("Missing completion item")
+                .sort_priority
+        };
+        assert!(priority("fooBar") < priority("feedback"));
+    }
+
+    #[test]
+    fn interpolation() {
+        check_trigger(r#""foo $$0""#, '$', expect!["${}"]);
+        check_trigger("''\n  $$0\n''", '$', expect!["${}"]);
+        // `$0` marks the cursor, so these are `$$` and `$$$` before it.
+        check_trigger(r#""foo $$$0""#, '$', expect![""]);
+        check_trigger(r#""foo $$$$0""#, '$', expect!["${}"]);
+        check_trigger(r#""foo \$$0""#, '$', expect![""]);
+
+        let (db, file_id, [pos]) = TestDB::single_file(r#""foo $$0""#).unwrap();
+        let items = super::completions(&db, file_id, pos, Some('$')).unwrap();
+        assert_eq!(items[0].replace, "${}");
+        assert_eq!(items[0].snippet.as_deref(), Some("\\${$0}"));
+    }
+}
@@ -1,40 +1,615 @@
-use crate::def::DefDatabase;
-use crate::{Diagnostic, FileId};
+use super::unused_bindings::unused_let_bindings;
+use crate::def::static_string_content;
+use crate::def::{
+    BinaryOp, BindingKey, BindingValue, DefDatabase, Expr, ExprId, Literal, ResolveResult,
+};
+use crate::{builtin, Diagnostic, DiagnosticKind, FileId, FixKind, InFile};
+use rowan::ast::AstNode;
+use rowan::NodeOrToken;
+use std::collections::{HashMap, HashSet};
+use syntax::ast::{self, HasBindings};
+use syntax::{SyntaxKind, SyntaxNode, TextRange};
 
 const MAX_DIAGNOSTIC_CNT: usize = 128;
 
-pub(crate) fn diagnostics(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+/// Errors from the parser, all of kind `DiagnosticKind::SyntaxError`.
+pub(crate) fn syntax_diagnostics(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
     let parse = db.parse(file).value;
-    let module = db.module(file);
     parse
         .errors()
         .iter()
         .map(|&err| Diagnostic::from(err))
-        .chain(module.diagnostics().iter().cloned())
         .take(MAX_DIAGNOSTIC_CNT)
         .collect()
 }
 
+/// Semantic diagnostics, excluding syntax errors.
+pub(crate) fn diagnostics(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let root = db.parse(file).value.syntax_node();
+    module
+        .diagnostics()
+        .iter()
+        .map(|diag| match diag.kind {
+            DiagnosticKind::DuplicatedKey(_) => with_remove_duplicate_fix(&root, diag.clone()),
+            _ => diag.clone(),
+        })
+        .chain(shadowed_names(db, file))
+        .chain(invalid_operands(db, file))
+        .chain(with_non_attrsets(db, file))
+        .chain(always_failing_asserts(db, file))
+        .chain(infinite_recursions(db, file))
+        .chain(duplicate_values(db, file))
+        .chain(lets_without_body(db, file))
+        .chain(unknown_builtins(db, file))
+        .chain(deprecated_builtins(db, file))
+        .chain(redundant_update_keys(db, file))
+        .chain(unused_bindings(db, file))
+        .take(MAX_DIAGNOSTIC_CNT)
+        .collect()
+}
+
+/// A duplicated `a = 1;` binding can be removed as a whole. Inherited names and attrpath
+/// prefixes may define other names together, so they are left alone.
+fn with_remove_duplicate_fix(root: &SyntaxNode, diag: Diagnostic) -> Diagnostic {
+    let entry = match root.covering_element(diag.range) {
+        NodeOrToken::Node(node) if node.text_range() == diag.range => node,
+        _ => return diag,
+    };
+    if !ast::AttrpathValue::can_cast(entry.kind()) {
+        return diag;
+    }
+    let mut range = entry.text_range();
+    if let Some(NodeOrToken::Token(space)) = entry.prev_sibling_or_token() {
+        if space.kind() == SyntaxKind::SPACE {
+            range = range.cover(space.text_range());
+        }
+    }
+    diag.with_fix(FixKind::RemoveDuplicate { range })
+}
+
+/// Report names defined in an inner scope which hide a visible definition of an outer scope.
+fn shadowed_names(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let scopes = db.scopes(file);
+    let source_map = db.source_map(file);
+
+    // `inherit x;` intentionally re-binds the outer `x`, which is not shadowing.
+    let inherited = module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            Expr::LetIn(bindings, _) | Expr::Attrset(bindings) | Expr::LetAttrset(bindings) => {
+                Some(bindings)
+            }
+            _ => None,
+        })
+        .flat_map(|bindings| bindings.entries.iter())
+        .filter_map(|(key, value)| match (key, value) {
+            (&BindingKey::NameDef(def), BindingValue::Inherit(_)) => Some(def),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let mut shadowed = scopes
+        .iter()
+        .filter_map(|(_, scope)| Some((scope.as_name_defs()?, scope.parent()?)))
+        .flat_map(|(defs, parent)| {
+            defs.iter()
+                .filter(|(_, def)| !inherited.contains(def))
+                .filter_map(|(name, &def)| {
+                    let outer = scopes
+                        .ancestors(parent)
+                        .find_map(|scope| scope.as_name_defs()?.get(name))?;
+                    Some((def, *outer))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    shadowed.sort_by_key(|(def, _)| def.into_raw());
+
+    shadowed
+        .into_iter()
+        .filter_map(|(def, outer)| {
+            let range = source_map.node_for_name_def(def)?.text_range();
+            let outer_range = source_map.node_for_name_def(outer)?.text_range();
+            Some(
+                Diagnostic::new(range, DiagnosticKind::ShadowedName)
+                    .with_note(InFile::new(file, outer_range), "Outer definition"),
+            )
+        })
+        .collect()
+}
+
+/// The type of an expression which is obvious from its syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LiteralType {
+    Number,
+    Bool,
+    Null,
+    String,
+    Path,
+    List,
+    Attrset,
+    Function,
+}
+
+/// Report binary operators whose operands are literals of types that always fail.
+/// Operands of unknown types are never reported to avoid false positives.
+fn invalid_operands(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    let root = db.parse(file).value.syntax_node();
+    module
+        .exprs()
+        .filter_map(|(expr, kind)| match *kind {
+            Expr::Binary(Some(op), lhs, rhs) => Some((expr, op, lhs, rhs)),
+            _ => None,
+        })
+        .filter(|&(_, _, lhs, rhs)| !module[lhs].is_missing() && !module[rhs].is_missing())
+        .filter(|&(_, op, lhs, rhs)| {
+            match (literal_type(db, file, lhs), literal_type(db, file, rhs)) {
+                (Some(lhs), Some(rhs)) => !is_valid_operation(op, lhs, rhs),
+                _ => false,
+            }
+        })
+        .filter_map(|(expr, ..)| {
+            let node = source_map.node_for_expr(expr)?.to_node(&root);
+            let op_range = ast::BinaryOp::cast(node)?.op_token()?.text_range();
+            Some(Diagnostic::new(op_range, DiagnosticKind::InvalidOperand))
+        })
+        .collect()
+}
+
+/// Report `with` expressions whose environments are literals which are never attrsets.
+/// References and applications are never reported, even if their values are known.
+fn with_non_attrsets(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    module
+        .exprs()
+        .filter_map(|(_, kind)| match *kind {
+            // The environment may be a recovered part of an incomplete `with`.
+            Expr::With(env, body) if !module[body].is_missing() => Some(env),
+            _ => None,
+        })
+        .filter(|&env| {
+            matches!(
+                module[env],
+                Expr::Literal(_)
+                    | Expr::StringInterpolation(_)
+                    | Expr::PathInterpolation(_)
+                    | Expr::List(_)
+            )
+        })
+        .filter_map(|env| {
+            let range = source_map.node_for_expr(env)?.text_range();
+            Some(Diagnostic::new(range, DiagnosticKind::WithNonAttrset))
+        })
+        .collect()
+}
+
+/// Report `assert` expressions whose conditions are the constant `false`.
+fn always_failing_asserts(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    module
+        .exprs()
+        .filter_map(|(_, kind)| match *kind {
+            Expr::Assert(cond, body) if !module[body].is_missing() => Some(cond),
+            _ => None,
+        })
+        .filter(|&cond| module[cond] == Expr::Literal(Literal::Bool(false)))
+        .filter_map(|cond| {
+            let range = source_map.node_for_expr(cond)?.text_range();
+            Some(Diagnostic::new(range, DiagnosticKind::AssertAlwaysFails))
+        })
+        .collect()
+}
+
+/// Report `let` or `rec` bindings whose values are chains of plain references back to
+/// themselves, eg. `rec { a = b; b = a; }`. Anything else may be lazy and is not checked.
+fn infinite_recursions(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    let root = db.parse(file).value.syntax_node();
+    let values = module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            Expr::LetIn(bindings, _) | Expr::Attrset(bindings) | Expr::LetAttrset(bindings) => {
+                Some(bindings)
+            }
+            _ => None,
+        })
+        .flat_map(|bindings| bindings.entries.iter())
+        .filter_map(|(key, value)| match (key, value) {
+            (&BindingKey::NameDef(def), &BindingValue::Expr(value)) => Some((def, value)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut defs = values
+        .iter()
+        .filter(|&(&def, &value)| {
+            let mut visited = HashSet::new();
+            let mut value = value;
+            while let Expr::Reference(_) = module[value] {
+                let next = match db.resolve_name(file, value) {
+                    Some(ResolveResult::NameDef(next)) => next,
+                    _ => return false,
+                };
+                if next == def {
+                    return true;
+                }
+                // Cycles not containing `def` are reported by their own members.
+                if !visited.insert(next) {
+                    return false;
+                }
+                value = match values.get(&next) {
+                    Some(&value) => value,
+                    None => return false,
+                };
+            }
+            false
+        })
+        .map(|(&def, _)| def)
+        .collect::<Vec<_>>();
+    defs.sort_by_key(|def| def.into_raw());
+
+    defs.into_iter()
+        .filter_map(|def| {
+            let name = source_map.node_for_name_def(def)?.to_node(&root);
+            let binding = name
+                .ancestors()
+                .find(|node| node.kind() == SyntaxKind::ATTR_PATH_VALUE)?;
+            Some(Diagnostic::new(
+                binding.text_range(),
+                DiagnosticKind::InfiniteRecursion,
+            ))
+        })
+        .collect()
+}
+
+fn literal_type(db: &dyn DefDatabase, file: FileId, expr: ExprId) -> Option<LiteralType> {
+    let module = db.module(file);
+    Some(match &module[expr] {
+        Expr::Literal(Literal::Int(_) | Literal::Float(_)) => LiteralType::Number,
+        Expr::Literal(Literal::String(_)) | Expr::StringInterpolation(_) => LiteralType::String,
+        Expr::Literal(Literal::Path(_)) | Expr::PathInterpolation(_) => LiteralType::Path,
+        Expr::Literal(Literal::Bool(_)) => LiteralType::Bool,
+        Expr::Literal(Literal::Null) => LiteralType::Null,
+        Expr::List(_) => LiteralType::List,
+        Expr::Attrset(_) => LiteralType::Attrset,
+        Expr::Lambda(..) => LiteralType::Function,
+        _ => return None,
+    })
+}
+
+fn is_valid_operation(op: BinaryOp, lhs: LiteralType, rhs: LiteralType) -> bool {
+    use LiteralType::*;
+    match op {
+        BinaryOp::Equal | BinaryOp::NotEqual => true,
+        BinaryOp::Imply | BinaryOp::Or | BinaryOp::And => lhs == Bool && rhs == Bool,
+        BinaryOp::Less | BinaryOp::Greater | BinaryOp::LessEqual | BinaryOp::GreaterEqual => {
+            matches!(
+                (lhs, rhs),
+                (Number, Number) | (String, String) | (Path, Path) | (List, List)
+            )
+        }
+        BinaryOp::Update => lhs == Attrset && rhs == Attrset,
+        BinaryOp::Concat => lhs == List && rhs == List,
+        // Strings and paths can be concatenated with each other.
+        BinaryOp::Add => matches!(
+            (lhs, rhs),
+            (Number, Number) | (String | Path, String | Path)
+        ),
+        BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => lhs == Number && rhs == Number,
+    }
+}
+
+/// Report bindings whose values are structurally identical to an earlier sibling,
+/// eg. `{ a = f 1; b = f 1; }`. Plain names and literals are too common to report.
+fn duplicate_values(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    let mut diags = Vec::new();
+    for (_, expr) in module.exprs() {
+        let bindings = match expr {
+            Expr::LetIn(bindings, _) | Expr::Attrset(bindings) | Expr::LetAttrset(bindings) => {
+                bindings
+            }
+            _ => continue,
+        };
+        let mut seen = HashMap::new();
+        for &(_, value) in bindings.entries.iter() {
+            let value = match value {
+                BindingValue::Expr(value) => value,
+                BindingValue::Inherit(_) | BindingValue::InheritFrom(_) => continue,
+            };
+            // Incomplete values are likely still being typed and are not reported.
+            if module.contains_missing(value) {
+                continue;
+            }
+            let is_trivial = match &module[value] {
+                Expr::Reference(_) | Expr::Literal(_) => true,
+                Expr::List(elems) => elems.is_empty(),
+                Expr::Attrset(bindings) => bindings.entries.is_empty(),
+                _ => false,
+            };
+            if is_trivial {
+                continue;
+            }
+            let first = *seen.entry(module.normalize_expr(value)).or_insert(value);
+            if first == value {
+                continue;
+            }
+            if let (Some(ptr), Some(first_ptr)) = (
+                source_map.node_for_expr(value),
+                source_map.node_for_expr(first),
+            ) {
+                diags.push(
+                    Diagnostic::new(ptr.text_range(), DiagnosticKind::DuplicateValue)
+                        .with_note(InFile::new(file, first_ptr.text_range()), "The same value"),
+                );
+            }
+        }
+    }
+    diags
+}
+
+/// Report `let` expressions whose bindings are complete but whose body is missing,
+/// eg. `let a = 1; in`.
+fn lets_without_body(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    module
+        .exprs()
+        .filter(|(_, expr)| match expr {
+            Expr::LetIn(bindings, body) => {
+                module[*body].is_missing()
+                    && !bindings.entries.is_empty()
+                    && bindings.entries.iter().all(|(_, value)| match *value {
+                        BindingValue::Expr(e) => !module[e].is_missing(),
+                        BindingValue::Inherit(_) | BindingValue::InheritFrom(_) => true,
+                    })
+            }
+            _ => false,
+        })
+        .filter_map(|(expr, _)| {
+            let range = source_map.node_for_expr(expr)?.text_range();
+            Some(Diagnostic::new(range, DiagnosticKind::LetWithoutBody))
+        })
+        .collect()
+}
+
+/// Report static attributes selected from the global `builtins` which are not known builtins,
+/// eg. `builtins.lenght`. Selections with defaults are feature checks and are not reported.
+fn unknown_builtins(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            Expr::Select(set, path, None) => Some((*set, *path.first()?)),
+            _ => None,
+        })
+        .filter(|&(set, _)| db.resolve_name(file, set) == Some(ResolveResult::Builtin("builtins")))
+        .filter(|&(_, attr)| match &module[attr] {
+            Expr::Literal(Literal::String(name)) => !builtin::is_builtins_attr(name),
+            _ => false,
+        })
+        .filter_map(|(_, attr)| {
+            let range = source_map.node_for_expr(attr)?.text_range();
+            Some(Diagnostic::new(range, DiagnosticKind::UnknownBuiltin))
+        })
+        .collect()
+}
+
+/// Report usages of deprecated builtins, either by global names like `isNull`,
+/// or selected from `builtins` like `builtins.toPath`.
+fn deprecated_builtins(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    module
+        .exprs()
+        .filter_map(|(expr, kind)| {
+            let (name, node_expr) = match kind {
+                Expr::Reference(_) => match db.resolve_name(file, expr)? {
+                    ResolveResult::Builtin(name) => (name, expr),
+                    ResolveResult::NameDef(_) | ResolveResult::WithExprs(_) => return None,
+                },
+                Expr::Select(set, path, _)
+                    if db.resolve_name(file, *set) == Some(ResolveResult::Builtin("builtins")) =>
+                {
+                    let attr = *path.first()?;
+                    match &module[attr] {
+                        Expr::Literal(Literal::String(name)) => (&**name, attr),
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            };
+            let info = builtin::get(name).filter(|info| info.deprecated.is_some())?;
+            let range = source_map.node_for_expr(node_expr)?.text_range();
+            Some(Diagnostic::new(
+                range,
+                DiagnosticKind::DeprecatedBuiltin(info.name),
+            ))
+        })
+        .collect()
+}
+
+/// Report keys of the left attrset literal of `//` which are also defined by the right one,
+/// eg. `a` in `{ a = 1; } // { a = 2; }`. Other operands are never reported.
+fn redundant_update_keys(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let module = db.module(file);
+    let source_map = db.source_map(file);
+    let root = db.parse(file).value.syntax_node();
+    let attrset_node = |expr: ExprId| match module[expr] {
+        Expr::Attrset(_) | Expr::LetAttrset(_) => {
+            ast::AttrSet::cast(source_map.node_for_expr(expr)?.to_node(&root))
+        }
+        _ => None,
+    };
+    let mut diags = Vec::new();
+    for (_, kind) in module.exprs() {
+        let (lhs, rhs) = match *kind {
+            Expr::Binary(Some(BinaryOp::Update), lhs, rhs) => (lhs, rhs),
+            _ => continue,
+        };
+        let (lhs, rhs) = match (attrset_node(lhs), attrset_node(rhs)) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => continue,
+        };
+        let mut rhs_keys = HashMap::new();
+        for (name, range) in static_keys(&rhs) {
+            rhs_keys.entry(name).or_insert(range);
+        }
+        for (name, range) in static_keys(&lhs) {
+            if let Some(&rhs_range) = rhs_keys.get(&name) {
+                diags.push(
+                    Diagnostic::new(range, DiagnosticKind::RedundantUpdateKey)
+                        .with_note(InFile::new(file, rhs_range), "Overridden here"),
+                );
+            }
+        }
+    }
+    diags
+}
+
+/// Report `let` bindings not reachable from the body, including clusters of bindings which only
+/// reference each other, like `let a = b; b = a; in 1`. A `let` without a body is being typed,
+/// so its bindings are not reported.
+fn unused_bindings(db: &dyn DefDatabase, file: FileId) -> Vec<Diagnostic> {
+    let source_map = db.source_map(file);
+    let root = db.parse(file).value.syntax_node();
+    unused_let_bindings(db, file)
+        .into_iter()
+        .filter_map(|binding| {
+            let name = source_map.node_for_name_def(binding.def)?.to_node(&root);
+            name.ancestors().find_map(ast::LetIn::cast)?.body()?;
+            let fix = FixKind::RemoveUnusedBinding {
+                range: binding.removal_range(),
+            };
+            Some(Diagnostic::new(name.text_range(), DiagnosticKind::UnusedBinding).with_fix(fix))
+        })
+        .collect()
+}
+
+/// The top-level static names defined by an attrset literal, with their ranges.
+fn static_keys(set: &ast::AttrSet) -> Vec<(String, TextRange)> {
+    set.bindings()
+        .flat_map(|binding| match binding {
+            ast::Binding::AttrpathValue(entry) => entry
+                .attrpath()
+                .and_then(|path| path.attrs().next())
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ast::Binding::Inherit(inherit) => inherit.attrs().collect(),
+        })
+        .filter_map(|attr| {
+            let name = match &attr {
+                ast::Attr::Name(name) => name.token()?.text().to_owned(),
+                ast::Attr::String(s) => static_string_content(s)?,
+                ast::Attr::Dynamic(_) => return None,
+            };
+            Some((name, attr.syntax().text_range()))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tests::TestDB;
+    use crate::{Change, Diagnostic, Severity};
     use expect_test::{expect, Expect};
 
     fn check(fixture: &str, expect: Expect) {
         let (db, file_id, []) = TestDB::single_file(fixture).unwrap();
         let diags = super::diagnostics(&db, file_id);
         assert!(!diags.is_empty());
-        let got = diags
-            .iter()
-            .map(|d| d.to_string() + "\n")
-            .collect::<Vec<_>>()
-            .join("");
-        expect.assert_eq(&got);
+        expect.assert_eq(&render(&diags));
+    }
+
+    fn check_syntax(fixture: &str, expect: Expect) {
+        let (db, file_id, []) = TestDB::single_file(fixture).unwrap();
+        let diags = super::syntax_diagnostics(&db, file_id);
+        assert!(!diags.is_empty());
+        expect.assert_eq(&render(&diags));
+    }
+
+    fn check_no(fixture: &str) {
+        let (db, file_id, []) = TestDB::single_file(fixture).unwrap();
+        let diags = super::diagnostics(&db, file_id);
+        assert!(diags.is_empty(), "Unexpected diagnostics: {:?}", diags);
+    }
+
+    fn render(diags: &[Diagnostic]) -> String {
+        diags.iter().map(|d| d.to_string() + "\n").collect()
+    }
+
+    #[test]
+    fn large_file() {
+        let src = "{ a = 1; a = 2; b = ./b/; }";
+        let (mut db, file_id, []) = TestDB::single_file(src).unwrap();
+        assert!(!super::diagnostics(&db, file_id).is_empty());
+        let mut change = Change::new();
+        change.set_max_file_size(src.len() - 1);
+        change.apply(&mut db);
+        assert!(super::diagnostics(&db, file_id).is_empty());
+        // It is still parsed.
+        assert_eq!(super::syntax_diagnostics(&db, file_id).len(), 1);
+    }
+
+    #[test]
+    fn remove_duplicate_fix() {
+        let src = "{ a = 1; a = 2; b.c = 1; b.c = 2; d = 1; inherit d; }";
+        let (db, file_id, []) = TestDB::single_file(src).unwrap();
+        let fixed = super::diagnostics(&db, file_id)
+            .into_iter()
+            .map(|diag| {
+                let mut text = src.to_owned();
+                for edit in diag.fix.iter().flat_map(|fix| fix.edits()).rev() {
+                    edit.apply(&mut text);
+                }
+                text + "\n"
+            })
+            .collect::<String>();
+        expect![[r#"
+            { a = 2; b.c = 1; b.c = 2; d = 1; inherit d; }
+            { a = 1; b.c = 1; b.c = 2; d = 1; inherit d; }
+            { a = 1; a = 2; b.c = 2; d = 1; inherit d; }
+            { a = 1; a = 2; b.c = 1; d = 1; inherit d; }
+            { a = 1; a = 2; b.c = 1; b.c = 2; inherit d; }
+            { a = 1; a = 2; b.c = 1; b.c = 2; d = 1; inherit d; }
+        "#]]
+        .assert_eq(&fixed);
+    }
+
+    #[test]
+    fn remove_unused_binding_fix() {
+        let src = "x: let inherit (x) a b; c = 1; in let d = 1; in b";
+        let (db, file_id, []) = TestDB::single_file(src).unwrap();
+        let fixed = super::diagnostics(&db, file_id)
+            .into_iter()
+            .map(|diag| {
+                let mut text = src.to_owned();
+                for edit in diag.fix.iter().flat_map(|fix| fix.edits()).rev() {
+                    edit.apply(&mut text);
+                }
+                text + "\n"
+            })
+            .collect::<String>();
+        expect![[r#"
+            x: let inherit (x) a b; c = 1; in b
+            x: let inherit (x) b; c = 1; in let d = 1; in b
+            x: let inherit (x) a b; in let d = 1; in b
+        "#]]
+        .assert_eq(&fixed);
     }
 
     #[test]
     fn syntax_error() {
-        check(
+        check_syntax(
             "1 == 2 == 3",
             expect![[r#"
                 Invalid usage of no-associative operators at 7..9
@@ -42,14 +617,343 @@ mod tests {
         );
     }
 
+    #[test]
+    fn syntax_error_severity() {
+        let (db, file_id, []) = TestDB::single_file("{ a = 1;").unwrap();
+        let diags = super::syntax_diagnostics(&db, file_id);
+        expect![[r#"
+            Missing R_CURLY at 8..8
+        "#]]
+        .assert_eq(&render(&diags));
+        assert!(diags
+            .iter()
+            .all(|diag| diag.severity() == Severity::IncompleteSyntax));
+
+        let (db, file_id, []) = TestDB::single_file("{ a = 1; } )").unwrap();
+        let diags = super::syntax_diagnostics(&db, file_id);
+        expect![[r#"
+            Multiple root expressions at 11..12
+            Unexpected token at 11..12
+        "#]]
+        .assert_eq(&render(&diags));
+        assert_eq!(diags[0].severity(), Severity::Error);
+    }
+
+    #[test]
+    fn empty_interpolation() {
+        // No cascading errors from the missing expression.
+        let (db, file_id, []) = TestDB::single_file(r#"let a = "foo${}bar"; in a"#).unwrap();
+        let diags = super::diagnostics(&db, file_id);
+        expect![[r#"
+            Empty interpolation at 12..15
+        "#]]
+        .assert_eq(&render(&diags));
+        assert!(diags
+            .iter()
+            .all(|diag| diag.severity() == Severity::IncompleteSyntax));
+        let (db, file_id, []) = TestDB::single_file("{ x.${} = 1; }").unwrap();
+        let diags = super::diagnostics(&db, file_id);
+        expect![[r#"
+            Empty interpolation at 4..7
+        "#]]
+        .assert_eq(&render(&diags));
+    }
+
+    #[test]
+    fn missing_operand() {
+        // Only the incomplete syntax is reported, without cascading semantic errors.
+        let (db, file_id, []) = TestDB::single_file("a + ").unwrap();
+        let diags = super::syntax_diagnostics(&db, file_id);
+        expect![[r#"
+            Unexpected token at 4..4
+        "#]]
+        .assert_eq(&render(&diags));
+        assert!(diags
+            .iter()
+            .all(|diag| diag.severity() == Severity::IncompleteSyntax));
+        check_no("a + ");
+        check_no("\"a\" + ");
+        check_no("with ; 1");
+        check_no("{ a = x + ; b = x + ; }");
+        check_no("{ a = f (); b = f (); }");
+    }
+
+    #[test]
+    fn syntax_and_semantic() {
+        let src = "{ a = 1; a = 2; } == 1 == 2";
+        check_syntax(
+            src,
+            expect![[r#"
+                Invalid usage of no-associative operators at 23..25
+            "#]],
+        );
+        check(
+            src,
+            expect![[r#"
+                Duplicated name definition at 2..8
+                Duplicated name definition at 9..15
+            "#]],
+        );
+    }
+
     #[test]
     fn lower_error() {
         check(
             "{ a = 1; a = 2; }",
             expect![[r#"
-                Duplicated name definition at 2..3
-                Duplicated name definition at 9..10
+                Duplicated name definition at 2..8
+                Duplicated name definition at 9..15
+            "#]],
+        );
+        check(
+            "{ a.b = 1; a.b = 2; }",
+            expect![[r#"
+                Attribute `a.b` is already defined at 2..10
+                Attribute `a.b` is already defined at 11..19
             "#]],
         );
     }
+
+    #[test]
+    fn update_overrides_keys() {
+        check(
+            "{ a = 1; } // { a = 2; a = 3; }",
+            expect![[r#"
+                Duplicated name definition at 16..22
+                Duplicated name definition at 23..29
+                This attribute is always overridden by the right operand of `//` at 2..3
+            "#]],
+        );
+    }
+
+    #[test]
+    fn redundant_update_key() {
+        check(
+            "{ a = 1; b = 2; inherit c; } // { a = 3; c = 4; }",
+            expect![[r#"
+                This attribute is always overridden by the right operand of `//` at 2..3
+                This attribute is always overridden by the right operand of `//` at 24..25
+            "#]],
+        );
+        // `//` is shallow.
+        check(
+            "{ a.b = 1; } // { a.c = 2; }",
+            expect![[r#"
+                This attribute is always overridden by the right operand of `//` at 2..3
+            "#]],
+        );
+        check(
+            "let x = { a = 1; }; in x // { a = 2; } // rec { a = 3; }",
+            expect![[r#"
+                This attribute is always overridden by the right operand of `//` at 30..31
+            "#]],
+        );
+        check_no("{ a = 1; b = 2; } // { c = 3; }");
+        check_no("let x = { a = 1; }; in x // { a = 2; }");
+        check_no("{ ${x} = 1; } // { ${x} = 2; }");
+    }
+
+    #[test]
+    fn invalid_operand() {
+        check(
+            "[ (true + 1) (1 + \"a\") ([ ] // { }) (null && true) ({ } ++ [ ]) ((x: x) - 1) ]",
+            expect![[r#"
+                Invalid operand types for this operator at 8..9
+                Invalid operand types for this operator at 16..17
+                Invalid operand types for this operator at 28..30
+                Invalid operand types for this operator at 42..44
+                Invalid operand types for this operator at 56..58
+                Invalid operand types for this operator at 72..73
+            "#]],
+        );
+        check_no("[ (1 + x) (1 + 2.0) (\"a\" + ./b) (./a + \"b\") ([ ] ++ [ ]) ({ } // { }) ]");
+        check_no(
+            "[ (true -> false) (1 < 2) (\"a\" < \"b\") (null == 1) (let true = 1; in true + 1) ]",
+        );
+    }
+
+    #[test]
+    fn with_non_attrset() {
+        check(
+            "with 1; x",
+            expect![[r#"
+                The environment of `with` is not an attrset at 5..6
+            "#]],
+        );
+        check(
+            "[ (with \"a${b}\"; x) (with ./a; x) (with [ ]; x) ]",
+            expect![[r#"
+                The environment of `with` is not an attrset at 8..15
+                The environment of `with` is not an attrset at 26..29
+                The environment of `with` is not an attrset at 40..43
+            "#]],
+        );
+        check_no("with pkgs; x");
+        check_no("with { }; with import ./a.nix; with f 1; x");
+    }
+
+    #[test]
+    fn assert_always_fails() {
+        check(
+            "assert false; x",
+            expect![[r#"
+                This assertion always fails at 7..12
+            "#]],
+        );
+        check_no("assert cond; x");
+        check_no("assert true; x");
+        check_no("let false = true; in assert false; x");
+    }
+
+    #[test]
+    fn infinite_recursion() {
+        check(
+            "rec { a = a; }",
+            expect![[r#"
+                This binding is defined as itself, which recurses infinitely at 6..12
+            "#]],
+        );
+        check(
+            "let a = b; b = a; c = a; in c",
+            expect![[r#"
+                This binding is defined as itself, which recurses infinitely at 4..10
+                This binding is defined as itself, which recurses infinitely at 11..17
+            "#]],
+        );
+        check_no("rec { a = [ a ]; }");
+        check_no("rec { a = b; b = 1; }");
+        check_no("let a = x: a x; in a");
+        check_no("a: { a = a; }");
+    }
+
+    #[test]
+    fn let_without_body() {
+        check(
+            "let a = 1; in",
+            expect![[r#"
+                This `let` has no body after `in` at 0..13
+            "#]],
+        );
+        check(
+            "x: let inherit x; b.c = 1; in ",
+            expect![[r#"
+                This `let` has no body after `in` at 3..30
+            "#]],
+        );
+        check_no("let a = 1; in a");
+        check_no("let a = ; in");
+        check_no("let in");
+    }
+
+    #[test]
+    fn duplicate_value() {
+        check(
+            "{ a = f 1; b = f 1; c = f 1; }",
+            expect![[r#"
+                This value is identical to a sibling binding, consider factoring it out with `let` at 15..18
+                This value is identical to a sibling binding, consider factoring it out with `let` at 24..27
+            "#]],
+        );
+        check(
+            "let a = { x = [ 1 ]; }; b.x = [ 1 ]; in a",
+            expect![[r#"
+                This value is identical to a sibling binding, consider factoring it out with `let` at 24..25
+            "#]],
+        );
+        check_no("{ a = f 1; b = f 2; c = g 1; d = f 1 1; }");
+        check_no("{ a = true; b = true; c = x; d = x; e = [ ]; f = [ ]; }");
+        check_no("{ a.x = f 1; b.y = f 1; }");
+    }
+
+    #[test]
+    fn shadowed_name() {
+        check(
+            "let x = 1; in let x = 2; in x",
+            expect![[r#"
+                This name shadows an outer definition at 18..19
+                This binding is never used at 4..5
+            "#]],
+        );
+        check(
+            "x: { x }: rec { x = 1; }",
+            expect![[r#"
+                This name shadows an outer definition at 5..6
+                This name shadows an outer definition at 16..17
+            "#]],
+        );
+        check_no("let x = 1; in let inherit x; in x");
+        check_no("let x = 1; in { x = x; }");
+        check_no("a@{ b, ... }: let c = 1; in a c");
+    }
+
+    #[test]
+    fn unknown_builtin() {
+        check(
+            "builtins.lenght [ ]",
+            expect![[r#"
+            This attribute does not exist in `builtins` at 9..15
+        "#]],
+        );
+        check(
+            "builtins.lenght.x",
+            expect![[r#"
+            This attribute does not exist in `builtins` at 9..15
+        "#]],
+        );
+        check(
+            "builtins.__length [ ]",
+            expect![[r#"
+            This attribute does not exist in `builtins` at 9..17
+        "#]],
+        );
+        check_no("builtins.length [ ]");
+        check_no("builtins.true");
+        check_no("builtins.convertHash { hash = h; toHashFormat = \"sri\"; }");
+        check_no("builtins.readFileType ./.");
+        check_no("let builtins = { }; in builtins.x");
+        check_no("builtins.getFlakeNext or null");
+        check_no("builtins.${x}");
+    }
+
+    #[test]
+    fn deprecated_builtin() {
+        check(
+            "x: [ (isNull x) (builtins.toPath x) ]",
+            expect![[r#"
+                `isNull` is deprecated, use `x == null` instead at 6..12
+                `toPath` is deprecated, use `/. + "/path"` instead at 26..32
+            "#]],
+        );
+        check_no("x: x == null");
+        check_no("let isNull = x: x == null; in isNull 1");
+    }
+
+    #[test]
+    fn unused_binding() {
+        check(
+            "let a = [ b ]; b = [ a ]; c = [ d ]; d = [ c ]; in c",
+            expect![[r#"
+                This binding is never used at 4..5
+                This binding is never used at 15..16
+            "#]],
+        );
+        check(
+            "x: let inherit (x) a; b = a; f = n: f n; in 1",
+            expect![[r#"
+                This binding is never used at 19..20
+                This binding is never used at 22..23
+                This binding is never used at 29..30
+            "#]],
+        );
+        check_no("let a = b; b = 1; in a");
+        check_no("rec { a = [ b ]; b = [ a ]; }");
+
+        // Only the missing body is reported during typing.
+        let (db, file_id, []) = TestDB::single_file("let a = 1; in").unwrap();
+        let diags = super::diagnostics(&db, file_id);
+        expect![[r#"
+            This `let` has no body after `in` at 0..13
+        "#]]
+        .assert_eq(&render(&diags));
+    }
 }
@@ -0,0 +1,111 @@
+/// The quality of a fuzzy match. Higher is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Score(pub u32);
+
+/// Scores are clamped below this when combined with priorities.
+const PRIORITY_SCALE: u32 = 1000;
+
+impl Score {
+    /// Combine with a priority where lower values are shown first, so that better matches
+    /// come first among items of the same priority.
+    pub fn rank(self, priority: u32) -> u32 {
+        priority * PRIORITY_SCALE + (PRIORITY_SCALE - 1 - self.0.min(PRIORITY_SCALE - 1))
+    }
+}
+
+const MATCH_SCORE: u32 = 1;
+/// Matched right after the previous matched character.
+const CONSECUTIVE_BONUS: u32 = 2;
+/// Matched at the start of a word, eg. `B` in `fooBar` or `b` in `foo-bar`.
+const BOUNDARY_BONUS: u32 = 3;
+const START_BONUS: u32 = 4;
+
+/// Match `query` as a case-insensitive subsequence of `candidate`, eg. `fb` in `fooBar`.
+/// Returns `None` if it does not match. An empty query matches everything with the lowest score.
+///
+/// Among all ways to match, the best score is returned, which prefers characters matched
+/// consecutively and at word boundaries.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<Score> {
+    if query.is_empty() {
+        return Some(Score(0));
+    }
+    let query = query.chars().collect::<Vec<_>>();
+    let cand = candidate.chars().collect::<Vec<_>>();
+    // `prev[i]` is the best score of the previous query characters, with the last one matched
+    // at `cand[i]`.
+    let mut prev: Vec<Option<u32>> = Vec::new();
+    for (j, &qc) in query.iter().enumerate() {
+        let mut row = vec![None; cand.len()];
+        // The best score of the previous query characters matched before `cand[i]`.
+        let mut best_before = (j == 0).then_some(0);
+        for (i, &cc) in cand.iter().enumerate() {
+            if qc.to_lowercase().eq(cc.to_lowercase()) {
+                let adjacent = (j != 0 && i != 0)
+                    .then(|| prev[i - 1])
+                    .flatten()
+                    .map(|score| score + CONSECUTIVE_BONUS);
+                row[i] = best_before
+                    .max(adjacent)
+                    .map(|score| score + MATCH_SCORE + boundary_bonus(&cand, i));
+            }
+            if j != 0 {
+                best_before = best_before.max(prev[i]);
+            }
+        }
+        prev = row;
+    }
+    prev.into_iter().flatten().max().map(Score)
+}
+
+fn boundary_bonus(cand: &[char], i: usize) -> u32 {
+    if i == 0 {
+        return START_BONUS;
+    }
+    let (before, cur) = (cand[i - 1], cand[i]);
+    if matches!(before, '_' | '-' | '\'' | '.' | '/')
+        || (before.is_lowercase() && cur.is_uppercase())
+    {
+        BOUNDARY_BONUS
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    #[test]
+    fn subsequence() {
+        assert!(fuzzy_match("", "foo").is_some());
+        assert!(fuzzy_match("fbr", "fooBar").is_some());
+        assert!(fuzzy_match("FOOB", "fooBar").is_some());
+        assert!(fuzzy_match("bf", "fooBar").is_none());
+        assert!(fuzzy_match("foo", "fo").is_none());
+        assert!(fuzzy_match("ß", "straße").is_some());
+    }
+
+    #[test]
+    fn score() {
+        // Camel case boundaries.
+        assert!(fuzzy_match("fb", "fooBar") > fuzzy_match("fb", "feedback"));
+        // Other word boundaries.
+        assert!(fuzzy_match("fb", "foo-bar") > fuzzy_match("fb", "feedback"));
+        assert!(fuzzy_match("fb", "foo_bar") > fuzzy_match("fb", "feedback"));
+        // Consecutive characters.
+        assert!(fuzzy_match("foo", "foobar") > fuzzy_match("foo", "fxoxo"));
+        // The best match is found, not the leftmost one.
+        assert!(fuzzy_match("fb", "fabBar") > fuzzy_match("fb", "fab"));
+        // Prefixes.
+        assert!(fuzzy_match("bar", "barFoo") > fuzzy_match("bar", "fooBar"));
+    }
+
+    #[test]
+    fn rank() {
+        let (good, bad) = (fuzzy_match("fb", "fooBar"), fuzzy_match("fb", "feedback"));
+        let (good, bad) = (good.unwrap(), bad.unwrap());
+        assert!(good.rank(1) < bad.rank(1));
+        // Priorities still take precedence.
+        assert!(bad.rank(1) < good.rank(2));
+    }
+}
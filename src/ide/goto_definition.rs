@@ -1,9 +1,15 @@
-use super::NavigationTarget;
-use crate::def::{AstPtr, DefDatabase, ResolveResult};
-use crate::FileId;
+use super::imports::{resolve_import_file, static_import_path};
+use super::{container_name, resolve_attrset, NavigationTarget};
+use crate::def::{
+    BindingKey, BindingValue, DefDatabase, Expr, ExprId, Literal, Module, NameDefId, ResolveResult,
+};
+use crate::{FileId, FileRange};
 use rowan::ast::AstNode;
 use rowan::TextSize;
-use syntax::{ast, match_ast, SyntaxKind, T};
+use smol_str::SmolStr;
+use std::slice;
+use syntax::ast::HasBindings;
+use syntax::{ast, match_ast, SyntaxKind, SyntaxNode, T};
 
 pub(crate) fn goto_definition(
     db: &dyn DefDatabase,
@@ -15,75 +21,390 @@ pub(crate) fn goto_definition(
     if !matches!(tok.kind(), T![or] | SyntaxKind::IDENT) {
         return None;
     }
-    let ptr = tok.parent_ancestors().find_map(|node| {
+
+    // An attribute of a selection, eg. `b` in `a.b.c`.
+    if let Some(attr) = tok
+        .parent_ancestors()
+        .take_while(|node| !ast::Ref::can_cast(node.kind()))
+        .find_map(ast::Attr::cast)
+    {
+        let select = attr
+            .syntax()
+            .parent()
+            .and_then(|path| path.parent())
+            .and_then(ast::Select::cast);
+        if let Some(select) = select {
+            return goto_select_attr(db, file_id, &parse.syntax_node(), select, attr);
+        }
+    }
+    let node = tok.parent_ancestors().find_map(|node| {
         match_ast! {
             match node {
-                ast::Ref(n) => Some(AstPtr::new(n.syntax())),
-                ast::Name(n) => Some(AstPtr::new(n.syntax())),
+                ast::Ref(n) => Some(n.syntax().clone()),
+                ast::Name(n) => Some(n.syntax().clone()),
                 _ => None,
             }
         }
     })?;
 
     let source_map = db.source_map(file_id);
-    let expr_id = source_map.node_expr(ptr)?;
+    let expr_id = source_map.expr_for_node(&node)?;
 
     match db.resolve_name(file_id, expr_id)? {
         ResolveResult::NameDef(def) => {
-            let name_node = source_map.name_def_node(def)?.to_node(&parse.syntax_node());
+            let name_node = source_map
+                .node_for_name_def(def)?
+                .to_node(&parse.syntax_node());
             let full_node = name_node.ancestors().find(|n| {
                 matches!(
                     n.kind(),
                     SyntaxKind::LAMBDA | SyntaxKind::ATTR_PATH_VALUE | SyntaxKind::INHERIT
                 )
             })?;
-            Some(vec![NavigationTarget {
+            let mut targets = vec![NavigationTarget {
                 file_id,
                 focus_range: name_node.text_range(),
                 full_range: full_node.text_range(),
-            }])
-        }
-        ResolveResult::WithExprs(withs) => {
-            let targets = withs
-                .iter()
-                .filter_map(|&with_expr| {
-                    // with expr; body
-                    // ^--^       focus
-                    // ^--------^ full
-                    let with_node = source_map
-                        .expr_node(with_expr)
-                        .expect("WithExprs must be valid")
-                        .to_node(&parse.syntax_node());
-                    let with_node = ast::With::cast(with_node).expect("WithExprs must be valid");
-                    let with_token_range = with_node.with_token()?.text_range();
-                    let with_header_end = with_node
-                        .semicolon_token()
-                        .map_or_else(|| with_node.syntax().text_range(), |tok| tok.text_range());
-                    let with_header = with_token_range.cover(with_header_end);
-                    Some(NavigationTarget {
+                container_name: container_name(&name_node),
+            }];
+            // For `inherit (e) a;`, the source expression `e` is also a target.
+            let module = db.module(file_id);
+            if let Some(from_expr) = inherit_from_expr(&module, def) {
+                if let Some(ptr) = source_map.node_for_expr(from_expr) {
+                    targets.push(NavigationTarget {
                         file_id,
-                        focus_range: with_token_range,
-                        full_range: with_header,
-                    })
-                })
-                .collect();
+                        focus_range: ptr.text_range(),
+                        full_range: full_node.text_range(),
+                        container_name: container_name(&name_node),
+                    });
+                }
+            }
             Some(targets)
         }
+        ResolveResult::WithExprs(withs) => {
+            let root = parse.syntax_node();
+            // The innermost `with` whose environment is a known attrset defining the name.
+            // Stop at an unknown one, since it may provide the name as well.
+            let module = db.module(file_id);
+            if let Expr::Reference(name) = &module[expr_id] {
+                for &with_expr in &withs {
+                    let env = match module[with_expr] {
+                        Expr::With(env, _) => env,
+                        _ => break,
+                    };
+                    let set_expr = match resolve_attrset(db, file_id, env) {
+                        Some(set_expr) => set_expr,
+                        None => break,
+                    };
+                    if let Some(targets) =
+                        attr_targets(db, file_id, &root, set_expr, slice::from_ref(name))
+                    {
+                        return Some(targets);
+                    }
+                }
+            }
+            Some(with_targets(db, file_id, &root, &withs))
+        }
         // Currently builtin names cannot "goto-definition".
         ResolveResult::Builtin(_) => None,
     }
 }
 
+/// Same as `goto_definition`, except that names provided by `with` jump to the `with`
+/// expressions instead of the attributes of their environments.
+pub(crate) fn goto_declaration(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<Vec<NavigationTarget>> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).right_biased()?;
+    if tok.kind() == SyntaxKind::IDENT {
+        if let Some(node) = tok.parent_ancestors().find_map(ast::Ref::cast) {
+            let expr_id = db.source_map(file_id).expr_for_node(node.syntax())?;
+            if let Some(ResolveResult::WithExprs(withs)) = db.resolve_name(file_id, expr_id) {
+                return Some(with_targets(db, file_id, &parse.syntax_node(), &withs));
+            }
+        }
+    }
+    goto_definition(db, file_id, pos)
+}
+
+/// The `with` headers providing a name, innermost first.
+fn with_targets(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    root: &SyntaxNode,
+    withs: &[ExprId],
+) -> Vec<NavigationTarget> {
+    let source_map = db.source_map(file_id);
+    withs
+        .iter()
+        .filter_map(|&with_expr| {
+            // with expr; body
+            // ^--^       focus
+            // ^--------^ full
+            let with_node = source_map
+                .node_for_expr(with_expr)
+                .expect("WithExprs must be valid")
+                .to_node(root);
+            let with_node = ast::With::cast(with_node).expect("WithExprs must be valid");
+            let with_token_range = with_node.with_token()?.text_range();
+            let with_header_end = with_node
+                .semicolon_token()
+                .map_or_else(|| with_node.syntax().text_range(), |tok| tok.text_range());
+            let with_header = with_token_range.cover(with_header_end);
+            Some(NavigationTarget {
+                file_id,
+                focus_range: with_token_range,
+                full_range: with_header,
+                container_name: container_name(with_node.syntax()),
+            })
+        })
+        .collect()
+}
+
+/// Jump to the value a referenced name is bound to, as an approximation of its type.
+/// Only lambdas, attrset literals and statically imported files are supported.
+pub(crate) fn goto_type_definition(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<Vec<NavigationTarget>> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).right_biased()?;
+    if !matches!(tok.kind(), T![or] | SyntaxKind::IDENT) {
+        return None;
+    }
+    let node = tok.parent_ancestors().find_map(ast::Ref::cast)?;
+    let source_map = db.source_map(file_id);
+    let expr_id = source_map.expr_for_node(node.syntax())?;
+    let def = match db.resolve_name(file_id, expr_id)? {
+        ResolveResult::NameDef(def) => def,
+        ResolveResult::Builtin(_) | ResolveResult::WithExprs(_) => return None,
+    };
+    let module = db.module(file_id);
+    let value = module.name_def_value(def)?;
+
+    if let Some(path) = static_import_path(db, file_id, value) {
+        let (_, target) = resolve_import_file(db, path).ok()?;
+        let root = db.parse(target).value.root();
+        let range = root
+            .expr()
+            .map_or_else(|| root.syntax().text_range(), |e| e.syntax().text_range());
+        let frange = FileRange::new(target, range);
+        return Some(vec![NavigationTarget::from_file_range(frange)]);
+    }
+    match module[value] {
+        Expr::Lambda(..) | Expr::Attrset(_) | Expr::LetAttrset(_) => {}
+        _ => return None,
+    }
+    let node = source_map
+        .node_for_expr(value)?
+        .to_node(&parse.syntax_node());
+    let range = match ast::Expr::cast(node.clone()) {
+        Some(expr) => expr.syntax().text_range(),
+        // Attrset values are mapped to their keys, eg. `a` in `{ a = { }; }` or `{ a.b = 1; }`.
+        // Jump to the value if it is a single literal, or the first definition otherwise.
+        None => {
+            let entry = node.ancestors().find_map(ast::AttrpathValue::cast)?;
+            let last_attr = entry.attrpath()?.attrs().last()?;
+            match entry.value() {
+                Some(value) if last_attr.syntax() == &node => value.syntax().text_range(),
+                _ => entry.syntax().text_range(),
+            }
+        }
+    };
+    let frange = FileRange::new(file_id, range);
+    Some(vec![NavigationTarget::from_file_range(frange)])
+}
+
+/// Find the `e` of `inherit (e)` if the name is defined by it.
+fn inherit_from_expr(module: &Module, def: NameDefId) -> Option<ExprId> {
+    module.exprs().find_map(|(_, expr)| match expr {
+        Expr::Attrset(bindings) | Expr::LetAttrset(bindings) | Expr::LetIn(bindings, _) => bindings
+            .entries
+            .iter()
+            .find_map(|(key, value)| match (key, value) {
+                (BindingKey::NameDef(d), BindingValue::InheritFrom(id)) if *d == def => {
+                    Some(bindings.inherit_from_expr(*id))
+                }
+                _ => None,
+            }),
+        _ => None,
+    })
+}
+
+fn goto_select_attr(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    root: &SyntaxNode,
+    select: ast::Select,
+    attr: ast::Attr,
+) -> Option<Vec<NavigationTarget>> {
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+    let (set_expr, attrpath) = match &module[source_map.expr_for_node(select.syntax())?] {
+        Expr::Select(set, attrpath, _) => (*set, attrpath),
+        _ => return None,
+    };
+    // Static names of the attrpath until the one under the cursor.
+    let attr_expr = source_map.expr_for_attr(&attr)?;
+    let idx = attrpath.iter().position(|&e| e == attr_expr)?;
+    let path = attrpath[..=idx]
+        .iter()
+        .map(|&e| match &module[e] {
+            Expr::Literal(Literal::String(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let set_expr = resolve_attrset(db, file_id, set_expr)?;
+    attr_targets(db, file_id, root, set_expr, &path)
+}
+
+/// Keys defining the attrpath in a resolved attrset expression.
+fn attr_targets(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    root: &SyntaxNode,
+    set_expr: ExprId,
+    path: &[SmolStr],
+) -> Option<Vec<NavigationTarget>> {
+    let source_map = db.source_map(file_id);
+    let set_node = source_map.node_for_expr(set_expr)?.to_node(root);
+    let mut targets = Vec::new();
+    if let Some(set) = ast::AttrSet::cast(set_node.clone()) {
+        find_keys(file_id, &set, path, &mut targets);
+    } else {
+        // An attrset merged from multiple definitions is mapped to the key, eg. `a` in
+        // `{ a.b = 1; a = { c = 2; }; }`. Search the container with the full path instead.
+        let entry = set_node.ancestors().find_map(ast::AttrpathValue::cast)?;
+        let mut full_path = Vec::new();
+        for attr in entry.attrpath()?.attrs() {
+            full_path.push(attr_name(&attr)?);
+            if attr.syntax() == &set_node {
+                break;
+            }
+        }
+        full_path.extend_from_slice(path);
+        let container = entry.syntax().parent()?;
+        if let Some(set) = ast::AttrSet::cast(container.clone()) {
+            find_keys(file_id, &set, &full_path, &mut targets);
+        } else if let Some(let_in) = ast::LetIn::cast(container) {
+            find_keys(file_id, &let_in, &full_path, &mut targets);
+        }
+    }
+    // The attribute may be missing, eg. `a.b or 1`.
+    (!targets.is_empty()).then_some(targets)
+}
+
+/// Find keys defining the attrpath in an attrset, descending into nested attrset values.
+fn find_keys(
+    file_id: FileId,
+    set: &impl HasBindings,
+    path: &[SmolStr],
+    targets: &mut Vec<NavigationTarget>,
+) {
+    for binding in set.bindings() {
+        match binding {
+            ast::Binding::Inherit(inherit) => {
+                if path.len() != 1 {
+                    continue;
+                }
+                targets.extend(
+                    inherit
+                        .attrs()
+                        .filter(|attr| attr_name(attr).as_ref() == Some(&path[0]))
+                        .map(|attr| NavigationTarget {
+                            file_id,
+                            focus_range: attr.syntax().text_range(),
+                            full_range: inherit.syntax().text_range(),
+                            container_name: container_name(attr.syntax()),
+                        }),
+                );
+            }
+            ast::Binding::AttrpathValue(entry) => {
+                let attrs = match entry.attrpath() {
+                    Some(attrpath) => attrpath.attrs().collect::<Vec<_>>(),
+                    None => continue,
+                };
+                let matched = attrs
+                    .iter()
+                    .zip(path)
+                    .take_while(|(attr, name)| attr_name(attr).as_ref() == Some(name))
+                    .count();
+                if matched == path.len() {
+                    targets.push(NavigationTarget {
+                        file_id,
+                        focus_range: attrs[matched - 1].syntax().text_range(),
+                        full_range: entry.syntax().text_range(),
+                        container_name: container_name(attrs[matched - 1].syntax()),
+                    });
+                } else if matched == attrs.len() {
+                    if let Some(ast::Expr::AttrSet(value)) = entry.value() {
+                        if value.let_token().is_none() {
+                            find_keys(file_id, &value, &path[matched..], targets);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn attr_name(attr: &ast::Attr) -> Option<SmolStr> {
+    match attr {
+        ast::Attr::Name(name) => Some(name.token()?.text().into()),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::NavigationTarget;
     use crate::base::SourceDatabase;
+    use crate::def::DefDatabase;
     use crate::tests::TestDB;
+    use crate::FileId;
     use expect_test::{expect, Expect};
+    use rowan::TextSize;
+
+    #[track_caller]
+    fn check_type(fixture: &str, expect: Expect) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [pos] = f.markers().unwrap();
+        let got = super::goto_type_definition(&db, pos.file_id, pos.value)
+            .into_iter()
+            .flatten()
+            .map(|target| {
+                let path = db.file_set().path_for_file(target.file_id).unwrap().clone();
+                // The root expression of a fixture file includes trailing whitespace.
+                let text = f[target.file_id][target.full_range].trim_end();
+                format!("{}: {}", path, text)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        expect.assert_eq(&got);
+    }
 
     fn check(fixture: &str, expect: Expect) {
+        check_impl(super::goto_definition, fixture, expect);
+    }
+
+    fn check_decl(fixture: &str, expect: Expect) {
+        check_impl(super::goto_declaration, fixture, expect);
+    }
+
+    #[track_caller]
+    fn check_impl(
+        goto: fn(&dyn DefDatabase, FileId, TextSize) -> Option<Vec<NavigationTarget>>,
+        fixture: &str,
+        expect: Expect,
+    ) {
         let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
         let src = db.file_content(file_id);
-        let targets = super::goto_definition(&db, file_id, pos)
+        let targets = goto(&db, file_id, pos)
             .into_iter()
             .flatten()
             .map(|target| {
@@ -136,6 +457,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn with_attr() {
+        let src = "let pkgs = { hello = 1; }; in with pkgs; $0hello";
+        check(src, expect!["<hello> = 1;"]);
+        check_decl(src, expect!["<with> pkgs;"]);
+        check_decl("pkgs: with pkgs; $0hello", expect!["<with> pkgs;"]);
+        // The unknown inner environment may provide it.
+        check(
+            "let a = { b = 1; }; in with a; with c; $0b",
+            expect![[r#"
+                <with> c;
+                <with> a;
+            "#]],
+        );
+        check(
+            "let a = { b = 1; }; in with a; with { c = 2; }; $0b",
+            expect!["<b> = 1;"],
+        );
+        // Lexical names are the same.
+        check_decl("let a = 1; in with { }; $0a", expect!["<a> = 1;"]);
+    }
+
+    #[test]
+    fn let_inherit_from() {
+        check(
+            "pkgs: let inherit (pkgs) hello; in $0hello",
+            expect![[r#"
+            inherit (pkgs) <hello>;
+            inherit (<pkgs>) hello;
+        "#]],
+        );
+        check(
+            "rec { inherit (import ./a.nix { }) x y; z = $0y; }",
+            expect![[r#"
+                inherit (import ./a.nix { }) x <y>;
+                inherit (<import ./a.nix { }>) x y;
+            "#]],
+        );
+        check(
+            "pkgs: let inherit (pkgs) hello; in $0pkgs",
+            expect!["<pkgs>: let inherit (pkgs) hello; in pkgs"],
+        );
+    }
+
+    #[test]
+    fn inherited_function() {
+        // The inherit site is the definition. The source `m` is not entered.
+        check(
+            "m: let inherit (m) f; in $0f 1",
+            expect![[r#"
+                inherit (m) <f>;
+                inherit (<m>) f;
+            "#]],
+        );
+        check(
+            "let m = { f = x: x; }; in let inherit (m) f; in $0f 1",
+            expect![[r#"
+                inherit (m) <f>;
+                inherit (<m>) f;
+            "#]],
+        );
+        check(
+            "let f = x: x; in let inherit f; in $0f 1",
+            expect!["inherit <f>;"],
+        );
+        check(
+            "let f = x: x; in let inherit $0f; in f 1",
+            expect!["<f> = x: x;"],
+        );
+    }
+
+    #[test]
+    fn merged_rec_bindings() {
+        check("rec { a = $0b; b = 1; }", expect!["<b> = 1;"]);
+        check("rec { a = 1; a.x = 2; b = $0a; }", expect!["<a> = 1;"]);
+        check("rec { a.x = 1; a.y = 2; b = $0a; }", expect!["<a>.x = 1;"]);
+        check("let a.x = 1; a.y = 2; in $0a", expect!["<a>.x = 1;"]);
+        check(
+            "rec { a = { x = 1; }; a.y = $0a; }",
+            expect!["<a> = { x = 1; };"],
+        );
+    }
+
     #[test]
     fn bindings() {
         check(
@@ -152,9 +556,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn container_name() {
+        let check_container = |fixture: &str, expect: Expect| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            let got = super::goto_definition(&db, file_id, pos)
+                .into_iter()
+                .flatten()
+                .map(|target| format!("{:?}", target.container_name))
+                .collect::<Vec<_>>()
+                .join("\n");
+            expect.assert_eq(&got);
+        };
+        check_container(
+            "{ a.b = { c.\"d e\" = rec { f.g = 1; h = $0f; }; }; }",
+            expect![[r#"Some("a.b.c.\"d e\"")"#]],
+        );
+        check_container(
+            "let a = { b = let c = 1; in $0c; }; in a",
+            expect![[r#"Some("a.b")"#]],
+        );
+        check_container("let c = 1; in $0c", expect!["None"]);
+        check_container("let a = { b = 1; }; in a.$0b", expect![[r#"Some("a")"#]]);
+    }
+
     #[test]
     fn builtin() {
         check("let true = 1; in $0true && false", expect!["<true> = 1;"]);
         check("let true = 1; in true && $0false", expect![""]);
     }
+
+    #[test]
+    fn type_definition() {
+        check_type(
+            "
+#- /default.nix
+let f = import ./m.nix; in $0f
+#- /m.nix
+{ x }: x
+            ",
+            expect!["/m.nix: { x }: x"],
+        );
+        check_type(
+            "
+#- /default.nix
+let f = x: x; g = f; in [ $0f g ]
+            ",
+            expect!["/default.nix: x: x"],
+        );
+        check_type(
+            "#- /default.nix\nlet a = { }; in $0a",
+            expect!["/default.nix: { }"],
+        );
+        check_type(
+            "#- /default.nix\nlet a.x = 1; a.y = 2; in $0a",
+            expect!["/default.nix: a.x = 1;"],
+        );
+        check_type("#- /default.nix\nlet a = 1; in $0a", expect![""]);
+        check_type("#- /default.nix\nx: $0x", expect![""]);
+        check_type(
+            "#- /default.nix\nlet a = import ./a.nix; in $0a",
+            expect![""],
+        );
+    }
+
+    #[test]
+    fn select_attr() {
+        check("let a = { b = 1; }; in a.$0b", expect!["<b> = 1;"]);
+        check(
+            "let a = { b.c = 1; b = { d = 2; }; }; in a.$0b.d",
+            expect![[r#"
+                <b>.c = 1;
+                <b> = { d = 2; };
+            "#]],
+        );
+        check(
+            "let a = { b.c = 1; b = { d = 2; }; }; in a.b.$0d",
+            expect!["<d> = 2;"],
+        );
+        check(
+            "let b = 1; a = { inherit b; }; in a.$0b",
+            expect!["inherit <b>;"],
+        );
+        check("let a = { b = 1; }; in a.$0b or 2", expect!["<b> = 1;"]);
+        check("let a = { b = 1; }; in a.$0c or 2", expect![""]);
+        check("let a = b: b; in a.$0b", expect![""]);
+    }
 }
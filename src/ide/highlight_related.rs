@@ -0,0 +1,94 @@
+use crate::base::SourceDatabase;
+use crate::FileId;
+use syntax::{SyntaxKind, SyntaxToken, TextRange, TextSize, T};
+
+/// The keywords belonging to the same construct as the keyword at the position, including itself.
+/// Pairs are `if`/`then`/`else`, `let`/`in`, and `assert` or `with` with their `;`.
+pub(crate) fn highlight_related(
+    db: &dyn SourceDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<Vec<TextRange>> {
+    let parse = db.parse(file_id);
+    let toks = parse.value.syntax_node().token_at_offset(pos);
+    let tok = toks
+        .clone()
+        .right_biased()
+        .filter(|tok| keywords_of(tok).is_some())
+        .or_else(|| toks.left_biased().filter(|tok| keywords_of(tok).is_some()))?;
+    let kinds = keywords_of(&tok)?;
+    // Keywords of nested constructs are inside child nodes, so only direct tokens are matched.
+    let ranges = tok
+        .parent()?
+        .children_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .filter(|t| kinds.contains(&t.kind()))
+        .map(|t| t.text_range())
+        .collect::<Vec<_>>();
+    (ranges.len() > 1).then_some(ranges)
+}
+
+fn keywords_of(tok: &SyntaxToken) -> Option<&'static [SyntaxKind]> {
+    let parent = tok.parent()?.kind();
+    Some(match (tok.kind(), parent) {
+        (T![if] | T![then] | T![else], SyntaxKind::IF_THEN_ELSE) => &[T![if], T![then], T![else]],
+        (T![let] | T![in], SyntaxKind::LET_IN) => &[T![let], T![in]],
+        (T![assert] | T![;], SyntaxKind::ASSERT) => &[T![assert], T![;]],
+        (T![with] | T![;], SyntaxKind::WITH) => &[T![with], T![;]],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = super::highlight_related(&db, file_id, pos).map(|ranges| {
+            let mut src = db.file_content(file_id).to_string();
+            for range in ranges.into_iter().rev() {
+                src.insert(usize::from(range.end()), '>');
+                src.insert(usize::from(range.start()), '<');
+            }
+            src
+        });
+        expect.assert_eq(got.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn if_then_else() {
+        check("$0if a then b else c", expect!["<if> a <then> b <else> c"]);
+        check("if a then$0 b else c", expect!["<if> a <then> b <else> c"]);
+        check(
+            "if a then if b then c else d $0else e",
+            expect!["<if> a <then> if b then c else d <else> e"],
+        );
+        check(
+            "if a then $0if b then c else d else e",
+            expect!["if a then <if> b <then> c <else> d else e"],
+        );
+        check("if a then b$0 else c", expect!["None"]);
+    }
+
+    #[test]
+    fn let_in() {
+        check("$0let a = 1; in a", expect!["<let> a = 1; <in> a"]);
+        check(
+            "let a = let b = 1; in b; $0in a",
+            expect!["<let> a = let b = 1; in b; <in> a"],
+        );
+        check("let a = 1;$0 in a", expect!["None"]);
+    }
+
+    #[test]
+    fn assert_with() {
+        check("$0assert a; b", expect!["<assert> a<;> b"]);
+        check("assert a$0; b", expect!["<assert> a<;> b"]);
+        check("with a; $0with b; c", expect!["with a; <with> b<;> c"]);
+        check("{ a = 1$0; }", expect!["None"]);
+    }
+}
@@ -0,0 +1,363 @@
+use super::imports::{resolve_import_file, static_import_path};
+use super::resolve_attrset;
+use crate::def::{BindingValue, DefDatabase, Expr, ExprId, Literal, ResolveResult};
+use crate::{builtin, FileId};
+use rowan::ast::AstNode;
+use rowan::{TextRange, TextSize};
+use syntax::{ast, SyntaxKind, SyntaxNode, SyntaxToken, T};
+
+/// The information to show when hovering over an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoverResult {
+    /// The range of the hovered expression.
+    pub range: TextRange,
+    /// The content in Markdown.
+    pub markup: String,
+}
+
+pub(crate) fn hover(db: &dyn DefDatabase, file_id: FileId, pos: TextSize) -> Option<HoverResult> {
+    let parse = db.parse(file_id).value;
+    let root = parse.syntax_node();
+    let tok = root.token_at_offset(pos).right_biased()?;
+    let source_map = db.source_map(file_id);
+
+    if let Some(apply) = tok.parent_ancestors().find_map(ast::Apply::cast) {
+        let expr_id = source_map.expr_for_node(apply.syntax())?;
+        if let Some(markup) = hover_import(db, file_id, expr_id) {
+            return Some(HoverResult {
+                range: apply.syntax().text_range(),
+                markup,
+            });
+        }
+    }
+
+    if let Some(ret) = hover_binding_doc(db, file_id, &tok) {
+        return Some(ret);
+    }
+
+    let select = tok.parent_ancestors().find_map(ast::Select::cast)?;
+    // Hovering on the default value is not about the selection.
+    if select
+        .default_expr()
+        .map_or(false, |default| default.syntax().text_range().contains(pos))
+    {
+        return None;
+    }
+    let expr_id = source_map.expr_for_node(select.syntax())?;
+    let markup = hover_select(db, file_id, &root, expr_id)?;
+    Some(HoverResult {
+        range: select.syntax().text_range(),
+        markup,
+    })
+}
+
+/// `a.b.c`, showing the value if the whole path is statically known,
+/// or what `a` resolves to otherwise.
+fn hover_select(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    root: &SyntaxNode,
+    expr_id: ExprId,
+) -> Option<String> {
+    let module = db.module(file_id);
+    let (set, attrpath) = match &module[expr_id] {
+        Expr::Select(set, attrpath, _) => (*set, attrpath),
+        _ => return None,
+    };
+
+    let value = attrpath.iter().try_fold(set, |cur, &attr| {
+        let name = match &module[attr] {
+            Expr::Literal(Literal::String(name)) => name,
+            _ => return None,
+        };
+        let bindings = match &module[resolve_attrset(db, file_id, cur)?] {
+            Expr::Attrset(bindings) => bindings,
+            _ => return None,
+        };
+        match bindings.get(&module, name)? {
+            BindingValue::Expr(e) | BindingValue::Inherit(e) => Some(e),
+            BindingValue::InheritFrom(_) => None,
+        }
+    });
+    if let Some(value) = value {
+        let source_map = db.source_map(file_id);
+        let mut markup = String::new();
+        // Merged attrsets have no single node to show, so they are rendered from the lowered
+        // expression instead.
+        let text = source_map
+            .node_for_expr(value)
+            .map(|ptr| ptr.to_node(root))
+            .filter(|node| ast::Expr::can_cast(node.kind()))
+            .map_or_else(|| module.expr_to_string(value), |node| node.to_string());
+        markup += &format!("```nix\n{}\n```\n", text);
+        let expr = &module[value];
+        if let Some(path) = match expr {
+            Expr::Literal(lit) => lit.path_display(),
+            _ => None,
+        } {
+            markup += &format!("Evaluates to the path `{}`.", path);
+        } else if let Some(kind) = describe_expr(expr) {
+            markup += &format!("Evaluates to {}.", kind);
+        }
+        return (!markup.is_empty()).then_some(markup);
+    }
+
+    let name = match &module[set] {
+        Expr::Reference(name) => name,
+        _ => return None,
+    };
+    let desc = match db.resolve_name(file_id, set)? {
+        ResolveResult::NameDef(def) => match module.name_def_value(def) {
+            Some(value) => {
+                let kind = describe_expr(&module[value])?;
+                format!("`{}` is defined as {}.", name, kind)
+            }
+            None => format!("`{}` is a parameter.", name),
+        },
+        ResolveResult::Builtin(_) => format!("`{}` is a builtin.", name),
+        ResolveResult::WithExprs(_) => format!("`{}` comes from a `with` expression.", name),
+    };
+    Some(desc)
+}
+
+/// A name definition or a reference to it, showing the comments above the binding.
+fn hover_binding_doc(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    tok: &SyntaxToken,
+) -> Option<HoverResult> {
+    if !matches!(tok.kind(), T![or] | SyntaxKind::IDENT) {
+        return None;
+    }
+    let source_map = db.source_map(file_id);
+    let def = tok.parent_ancestors().find_map(|node| {
+        if let Some(def) = source_map.name_def_for_node(&node) {
+            return Some(def);
+        }
+        let expr = source_map.expr_for_node(ast::Ref::cast(node)?.syntax())?;
+        db.resolve_name(file_id, expr)?.as_name_def()
+    })?;
+    let markup = db.binding_doc(file_id, def)?;
+    Some(HoverResult {
+        range: tok.text_range(),
+        markup,
+    })
+}
+
+/// `import ./path` or `builtins.import ./path`, with a static path.
+fn hover_import(db: &dyn DefDatabase, file_id: FileId, expr_id: ExprId) -> Option<String> {
+    let path = static_import_path(db, file_id, expr_id)?;
+    let mut markup = String::new();
+    if let Some(sig) = builtin::get("import").and_then(|info| info.signature) {
+        markup += &format!("```nix\n{}\n```\n", sig);
+    }
+    match resolve_import_file(db, path) {
+        Ok((path, target)) => {
+            markup += &format!("Resolves to `{}`", path);
+            let target_module = db.module(target);
+            if let Some(kind) = describe_expr(&target_module[target_module.entry_expr()]) {
+                markup += &format!(", which evaluates to {}", kind);
+            }
+            markup += ".";
+        }
+        Err(path) => markup += &format!("Resolves to `{}`.", path),
+    }
+    Some(markup)
+}
+
+fn describe_expr(expr: &Expr) -> Option<&'static str> {
+    Some(match expr {
+        Expr::Missing => return None,
+        Expr::Reference(_) => "a variable",
+        Expr::Literal(Literal::Int(_) | Literal::Float(_)) => "a number",
+        Expr::Literal(Literal::String(_)) | Expr::StringInterpolation(_) => "a string",
+        Expr::Literal(Literal::Path(_)) | Expr::PathInterpolation(_) => "a path",
+        Expr::Literal(Literal::Bool(_)) => "a boolean",
+        Expr::Literal(Literal::Null) => "null",
+        Expr::Lambda(..) => "a function",
+        Expr::With(..) => "a `with` expression",
+        Expr::Assert(..) => "an `assert` expression",
+        Expr::IfThenElse(..) => "an `if` expression",
+        Expr::Binary(..) | Expr::Unary(..) | Expr::HasAttr(..) => "an operator expression",
+        Expr::Apply(..) => "a function application",
+        Expr::Select(..) => "an attribute selection",
+        Expr::List(_) => "a list",
+        Expr::LetIn(..) => "a `let` expression",
+        Expr::Attrset(_) | Expr::LetAttrset(_) => "an attribute set",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [pos] = f.markers().unwrap();
+        let ret = super::hover(&db, pos.file_id, pos.value).expect("No hover");
+        let src = &f[pos.file_id];
+        let text = &src[ret.range];
+        expect.assert_eq(&format!("{}\n{}", text, ret.markup));
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [pos] = f.markers().unwrap();
+        assert_eq!(super::hover(&db, pos.file_id, pos.value), None);
+    }
+
+    #[test]
+    fn import_file() {
+        check(
+            "
+#- /default.nix
+$0import ./lib.nix
+#- /lib.nix
+{ }
+            ",
+            expect![[r#"
+                import ./lib.nix
+                ```nix
+                import :: Path -> a
+                ```
+                Resolves to `/lib.nix`, which evaluates to an attribute set."#]],
+        );
+        check(
+            "
+#- /foo/bar.nix
+builtins.import ../.$0
+#- /default.nix
+{ x }: x
+            ",
+            expect![[r#"
+                builtins.import ../.
+                ```nix
+                import :: Path -> a
+                ```
+                Resolves to `/default.nix`, which evaluates to a function."#]],
+        );
+    }
+
+    #[test]
+    fn import_unknown_file() {
+        check(
+            "import $0/etc/nixos/configuration.nix",
+            expect![[r#"
+                import /etc/nixos/configuration.nix
+                ```nix
+                import :: Path -> a
+                ```
+                Resolves to `/etc/nixos/configuration.nix`."#]],
+        );
+    }
+
+    #[test]
+    fn not_static_import() {
+        check_no("import $0<nixpkgs>");
+        check_no("import $0./${a}.nix");
+        check_no("let import = x: x; in import $0./a.nix");
+        check_no("$0f ./a.nix");
+    }
+
+    #[test]
+    fn select() {
+        check(
+            "let cfg = { enable = true; }; in cfg.$0enable",
+            expect![[r#"
+                cfg.enable
+                ```nix
+                true
+                ```
+                Evaluates to a boolean."#]],
+        );
+        check(
+            "let a = { b.c = 1; }; in a.b$0.c",
+            expect![[r#"
+                a.b.c
+                ```nix
+                1
+                ```
+                Evaluates to a number."#]],
+        );
+        check(
+            "let a = { b.c = 1; b.d = x: x; }; in a.b$0",
+            expect![[r#"
+                a.b
+                ```nix
+                { c = 1; d = x: x; }
+                ```
+                Evaluates to an attribute set."#]],
+        );
+        check(
+            "let a = { b = x: x; }; in a.$0b or null",
+            expect![[r#"
+                a.b or null
+                ```nix
+                x: x
+                ```
+                Evaluates to a function."#]],
+        );
+        check(
+            "let a = { b = <nixpkgs/./lib>; }; in a.$0b",
+            expect![[r#"
+                a.b
+                ```nix
+                <nixpkgs/./lib>
+                ```
+                Evaluates to the path `<nixpkgs>/lib`."#]],
+        );
+    }
+
+    #[test]
+    fn binding_doc() {
+        check(
+            "
+let
+  # greet
+  greet = name: name;
+in $0greet 1
+            ",
+            expect![[r#"
+                greet
+                greet"#]],
+        );
+        check(
+            "
+rec {
+  # Say hello.
+  $0greet = name: name;
+}
+            ",
+            expect![[r#"
+                greet
+                Say hello."#]],
+        );
+        check_no("let greet = name: name; in $0greet 1");
+    }
+
+    #[test]
+    fn select_fallback() {
+        check(
+            "let a = { }; in a.${b}$0",
+            expect![[r#"
+                a.${b}
+                `a` is defined as an attribute set."#]],
+        );
+        check(
+            "a: a.b$0",
+            expect![[r#"
+                a.b
+                `a` is a parameter."#]],
+        );
+        check(
+            "builtins.$0foo",
+            expect![[r#"
+                builtins.foo
+                `builtins` is a builtin."#]],
+        );
+        check_no("let a = { }; in a.b or $01");
+    }
+}
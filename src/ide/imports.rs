@@ -0,0 +1,302 @@
+use super::MAX_RESOLVE_DEPTH;
+use crate::def::{DefDatabase, Expr, ExprId, Literal, PathAnchor, ResolveResult};
+use crate::{FileId, VfsPath};
+use rowan::ast::AstNode;
+use rowan::{TextRange, TextSize};
+use syntax::ast::{self, LiteralKind};
+use syntax::{SyntaxKind, T};
+
+/// Files referred by static path literals in a file, eg. `import ./lib.nix` or `callPackage ./.`.
+pub(crate) fn imported_files(db: &dyn DefDatabase, file_id: FileId) -> Vec<FileId> {
+    let module = db.module(file_id);
+    let mut files = module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            Expr::Literal(Literal::Path(path)) => path.resolve(db),
+            _ => None,
+        })
+        .filter_map(|path| resolve_import_file(db, path).ok())
+        .map(|(_, file)| file)
+        .collect::<Vec<_>>();
+    files.sort_unstable_by_key(|file| file.0);
+    files.dedup();
+    files
+}
+
+/// Files in the file set which import the given file, the reverse of [`imported_files`].
+pub(crate) fn importing_files(db: &dyn DefDatabase, file_id: FileId) -> Vec<FileId> {
+    let file_set = db.file_set();
+    let mut files = file_set
+        .iter()
+        .map(|(file, _)| file)
+        .filter(|&file| file != file_id && imported_files(db, file).contains(&file_id))
+        .collect::<Vec<_>>();
+    files.sort_unstable_by_key(|file| file.0);
+    files
+}
+
+/// Whether the expression is `import ./path` of the target file, or a name defined as it.
+pub(crate) fn is_import_of(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    expr_id: ExprId,
+    target: FileId,
+) -> bool {
+    let module = db.module(file_id);
+    let mut expr_id = expr_id;
+    for _ in 0..MAX_RESOLVE_DEPTH {
+        let def = match db.resolve_name(file_id, expr_id) {
+            Some(ResolveResult::NameDef(def)) => def,
+            _ => break,
+        };
+        match module.name_def_value(def) {
+            Some(value) => expr_id = value,
+            None => return false,
+        }
+    }
+    static_import_path(db, file_id, expr_id)
+        .and_then(|path| resolve_import_file(db, path).ok())
+        .map_or(false, |(_, file)| file == target)
+}
+
+/// The path of `import ./path` or `builtins.import ./path`, if the path is static.
+pub(crate) fn static_import_path(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    expr_id: ExprId,
+) -> Option<VfsPath> {
+    let module = db.module(file_id);
+    match &module[import_arg(db, file_id, expr_id)?] {
+        Expr::Literal(Literal::Path(path)) => path.resolve(db),
+        _ => None,
+    }
+}
+
+/// Whether the expression is `import <nixpkgs> { ... }`, the instantiated package set,
+/// or a name defined as it.
+pub(crate) fn is_nixpkgs_import(db: &dyn DefDatabase, file_id: FileId, expr_id: ExprId) -> bool {
+    let module = db.module(file_id);
+    let mut expr_id = expr_id;
+    for _ in 0..MAX_RESOLVE_DEPTH {
+        let def = match db.resolve_name(file_id, expr_id) {
+            Some(ResolveResult::NameDef(def)) => def,
+            _ => break,
+        };
+        match module.name_def_value(def) {
+            Some(value) => expr_id = value,
+            None => return false,
+        }
+    }
+    let import = match module[expr_id] {
+        Expr::Apply(import, _) => import,
+        _ => return false,
+    };
+    import_arg(db, file_id, import).map_or(false, |arg| match &module[arg] {
+        Expr::Literal(Literal::Path(path)) => {
+            path.anchor == PathAnchor::Search("nixpkgs".into())
+                && path.supers == 0
+                && path.segments().next().is_none()
+        }
+        _ => false,
+    })
+}
+
+/// The argument of `import arg` or `builtins.import arg`.
+fn import_arg(db: &dyn DefDatabase, file_id: FileId, expr_id: ExprId) -> Option<ExprId> {
+    let module = db.module(file_id);
+    let (func, arg) = match &module[expr_id] {
+        &Expr::Apply(func, arg) => (func, arg),
+        _ => return None,
+    };
+    let is_import = match &module[func] {
+        Expr::Reference(_) => {
+            db.resolve_name(file_id, func) == Some(ResolveResult::Builtin("import"))
+        }
+        Expr::Select(set, attrpath, None) => {
+            matches!(&module[*set], Expr::Reference(name) if name == "builtins")
+                && db.resolve_name(file_id, *set) == Some(ResolveResult::Builtin("builtins"))
+                && matches!(
+                    &**attrpath,
+                    [attr] if module[*attr] == Expr::Literal(Literal::String("import".into()))
+                )
+        }
+        _ => false,
+    };
+    is_import.then_some(arg)
+}
+
+/// The search path literal under the cursor without angle brackets, eg. `nixpkgs/lib`.
+/// It depends on the environment, so it is resolved by the caller.
+pub(crate) fn search_path_at(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<String> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).right_biased()?;
+    let lit = tok.parent().and_then(ast::Literal::cast)?;
+    if lit.kind()? != LiteralKind::SearchPath {
+        return None;
+    }
+    let text = tok.text();
+    Some(text[1..text.len() - 1].into())
+}
+
+/// A path literal being typed, whose last segment is completed by the caller with entries of
+/// the directory on the file system, eg. `./src/ma` in `import ./src/ma`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPrefix {
+    /// The directory to list. It starts with `~` for home-relative paths.
+    pub dir: String,
+    /// The typed part of the last segment.
+    pub prefix: String,
+    /// The range of the last segment, to be replaced by the entry name.
+    pub source_range: TextRange,
+}
+
+/// The path literal under the cursor, or an incomplete one like `./` which is not lexed as a path.
+/// Search paths and paths with interpolations are not supported.
+pub(crate) fn path_prefix_at(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<PathPrefix> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).left_biased()?;
+    let src = db.file_content(file_id);
+    let (before, after) = match tok.kind() {
+        SyntaxKind::PATH => tok
+            .text()
+            .split_at(usize::from(pos - tok.text_range().start())),
+        T![/] => {
+            let before = &src[..usize::from(pos)];
+            let start = ["./", "../", "~/"].into_iter().find(|start| {
+                before.strip_suffix(start).map_or(false, |rest| {
+                    !rest.ends_with(|c: char| c.is_ascii_alphanumeric() || "._+-~".contains(c))
+                })
+            })?;
+            (start, "")
+        }
+        _ => return None,
+    };
+
+    let (dir_part, prefix) = before.rsplit_once('/')?;
+    let dir = if let Some(rest) = dir_part.strip_prefix('~') {
+        format!("~{}/", rest)
+    } else {
+        let mut dir = if before.starts_with('/') {
+            VfsPath::new("/")?
+        } else {
+            db.file_set().path_for_file(file_id)?.parent()?
+        };
+        for seg in dir_part.split('/') {
+            dir.push_segment(seg);
+        }
+        dir.to_string()
+    };
+    let rest_len = after.find('/').unwrap_or(after.len());
+    Some(PathPrefix {
+        dir,
+        prefix: prefix.into(),
+        source_range: TextRange::new(
+            pos - TextSize::of(prefix),
+            pos + TextSize::try_from(rest_len).ok()?,
+        ),
+    })
+}
+
+/// Find the imported file. Importing a directory imports the `default.nix` inside it.
+pub(crate) fn resolve_import_file(
+    db: &dyn DefDatabase,
+    path: VfsPath,
+) -> Result<(VfsPath, FileId), VfsPath> {
+    let file_set = db.file_set();
+    if let Some(file) = file_set.file_for_path(&path) {
+        return Ok((path, file));
+    }
+    let mut default_path = path.clone();
+    default_path.push_segment("default.nix");
+    match file_set.file_for_path(&default_path) {
+        Some(file) => Ok((default_path, file)),
+        None => Err(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use crate::FileId;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, _) = TestDB::from_fixture(fixture).unwrap();
+        let file_set = db.file_set();
+        let got = super::imported_files(&db, FileId(0))
+            .into_iter()
+            .map(|file| file_set.path_for_file(file).unwrap().to_string())
+            .collect::<Vec<_>>();
+        expect.assert_debug_eq(&got);
+    }
+
+    #[test]
+    fn search_path() {
+        let check = |fixture: &str| {
+            let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+            super::search_path_at(&db, file_id, pos)
+        };
+        assert_eq!(check("import $0<nixpkgs/lib>"), Some("nixpkgs/lib".into()));
+        assert_eq!(check("$0import <nixpkgs>"), None);
+        assert_eq!(check("import $0./nixpkgs"), None);
+    }
+
+    #[test]
+    fn path_prefix() {
+        let check = |fixture: &str| {
+            let (db, f) =
+                TestDB::from_fixture(&format!("#- /foo/bar/default.nix\n{}", fixture)).unwrap();
+            let [pos] = f.markers().unwrap();
+            let ret = super::path_prefix_at(&db, pos.file_id, pos.value)?;
+            let src = db.file_content(pos.file_id);
+            Some(format!(
+                "{} {:?} {:?}",
+                ret.dir, ret.prefix, &src[ret.source_range]
+            ))
+        };
+        assert_eq!(check("import ./$0").as_deref(), Some(r#"/foo/bar "" """#));
+        assert_eq!(check("../$0").as_deref(), Some(r#"/foo "" """#));
+        assert_eq!(check("~/$0").as_deref(), Some(r#"~/ "" """#));
+        assert_eq!(
+            check("./src/ma$0in.nix").as_deref(),
+            Some(r#"/foo/bar/src "ma" "main.nix""#),
+        );
+        assert_eq!(check("../a/../b$0/c").as_deref(), Some(r#"/foo "b" "b""#),);
+        assert_eq!(check("/et$0c").as_deref(), Some(r#"/ "et" "etc""#));
+        assert_eq!(check("~/a/b$0").as_deref(), Some(r#"~/a/ "b" "b""#));
+        assert_eq!(check("1 / $0"), None);
+        assert_eq!(check("a./$0"), None);
+        assert_eq!(check(r#""./$0""#), None);
+        assert_eq!(check("<nixpkgs/li$0b>"), None);
+    }
+
+    #[test]
+    fn imported_files() {
+        check(
+            "
+#- /default.nix
+{ a = import ./lib.nix; b = callPackage ./pkgs { }; c = ./lib.nix; d = ./missing.nix; }
+#- /lib.nix
+{ }
+#- /pkgs/default.nix
+{ }
+            ",
+            expect![[r#"
+                [
+                    "/lib.nix",
+                    "/pkgs/default.nix",
+                ]
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use super::rename::{is_inherited, name_def_at, name_range};
+use crate::def::DefDatabase;
+use crate::FileId;
+use syntax::{TextRange, TextSize};
+
+/// The ranges of the name definition at the position and all its references, or of the
+/// definition referenced by the name at the position, which can be edited simultaneously.
+///
+/// Returns `None` if any of them is not a plain identifier, eg. a quoted attribute name or an
+/// inherited name, since editing it would also change an attribute name or another reference.
+pub(crate) fn linked_editing_ranges(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<Vec<TextRange>> {
+    let def = name_def_at(db, file_id, pos)?;
+    let parse = db.parse(file_id).value;
+    let root = parse.syntax_node();
+    let src = db.file_content(file_id);
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+    let ref_map = db.name_reference_map(file_id);
+    let name = &module[def].name;
+
+    let def_node = source_map.node_for_name_def(def)?.to_node(&root);
+    let refs = ref_map.def_references(def).unwrap_or_default();
+    let mut ranges = Vec::with_capacity(1 + refs.len());
+    ranges.push(name_range(&def_node));
+    for &expr in refs.iter() {
+        let node = source_map.node_for_expr(expr)?.to_node(&root);
+        if is_inherited(&node) {
+            return None;
+        }
+        ranges.push(name_range(&node));
+    }
+    if is_inherited(&def_node) || ranges.iter().any(|&range| src[range] != **name) {
+        return None;
+    }
+    ranges.sort_by_key(|range| range.start());
+    Some(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = super::linked_editing_ranges(&db, file_id, pos).map(|ranges| {
+            let mut src = db.file_content(file_id).to_string();
+            for range in ranges.into_iter().rev() {
+                src.insert(usize::from(range.end()), '>');
+                src.insert(usize::from(range.start()), '<');
+            }
+            src
+        });
+        expect.assert_eq(got.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn lambda_param() {
+        check("$0a: a + a", expect!["<a>: <a> + <a>"]);
+        check("a: a + $0a", expect!["<a>: <a> + <a>"]);
+        check("a: $0b: a", expect!["a: <b>: a"]);
+        check("{ a, b ? a }@c: $0c", expect!["{ a, b ? a }@<c>: <c>"]);
+    }
+
+    #[test]
+    fn bindings() {
+        check(
+            "let $0a = 1; b = a; in a + b",
+            expect!["let <a> = 1; b = <a>; in <a> + b"],
+        );
+        check("rec { \"a\" = 1; b = $0a; }", expect!["None"]);
+        check("let $0a = 1; in { inherit a; }", expect!["None"]);
+        check("let a = 1; in $0b", expect!["None"]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::base::SourceDatabase;
+use crate::FileId;
+use rowan::Direction;
+use syntax::{SyntaxKind, SyntaxToken, TextRange, TextSize, T};
+
+/// The range from `{`, `[` or `(` to its matching delimiter, of the innermost attrset, pattern,
+/// list or parentheses strictly containing the position.
+/// The braces of interpolations `${ }` are skipped since they are not in these nodes.
+pub(crate) fn enclosing_delimiter(
+    db: &dyn SourceDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<TextRange> {
+    let parse = db.parse(file_id);
+    let tok = parse
+        .value
+        .syntax_node()
+        .token_at_offset(pos)
+        .left_biased()?;
+    tok.parent_ancestors()
+        .filter(|node| {
+            matches!(
+                node.kind(),
+                SyntaxKind::ATTR_SET | SyntaxKind::PAT | SyntaxKind::LIST | SyntaxKind::PAREN
+            )
+        })
+        .find_map(|node| {
+            let mut toks = node
+                .children_with_tokens()
+                .filter_map(|elem| elem.into_token());
+            let open = toks.find(|tok| matches!(tok.kind(), T!['{'] | T!['['] | T!['(']))?;
+            // The closing delimiter may be missing during typing.
+            let end = toks
+                .filter(|tok| matches!(tok.kind(), T!['}'] | T![']'] | T![')']))
+                .last()
+                .map_or(node.text_range().end(), |tok| tok.text_range().end());
+            let range = TextRange::new(open.text_range().start(), end);
+            (range.start() < pos && pos < range.end()).then_some(range)
+        })
+}
+
+/// The position of the delimiter matching the one at the position, preferring the one right to
+/// it. Pairs are `{ }`, `[ ]`, `( )`, `${ }` of interpolations and `'' ''` of indented strings.
+pub(crate) fn matching_brace(
+    db: &dyn SourceDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<TextSize> {
+    let parse = db.parse(file_id);
+    let toks = parse.value.syntax_node().token_at_offset(pos);
+    let is_delimiter = |tok: &SyntaxToken| {
+        matches!(
+            tok.kind(),
+            T!['{'] | T!['}'] | T!["${"] | T!['['] | T![']'] | T!['('] | T![')'] | T!["''"]
+        )
+    };
+    let tok = toks
+        .clone()
+        .right_biased()
+        .filter(is_delimiter)
+        .or_else(|| toks.left_biased().filter(is_delimiter))?;
+    // Nested delimiters are inside child nodes, so the matching one is a sibling token.
+    let matched = match tok.kind() {
+        T!['{'] | T!["${"] => find_sibling(&tok, Direction::Next, &[T!['}']]),
+        T!['['] => find_sibling(&tok, Direction::Next, &[T![']']]),
+        T!['('] => find_sibling(&tok, Direction::Next, &[T![')']]),
+        T!['}'] => find_sibling(&tok, Direction::Prev, &[T!['{'], T!["${"]]),
+        T![']'] => find_sibling(&tok, Direction::Prev, &[T!['[']]),
+        T![')'] => find_sibling(&tok, Direction::Prev, &[T!['(']]),
+        _ => match find_sibling(&tok, Direction::Prev, &[T!["''"]]) {
+            Some(open) => Some(open),
+            None => find_sibling(&tok, Direction::Next, &[T!["''"]]),
+        },
+    }?;
+    Some(matched.text_range().start())
+}
+
+fn find_sibling(tok: &SyntaxToken, dir: Direction, kinds: &[SyntaxKind]) -> Option<SyntaxToken> {
+    tok.siblings_with_tokens(dir)
+        .skip(1)
+        .filter_map(|elem| elem.into_token())
+        .find(|t| kinds.contains(&t.kind()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = super::enclosing_delimiter(&db, file_id, pos)
+            .map(|range| db.file_content(file_id)[range].to_owned());
+        expect.assert_eq(got.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn nested_list() {
+        check("[ 1 [ 2 $03 ] 4 ]", expect!["[ 2 3 ]"]);
+        check("[ 1 [$0 2 3 ] 4 ]", expect!["[ 2 3 ]"]);
+        check("[ 1 $0[ 2 3 ] 4 ]", expect!["[ 1 [ 2 3 ] 4 ]"]);
+        check("[ 1 [ 2 3 ]$0 4 ]", expect!["[ 1 [ 2 3 ] 4 ]"]);
+    }
+
+    #[test]
+    fn kinds() {
+        check("rec { a = $0b; }", expect!["{ a = b; }"]);
+        check(r#"{ a = "${$0b}"; }"#, expect![[r#"{ a = "${b}"; }"#]]);
+        check("{ a, $0b }: a", expect!["{ a, b }"]);
+        check("f (g $0x) y", expect!["(g x)"]);
+        check("{ inherit (a$0) b; }", expect!["(a)"]);
+        check("a$0 + b", expect!["None"]);
+        check("[ 1 $0 2", expect!["[ 1  2"]);
+    }
+
+    #[track_caller]
+    fn check_brace(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = super::matching_brace(&db, file_id, pos).map(|matched| {
+            let mut src = db.file_content(file_id).to_string();
+            src.insert_str(matched.into(), "$1");
+            src
+        });
+        expect.assert_eq(got.as_deref().unwrap_or("None"));
+    }
+
+    #[test]
+    fn matching_brace() {
+        check_brace(
+            "{ a = { b = [ 1 ]; }; $0}",
+            expect!["$1{ a = { b = [ 1 ]; }; }"],
+        );
+        check_brace(
+            "{ a = $0{ b = [ 1 ]; }; }",
+            expect!["{ a = { b = [ 1 ]; $1}; }"],
+        );
+        check_brace(
+            "{ a = { b = [ 1 ]$0; }; }",
+            expect!["{ a = { b = $1[ 1 ]; }; }"],
+        );
+        check_brace("f ($0(g) x)", expect!["f ((g$1) x)"]);
+        check_brace(r#"{ a = "${x}$0"; }"#, expect![[r#"{ a = "$1${x}"; }"#]]);
+        check_brace(
+            r#"{ a = "$0${{ }}"; }"#,
+            expect![[r#"{ a = "${{ }$1}"; }"#]],
+        );
+        check_brace("$0'' a ${b} ''", expect!["'' a ${b} $1''"]);
+        check_brace("'' a ${b} $0''", expect!["$1'' a ${b} ''"]);
+        check_brace("{ a = $01; }", expect!["None"]);
+    }
+}
@@ -1,22 +1,72 @@
+mod assists;
+mod call_hierarchy;
 mod completion;
 mod diagnostics;
+mod fuzzy;
 mod goto_definition;
+mod highlight_related;
+mod hover;
+mod imports;
+mod linked_editing;
+mod matching_delimiter;
+mod on_type_formatting;
 mod references;
+mod rename;
+mod runnables;
+mod signature_help;
+mod symbol_hierarchy;
+mod unused_bindings;
 
-use crate::base::SourceDatabaseStorage;
-use crate::def::DefDatabaseStorage;
-use crate::{Change, Diagnostic, FileId, FilePos, FileRange};
-use rowan::TextRange;
+use crate::base::{SourceDatabase, SourceDatabaseStorage};
+use crate::def::{DefDatabase, DefDatabaseStorage, Expr, ExprId, ResolveResult};
+use crate::{Change, Diagnostic, FileId, FilePos, FileRange, TextEdit, VfsPath};
+use rowan::ast::AstNode;
+use rowan::{NodeOrToken, TextRange, TextSize};
 use salsa::{Cancelled, Database, Durability, ParallelDatabase};
+use smol_str::SmolStr;
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use syntax::{ast, SyntaxNode};
 
+pub use assists::Assist;
+pub use call_hierarchy::CallHierarchyItem;
 pub use completion::{CompletionItem, CompletionItemKind};
+pub use fuzzy::{fuzzy_match, Score};
+pub use hover::HoverResult;
+pub use imports::PathPrefix;
+pub use runnables::Runnable;
+pub use signature_help::SignatureHelp;
+pub use symbol_hierarchy::{SymbolKind, SymbolTree};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NavigationTarget {
     pub file_id: FileId,
     pub full_range: TextRange,
     pub focus_range: TextRange,
+    /// The attrpath of enclosing bindings for breadcrumbs, eg. `a.b` for `c` in
+    /// `{ a.b = { c = 1; }; }`.
+    pub container_name: Option<SmolStr>,
+}
+
+impl NavigationTarget {
+    /// A target whose full range is the same as the focus range.
+    pub fn from_file_range(frange: FileRange) -> Self {
+        Self {
+            file_id: frange.file_id,
+            full_range: frange.value,
+            focus_range: frange.value,
+            container_name: None,
+        }
+    }
+
+    pub fn focus_file_range(&self) -> FileRange {
+        FileRange::new(self.file_id, self.focus_range)
+    }
+
+    pub fn full_file_range(&self) -> FileRange {
+        FileRange::new(self.file_id, self.full_range)
+    }
 }
 
 pub type Cancellable<T> = Result<T, Cancelled>;
@@ -43,9 +93,24 @@ impl fmt::Debug for RootDatabase {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct AnalysisHost {
     db: RootDatabase,
+    /// The number of changes applied, shared with snapshots to detect staleness.
+    revision: Arc<AtomicU64>,
+}
+
+impl Default for AnalysisHost {
+    fn default() -> Self {
+        let mut db = RootDatabase::default();
+        db.set_file_set_with_durability(Arc::default(), Durability::MEDIUM);
+        db.set_nixpkgs_attr_names_with_durability(Arc::default(), Durability::HIGH);
+        db.set_max_file_size_with_durability(usize::MAX, Durability::HIGH);
+        Self {
+            db,
+            revision: Arc::default(),
+        }
+    }
 }
 
 impl AnalysisHost {
@@ -56,6 +121,8 @@ impl AnalysisHost {
     pub fn snapshot(&self) -> Analysis {
         Analysis {
             db: self.db.snapshot(),
+            revision: self.revision.load(Ordering::SeqCst),
+            current_revision: Arc::clone(&self.revision),
         }
     }
 
@@ -64,6 +131,9 @@ impl AnalysisHost {
     }
 
     pub fn apply_change(&mut self, change: Change) {
+        // Bump the revision first, so that running snapshots can observe it and stop early,
+        // since the cancellation blocks until all snapshots are dropped.
+        self.revision.fetch_add(1, Ordering::SeqCst);
         self.request_cancellation();
         change.apply(&mut self.db);
     }
@@ -72,9 +142,22 @@ impl AnalysisHost {
 #[derive(Debug)]
 pub struct Analysis {
     db: salsa::Snapshot<RootDatabase>,
+    revision: u64,
+    current_revision: Arc<AtomicU64>,
 }
 
 impl Analysis {
+    /// The revision of the host when this snapshot was taken.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether changes are applied to the host after this snapshot was taken.
+    /// Results computed from a stale snapshot may not match the current files.
+    pub fn is_stale(&self) -> bool {
+        self.current_revision.load(Ordering::SeqCst) != self.revision
+    }
+
     fn with_db<F, T>(&self, f: F) -> Cancellable<T>
     where
         F: FnOnce(&RootDatabase) -> T + std::panic::UnwindSafe,
@@ -82,19 +165,341 @@ impl Analysis {
         Cancelled::catch(|| f(&self.db))
     }
 
+    pub fn syntax_diagnostics(&self, file: FileId) -> Cancellable<Vec<Diagnostic>> {
+        self.with_db(|db| diagnostics::syntax_diagnostics(db, file))
+    }
+
     pub fn diagnostics(&self, file: FileId) -> Cancellable<Vec<Diagnostic>> {
         self.with_db(|db| diagnostics::diagnostics(db, file))
     }
 
+    /// Definitions of the name at the position. A name bound by `inherit (m) f;` lands on the
+    /// inherit site, with the source `m` as a secondary target, and does not resolve into `m`.
+    /// A name provided by `with` lands on the attribute of the environment if it is statically
+    /// known, or on the `with` expressions otherwise.
     pub fn goto_definition(&self, pos: FilePos) -> Cancellable<Option<Vec<NavigationTarget>>> {
         self.with_db(|db| goto_definition::goto_definition(db, pos.file_id, pos.value))
     }
 
-    pub fn completions(&self, pos: FilePos) -> Cancellable<Option<Vec<CompletionItem>>> {
-        self.with_db(|db| completion::completions(db, pos.file_id, pos.value))
+    /// Same as `goto_definition`, except that a name provided by `with` always lands on the
+    /// `with` expressions.
+    pub fn goto_declaration(&self, pos: FilePos) -> Cancellable<Option<Vec<NavigationTarget>>> {
+        self.with_db(|db| goto_definition::goto_declaration(db, pos.file_id, pos.value))
+    }
+
+    pub fn goto_type_definition(&self, pos: FilePos) -> Cancellable<Option<Vec<NavigationTarget>>> {
+        self.with_db(|db| goto_definition::goto_type_definition(db, pos.file_id, pos.value))
+    }
+
+    pub fn completions(
+        &self,
+        pos: FilePos,
+        trigger: Option<char>,
+    ) -> Cancellable<Option<Vec<CompletionItem>>> {
+        self.with_db(|db| completion::completions(db, pos.file_id, pos.value, trigger))
     }
 
     pub fn references(&self, pos: FilePos) -> Cancellable<Option<Vec<FileRange>>> {
         self.with_db(|db| references::references(db, pos.file_id, pos.value))
     }
+
+    /// Edits to rename the definition at the position and all its references, or `None` if it
+    /// cannot be renamed to `new_name` safely.
+    pub fn rename(&self, pos: FilePos, new_name: &str) -> Cancellable<Option<Vec<TextEdit>>> {
+        self.with_db(|db| rename::rename(db, pos.file_id, pos.value, new_name))
+    }
+
+    /// The ranges of the name at the position, its definition and all its references,
+    /// which can be edited together.
+    pub fn linked_editing_ranges(&self, pos: FilePos) -> Cancellable<Option<Vec<TextRange>>> {
+        self.with_db(|db| linked_editing::linked_editing_ranges(db, pos.file_id, pos.value))
+    }
+
+    pub fn hover(&self, pos: FilePos) -> Cancellable<Option<HoverResult>> {
+        self.with_db(|db| hover::hover(db, pos.file_id, pos.value))
+    }
+
+    pub fn signature_help(&self, pos: FilePos) -> Cancellable<Option<SignatureHelp>> {
+        self.with_db(|db| signature_help::signature_help(db, pos.file_id, pos.value))
+    }
+
+    pub fn prepare_call_hierarchy(&self, pos: FilePos) -> Cancellable<Option<CallHierarchyItem>> {
+        self.with_db(|db| call_hierarchy::prepare_call_hierarchy(db, pos.file_id, pos.value))
+    }
+
+    /// The range of the nearest enclosing `{ }`, `[ ]` or `( )` around the position,
+    /// including the delimiters themselves.
+    pub fn expand_selection_to_matching_delimiter(
+        &self,
+        pos: FilePos,
+    ) -> Cancellable<Option<TextRange>> {
+        self.with_db(|db| matching_delimiter::enclosing_delimiter(db, pos.file_id, pos.value))
+    }
+
+    /// The position of the delimiter matching the one at the position.
+    pub fn matching_brace(&self, pos: FilePos) -> Cancellable<Option<TextSize>> {
+        self.with_db(|db| matching_delimiter::matching_brace(db, pos.file_id, pos.value))
+    }
+
+    /// The keywords of the same `if`, `let`, `assert` or `with` as the keyword at the position.
+    pub fn highlight_related(&self, pos: FilePos) -> Cancellable<Option<Vec<TextRange>>> {
+        self.with_db(|db| highlight_related::highlight_related(db, pos.file_id, pos.value))
+    }
+
+    /// Re-indent the line at the position after typing a newline or `;`.
+    /// `indent_unit` is the whitespace of one indentation level.
+    pub fn on_type_formatting(
+        &self,
+        pos: FilePos,
+        indent_unit: &str,
+    ) -> Cancellable<Option<TextEdit>> {
+        self.with_db(|db| {
+            on_type_formatting::on_type_formatting(db, pos.file_id, pos.value, indent_unit)
+        })
+    }
+
+    pub fn runnables(&self, file: FileId) -> Cancellable<Vec<Runnable>> {
+        self.with_db(|db| runnables::runnables(db, file))
+    }
+
+    pub fn symbol_hierarchy(&self, file: FileId) -> Cancellable<Vec<SymbolTree>> {
+        self.with_db(|db| symbol_hierarchy::symbol_hierarchy(db, file))
+    }
+
+    /// A textual dump of the syntax tree of a file, or of the smallest node covering `range`.
+    /// It is for bug reports, and its format is not stable.
+    pub fn syntax_tree(&self, file: FileId, range: Option<TextRange>) -> Cancellable<String> {
+        self.with_db(|db| {
+            let root = db.parse(file).value.syntax_node();
+            let node = match range {
+                Some(range) if root.text_range().contains_range(range) => {
+                    match root.covering_element(range) {
+                        NodeOrToken::Node(node) => node,
+                        NodeOrToken::Token(tok) => tok.parent().unwrap_or(root),
+                    }
+                }
+                _ => root,
+            };
+            format!("{:#?}", node)
+        })
+    }
+
+    /// The parsed syntax tree of a file. For debugging only.
+    #[cfg(any(test, feature = "dev"))]
+    pub fn file_syntax(&self, file: FileId) -> Cancellable<syntax::Parse> {
+        self.with_db(|db| db.parse(file).value)
+    }
+
+    /// The memoized lowered module of a file. For debugging only.
+    #[cfg(any(test, feature = "dev"))]
+    pub fn module(&self, file: FileId) -> Cancellable<Arc<crate::def::Module>> {
+        self.with_db(|db| db.module(file))
+    }
+
+    pub fn file_for_path(&self, path: &VfsPath) -> Cancellable<Option<FileId>> {
+        self.with_db(|db| db.file_set().file_for_path(path))
+    }
+
+    pub fn search_path_at(&self, pos: FilePos) -> Cancellable<Option<String>> {
+        self.with_db(|db| imports::search_path_at(db, pos.file_id, pos.value))
+    }
+
+    pub fn path_prefix_at(&self, pos: FilePos) -> Cancellable<Option<PathPrefix>> {
+        self.with_db(|db| imports::path_prefix_at(db, pos.file_id, pos.value))
+    }
+
+    pub fn imported_files(&self, file: FileId) -> Cancellable<Vec<FileId>> {
+        self.with_db(|db| imports::imported_files(db, file))
+    }
+
+    pub fn is_flake_file(&self, file: FileId) -> Cancellable<bool> {
+        self.with_db(|db| is_flake_file(db, file))
+    }
+
+    pub fn remove_unused_bindings(&self, file: FileId) -> Cancellable<Vec<TextEdit>> {
+        self.with_db(|db| unused_bindings::remove_unused_bindings(db, file))
+    }
+
+    pub fn assists(&self, frange: FileRange) -> Cancellable<Vec<Assist>> {
+        self.with_db(|db| assists::assists(db, frange))
+    }
+}
+
+pub(crate) fn is_flake_file(db: &dyn SourceDatabase, file_id: FileId) -> bool {
+    db.file_set()
+        .path_for_file(file_id)
+        .map_or(false, |path| path.file_name() == Some("flake.nix"))
+}
+
+/// The attrpath of bindings enclosing a node, joined by `.`.
+/// For a key, the attrs before it in the same attrpath are also included.
+pub(crate) fn container_name(node: &SyntaxNode) -> Option<SmolStr> {
+    let mut segments = Vec::new();
+    for entry in node.ancestors().filter_map(ast::AttrpathValue::cast) {
+        let attrs = match entry.attrpath() {
+            Some(path) => path.attrs().collect::<Vec<_>>(),
+            None => continue,
+        };
+        let end = attrs
+            .iter()
+            .position(|attr| attr.syntax().text_range().contains_range(node.text_range()))
+            .unwrap_or(attrs.len());
+        for attr in attrs[..end].iter().rev() {
+            segments.push(attr.syntax().text().to_string().trim_end().to_owned());
+        }
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    segments.reverse();
+    Some(segments.join(".").into())
+}
+
+/// Guard against cyclic definitions like `let a = a; in a.b`.
+const MAX_RESOLVE_DEPTH: usize = 16;
+
+/// Follow references to find the attrset literal an expression evaluates to.
+pub(crate) fn resolve_attrset(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    expr: ExprId,
+) -> Option<ExprId> {
+    let module = db.module(file_id);
+    let mut expr = expr;
+    for _ in 0..MAX_RESOLVE_DEPTH {
+        match &module[expr] {
+            Expr::Attrset(_) => return Some(expr),
+            Expr::Reference(_) => match db.resolve_name(file_id, expr)? {
+                ResolveResult::NameDef(def) => expr = module.name_def_value(def)?,
+                ResolveResult::Builtin(_) | ResolveResult::WithExprs(_) => return None,
+            },
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalysisHost, NavigationTarget};
+    use crate::def::Expr;
+    use crate::{Change, FileId, FileRange, VfsPath};
+    use rowan::TextRange;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn cross_file_target() {
+        let range = |start: u32, end: u32| TextRange::new(start.into(), end.into());
+        let frange = FileRange::new(FileId(0), range(1, 2));
+        let target = NavigationTarget::from_file_range(frange.with_value(range(4, 5)));
+        assert_eq!(target.file_id, FileId(0));
+        assert_eq!(target.full_range, range(4, 5));
+
+        let other = NavigationTarget::from_file_range(FileRange::new(FileId(1), range(3, 6)));
+        assert_eq!(
+            other.focus_file_range(),
+            FileRange::new(FileId(1), range(3, 6))
+        );
+        assert_eq!(other.full_file_range(), other.focus_file_range());
+    }
+
+    #[test]
+    fn create_and_delete_files() {
+        let path = |p: &str| VfsPath::new(p).unwrap();
+        let mut host = AnalysisHost::new();
+        let mut change = Change::new();
+        change.create_file(path("/default.nix"), "import ./lib.nix".into());
+        change.create_file(path("/lib.nix"), "{ foo = 1; }".into());
+        host.apply_change(change);
+
+        let analysis = host.snapshot();
+        let main = analysis
+            .file_for_path(&path("/default.nix"))
+            .unwrap()
+            .unwrap();
+        let lib = analysis.file_for_path(&path("/lib.nix")).unwrap().unwrap();
+        assert_ne!(main, lib);
+        assert_eq!(analysis.imported_files(main).unwrap(), [lib]);
+        let symbols = analysis.symbol_hierarchy(lib).unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "foo");
+        drop(analysis);
+
+        let mut change = Change::new();
+        change.delete_file(path("/lib.nix"));
+        host.apply_change(change);
+        let analysis = host.snapshot();
+        assert_eq!(analysis.file_for_path(&path("/lib.nix")).unwrap(), None);
+        assert_eq!(analysis.imported_files(main).unwrap(), []);
+    }
+
+    #[test]
+    fn stale_snapshot() {
+        let path = VfsPath::new("/default.nix").unwrap();
+        let mut host = AnalysisHost::new();
+        let mut change = Change::new();
+        change.create_file(path.clone(), "1".into());
+        host.apply_change(change);
+
+        let old = host.snapshot();
+        assert!(!old.is_stale());
+        let file = old.file_for_path(&path).unwrap().unwrap();
+        let revision = old.revision();
+        // Applying changes blocks until all snapshots are dropped.
+        let worker = thread::spawn(move || {
+            while !old.is_stale() {
+                thread::yield_now();
+            }
+        });
+        let mut change = Change::new();
+        change.change_file(file, Some("2".into()));
+        host.apply_change(change);
+        worker.join().unwrap();
+
+        let new = host.snapshot();
+        assert!(!new.is_stale());
+        assert_eq!(new.revision(), revision + 1);
+    }
+
+    #[test]
+    fn syntax_tree() {
+        let mut host = AnalysisHost::new();
+        let mut change = Change::new();
+        change.create_file(VfsPath::new("/default.nix").unwrap(), "1 + 2".into());
+        host.apply_change(change);
+        let analysis = host.snapshot();
+
+        let tree = analysis.syntax_tree(FileId(0), None).unwrap();
+        assert!(tree.starts_with("SOURCE_FILE@0..5"), "{}", tree);
+        assert!(tree.contains("BINARY_OP@0..5"), "{}", tree);
+        assert!(tree.contains("LITERAL@4..5"), "{}", tree);
+
+        let range = TextRange::new(4.into(), 5.into());
+        let tree = analysis.syntax_tree(FileId(0), Some(range)).unwrap();
+        assert_eq!(tree, "LITERAL@4..5\n  INT@4..5 \"2\"\n");
+
+        let out_of_bound = TextRange::new(4.into(), 10.into());
+        let tree = analysis.syntax_tree(FileId(0), Some(out_of_bound)).unwrap();
+        assert!(tree.starts_with("SOURCE_FILE@0..5"), "{}", tree);
+    }
+
+    #[test]
+    fn dev_introspection() {
+        let path = VfsPath::new("/default.nix").unwrap();
+        let mut host = AnalysisHost::new();
+        let mut change = Change::new();
+        change.create_file(path.clone(), "{ a = 1; }".into());
+        host.apply_change(change);
+        let analysis = host.snapshot();
+        let file = analysis.file_for_path(&path).unwrap().unwrap();
+
+        let parse = analysis.file_syntax(file).unwrap();
+        assert!(parse.errors().is_empty());
+        assert_eq!(parse.syntax_node().to_string(), "{ a = 1; }");
+
+        let module = analysis.module(file).unwrap();
+        assert!(Arc::ptr_eq(&module, &analysis.module(file).unwrap()));
+        assert!(matches!(module[module.entry_expr()], Expr::Attrset(_)));
+    }
 }
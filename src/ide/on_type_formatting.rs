@@ -0,0 +1,167 @@
+use crate::base::SourceDatabase;
+use crate::{FileId, TextEdit};
+use syntax::{SyntaxKind, SyntaxNode, TextRange, TextSize, T};
+
+/// Re-indent the line at the position after typing a newline or `;`, by the depth of the
+/// enclosing attrsets, lists and `let` bindings. Only the leading whitespace is changed, and only
+/// for blank lines and lines starting an entry or closing the container, so continuation lines
+/// and string contents are kept as is.
+pub(crate) fn on_type_formatting(
+    db: &dyn SourceDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    indent_unit: &str,
+) -> Option<TextEdit> {
+    let src = db.file_content(file_id);
+    let line_start = src[..usize::from(pos)].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = src[line_start..]
+        .find('\n')
+        .map_or(src.len(), |i| line_start + i);
+    let line = &src[line_start..line_end];
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let is_blank = indent_len == line.len();
+    let line_start = TextSize::try_from(line_start).ok()?;
+    let offset = line_start + TextSize::try_from(indent_len).ok()?;
+
+    let parse = db.parse(file_id);
+    let toks = parse.value.syntax_node().token_at_offset(offset);
+    let tok = toks.clone().right_biased().or_else(|| toks.left_biased())?;
+    if tok.text_range().start() < line_start && tok.kind() != SyntaxKind::SPACE {
+        return None;
+    }
+    if tok
+        .parent_ancestors()
+        .any(|node| matches!(node.kind(), SyntaxKind::STRING | SyntaxKind::INDENT_STRING))
+    {
+        return None;
+    }
+
+    let containers = tok
+        .parent_ancestors()
+        .filter(|node| is_indenting(node, line_start, offset))
+        .collect::<Vec<_>>();
+    let innermost = containers.first()?;
+    let is_closing = matches!(tok.kind(), T!['}'] | T![']'] | T![in]);
+    if !is_blank && !is_closing {
+        let child = innermost
+            .children_with_tokens()
+            .find(|child| child.text_range().contains(offset))?;
+        if child.text_range().start() != offset {
+            return None;
+        }
+    }
+
+    // Containers opened on the same line only indent once, like `{ a = [`.
+    let mut start_lines = containers
+        .iter()
+        .map(|node| {
+            src[..usize::from(node.text_range().start())]
+                .matches('\n')
+                .count()
+        })
+        .collect::<Vec<_>>();
+    start_lines.dedup();
+    let indent = indent_unit.repeat(start_lines.len());
+    if line[..indent_len] == indent {
+        return None;
+    }
+    Some(TextEdit {
+        delete: TextRange::new(line_start, offset),
+        insert: indent.into(),
+    })
+}
+
+/// Whether the line is inside the node, after its start and before its closing `}`, `]` or `in`.
+fn is_indenting(node: &SyntaxNode, line_start: TextSize, offset: TextSize) -> bool {
+    if !matches!(
+        node.kind(),
+        SyntaxKind::ATTR_SET | SyntaxKind::LIST | SyntaxKind::LET_IN
+    ) {
+        return false;
+    }
+    let close = node
+        .children_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .find(|tok| matches!(tok.kind(), T!['}'] | T![']'] | T![in]));
+    node.text_range().start() < line_start
+        && close.map_or(true, |tok| tok.text_range().start() > offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    /// Show the text after the edit, with `$0` marking the end of the new indentation.
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let edit = super::on_type_formatting(&db, file_id, pos, "  ").expect("No edit");
+        let mut src = db.file_content(file_id).to_string();
+        edit.apply(&mut src);
+        src.insert_str(usize::from(edit.delete.start()) + edit.insert.len(), "$0");
+        expect.assert_eq(&src);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let edit = super::on_type_formatting(&db, file_id, pos, "  ");
+        assert_eq!(edit, None);
+    }
+
+    #[test]
+    fn newline() {
+        check(
+            "{\n  a = {\n    b = 1;\n$0\n  };\n}",
+            expect![[r#"
+                {
+                  a = {
+                    b = 1;
+                    $0
+                  };
+                }"#]],
+        );
+        check(
+            "let\n  a = [\n$0\n  ]; in a",
+            expect![[r#"
+                let
+                  a = [
+                    $0
+                  ]; in a"#]],
+        );
+        check(
+            "{ a = [\n$0] ; }",
+            expect![[r#"
+                { a = [
+                  $0] ; }"#]],
+        );
+        check_no("{\n  a = 1;\n  $0\n}");
+        check_no("let a = 1; in\n$0a");
+    }
+
+    #[test]
+    fn semicolon() {
+        check(
+            "{\n  a = {\n        b = 1;$0\n  };\n}",
+            expect![[r#"
+                {
+                  a = {
+                    $0b = 1;
+                  };
+                }"#]],
+        );
+        check(
+            "{\n  a = 1;\nb = 2;$0\n}",
+            expect![[r#"
+                {
+                  a = 1;
+                  $0b = 2;
+                }"#]],
+        );
+        // Continuation lines.
+        check_no("{\n  a =\n    1;$0\n}");
+        check_no("{\n  a = ''\n    b\n  '';$0\n}");
+    }
+}
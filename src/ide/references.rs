@@ -1,8 +1,11 @@
-use crate::def::{AstPtr, DefDatabase};
+use super::imports::{importing_files, is_import_of};
+use super::resolve_attrset;
+use crate::def::{static_string_content, DefDatabase, Expr, Literal};
 use crate::{FileId, FileRange, InFile};
 use rowan::ast::AstNode;
 use rowan::TextSize;
-use syntax::{ast, match_ast, SyntaxKind, T};
+use smol_str::SmolStr;
+use syntax::{ast, match_ast, SyntaxKind, SyntaxNode, T};
 
 pub(crate) fn references(
     db: &dyn DefDatabase,
@@ -23,15 +26,15 @@ pub(crate) fn references(
     }
 
     enum DefKind {
-        Attr(AstPtr),
-        With(AstPtr),
+        Attr(SyntaxNode),
+        With(SyntaxNode),
     }
 
     let kind = tok.parent_ancestors().find_map(|node| {
         match_ast! {
             match node {
-                ast::Attr(n) => Some(DefKind::Attr(AstPtr::new(n.syntax()))),
-                ast::With(n) => Some(DefKind::With(AstPtr::new(n.syntax()))),
+                ast::Attr(n) => Some(DefKind::Attr(n.syntax().clone())),
+                ast::With(n) => Some(DefKind::With(n.syntax().clone())),
                 _ => None,
             }
         }
@@ -39,29 +42,91 @@ pub(crate) fn references(
 
     let source_map = db.source_map(file_id);
     let ref_map = db.name_reference_map(file_id);
-    let refs = match kind {
-        DefKind::Attr(ptr) => {
-            let def = source_map.node_name_def(ptr)?;
-            ref_map.def_references(def)
+    let (refs, exported) = match kind {
+        DefKind::Attr(node) => {
+            let exported = exported_attr_name(db, file_id, &node);
+            match source_map.name_def_for_node(&node) {
+                Some(def) => (ref_map.def_references(def), exported),
+                None => (None, Some(exported?)),
+            }
         }
-        DefKind::With(ptr) => {
-            let expr = source_map.node_expr(ptr)?;
-            ref_map.with_references(expr)
+        DefKind::With(node) => {
+            let expr = source_map.expr_for_node(&node)?;
+            (ref_map.with_references(expr), None)
         }
     };
     // When {def,with}_references returns None, it means no references,
     // not a failure.
-    let refs = refs.map_or(Vec::new(), |refs| {
+    let mut refs = refs.map_or(Vec::new(), |refs| {
         refs.iter()
             .map(|&expr| {
-                let ptr = source_map.expr_node(expr).expect("Id must be valid");
+                let ptr = source_map.node_for_expr(expr).expect("Id must be valid");
                 InFile::new(file_id, ptr.text_range())
             })
             .collect()
     });
+    if let Some(name) = exported {
+        refs.extend(imported_references(db, file_id, &name));
+    }
     Some(refs)
 }
 
+/// The name of a key directly in the attrset the file evaluates to, which importing files can
+/// access as `(import ./file.nix).name`.
+fn exported_attr_name(db: &dyn DefDatabase, file_id: FileId, node: &SyntaxNode) -> Option<SmolStr> {
+    let attr = ast::Attr::cast(node.clone())?;
+    let parent = node.parent()?;
+    let binding = match ast::Attrpath::cast(parent.clone()) {
+        Some(path) if path.attrs().next()?.syntax() == node => path.syntax().parent()?,
+        Some(_) => return None,
+        None if ast::Inherit::can_cast(parent.kind()) => parent,
+        None => return None,
+    };
+
+    let module = db.module(file_id);
+    let mut entry = module.entry_expr();
+    while let Expr::LetIn(_, body) = &module[entry] {
+        entry = *body;
+    }
+    let set = resolve_attrset(db, file_id, entry)?;
+    if db.source_map(file_id).expr_for_node(&binding.parent()?)? != set {
+        return None;
+    }
+
+    match attr {
+        ast::Attr::Name(name) => Some(name.token()?.text().into()),
+        ast::Attr::String(s) => static_string_content(&s).map(Into::into),
+        ast::Attr::Dynamic(_) => None,
+    }
+}
+
+/// Selections of the name on imports of the file, eg. `(import ./lib.nix).name`,
+/// in other files of the file set.
+fn imported_references(db: &dyn DefDatabase, file_id: FileId, name: &SmolStr) -> Vec<FileRange> {
+    let key = Expr::Literal(Literal::String(name.clone()));
+    let mut refs = Vec::new();
+    for file in importing_files(db, file_id) {
+        let module = db.module(file);
+        let source_map = db.source_map(file);
+        for (_, expr) in module.exprs() {
+            let (set, attr) = match expr {
+                Expr::Select(set, attrpath, _) => match attrpath.first() {
+                    Some(&attr) => (*set, attr),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if module[attr] != key || !is_import_of(db, file, set, file_id) {
+                continue;
+            }
+            if let Some(ptr) = source_map.node_for_expr(attr) {
+                refs.push(InFile::new(file, ptr.text_range()));
+            }
+        }
+    }
+    refs
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tests::TestDB;
@@ -85,6 +150,7 @@ mod tests {
         check::<2>("let $0a = 1; b = 1; in $1a");
         check::<1>("let a = 1; $0b = 1; in a");
         check::<2>("let $0a.b = $1a.c; in 1");
+        check::<2>("let inherit (pkgs) $0hello; in $1hello");
     }
 
     #[test]
@@ -115,4 +181,52 @@ mod tests {
         check::<3>("a: $0with {}; $1x + (with {}; { inherit a $2b; })");
         check::<2>("a: with {}; x + ($0with {}; { inherit a $1b; })");
     }
+
+    #[track_caller]
+    fn check_files<const N: usize>(fixture: &str) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let poses = f.markers::<N>().unwrap();
+        let mut got = super::references(&db, poses[0].file_id, poses[0].value)
+            .into_iter()
+            .flatten()
+            .map(|frange| (frange.file_id, frange.value.start()))
+            .collect::<Vec<_>>();
+        got.sort_by_key(|&(file, pos)| (file.0, pos));
+        let expect = poses[1..]
+            .iter()
+            .map(|pos| (pos.file_id, pos.value))
+            .collect::<Vec<_>>();
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn imported_attr() {
+        check_files::<2>(
+            "
+#- /lib.nix
+{ $0f = x: x; g = 1; }
+#- /main.nix
+(import ./lib.nix).$1f (import ./lib.nix).g
+            ",
+        );
+        check_files::<5>(
+            "
+#- /lib.nix
+let h = 1; in rec { inherit $0h; g = $1h; }
+#- /main.nix
+let lib = import ./lib.nix; in [ lib.$2h lib.$3h.x ({ h = 1; }).h ]
+#- /foo/default.nix
+(import ../lib.nix).$4h
+            ",
+        );
+        // Nested keys are not accessed directly.
+        check_files::<1>(
+            "
+#- /lib.nix
+{ a.$0f = 1; }
+#- /main.nix
+(import ./lib.nix).a.f
+            ",
+        );
+    }
 }
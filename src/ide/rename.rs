@@ -0,0 +1,211 @@
+use crate::def::{DefDatabase, Expr, ExprId, ModuleScopes, NameDefId, ScopeId};
+use crate::{FileId, TextEdit};
+use rowan::ast::AstNode;
+use rowan::{TextRange, TextSize};
+use smol_str::SmolStr;
+use syntax::lexer::is_valid_ident;
+use syntax::{ast, SyntaxKind, SyntaxNode, T};
+
+/// Rename a name definition of `let`, `rec`, a lambda parameter or a pattern field,
+/// together with all its references.
+///
+/// Returns `None` if there is nothing to rename at the position, or the rename is rejected:
+/// - The new name is not a valid identifier.
+/// - The name is inherited, eg. `inherit a;`, whose attribute name would change.
+/// - Another definition of the new name exists in the same scope, eg. pattern fields
+///   `{ a, b }` renaming `a` to `b`.
+/// - A reference would resolve to a different definition after renaming.
+pub(crate) fn rename(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+    new_name: &str,
+) -> Option<Vec<TextEdit>> {
+    if !is_valid_ident(new_name) {
+        return None;
+    }
+    let new_name = SmolStr::from(new_name);
+
+    let def = name_def_at(db, file_id, pos)?;
+    let parse = db.parse(file_id).value;
+    let source_map = db.source_map(file_id);
+    let module = db.module(file_id);
+    let scopes = db.scopes(file_id);
+    let ref_map = db.name_reference_map(file_id);
+    let root = parse.syntax_node();
+    let old_name = &module[def].name;
+    if *old_name == new_name {
+        return Some(Vec::new());
+    }
+
+    let def_node = source_map.node_for_name_def(def)?.to_node(&root);
+    let refs = ref_map.def_references(def).unwrap_or_default();
+    let ref_nodes = refs
+        .iter()
+        .map(|&expr| Some(source_map.node_for_expr(expr)?.to_node(&root)))
+        .collect::<Option<Vec<_>>>()?;
+    if std::iter::once(&def_node)
+        .chain(&ref_nodes)
+        .any(is_inherited)
+    {
+        return None;
+    }
+
+    let (def_scope, defs) = scopes
+        .iter()
+        .filter_map(|(scope, data)| Some((scope, data.as_name_defs()?)))
+        .find(|(_, defs)| defs.get(old_name) == Some(&def))?;
+    if defs.contains_key(&new_name) {
+        return None;
+    }
+
+    // References of the definition must not be captured by inner definitions of the new name,
+    // and existing references of the new name must not be captured by the renamed definition.
+    if !refs
+        .iter()
+        .all(|&expr| reaches_scope(&scopes, expr, &new_name, def_scope))
+    {
+        return None;
+    }
+    if module.exprs().any(|(expr, kind)| {
+        matches!(kind, Expr::Reference(name) if *name == new_name)
+            && reaches_scope(&scopes, expr, &new_name, def_scope)
+    }) {
+        return None;
+    }
+
+    let edits = std::iter::once(def_node)
+        .chain(ref_nodes)
+        .map(|node| TextEdit {
+            delete: name_range(&node),
+            insert: new_name.clone(),
+        })
+        .collect();
+    Some(edits)
+}
+
+/// The name definition at the position, or the one referenced by the name at the position.
+pub(super) fn name_def_at(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<NameDefId> {
+    let parse = db.parse(file_id).value;
+    let tok = parse.syntax_node().token_at_offset(pos).right_biased()?;
+    if !matches!(tok.kind(), T![or] | SyntaxKind::IDENT) {
+        return None;
+    }
+    let source_map = db.source_map(file_id);
+    tok.parent_ancestors().find_map(|node| {
+        if let Some(def) = source_map.name_def_for_node(&node) {
+            return Some(def);
+        }
+        let expr = source_map.expr_for_node(ast::Ref::cast(node)?.syntax())?;
+        db.resolve_name(file_id, expr)?.as_name_def()
+    })
+}
+
+/// Whether `def_scope` is reached when looking up the name from the scope of `expr`,
+/// before any other definition of the name.
+fn reaches_scope(scopes: &ModuleScopes, expr: ExprId, name: &SmolStr, def_scope: ScopeId) -> bool {
+    let mut scope = scopes.scope_by_expr(expr);
+    while let Some(cur) = scope {
+        if cur == def_scope {
+            return true;
+        }
+        let data = &scopes[cur];
+        if data
+            .as_name_defs()
+            .map_or(false, |defs| defs.contains_key(name))
+        {
+            return false;
+        }
+        scope = data.parent();
+    }
+    false
+}
+
+/// Inherited names are both definitions and references, eg. `let inherit a; in a`.
+pub(super) fn is_inherited(node: &SyntaxNode) -> bool {
+    node.parent()
+        .map_or(false, |parent| parent.kind() == SyntaxKind::INHERIT)
+}
+
+/// The range of the identifier, excluding trailing whitespace. Quoted names are replaced
+/// as a whole.
+pub(super) fn name_range(node: &SyntaxNode) -> TextRange {
+    node.children_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .find(|tok| matches!(tok.kind(), T![or] | SyntaxKind::IDENT))
+        .map_or_else(|| node.text_range(), |tok| tok.text_range())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, new_name: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let got = match super::rename(&db, file_id, pos, new_name) {
+            Some(mut edits) => {
+                let mut src = db.file_content(file_id).to_string();
+                edits.sort_by_key(|edit| edit.delete.start());
+                for edit in edits.iter().rev() {
+                    edit.apply(&mut src);
+                }
+                src
+            }
+            None => "None".into(),
+        };
+        expect.assert_eq(&got);
+    }
+
+    #[test]
+    fn pat_field() {
+        check(
+            "{ $0foo, baz }: foo + baz",
+            "bar",
+            expect!["{ bar, baz }: bar + baz"],
+        );
+        check(
+            "{ foo, baz }: $0foo + baz",
+            "bar",
+            expect!["{ bar, baz }: bar + baz"],
+        );
+        check(
+            "{ foo ? baz, baz }: $0foo",
+            "bar",
+            expect!["{ bar ? baz, baz }: bar"],
+        );
+        check("{ foo, baz }@args: $0foo", "baz", expect!["None"]);
+        check("{ foo, baz }@args: $0foo", "args", expect!["None"]);
+    }
+
+    #[test]
+    fn bindings() {
+        check("let $0a = 1; in a + a", "b", expect!["let b = 1; in b + b"]);
+        check(
+            "rec { a = 1; \"b\" = $0a; }",
+            "c",
+            expect![[r#"rec { c = 1; "b" = c; }"#]],
+        );
+        check("x: let a = x; in $0a", "y", expect!["x: let y = x; in y"]);
+        check("$0a: a", "b", expect!["b: b"]);
+    }
+
+    #[test]
+    fn rejected() {
+        check("let $0a = 1; in a", "in", expect!["None"]);
+        check("let $0a = 1; in a", "1a", expect!["None"]);
+        check("let a = 1; in { inherit $0a; }", "b", expect!["None"]);
+        check("let $0a = 1; in { inherit a; }", "b", expect!["None"]);
+        // Capturing and being captured.
+        check("x: let $0a = 1; in a + x", "x", expect!["None"]);
+        check("let $0a = 1; in x: a", "x", expect!["None"]);
+        check("let $0a = 1; in map a", "map", expect!["None"]);
+        check("{ a.b = 1; }.$0a", "c", expect!["None"]);
+    }
+}
@@ -0,0 +1,156 @@
+use super::is_flake_file;
+use super::symbol_hierarchy::{symbol_hierarchy, SymbolKind, SymbolTree};
+use crate::base::SourceDatabase;
+use crate::FileId;
+use rowan::TextRange;
+use syntax::lexer::is_valid_ident;
+
+/// An attribute which can be built or run by a `nix` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Runnable {
+    /// A short description of the action, eg. `Build hello`.
+    pub label: String,
+    /// The range of the attribute name.
+    pub range: TextRange,
+    /// The command line to run in the directory of the file, eg. `nix build .#hello`.
+    pub command: Vec<String>,
+}
+
+/// Flake output categories and the `nix` subcommands acting on their entries.
+const FLAKE_OUTPUTS: &[(&str, &str, &str)] = &[
+    ("packages", "build", "Build"),
+    ("checks", "build", "Check"),
+    ("devShells", "develop", "Develop"),
+    ("apps", "run", "Run"),
+];
+
+/// Recognize runnable attributes structurally, without evaluation.
+/// For flakes, they are `<category>.<system>.<name>` in outputs, eg. `packages.x86_64-linux.hello`.
+/// For other files, they are top-level attributes built by `nix-build`.
+pub(crate) fn runnables(db: &dyn SourceDatabase, file_id: FileId) -> Vec<Runnable> {
+    let symbols = symbol_hierarchy(db, file_id);
+    let mut ret = Vec::new();
+    if !is_flake_file(db, file_id) {
+        let file_name = db
+            .file_set()
+            .path_for_file(file_id)
+            .and_then(|path| path.file_name().map(str::to_owned))
+            .unwrap_or_else(|| "default.nix".into());
+        for sym in symbols
+            .iter()
+            .filter(|sym| sym.kind != SymbolKind::Function)
+        {
+            ret.push(Runnable {
+                label: format!("Build {}", sym.name),
+                range: sym.focus_range,
+                command: vec![
+                    "nix-build".into(),
+                    format!("./{}", file_name),
+                    "-A".into(),
+                    quote_attr(&sym.name),
+                ],
+            });
+        }
+        return ret;
+    }
+
+    let outputs = match symbols.iter().find(|sym| sym.name == "outputs") {
+        Some(outputs) => outputs,
+        None => return ret,
+    };
+    for &(category, subcommand, verb) in FLAKE_OUTPUTS {
+        let systems = children_of(&outputs.children, category);
+        for system in systems {
+            for sym in &system.children {
+                let path = [category, &system.name, &sym.name]
+                    .iter()
+                    .map(|name| quote_attr(name))
+                    .collect::<Vec<_>>()
+                    .join(".");
+                ret.push(Runnable {
+                    label: format!("{} {}", verb, sym.name),
+                    range: sym.focus_range,
+                    command: vec!["nix".into(), subcommand.into(), format!(".#{}", path)],
+                });
+            }
+        }
+    }
+    ret
+}
+
+fn children_of<'a>(symbols: &'a [SymbolTree], name: &str) -> &'a [SymbolTree] {
+    symbols
+        .iter()
+        .find(|sym| sym.name == name)
+        .map_or(&[], |sym| &sym.children)
+}
+
+fn quote_attr(name: &str) -> String {
+    if is_valid_ident(name) {
+        name.into()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [] = f.markers().unwrap();
+        let got = super::runnables(&db, crate::FileId(0))
+            .into_iter()
+            .map(|r| format!("{} @ {:?}: {}\n", r.label, r.range, r.command.join(" ")))
+            .collect::<String>();
+        expect.assert_eq(&got);
+    }
+
+    #[test]
+    fn flake_outputs() {
+        check(
+            r#"
+#- /flake.nix
+{
+  outputs = { self, nixpkgs }: {
+    packages.x86_64-linux.hello = nixpkgs.legacyPackages.x86_64-linux.hello;
+    packages.x86_64-linux."hello-2.0" = self.packages.x86_64-linux.hello;
+    devShells.aarch64-darwin = { default = { }; };
+    checks.x86_64-linux.test = { };
+    formatter.x86_64-linux = { };
+  };
+}
+            "#,
+            expect![[r#"
+                Build hello @ 61..66: nix build .#packages.x86_64-linux.hello
+                Build hello-2.0 @ 138..149: nix build .#packages.x86_64-linux."hello-2.0"
+                Check test @ 261..265: nix build .#checks.x86_64-linux.test
+                Develop default @ 219..226: nix develop .#devShells.aarch64-darwin.default
+            "#]],
+        );
+    }
+
+    #[test]
+    fn top_level_attrs() {
+        check(
+            r#"
+#- /default.nix
+{ hello = { }; world = { }; mkHello = name: { }; }
+            "#,
+            expect![[r#"
+                Build hello @ 2..7: nix-build ./default.nix -A hello
+                Build world @ 15..20: nix-build ./default.nix -A world
+            "#]],
+        );
+        check(
+            r#"
+#- /module.nix
+{ config, ... }: { hello = { }; }
+            "#,
+            expect![""],
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::def::{BindingValue, DefDatabase, Expr, ExprId, ResolveResult};
+use crate::FileId;
+use rowan::ast::AstNode;
+use rowan::{TextRange, TextSize};
+use syntax::{ast, SyntaxNode};
+
+/// The signature of the function being applied at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHelp {
+    /// The text of the signature, eg. `a: { b, c }: ...`.
+    pub label: String,
+    /// The ranges of parameters in `label`.
+    pub parameters: Vec<TextRange>,
+    /// The index of the parameter the cursor is on, if any.
+    pub active_parameter: Option<usize>,
+}
+
+/// Guard against cyclic definitions like `let f = f; in f 1`.
+const MAX_RESOLVE_DEPTH: usize = 16;
+
+pub(crate) fn signature_help(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    pos: TextSize,
+) -> Option<SignatureHelp> {
+    let parse = db.parse(file_id).value;
+    let root = parse.syntax_node();
+    let tok = root.token_at_offset(pos).left_biased()?;
+    let mut apply = tok.parent_ancestors().find_map(ast::Apply::cast)?;
+    // Go to the outermost application of a curried call `f a b`.
+    while let Some(parent) = apply.syntax().parent().and_then(ast::Apply::cast) {
+        if parent
+            .function()
+            .map_or(false, |f| f.syntax() == apply.syntax())
+        {
+            apply = parent;
+        } else {
+            break;
+        }
+    }
+
+    let mut args = Vec::new();
+    let mut callee = ast::Expr::Apply(apply);
+    while let ast::Expr::Apply(apply) = &callee {
+        args.push(apply.argument()?);
+        callee = apply.function()?;
+    }
+    args.reverse();
+    while let ast::Expr::Paren(paren) = &callee {
+        callee = paren.expr()?;
+    }
+
+    let source_map = db.source_map(file_id);
+    let callee_expr = source_map.expr_for_node(callee.syntax())?;
+    let (lambda, skip) = resolve_lambda(db, file_id, callee_expr, 0)?;
+    let lambda = source_map.node_for_expr(lambda)?.to_node(&root);
+
+    let mut label = String::new();
+    let mut parameters = Vec::new();
+    for param in lambda_params(lambda).skip(skip) {
+        let start = TextSize::of(&label);
+        label += &param;
+        parameters.push(TextRange::new(start, TextSize::of(&label)));
+        label += ": ";
+    }
+    if parameters.is_empty() {
+        return None;
+    }
+    label += "...";
+
+    // The cursor is on the first argument which does not end before it.
+    let active = args
+        .iter()
+        .take_while(|arg| trimmed_end(arg.syntax()) < pos)
+        .count();
+    Some(SignatureHelp {
+        label,
+        active_parameter: (active < parameters.len()).then_some(active),
+        parameters,
+    })
+}
+
+/// Find the lambda which is called when applying `expr`.
+/// Returns the lambda and the number of parameters already applied.
+fn resolve_lambda(
+    db: &dyn DefDatabase,
+    file_id: FileId,
+    expr: ExprId,
+    depth: usize,
+) -> Option<(ExprId, usize)> {
+    if depth >= MAX_RESOLVE_DEPTH {
+        return None;
+    }
+    let module = db.module(file_id);
+    match &module[expr] {
+        Expr::Lambda(..) => Some((expr, 0)),
+        Expr::Reference(_) => match db.resolve_name(file_id, expr)? {
+            ResolveResult::NameDef(def) => {
+                let value = module.name_def_value(def)?;
+                resolve_lambda(db, file_id, value, depth + 1)
+            }
+            ResolveResult::Builtin(_) | ResolveResult::WithExprs(_) => None,
+        },
+        // Calling an attrset calls its `__functor` with the attrset itself as the first argument.
+        Expr::Attrset(bindings) | Expr::LetAttrset(bindings) => {
+            match bindings.get(&module, "__functor")? {
+                BindingValue::Expr(functor) | BindingValue::Inherit(functor) => {
+                    let (lambda, skip) = resolve_lambda(db, file_id, functor, depth + 1)?;
+                    Some((lambda, skip + 1))
+                }
+                BindingValue::InheritFrom(_) => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The parameters of a curried lambda `a: b: ...`.
+fn lambda_params(lambda: SyntaxNode) -> impl Iterator<Item = String> {
+    let mut lambda = ast::Lambda::cast(lambda);
+    std::iter::from_fn(move || {
+        let cur = lambda.take()?;
+        let param = cur.param()?.syntax().to_string();
+        lambda = match cur.body() {
+            Some(ast::Expr::Lambda(body)) => Some(body),
+            _ => None,
+        };
+        Some(param.trim_end().to_owned())
+    })
+}
+
+fn trimmed_end(node: &SyntaxNode) -> TextSize {
+    let text = node.to_string();
+    node.text_range().start() + TextSize::of(text.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        let sig = super::signature_help(&db, file_id, pos).expect("No signature help");
+        let mut got = sig.label.clone();
+        if let Some(i) = sig.active_parameter {
+            let range = sig.parameters[i];
+            got.insert(usize::from(range.end()), '>');
+            got.insert(usize::from(range.start()), '<');
+        }
+        expect.assert_eq(&got);
+    }
+
+    #[track_caller]
+    fn check_no(fixture: &str) {
+        let (db, file_id, [pos]) = TestDB::single_file(fixture).unwrap();
+        assert_eq!(super::signature_help(&db, file_id, pos), None);
+    }
+
+    #[test]
+    fn lambda() {
+        check(
+            "let f = a: { b, c ? 1 }: a; in f 1$0",
+            expect!["<a>: { b, c ? 1 }: ..."],
+        );
+        check(
+            "let f = a: { b, c ? 1 }: a; in f 1 $0{}",
+            expect!["a: <{ b, c ? 1 }>: ..."],
+        );
+        check("let f = a: b: a; in f 1 2 $03", expect!["a: b: ..."]);
+        check("(x: y: x) $01", expect!["<x>: y: ..."]);
+        check("rec { f = x: x; g = f $01; }", expect!["<x>: ..."]);
+    }
+
+    #[test]
+    fn functor() {
+        check(
+            "let s = { __functor = self: x: x; }; in s 1$0",
+            expect!["<x>: ..."],
+        );
+        check(
+            "let s = { __functor = self: x: y: x; }; in s 1 $02",
+            expect!["x: <y>: ..."],
+        );
+        check_no("let s = { __functor = self: self; }; in s 1$0");
+    }
+
+    #[test]
+    fn unknown() {
+        check_no("f 1$0");
+        check_no("let f = f; in f 1$0");
+        check_no("let f = 1; in f 1$0");
+    }
+}
@@ -0,0 +1,254 @@
+use super::is_flake_file;
+use crate::base::SourceDatabase;
+use crate::FileId;
+use rowan::ast::AstNode;
+use rowan::TextRange;
+use smol_str::SmolStr;
+use syntax::ast::{self, HasBindings, HasStringParts};
+
+/// A node in the outline of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolTree {
+    pub name: SmolStr,
+    pub kind: SymbolKind,
+    /// The range of the whole definition.
+    pub full_range: TextRange,
+    /// The range of the name.
+    pub focus_range: TextRange,
+    pub children: Vec<SymbolTree>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// An attribute with an attrset value, which has children.
+    Namespace,
+    /// An attribute with a lambda value.
+    Function,
+    /// Other attributes.
+    Attribute,
+}
+
+pub(crate) fn symbol_hierarchy(db: &dyn SourceDatabase, file_id: FileId) -> Vec<SymbolTree> {
+    let parse = db.parse(file_id).value;
+    let is_flake = is_flake_file(db, file_id);
+    let mut ret = Vec::new();
+    if let Some(set) = parse.root().expr().and_then(strip_to_attrset) {
+        collect_bindings(&set, &mut ret);
+    }
+    if is_flake {
+        // The outputs are inside the body of the `outputs` lambda.
+        if let Some(outputs) = ret.iter_mut().find(|sym| sym.name == "outputs") {
+            if let Some(set) = find_outputs_attrset(&parse.syntax_node(), outputs.full_range) {
+                outputs.kind = SymbolKind::Namespace;
+                collect_bindings(&set, &mut outputs.children);
+            }
+        }
+    }
+    ret
+}
+
+fn find_outputs_attrset(root: &syntax::SyntaxNode, range: TextRange) -> Option<ast::AttrSet> {
+    let entry = root
+        .covering_element(range)
+        .into_node()?
+        .ancestors()
+        .find_map(ast::AttrpathValue::cast)?;
+    let mut e = entry.value()?;
+    loop {
+        e = match e {
+            ast::Expr::Paren(p) => p.expr()?,
+            ast::Expr::Lambda(lam) => lam.body()?,
+            ast::Expr::LetIn(let_in) => let_in.body()?,
+            ast::Expr::AttrSet(set) if set.let_token().is_none() => return Some(set),
+            _ => return None,
+        };
+    }
+}
+
+fn strip_to_attrset(mut e: ast::Expr) -> Option<ast::AttrSet> {
+    loop {
+        e = match e {
+            ast::Expr::Paren(p) => p.expr()?,
+            ast::Expr::AttrSet(set) if set.let_token().is_none() => return Some(set),
+            _ => return None,
+        };
+    }
+}
+
+/// Collect symbols of bindings, merging attrpaths with the same prefix.
+fn collect_bindings(n: &impl HasBindings, out: &mut Vec<SymbolTree>) {
+    for binding in n.bindings() {
+        match binding {
+            ast::Binding::Inherit(i) => {
+                for attr in i.attrs() {
+                    if let Some(name) = static_attr_name(&attr) {
+                        let range = attr.syntax().text_range();
+                        insert_symbol(out, name, range, range);
+                    }
+                }
+            }
+            ast::Binding::AttrpathValue(entry) => {
+                let full_range = entry.syntax().text_range();
+                let mut attrs = entry.attrpath().into_iter().flat_map(|path| path.attrs());
+                let mut cur = match attrs.next() {
+                    Some(attr) => attr,
+                    None => continue,
+                };
+                let mut out = &mut *out;
+                while let Some(name) = static_attr_name(&cur) {
+                    let sym = insert_symbol(out, name, full_range, cur.syntax().text_range());
+                    match attrs.next() {
+                        Some(next) => {
+                            sym.kind = SymbolKind::Namespace;
+                            out = &mut sym.children;
+                            cur = next;
+                        }
+                        None => {
+                            match entry.value().map(strip_paren) {
+                                Some(ast::Expr::AttrSet(set)) if set.let_token().is_none() => {
+                                    sym.kind = SymbolKind::Namespace;
+                                    collect_bindings(&set, &mut sym.children);
+                                }
+                                Some(ast::Expr::Lambda(_)) => sym.kind = SymbolKind::Function,
+                                _ => {}
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn insert_symbol(
+    out: &mut Vec<SymbolTree>,
+    name: SmolStr,
+    full_range: TextRange,
+    focus_range: TextRange,
+) -> &mut SymbolTree {
+    match out.iter().position(|sym| sym.name == name) {
+        Some(i) => &mut out[i],
+        None => {
+            out.push(SymbolTree {
+                name,
+                kind: SymbolKind::Attribute,
+                full_range,
+                focus_range,
+                children: Vec::new(),
+            });
+            out.last_mut().unwrap()
+        }
+    }
+}
+
+fn strip_paren(mut e: ast::Expr) -> ast::Expr {
+    while let ast::Expr::Paren(p) = &e {
+        match p.expr() {
+            Some(inner) => e = inner,
+            None => break,
+        }
+    }
+    e
+}
+
+fn static_attr_name(attr: &ast::Attr) -> Option<SmolStr> {
+    let s = match attr {
+        ast::Attr::Name(n) => return Some(n.token()?.text().into()),
+        ast::Attr::String(s) => s.clone(),
+        ast::Attr::Dynamic(d) => match d.expr()? {
+            ast::Expr::String(s) => s,
+            _ => return None,
+        },
+    };
+    s.string_parts()
+        .map(|part| match part {
+            ast::StringPart::Fragment(tok) | ast::StringPart::Escape(tok) => {
+                Some(tok.text().to_owned())
+            }
+            ast::StringPart::Dynamic(_) => None,
+        })
+        .collect::<Option<String>>()
+        .map(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolTree;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+    use std::fmt::Write;
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, f) = TestDB::from_fixture(fixture).unwrap();
+        let [] = f.markers().unwrap();
+        let syms = super::symbol_hierarchy(&db, crate::FileId(0));
+
+        fn go(out: &mut String, depth: usize, syms: &[SymbolTree]) {
+            for sym in syms {
+                writeln!(
+                    out,
+                    "{:indent$}{} {:?} {:?}",
+                    "",
+                    sym.name,
+                    sym.kind,
+                    sym.focus_range,
+                    indent = depth * 2,
+                )
+                .unwrap();
+                go(out, depth + 1, &sym.children);
+            }
+        }
+        let mut got = String::new();
+        go(&mut got, 0, &syms);
+        expect.assert_eq(&got);
+    }
+
+    #[test]
+    fn flake() {
+        check(
+            r#"
+#- /flake.nix
+{
+  description = "A flake";
+  inputs.nixpkgs.url = "github:NixOS/nixpkgs";
+  inputs.flake-utils = { url = "github:numtide/flake-utils"; };
+  outputs = { self, nixpkgs, ... }: {
+    packages.x86_64-linux.hello = nixpkgs.hello;
+    lib = { };
+  };
+}
+            "#,
+            expect![[r#"
+                description Attribute 4..15
+                inputs Namespace 31..37
+                  nixpkgs Namespace 38..45
+                    url Attribute 46..49
+                  flake-utils Namespace 85..96
+                    url Attribute 101..104
+                outputs Namespace 142..149
+                  packages Namespace 182..190
+                    x86_64-linux Namespace 191..203
+                      hello Attribute 204..209
+                  lib Namespace 231..234
+            "#]],
+        );
+    }
+
+    #[test]
+    fn non_flake() {
+        check(
+            "
+#- /default.nix
+{ outputs = { ... }: { a = 1; }; b.c = 1; inherit (d) e; }
+            ",
+            expect![[r#"
+                outputs Function 2..9
+                b Namespace 33..34
+                  c Attribute 35..36
+                e Attribute 54..55
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Remove all unused `let` bindings of a file at once.
+//!
+//! ```nix
+//! let b = 1; a = b; in 1
+//! ```
+//! =>
+//! ```nix
+//! 1
+//! ```
+use crate::def::{BindingKey, DefDatabase, Expr, NameDefId};
+use crate::{FileId, TextEdit};
+use rowan::ast::AstNode;
+use std::collections::HashMap;
+use syntax::ast::{self, HasBindings};
+use syntax::{SyntaxKind, SyntaxNode, TextRange};
+
+pub(crate) struct LetBinding {
+    pub(crate) def: NameDefId,
+    /// The `a = 1;` entry, or the `inherit` containing the name.
+    node: SyntaxNode,
+    /// The name node if it is inherited.
+    inherited_name: Option<SyntaxNode>,
+}
+
+/// Bindings only used by removed ones are also removed, including mutually referencing ones.
+/// Bindings defined by multi-segment attrpaths like `let a.b = 1; in a` are kept.
+pub(crate) fn remove_unused_bindings(db: &dyn DefDatabase, file_id: FileId) -> Vec<TextEdit> {
+    let (bindings, removed) = let_bindings_usage(db, file_id);
+    let units = removed_units(&bindings, &removed);
+    deletes_of(&units)
+        .into_iter()
+        .map(|delete| TextEdit {
+            delete,
+            insert: "".into(),
+        })
+        .collect()
+}
+
+impl LetBinding {
+    /// The range to delete to remove this binding alone, keeping others of the same `inherit`.
+    pub(crate) fn removal_range(&self) -> TextRange {
+        let unit = match &self.inherited_name {
+            Some(name)
+                if ast::Inherit::cast(self.node.clone()).map_or(0, |i| i.attrs().count()) > 1 =>
+            {
+                name.clone()
+            }
+            _ => self.node.clone(),
+        };
+        deletes_of(&[unit])
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| self.node.text_range())
+    }
+}
+
+/// The non-overlapping ranges to delete to remove the syntax nodes, sorted by position.
+fn deletes_of(units: &[SyntaxNode]) -> Vec<TextRange> {
+    let mut let_removed_cnt = HashMap::new();
+    for node in units {
+        if let Some(let_in) = node.parent().and_then(ast::LetIn::cast) {
+            *let_removed_cnt.entry(let_in).or_insert(0usize) += 1;
+        }
+    }
+
+    let mut deletes = Vec::new();
+    for node in units {
+        let let_in = node.parent().and_then(ast::LetIn::cast);
+        match let_in {
+            // An empty `let` is replaced by its body.
+            Some(let_in) if let_removed_cnt[&let_in] == let_in.bindings().count() => {
+                if let Some(body) = let_in.body() {
+                    let start = let_in.syntax().text_range().start();
+                    deletes.push(TextRange::new(start, body.syntax().text_range().start()));
+                }
+            }
+            _ => deletes.push(with_leading_space(node)),
+        }
+    }
+    // Bindings nested in removed ones go away with them.
+    deletes.sort_by_key(|range| (range.start(), std::cmp::Reverse(range.end())));
+    deletes.dedup();
+    let mut ret: Vec<TextRange> = Vec::new();
+    for delete in deletes {
+        if ret.last().map_or(false, |last| last.contains_range(delete)) {
+            continue;
+        }
+        ret.push(delete);
+    }
+    ret
+}
+
+/// `let` bindings which are not reachable from the file root through references,
+/// like `let a = b; b = a; in 1`. Bindings used only by themselves or other unreachable ones
+/// are included.
+pub(crate) fn unused_let_bindings(db: &dyn DefDatabase, file_id: FileId) -> Vec<LetBinding> {
+    let (bindings, removed) = let_bindings_usage(db, file_id);
+    bindings
+        .into_iter()
+        .zip(removed)
+        .filter_map(|(binding, removed)| removed.then_some(binding))
+        .collect()
+}
+
+/// All removable `let` bindings, and whether each of them is unused.
+fn let_bindings_usage(db: &dyn DefDatabase, file_id: FileId) -> (Vec<LetBinding>, Vec<bool>) {
+    let module = db.module(file_id);
+    let source_map = db.source_map(file_id);
+    let ref_map = db.name_reference_map(file_id);
+    let root = db.parse(file_id).value.syntax_node();
+
+    let bindings = module
+        .exprs()
+        .filter_map(|(_, expr)| match expr {
+            Expr::LetIn(bindings, _) => Some(bindings),
+            _ => None,
+        })
+        .flat_map(|bindings| bindings.entries.iter())
+        .filter_map(|(key, _)| match *key {
+            BindingKey::NameDef(def) => Some(def),
+            BindingKey::Name(_) | BindingKey::Dynamic(_) => None,
+        })
+        .filter_map(|def| {
+            let name = source_map.node_for_name_def(def)?.to_node(&root);
+            let parent = name.parent()?;
+            let (node, inherited_name) = match parent.kind() {
+                SyntaxKind::INHERIT => (parent, Some(name)),
+                SyntaxKind::ATTR_PATH
+                    if ast::Attrpath::cast(parent.clone())?.attrs().count() == 1 =>
+                {
+                    (parent.parent()?, None)
+                }
+                _ => return None,
+            };
+            ast::LetIn::cast(node.parent()?)?;
+            Some(LetBinding {
+                def,
+                node,
+                inherited_name,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Bindings by their entries, where names of the same `inherit` share one.
+    let mut by_node: HashMap<SyntaxNode, Vec<usize>> = HashMap::new();
+    for (i, binding) in bindings.iter().enumerate() {
+        by_node.entry(binding.node.clone()).or_default().push(i);
+    }
+
+    // A reference counts if each binding around it is used. For the shared part of an
+    // `inherit`, like `(x)` in `inherit (x) a b;`, any of its names being used is enough.
+    let ref_owners = bindings
+        .iter()
+        .map(|binding| {
+            ref_map
+                .def_references(binding.def)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|&expr| Some(source_map.node_for_expr(expr)?.to_node(&root)))
+                .map(|node| owners_of(&bindings, &by_node, &node))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // The bindings whose references may become used when the binding is.
+    let mut dependents = vec![Vec::new(); bindings.len()];
+    for (i, owners) in ref_owners.iter().enumerate() {
+        for &owner in owners.iter().flatten().flatten() {
+            dependents[owner].push(i);
+        }
+    }
+
+    // Propagate the usage from the root. References inside its own value are recursive uses,
+    // which are never reached this way.
+    let is_used = |used: &[bool], i: usize| {
+        ref_owners[i].iter().any(|groups| {
+            groups
+                .iter()
+                .all(|group| group.iter().any(|&owner| used[owner]))
+        })
+    };
+    let mut used = vec![false; bindings.len()];
+    let mut stack = Vec::new();
+    for i in 0..bindings.len() {
+        if is_used(&used, i) {
+            used[i] = true;
+            stack.push(i);
+        }
+    }
+    while let Some(owner) = stack.pop() {
+        for &i in &dependents[owner] {
+            if !used[i] && is_used(&used, i) {
+                used[i] = true;
+                stack.push(i);
+            }
+        }
+    }
+    let removed = used.into_iter().map(|used| !used).collect();
+    (bindings, removed)
+}
+
+/// The groups of bindings containing the reference node, of which at least one in each group
+/// must be used for the reference to be used. Names of the same `inherit` are grouped together.
+fn owners_of(
+    bindings: &[LetBinding],
+    by_node: &HashMap<SyntaxNode, Vec<usize>>,
+    node: &SyntaxNode,
+) -> Vec<Vec<usize>> {
+    let range = node.text_range();
+    node.ancestors()
+        .filter_map(|anc| by_node.get(&anc))
+        .map(|group| {
+            // Inside an inherited name itself, only that name owns it.
+            let in_name = group.iter().copied().find(|&i| {
+                bindings[i]
+                    .inherited_name
+                    .as_ref()
+                    .map_or(false, |name| name.text_range().contains_range(range))
+            });
+            in_name.map_or_else(|| group.clone(), |i| vec![i])
+        })
+        .collect()
+}
+
+/// The syntax nodes to remove. An `inherit` is removed as a whole if all its names are
+/// removed, otherwise only the removed names are.
+fn removed_units(bindings: &[LetBinding], removed: &[bool]) -> Vec<SyntaxNode> {
+    let mut units = Vec::new();
+    let mut inherits: Vec<(SyntaxNode, Vec<SyntaxNode>)> = Vec::new();
+    for (binding, _) in bindings.iter().zip(removed).filter(|(_, &removed)| removed) {
+        match &binding.inherited_name {
+            None => units.push(binding.node.clone()),
+            Some(name) => match inherits.iter_mut().find(|(node, _)| *node == binding.node) {
+                Some((_, names)) => names.push(name.clone()),
+                None => inherits.push((binding.node.clone(), vec![name.clone()])),
+            },
+        }
+    }
+    for (inherit, names) in inherits {
+        let attr_cnt = ast::Inherit::cast(inherit.clone()).map_or(0, |i| i.attrs().count());
+        if names.len() == attr_cnt {
+            units.push(inherit);
+        } else {
+            units.extend(names);
+        }
+    }
+    units
+}
+
+/// Also remove the whitespace before the node, so no blank line is left.
+fn with_leading_space(node: &SyntaxNode) -> TextRange {
+    let range = node.text_range();
+    match node
+        .prev_sibling_or_token()
+        .and_then(|elem| elem.into_token())
+    {
+        Some(tok) if tok.kind() == SyntaxKind::SPACE => tok.text_range().cover(range),
+        _ => range,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base::SourceDatabase;
+    use crate::tests::TestDB;
+    use expect_test::{expect, Expect};
+
+    #[track_caller]
+    fn check(fixture: &str, expect: Expect) {
+        let (db, file_id, []) = TestDB::single_file(fixture).unwrap();
+        let mut src = db.file_content(file_id).to_string();
+        let mut edits = super::remove_unused_bindings(&db, file_id);
+        edits.sort_by_key(|edit| edit.delete.start());
+        for edit in edits.iter().rev() {
+            edit.apply(&mut src);
+        }
+        expect.assert_eq(&src);
+    }
+
+    #[test]
+    fn transitive() {
+        check("let b = 1; a = b; in 1", expect!["1"]);
+        check("let b = 1; a = b; c = 2; in c", expect!["let c = 2; in c"]);
+        check("let f = x: f x; in 1", expect!["1"]);
+        check("let a = let b = 1; in 2; in 3", expect!["3"]);
+    }
+
+    #[test]
+    fn cluster() {
+        check("let a = b; b = a; in 1", expect!["1"]);
+        check(
+            "let a = b; b = a; c = d; d = c; in c",
+            expect!["let c = d; d = c; in c"],
+        );
+        check(
+            "let x = 1; inherit (x) a b; in b",
+            expect!["let x = 1; inherit (x) b; in b"],
+        );
+    }
+
+    #[test]
+    fn inherit() {
+        check(
+            "x: let inherit (x) a b; in a",
+            expect!["x: let inherit (x) a; in a"],
+        );
+        check(
+            "x: let inherit (x) a b; c = 1; in c",
+            expect!["x: let c = 1; in c"],
+        );
+    }
+
+    #[test]
+    fn kept() {
+        check("let a = 1; in a", expect!["let a = 1; in a"]);
+        check("let a.b = 1; c = 2; in 1", expect!["let a.b = 1; in 1"]);
+        check("rec { a = 1; b = 2; }", expect!["rec { a = 1; b = 2; }"]);
+    }
+}
@@ -1,5 +1,5 @@
 mod base;
-mod builtin;
+pub mod builtin;
 mod def;
 mod diagnostic;
 mod ide;
@@ -7,8 +7,12 @@ mod ide;
 #[cfg(test)]
 mod tests;
 
-pub use base::{Change, FileId, FilePos, FileRange, InFile};
-pub use diagnostic::{Diagnostic, DiagnosticKind, Severity};
+pub use base::{Change, FileId, FilePos, FileRange, FileSet, InFile, TextEdit, VfsPath};
+pub use diagnostic::{Diagnostic, DiagnosticKind, FixKind, Severity};
 pub use ide::{
-    Analysis, AnalysisHost, CompletionItem, CompletionItemKind, NavigationTarget, RootDatabase,
+    fuzzy_match, Analysis, AnalysisHost, Assist, CallHierarchyItem, CompletionItem,
+    CompletionItemKind, HoverResult, NavigationTarget, PathPrefix, RootDatabase, Runnable, Score,
+    SignatureHelp, SymbolKind, SymbolTree,
 };
+#[cfg(feature = "dev")]
+pub use {def::Module, syntax::Parse};
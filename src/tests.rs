@@ -1,12 +1,14 @@
 use crate::base::{SourceDatabase, SourceDatabaseStorage};
 use crate::def::DefDatabaseStorage;
-use crate::{Change, FileId};
+use crate::{Change, FileId, FilePos, FileSet, VfsPath};
 use rowan::ast::AstNode;
 use rowan::TextSize;
 use std::ops;
+use std::sync::Arc;
 use syntax::{NixLanguage, SyntaxNode};
 
 pub const MARKER_INDICATOR: char = '$';
+pub const FILE_HEADER: &str = "#- ";
 
 #[salsa::database(SourceDatabaseStorage, DefDatabaseStorage)]
 #[derive(Default)]
@@ -20,18 +22,29 @@ impl TestDB {
     pub fn single_file<const MARKERS: usize>(
         fixture: &str,
     ) -> Result<(Self, FileId, [TextSize; MARKERS]), String> {
-        let (f, file, poses) = Fixture::single_file(fixture)?;
-        let db = Self::from_fixture(f);
-        Ok((db, file, poses))
+        let (db, f) = Self::from_fixture(fixture)?;
+        if f.files.len() != 1 {
+            return Err("Expecting a single file".into());
+        }
+        let poses = f.markers::<MARKERS>()?.map(|pos| pos.value);
+        Ok((db, FileId(0), poses))
     }
 
-    fn from_fixture(fixture: Fixture) -> Self {
+    pub fn from_fixture(fixture: &str) -> Result<(Self, Fixture), String> {
+        let f = Fixture::new(fixture)?;
         let mut db = Self::default();
-        let root = FileId(0);
         let mut change = Change::new();
-        change.change_file(root, Some(fixture[root].into()));
+        let mut file_set = FileSet::default();
+        for (i, (path, text)) in f.files.iter().enumerate() {
+            let file = FileId(i as u32);
+            file_set.insert(file, path.clone());
+            change.change_file(file, Some(text.as_str().into()));
+        }
+        change.set_file_set(file_set);
+        change.set_nixpkgs_attr_names(Arc::default());
+        change.set_max_file_size(usize::MAX);
         change.apply(&mut db);
-        db
+        Ok((db, f))
     }
 
     pub fn find_node<T>(
@@ -58,26 +71,51 @@ impl TestDB {
     }
 }
 
+/// A test fixture of one or more files.
+///
+/// Multiple files are separated by header lines of the form `#- /path/to/file.nix`.
+/// Without any header, the fixture is a single file at `/default.nix`.
 #[derive(Debug)]
-struct Fixture {
-    texts: Vec<String>,
+pub struct Fixture {
+    files: Vec<(VfsPath, String)>,
+    markers: [Option<FilePos>; 10],
 }
 
 impl ops::Index<FileId> for Fixture {
     type Output = str;
     fn index(&self, index: FileId) -> &Self::Output {
-        &self.texts[index.0 as usize]
+        &self.files[index.0 as usize].1
     }
 }
 
 impl Fixture {
-    fn single_file<const MARKERS: usize>(
-        fixture: &str,
-    ) -> Result<(Self, FileId, [TextSize; MARKERS]), String> {
+    pub fn new(fixture: &str) -> Result<Self, String> {
         if fixture.len() >= u32::MAX as usize {
             return Err("Size too large".into());
         }
-        let mut markers = [TextSize::from(!0u32); MARKERS];
+
+        let mut this = Self {
+            files: Vec::new(),
+            markers: [None; 10],
+        };
+        let rest = match fixture.trim_start().strip_prefix(FILE_HEADER) {
+            Some(rest) => rest,
+            None => {
+                this.push_file(VfsPath::new("/default.nix").unwrap(), fixture)?;
+                return Ok(this);
+            }
+        };
+        for chunk in rest.split(&format!("\n{}", FILE_HEADER)) {
+            let (path, text) = chunk.split_once('\n').unwrap_or((chunk, ""));
+            let path = VfsPath::new(path.trim())
+                .ok_or_else(|| format!("Invalid file path: {:?}", path))?;
+            this.push_file(path, text)?;
+        }
+        Ok(this)
+    }
+
+    fn push_file(&mut self, path: VfsPath, fixture: &str) -> Result<(), String> {
+        let file = FileId(self.files.len() as u32);
         let mut text = String::new();
         let mut chars = fixture.chars().peekable();
         while let Some(c) = chars.next() {
@@ -85,23 +123,28 @@ impl Fixture {
                 if let Some(n @ '0'..='9') = chars.peek().copied() {
                     chars.next();
                     let i = n.to_digit(10).unwrap() as usize;
-                    let place = markers
-                        .get_mut(i)
-                        .ok_or_else(|| format!("Marker {} out of bound", i))?;
-                    if *place != TextSize::from(!0u32) {
+                    let place = &mut self.markers[i];
+                    if place.is_some() {
                         return Err(format!("Marker {} redefined", i));
                     }
-                    *place = TextSize::from(text.len() as u32);
+                    *place = Some(FilePos::new(file, TextSize::from(text.len() as u32)));
                     continue;
                 }
             }
             text.push(c);
         }
-        for (i, &pos) in markers.iter().enumerate() {
-            if pos == TextSize::from(!0u32) {
-                return Err(format!("Marker {} not set", i));
-            }
+        self.files.push((path, text));
+        Ok(())
+    }
+
+    pub fn markers<const MARKERS: usize>(&self) -> Result<[FilePos; MARKERS], String> {
+        if let Some(i) = (MARKERS..self.markers.len()).find(|&i| self.markers[i].is_some()) {
+            return Err(format!("Marker {} out of bound", i));
+        }
+        let mut ret = [FilePos::new(FileId(0), 0.into()); MARKERS];
+        for (i, place) in ret.iter_mut().enumerate() {
+            *place = self.markers[i].ok_or_else(|| format!("Marker {} not set", i))?;
         }
-        Ok((Self { texts: vec![text] }, FileId(0), markers))
+        Ok(ret)
     }
 }
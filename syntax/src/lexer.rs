@@ -163,19 +163,7 @@ pub fn lex(src: &[u8]) -> LexTokens {
                 ctxs.pop();
             }
             IDENT => {
-                tok = match &rest[..usize::from(len)] {
-                    b"assert" => T![assert],
-                    b"else" => T![else],
-                    b"if" => T![if],
-                    b"in" => T![in],
-                    b"inherit" => T![inherit],
-                    b"let" => T![let],
-                    b"or" => T![or],
-                    b"rec" => T![rec],
-                    b"then" => T![then],
-                    b"with" => T![with],
-                    _ => IDENT,
-                };
+                tok = keyword_kind(&rest[..usize::from(len)]).unwrap_or(IDENT);
             }
             PATH_START => {
                 len -= TextSize::of("${");
@@ -197,6 +185,30 @@ pub fn lex(src: &[u8]) -> LexTokens {
     out
 }
 
+fn keyword_kind(word: &[u8]) -> Option<SyntaxKind> {
+    Some(match word {
+        b"assert" => T![assert],
+        b"else" => T![else],
+        b"if" => T![if],
+        b"in" => T![in],
+        b"inherit" => T![inherit],
+        b"let" => T![let],
+        b"or" => T![or],
+        b"rec" => T![rec],
+        b"then" => T![then],
+        b"with" => T![with],
+        _ => return None,
+    })
+}
+
+/// Check if a name can be written as a bare identifier, without quotes.
+pub fn is_valid_ident(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    matches!(bytes.next(), Some(b'a'..=b'z' | b'A'..=b'Z' | b'_'))
+        && bytes.all(|b| matches!(b, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'\'' | b'-'))
+        && keyword_kind(name.as_bytes()).is_none()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -515,4 +527,15 @@ mod test {
             "#]],
         );
     }
+
+    #[test]
+    fn valid_ident() {
+        assert!(is_valid_ident("a"));
+        assert!(is_valid_ident("_a-b'c0"));
+        assert!(!is_valid_ident(""));
+        assert!(!is_valid_ident("0a"));
+        assert!(!is_valid_ident("a b"));
+        assert!(!is_valid_ident("in"));
+        assert!(!is_valid_ident("or"));
+    }
 }
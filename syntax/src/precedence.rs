@@ -0,0 +1,66 @@
+//! Binding powers of expressions, higher binds tighter, to decide where parentheses are needed.
+//! Constructs extending to the right, like lambdas and `let`-`in`, have the lowest one.
+//! They agree with the grammar in `parser`.
+use crate::ast::{BinaryOpKind, UnaryOpKind};
+
+pub const LOWEST: u8 = 0;
+pub const IMPLY: u8 = 1;
+pub const OR: u8 = 2;
+pub const AND: u8 = 3;
+pub const EQUALITY: u8 = 4;
+pub const COMPARISON: u8 = 5;
+pub const UPDATE: u8 = 6;
+pub const NOT: u8 = 7;
+pub const ADD: u8 = 8;
+pub const MUL: u8 = 9;
+pub const CONCAT: u8 = 10;
+pub const HAS_ATTR: u8 = 11;
+pub const NEGATE: u8 = 12;
+pub const APPLY: u8 = 13;
+pub const SELECT: u8 = 14;
+pub const ATOM: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+pub fn binary(op: BinaryOpKind) -> u8 {
+    binary_assoc(op).0
+}
+
+fn binary_assoc(op: BinaryOpKind) -> (u8, Assoc) {
+    match op {
+        BinaryOpKind::Imply => (IMPLY, Assoc::Right),
+        BinaryOpKind::Or => (OR, Assoc::Left),
+        BinaryOpKind::And => (AND, Assoc::Left),
+        BinaryOpKind::Equal | BinaryOpKind::NotEqual => (EQUALITY, Assoc::None),
+        BinaryOpKind::Less
+        | BinaryOpKind::Greater
+        | BinaryOpKind::LessEqual
+        | BinaryOpKind::GreaterEqual => (COMPARISON, Assoc::None),
+        BinaryOpKind::Update => (UPDATE, Assoc::Right),
+        BinaryOpKind::Add | BinaryOpKind::Sub => (ADD, Assoc::Left),
+        BinaryOpKind::Mul | BinaryOpKind::Div => (MUL, Assoc::Left),
+        BinaryOpKind::Concat => (CONCAT, Assoc::Right),
+    }
+}
+
+pub fn unary(op: UnaryOpKind) -> u8 {
+    match op {
+        UnaryOpKind::Not => NOT,
+        UnaryOpKind::Negate => NEGATE,
+    }
+}
+
+/// The minimal binding powers of the left and right operands of a binary operator.
+pub fn binary_operands(op: BinaryOpKind) -> (u8, u8) {
+    let (prec, assoc) = binary_assoc(op);
+    match assoc {
+        Assoc::Left => (prec, prec + 1),
+        Assoc::Right => (prec + 1, prec),
+        Assoc::None => (prec + 1, prec + 1),
+    }
+}